@@ -0,0 +1,165 @@
+use std::fs;
+
+use chess_core::{
+    explorer::{GameHit, Material, PositionIndex},
+    hash::{HashedState, NoopHasher},
+    r#move::san::to_san,
+    pgn::{PgnGame, parse_pgn},
+    position::Position,
+    state::State,
+};
+
+// Explore a PGN collection offline. `build` replays the games into a position
+// index, the other commands query it: the games reaching a position, the moves
+// played from a position with their results, and the games reaching a
+// material like KRPvKR. Listing games needs the PGN the index was built from,
+// games are found in it by their number.
+
+const USAGE: &str = "usage: chess_explorer build <games.pgn> <index>
+       chess_explorer games <games.pgn> <index> <fen>
+       chess_explorer tree <index> <fen>
+       chess_explorer material <games.pgn> <index> <material>
+       the material gives white first, as in KRPvKR, and matches either color";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
+
+/// `White - Black Result (Event, Date)`, with the tags the game has.
+fn describe(game: &PgnGame) -> String {
+    let tag = |name| game.tag(name).unwrap_or("?");
+    let mut line = format!("{} - {} {}", tag("White"), tag("Black"), game.result);
+    let details = ["Event", "Date"]
+        .into_iter()
+        .filter_map(|name| game.tag(name))
+        .collect::<Vec<_>>();
+    if !details.is_empty() {
+        line += &format!(" ({})", details.join(", "));
+    }
+    line
+}
+
+/// One line per game hit, numbered from 1 as in the PGN file.
+fn list_games(games: &[PgnGame], hits: &[GameHit]) -> Vec<String> {
+    hits.iter()
+        .map(|hit| {
+            let game = games
+                .get(hit.game as usize)
+                .map_or("not in the PGN file".to_string(), describe);
+            format!("game {} ply {}: {}", hit.game + 1, hit.ply, game)
+        })
+        .collect()
+}
+
+/// One line per move, e.g. `e4  2 games  50.0% / 50.0% / 0.0%`.
+fn tree(index: &PositionIndex, state: &State) -> Vec<String> {
+    let mut position = Position::new(HashedState::new(state.clone(), NoopHasher {}));
+    index
+        .move_stats(state)
+        .iter()
+        .map(|stats| {
+            let [white, draws, black] = stats.percentages();
+            format!(
+                "{:<7} {:>6} games  {:5.1}% / {:5.1}% / {:5.1}%",
+                to_san(&mut position, stats.r#move),
+                stats.games,
+                white,
+                draws,
+                black
+            )
+        })
+        .collect()
+}
+
+fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let fen = |words: &[String]| {
+        State::try_from_fen(&words.join(" ")).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    };
+    let load = |file: &str| PositionIndex::load(file).unwrap();
+    let games = |file: &str| parse_pgn(&fs::read_to_string(file).unwrap()).unwrap();
+
+    match args.get(1..).unwrap_or_default() {
+        [command, pgn, index] if command == "build" => {
+            let mut position_index = PositionIndex::new();
+            let skipped = position_index
+                .add_pgn(&fs::read_to_string(pgn).unwrap())
+                .unwrap();
+            position_index.save(index).unwrap();
+            println!(
+                "{} games, {} positions, {} skipped for an illegal move",
+                position_index.game_count(),
+                position_index.position_count(),
+                skipped
+            );
+        }
+        [command, pgn, index, words @ ..] if command == "games" && !words.is_empty() => {
+            let hits = load(index).games(&fen(words));
+            for line in list_games(&games(pgn), &hits) {
+                println!("{}", line);
+            }
+            println!("{} games", hits.len());
+        }
+        [command, index, words @ ..] if command == "tree" && !words.is_empty() => {
+            println!("move     games  white / draw / black");
+            for line in tree(&load(index), &fen(words)) {
+                println!("{}", line);
+            }
+        }
+        [command, pgn, index, material] if command == "material" => {
+            let material = Material::parse(material).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let hits = load(index).material_games(material, true);
+            for line in list_games(&games(pgn), &hits) {
+                println!("{}", line);
+            }
+            println!("{} games", hits.len());
+        }
+        _ => usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = "[Event \"Club\"]
+[White \"Ann\"]
+[Black \"Bob\"]
+[Result \"1-0\"]
+
+1. e4 e5 2. Nf3 1-0
+
+[White \"Bob\"]
+[Black \"Ann\"]
+[Result \"1/2-1/2\"]
+
+1. e4 c5 1/2-1/2
+";
+
+    #[test]
+    fn test_listing() {
+        let games = parse_pgn(PGN).unwrap();
+        let mut index = PositionIndex::new();
+        index.add_pgn(PGN).unwrap();
+
+        let hits = index.games(&State::default());
+        assert_eq!(
+            list_games(&games, &hits),
+            [
+                "game 1 ply 0: Ann - Bob 1-0 (Club)",
+                "game 2 ply 0: Bob - Ann 1/2-1/2"
+            ]
+        );
+        assert_eq!(
+            tree(&index, &State::default()),
+            ["e4           2 games   50.0% /  50.0% /   0.0%"]
+        );
+    }
+}
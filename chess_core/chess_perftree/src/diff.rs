@@ -0,0 +1,361 @@
+// Divide-diff: compare our divide with a reference one, and descend into the
+// first move whose counts differ until the moves themselves differ. The
+// reference divides come one after the other from a file or stdin, or from a
+// perftree compatible command run for every position.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{self, BufRead},
+    iter::Peekable,
+    process::Command,
+};
+
+use chess_core::state::State;
+
+use crate::{
+    perft::{PerftTable, divide},
+    play,
+};
+
+/// Moves and node counts of a divide, moves in lowercase perft format (a7b8q).
+pub type Divide = Vec<(String, u64)>;
+
+/// Lines of the reference divides.
+pub type Lines = Peekable<Box<dyn Iterator<Item = String>>>;
+
+fn is_perft_move(word: &str) -> bool {
+    match word.as_bytes() {
+        [from_file, from_rank, to_file, to_rank, promotion @ ..] => {
+            (b'a'..=b'h').contains(from_file)
+                && (b'1'..=b'8').contains(from_rank)
+                && (b'a'..=b'h').contains(to_file)
+                && (b'1'..=b'8').contains(to_rank)
+                && promotion.len() <= 1
+        }
+        _ => false,
+    }
+}
+
+/// Parse the moves of a divide output, as printed by perftree compatible tools
+/// ("e2e4 20") or Stockfish ("e2e4: 20"). The moves end at a blank line,
+/// usually followed by the total.
+pub fn parse_divide(lines: &mut Lines) -> Result<Option<Divide>, &'static str> {
+    let mut moves = Vec::new();
+    while lines.next_if(|line| line.trim().is_empty()).is_some() {}
+    for line in lines.by_ref() {
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        let (r#move, nodes) = line
+            .split_once(char::is_whitespace)
+            .ok_or("Divide line malformed")?;
+        let r#move = r#move.trim_end_matches(':').to_lowercase();
+        if !is_perft_move(&r#move) {
+            return Err("Divide move malformed");
+        }
+        let nodes = nodes
+            .trim()
+            .parse()
+            .map_err(|_| "Divide node count malformed")?;
+        moves.push((r#move, nodes));
+    }
+    if moves.is_empty() {
+        return Ok(None);
+    }
+    // The total is recomputed from the moves, so it is skipped
+    while lines.next_if(|line| line.trim().is_empty()).is_some() {}
+    lines.next_if(|line| {
+        let word = line.split_whitespace().next().unwrap_or_default();
+        !is_perft_move(word.trim_end_matches(':'))
+    });
+    Ok(Some(moves))
+}
+
+pub fn lines(text: impl BufRead + 'static) -> Lines {
+    (Box::new(text.lines().map_while(Result::ok)) as Box<dyn Iterator<Item = String>>).peekable()
+}
+
+pub enum Reference {
+    /// Divides read in order, one for every position we descend into.
+    Reader(Lines),
+    /// A program called like perftree calls its script: `<program> <depth> <fen> [moves]`.
+    Command(String),
+}
+
+impl Reference {
+    /// Reference divide of the position reached by `moves`, `None` when there is no more.
+    fn divide(
+        &mut self,
+        depth: u8,
+        fen: &str,
+        moves: &[String],
+    ) -> Result<Option<Divide>, &'static str> {
+        match self {
+            Reference::Reader(lines) => {
+                // Tell the user what to paste next
+                eprintln!(
+                    "reference divide of: {} \"{}\" \"{}\"",
+                    depth,
+                    fen,
+                    moves.join(" ")
+                );
+                parse_divide(lines)
+            }
+            Reference::Command(command) => {
+                let mut words = command.split_whitespace();
+                let mut command = Command::new(words.next().ok_or("Reference command is empty")?);
+                command.args(words).arg(depth.to_string()).arg(fen);
+                if !moves.is_empty() {
+                    command.arg(moves.join(" "));
+                }
+                let output = command
+                    .output()
+                    .map_err(|_| "Cannot run the reference command")?;
+                if !output.status.success() {
+                    return Err("Reference command failed");
+                }
+                let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+                parse_divide(&mut lines(io::Cursor::new(stdout)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Difference {
+    /// The divides are identical.
+    None,
+    /// The position reached by `moves` from `fen` has moves we don't generate,
+    /// or generates moves it shouldn't.
+    Moves {
+        fen: String,
+        moves: Vec<String>,
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// The counts below `move` differ, but the reference ended before the
+    /// moves themselves differed.
+    Count {
+        fen: String,
+        moves: Vec<String>,
+        r#move: String,
+        ours: u64,
+        reference: u64,
+    },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::None => write!(f, "no difference"),
+            Difference::Moves {
+                fen,
+                moves,
+                missing,
+                extra,
+            } => {
+                writeln!(f, "fen: {}", fen)?;
+                writeln!(f, "moves: {}", moves.join(" "))?;
+                writeln!(f, "missing moves: {}", missing.join(" "))?;
+                write!(f, "extra moves: {}", extra.join(" "))
+            }
+            Difference::Count {
+                fen,
+                moves,
+                r#move,
+                ours,
+                reference,
+            } => {
+                writeln!(f, "fen: {}", fen)?;
+                writeln!(f, "moves: {}", moves.join(" "))?;
+                write!(
+                    f,
+                    "nodes after {}: {} found, {} expected",
+                    r#move, ours, reference
+                )
+            }
+        }
+    }
+}
+
+/// Find the smallest difference between our perft of `state` and the reference.
+pub fn divide_diff(
+    state: &State,
+    mut depth: u8,
+    reference: &mut Reference,
+    threads: usize,
+    table: Option<&PerftTable>,
+) -> Result<Difference, &'static str> {
+    let fen = state.to_fen();
+    let mut moves = Vec::new();
+    let mut last_count = None;
+    loop {
+        let Some(theirs) = reference.divide(depth, &fen, &moves)? else {
+            return match last_count {
+                Some((r#move, ours, reference)) => {
+                    moves.pop();
+                    Ok(Difference::Count {
+                        fen,
+                        moves,
+                        r#move,
+                        ours,
+                        reference,
+                    })
+                }
+                None => Err("No reference divide"),
+            };
+        };
+        let ours = divide(
+            &play(state.clone(), &moves.join(" "))?,
+            depth,
+            threads,
+            table,
+        )
+        .into_iter()
+        .map(|(m, nodes)| (m.to_string().to_lowercase(), nodes))
+        .collect::<Divide>();
+
+        let their_counts = theirs.iter().cloned().collect::<HashMap<_, _>>();
+        let our_counts = ours.iter().cloned().collect::<HashMap<_, _>>();
+        let missing = theirs
+            .iter()
+            .filter(|(m, _)| !our_counts.contains_key(m))
+            .map(|(m, _)| m.clone())
+            .collect::<Vec<_>>();
+        let extra = ours
+            .iter()
+            .filter(|(m, _)| !their_counts.contains_key(m))
+            .map(|(m, _)| m.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() || !extra.is_empty() {
+            return Ok(Difference::Moves {
+                fen,
+                moves,
+                missing,
+                extra,
+            });
+        }
+
+        let Some((r#move, nodes)) = ours.into_iter().find(|(m, n)| their_counts[m] != *n) else {
+            return Ok(Difference::None);
+        };
+        let reference = their_counts[&r#move];
+        if depth == 1 {
+            // Only a broken reference counts more than a node below a move
+            return Ok(Difference::Count {
+                fen,
+                moves,
+                r#move,
+                ours: nodes,
+                reference,
+            });
+        }
+        last_count = Some((r#move.clone(), nodes, reference));
+        moves.push(r#move);
+        depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+
+    /// Divide output of our own perft, with changes.
+    fn output(state: &State, depth: u8, change: impl Fn(&mut Divide)) -> String {
+        let mut moves = divide(state, depth, 1, None)
+            .into_iter()
+            .map(|(m, nodes)| (m.to_string(), nodes))
+            .collect::<Divide>();
+        change(&mut moves);
+        let mut output = moves
+            .iter()
+            .map(|(m, nodes)| format!("{}: {}\n", m, nodes))
+            .collect::<String>();
+        output.push_str(&format!(
+            "\nNodes searched: {}\n\n",
+            moves.iter().map(|(_, n)| n).sum::<u64>()
+        ));
+        output
+    }
+
+    #[test]
+    fn test_parse_divide() {
+        // The second divide has no total
+        let text = "a2a4 20\ne7e8Q 3\n\n23\nb1c3: 4\n\ng1f3: 2\n\nNodes searched: 2\n";
+        let mut lines = lines(Cursor::new(text));
+        assert_eq!(
+            parse_divide(&mut lines),
+            Ok(Some(vec![
+                ("a2a4".to_string(), 20),
+                ("e7e8q".to_string(), 3)
+            ]))
+        );
+        assert_eq!(
+            parse_divide(&mut lines),
+            Ok(Some(vec![("b1c3".to_string(), 4)]))
+        );
+        assert_eq!(
+            parse_divide(&mut lines),
+            Ok(Some(vec![("g1f3".to_string(), 2)]))
+        );
+        assert_eq!(parse_divide(&mut lines), Ok(None));
+        assert!(parse_divide(&mut self::lines(Cursor::new("a2a4 x"))).is_err());
+        assert!(parse_divide(&mut self::lines(Cursor::new("Nodes: 20"))).is_err());
+    }
+
+    #[test]
+    fn test_divide_diff() {
+        let state = State::from_fen(POSITION_3);
+        let same = output(&state, 3, |_| {});
+        let reference = &mut Reference::Reader(lines(Cursor::new(same)));
+        assert_eq!(
+            divide_diff(&state, 3, reference, 1, None),
+            Ok(Difference::None)
+        );
+
+        // The reference has one more node below e2e4, then after e2e4 c7c5,
+        // then a move we don't generate, b4c8, after e2e4 c7c5
+        let after = |moves: &str| play(state.clone(), moves).unwrap();
+        let add_one = |r#move: &'static str| {
+            move |moves: &mut Divide| moves.iter_mut().find(|(m, _)| m == r#move).unwrap().1 += 1
+        };
+        let text = [
+            output(&state, 3, add_one("e2e4")),
+            output(&after("e2e4"), 2, add_one("c7c5")),
+            output(&after("e2e4 c7c5"), 1, |moves| {
+                moves.push(("b4c8".to_string(), 1))
+            }),
+        ]
+        .concat();
+        let reference = &mut Reference::Reader(lines(Cursor::new(text.clone())));
+        let difference = divide_diff(&state, 3, reference, 1, None).unwrap();
+        assert_eq!(
+            difference,
+            Difference::Moves {
+                fen: state.to_fen(),
+                moves: vec!["e2e4".to_string(), "c7c5".to_string()],
+                missing: vec!["b4c8".to_string()],
+                extra: Vec::new(),
+            }
+        );
+
+        // Without the last divide the smallest difference found is a count
+        let text = [
+            output(&state, 3, add_one("e2e4")),
+            output(&after("e2e4"), 2, add_one("c7c5")),
+        ]
+        .concat();
+        let reference = &mut Reference::Reader(lines(Cursor::new(text)));
+        let difference = divide_diff(&state, 3, reference, 1, None).unwrap();
+        assert!(matches!(
+            difference,
+            Difference::Count { ref moves, ref r#move, .. } if moves == &["e2e4"] && r#move == "c7c5"
+        ));
+    }
+}
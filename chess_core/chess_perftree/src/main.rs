@@ -1,156 +1,261 @@
-use chess_core::{hash::NoopHasher, r#move::MoveList, position::Position};
+mod diff;
+mod perft;
+mod stats;
+mod suite;
 
-fn main() {
-    let args: Vec<_> = std::env::args().collect::<Vec<_>>();
-    let (depth, fen, moves) = match &args[..] {
-        [_, depth, fen, moves] => (depth, fen, Some(moves)),
-        [_, depth, fen] => (depth, fen, None),
-        _ => panic!(),
-    };
+use std::{
+    fs,
+    io::{self, BufReader},
+    thread,
+    time::Instant,
+};
 
-    perftree(
-        depth.parse().unwrap(),
-        fen,
-        moves.map(|x| x.split_whitespace().collect()),
-    );
-}
+use chess_core::{
+    hash::{HashedState, NoopHasher},
+    position::Position,
+    state::State,
+};
 
-fn perftree(depth: u8, fen: &str, moves: Option<Vec<&str>>) {
-    // depth is the maximum depth of the evaluation,
-    // fen is the Forsyth-Edwards Notation string of some base position,
-    // moves is an optional list of moves from the base position to the position to be evaluated, where each move is formatted as $source$target$promotion, e.g. e2e4 or a7b8Q.
+use diff::{Reference, divide_diff, lines};
+use perft::{PerftTable, divide};
+use stats::{NodeStats, node_stats};
+use suite::parse_suite;
 
-    // The script is expected to output the results of the perft function to standard output, with the following format:
-    // For each move available at the current position, print the move and the number of nodes at the given depth which are an ancestor of that move, separated by whitespace.
-    // After the list of moves, print a blank line.
-    // Finally, print the total node count on its own line.
+const USAGE: &str = "usage: chess_perftree <depth> <fen> [moves]
+       chess_perftree divide <depth> <fen> [moves] [options]
+       chess_perftree stats <depth> <fen> [moves] [--threads <n>]
+       chess_perftree suite <suite.epd> [max depth] [options]
+       chess_perftree diff <depth> <fen> [moves] [--reference <file>|--engine <command>] [options]
+options: --threads <n>     threads sharing the root moves, all cores by default
+         --hash <MB>       size of the node count table, 0 to disable, 64 by default
+         --reference <file>  reference divides, one for each position descended into,
+                           read from stdin if neither this nor --engine is given
+         --engine <command>  perftree compatible command giving the reference divides";
 
-    let total_nodes = &mut 0;
-    let mut position = Position::from_fen(fen, NoopHasher {});
+struct Options {
+    threads: usize,
+    hash: usize,
+    reference: Option<String>,
+    engine: Option<String>,
+}
 
-    if let Some(moves) = moves {
-        make_move_sequence(&mut position, moves);
+impl Options {
+    fn table(&self) -> Option<PerftTable> {
+        (self.hash > 0).then(|| PerftTable::new(self.hash))
     }
+}
 
-    // let start = std::time::Instant::now();
-    iter_first_level_moves(&mut position, depth, total_nodes);
-    println!();
-    println!("{}", *total_nodes);
-    // dbg!(start.elapsed());
+/// Split the arguments into positional arguments and options.
+fn parse_options(args: &[String]) -> Result<(Vec<&str>, Options), &'static str> {
+    let mut positional = Vec::new();
+    let mut options = Options {
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        hash: 64,
+        reference: None,
+        engine: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threads" => {
+                options.threads = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| *n > 0)
+                    .ok_or("--threads needs a positive number")?;
+            }
+            "--hash" => {
+                options.hash = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--hash needs a size in megabytes")?;
+            }
+            "--reference" => {
+                options.reference = Some(args.next().ok_or("--reference needs a file")?.clone());
+            }
+            "--engine" => {
+                options.engine = Some(args.next().ok_or("--engine needs a command")?.clone());
+            }
+            arg => positional.push(arg),
+        }
+    }
+    Ok((positional, options))
 }
 
-fn make_move_sequence(position: &mut Position<NoopHasher>, moves: Vec<&str>) {
-    for m in moves {
-        let mut found_move = None;
-        let mut move_list = MoveList::new();
-        move_list.new_ply();
+fn parse_depth(depth: &str) -> Result<u8, &'static str> {
+    match depth.parse() {
+        Ok(depth) if depth > 0 => Ok(depth),
+        _ => Err("Depth must be a number of at least 1"),
+    }
+}
 
-        position.pseudo_legal_moves(&mut move_list);
+/// The position reached from `state` by the moves, formatted as $source$target$promotion
+/// and separated by whitespace, e.g. "e2e4 e7e5" or "a7b8q".
+fn play(state: State, moves: &str) -> Result<State, &'static str> {
+    let mut position = Position::new(HashedState::new(state, NoopHasher {}));
+    for m in moves.split_whitespace() {
+        let found_move = position
+            .legal_moves()
+            .into_iter()
+            .find(|m2| m2.to_string().eq_ignore_ascii_case(m))
+            .ok_or("Move not found")?;
+        position.make(found_move);
+    }
+    Ok(position.state.get().clone())
+}
 
-        for m2 in move_list.current_ply() {
-            if m2.matches_perft_string(m) {
-                found_move = Some(m2);
-                break;
-            }
-        }
-        if let Some(m) = found_move {
-            position.make(*m);
-        } else {
-            panic!("Move not found");
-        }
+fn starting_state(fen: &str, moves: Option<&str>) -> Result<State, &'static str> {
+    play(State::try_from_fen(fen)?, moves.unwrap_or_default())
+}
+
+/// The script is expected to output the results of the perft function to standard output, with the following format:
+/// For each move available at the current position, print the move and the number of nodes at the given depth which are an ancestor of that move, separated by whitespace.
+/// After the list of moves, print a blank line.
+/// Finally, print the total node count on its own line.
+fn print_divide(state: &State, depth: u8, threads: usize, table: Option<&PerftTable>) {
+    let moves = divide(state, depth, threads, table);
+    for (m, nodes) in &moves {
+        println!("{} {}", m, nodes);
     }
+    println!();
+    println!("{}", moves.iter().map(|(_, nodes)| nodes).sum::<u64>());
 }
 
-fn iter_first_level_moves(position: &mut Position<NoopHasher>, depth: u8, total_nodes: &mut u64) {
-    let move_list = &mut MoveList::new();
-    move_list.new_ply();
-    position.pseudo_legal_moves(move_list);
-
-    let ply_number = move_list.ply_number();
-    let ply_size = move_list.ply_size(ply_number);
-    for m in 0..ply_size {
-        let m = move_list.r#move(ply_number, m);
-        position.make(m);
-        if position.was_move_legal() {
-            let count = &mut 0;
-            recursive_perft(position, move_list, depth - 1, count);
-            println!("{} {}", m, count);
-            *total_nodes += *count;
+fn run_suite(path: &str, max_depth: u8, options: &Options) -> Result<bool, &'static str> {
+    let text = fs::read_to_string(path).map_err(|_| "Cannot read the suite file")?;
+    let cases = parse_suite(&text)?;
+    let table = options.table();
+    let (mut passed, mut total) = (0, 0);
+    for case in &cases {
+        let name = case
+            .epd
+            .id()
+            .map(str::to_string)
+            .unwrap_or_else(|| case.epd.state.to_fen());
+        for (depth, expected) in case.expected.iter().filter(|(d, _)| *d <= max_depth) {
+            let start = Instant::now();
+            let nodes = divide(&case.epd.state, *depth, options.threads, table.as_ref())
+                .iter()
+                .map(|(_, nodes)| nodes)
+                .sum::<u64>();
+            let ok = nodes == *expected;
+            println!(
+                "{} D{} {} {} {} ({} ms)",
+                name,
+                depth,
+                expected,
+                nodes,
+                if ok { "ok" } else { "FAIL" },
+                start.elapsed().as_millis()
+            );
+            passed += ok as usize;
+            total += 1;
         }
-        position.unmake(m);
     }
+    println!("passed {}/{}", passed, total);
+    Ok(passed == total)
 }
 
-fn recursive_perft(
-    position: &mut Position<NoopHasher>,
-    move_list: &mut MoveList,
-    depth: u8,
-    nodes: &mut u64,
-) {
-    if depth == 0 {
-        *nodes += 1;
-        return;
-    }
-    move_list.new_ply();
-    position.pseudo_legal_moves(move_list);
-    let ply_number = move_list.ply_number();
-    let ply_size = move_list.ply_size(ply_number);
-    for m in 0..ply_size {
-        let m = move_list.r#move(ply_number, m);
-        position.make(m);
-        if position.was_move_legal() {
-            if depth == 1 {
-                *nodes += 1;
-            } else {
-                // SearchContext::new(make_unmaker.state, 0).evaluate();
-                recursive_perft(position, move_list, depth - 1, nodes);
+fn run(args: &[String]) -> Result<bool, &'static str> {
+    let (positional, options) = parse_options(args)?;
+    match positional[..] {
+        // Compatible with the perftree debugging tool, so counted without the table
+        [depth, fen] | [depth, fen, _] if depth.parse::<u8>().is_ok() => {
+            let depth = parse_depth(depth)?;
+            let state = starting_state(fen, positional.get(2).copied())?;
+            print_divide(&state, depth, 1, None);
+        }
+        ["divide", depth, fen] | ["divide", depth, fen, _] => {
+            let depth = parse_depth(depth)?;
+            let state = starting_state(fen, positional.get(3).copied())?;
+            let start = Instant::now();
+            print_divide(&state, depth, options.threads, options.table().as_ref());
+            eprintln!("{} ms", start.elapsed().as_millis());
+        }
+        ["stats", depth, fen] | ["stats", depth, fen, _] => {
+            let depth = parse_depth(depth)?;
+            let state = starting_state(fen, positional.get(3).copied())?;
+            println!("{}", NodeStats::HEADER);
+            for (depth, stats) in node_stats(&state, depth, options.threads)
+                .iter()
+                .enumerate()
+            {
+                println!("{:>5} {}", depth + 1, stats);
             }
         }
-        position.unmake(m);
+        ["diff", depth, fen] | ["diff", depth, fen, _] => {
+            let depth = parse_depth(depth)?;
+            let state = starting_state(fen, positional.get(3).copied())?;
+            let mut reference = match (&options.reference, &options.engine) {
+                (Some(_), Some(_)) => return Err("Give either a reference file or an engine"),
+                (Some(path), None) => Reference::Reader(lines(BufReader::new(
+                    fs::File::open(path).map_err(|_| "Cannot open the reference file")?,
+                ))),
+                (None, Some(command)) => Reference::Command(command.clone()),
+                (None, None) => Reference::Reader(lines(io::stdin().lock())),
+            };
+            let difference = divide_diff(
+                &state,
+                depth,
+                &mut reference,
+                options.threads,
+                options.table().as_ref(),
+            )?;
+            println!("{}", difference);
+            return Ok(difference == diff::Difference::None);
+        }
+        ["suite", path] => return run_suite(path, u8::MAX, &options),
+        ["suite", path, max_depth] => return run_suite(path, parse_depth(max_depth)?, &options),
+        _ => return Err("Unexpected arguments"),
+    }
+    Ok(true)
+}
+
+fn main() {
+    let args: Vec<_> = std::env::args().skip(1).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
     }
-    move_list.drop_current_ply();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
     #[test]
-    fn recursive_perft_test() {
-        let initial_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-        let position_2 = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
-        let position_3 = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
-        let cases = [
-            // initial position
-            (initial_fen, 1, 20),
-            (initial_fen, 2, 400),
-            (initial_fen, 3, 8902),
-            (initial_fen, 4, 197281),
-            // (initial_fen, 5, 4865609),
-            // (initial_fen, 6, 119060324),
-
-            // position 2
-            (position_2, 1, 48),
-            (position_2, 2, 2039),
-            (position_2, 3, 97862),
-            // (position_2, 4, 4085603),
-            // (position_2, 5, 193690690),
-
-            // position 3
-            (position_3, 1, 14),
-            (position_3, 2, 191),
-            (position_3, 3, 2812),
-            (position_3, 4, 43238),
-            // (position_3, 5, 674624),
-            // (position_3, 6, 11030083),
-        ];
-        for (fen, depth, nodes) in cases {
-            let mut position = Position::from_fen(fen, NoopHasher {});
-            dbg!(depth);
-            let mut move_list = MoveList::new();
-            let mut count = 0;
-            recursive_perft(&mut position, &mut move_list, depth, &mut count);
-            assert_eq!(count, nodes);
-        }
+    fn test_parse_options() {
+        let args = args("divide 3 fen --threads 3 --hash 0");
+        let (positional, options) = parse_options(&args).unwrap();
+        assert_eq!(positional, ["divide", "3", "fen"]);
+        assert_eq!((options.threads, options.hash), (3, 0));
+        assert!(options.table().is_none());
+
+        assert!(parse_options(&self::args("suite x --threads 0")).is_err());
+        assert!(parse_options(&self::args("suite x --hash")).is_err());
+        assert!(parse_depth("0").is_err());
+        assert!(run(&self::args("divide x 8/8/8/8/8/8/8/K1k5")).is_err());
+        assert!(run(&self::args("suite")).is_err());
+    }
+
+    #[test]
+    fn test_starting_state() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let state = starting_state(fen, Some("e2e4 e7e5")).unwrap();
+        assert_eq!(
+            state.boards.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR"
+        );
+        assert!(starting_state(fen, Some("e2e5")).is_err());
+        assert!(starting_state("rnbqkbnr/pppppppp w KQkq - 0 1", None).is_err());
     }
 }
@@ -0,0 +1,244 @@
+// Perft counts the leaves of the legal move tree to a given depth. Deep counts
+// use a table of subtree counts keyed by the Zobrist hash of the position, and
+// share the moves of the root between threads.
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+};
+
+use chess_core::{
+    hash::{HashedState, Hasher, zobrist::ZobristHasher},
+    r#move::{Move, MoveList},
+    position::Position,
+    state::State,
+};
+
+pub fn recursive_perft<H: Hasher>(
+    position: &mut Position<H>,
+    move_list: &mut MoveList,
+    depth: u8,
+    nodes: &mut u64,
+) {
+    if depth == 0 {
+        *nodes += 1;
+        return;
+    }
+    move_list.new_ply();
+    position.pseudo_legal_moves(move_list);
+    let ply_number = move_list.ply_number();
+    let ply_size = move_list.ply_size(ply_number);
+    for m in 0..ply_size {
+        let m = move_list.r#move(ply_number, m);
+        position.make(m);
+        if position.was_move_legal() {
+            if depth == 1 {
+                *nodes += 1;
+            } else {
+                recursive_perft(position, move_list, depth - 1, nodes);
+            }
+        }
+        position.unmake(m);
+    }
+    move_list.drop_current_ply();
+}
+
+/// Subtree counts by hash and depth, shared by the threads. The hash is stored
+/// xored with the data, so an entry torn by two threads writing at once
+/// doesn't match any position.
+pub struct PerftTable {
+    entries: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl PerftTable {
+    pub fn new(megabytes: usize) -> Self {
+        let size = (megabytes << 20) / size_of::<(AtomicU64, AtomicU64)>();
+        PerftTable {
+            entries: (0..size.max(1))
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    fn get(&self, hash: u64, depth: u8) -> Option<u64> {
+        let (key, data) = &self.entries[hash as usize % self.entries.len()];
+        let data = data.load(Ordering::Relaxed);
+        let key = key.load(Ordering::Relaxed);
+        (key ^ data == hash && data & 0xff == depth as u64).then_some(data >> 8)
+    }
+
+    fn store(&self, hash: u64, depth: u8, nodes: u64) {
+        let (key, data) = &self.entries[hash as usize % self.entries.len()];
+        let value = nodes << 8 | depth as u64;
+        key.store(hash ^ value, Ordering::Relaxed);
+        data.store(value, Ordering::Relaxed);
+    }
+}
+
+pub fn hashed_perft(
+    position: &mut Position<ZobristHasher>,
+    move_list: &mut MoveList,
+    depth: u8,
+    table: &PerftTable,
+) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let hash = position.state.get_hash();
+    if let Some(nodes) = table.get(hash, depth) {
+        return nodes;
+    }
+
+    let mut nodes = 0;
+    move_list.new_ply();
+    position.pseudo_legal_moves(move_list);
+    let ply_number = move_list.ply_number();
+    for m in 0..move_list.ply_size(ply_number) {
+        let m = move_list.r#move(ply_number, m);
+        position.make(m);
+        if position.was_move_legal() {
+            nodes += match depth {
+                1 => 1,
+                _ => hashed_perft(position, move_list, depth - 1, table),
+            };
+        }
+        position.unmake(m);
+    }
+    move_list.drop_current_ply();
+
+    table.store(hash, depth, nodes);
+    nodes
+}
+
+/// Call `count` on every legal move of `state`, after the move is made. The
+/// moves are shared between `threads` threads, and the results are returned in
+/// move generation order.
+pub fn split_root<T: Send>(
+    state: &State,
+    threads: usize,
+    count: impl Fn(&mut Position<ZobristHasher>, Move) -> T + Sync,
+) -> Vec<(Move, T)> {
+    let position = |r#move| {
+        let mut position = Position::new(HashedState::new(state.clone(), ZobristHasher::new()));
+        position.make(r#move);
+        position
+    };
+    let moves = Position::new(HashedState::new(state.clone(), ZobristHasher::new())).legal_moves();
+    let next = AtomicUsize::new(0);
+
+    let mut results = thread::scope(|scope| {
+        (0..threads.clamp(1, moves.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(r#move) = moves.get(i) else {
+                            return results;
+                        };
+                        results.push((i, count(&mut position(*r#move), *r#move)));
+                    }
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(i, t)| (moves[i], t)).collect()
+}
+
+/// Node count below every legal move of `state`, with the table if any.
+pub fn divide(
+    state: &State,
+    depth: u8,
+    threads: usize,
+    table: Option<&PerftTable>,
+) -> Vec<(Move, u64)> {
+    assert!(depth > 0, "Divide needs a depth of at least 1");
+    split_root(state, threads, |position, _| {
+        let move_list = &mut MoveList::new();
+        match table {
+            Some(table) => hashed_perft(position, move_list, depth - 1, table),
+            None => {
+                let mut nodes = 0;
+                recursive_perft(position, move_list, depth - 1, &mut nodes);
+                nodes
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::hash::NoopHasher;
+
+    use super::*;
+
+    const INITIAL_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const POSITION_2: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+
+    #[test]
+    fn recursive_perft_test() {
+        let cases = [
+            // initial position
+            (INITIAL_FEN, 1, 20),
+            (INITIAL_FEN, 2, 400),
+            (INITIAL_FEN, 3, 8902),
+            (INITIAL_FEN, 4, 197281),
+            // position 2
+            (POSITION_2, 1, 48),
+            (POSITION_2, 2, 2039),
+            (POSITION_2, 3, 97862),
+            // position 3
+            (POSITION_3, 1, 14),
+            (POSITION_3, 2, 191),
+            (POSITION_3, 3, 2812),
+            (POSITION_3, 4, 43238),
+        ];
+        for (fen, depth, nodes) in cases {
+            let mut position = Position::from_fen(fen, NoopHasher {});
+            dbg!(depth);
+            let mut move_list = MoveList::new();
+            let mut count = 0;
+            recursive_perft(&mut position, &mut move_list, depth, &mut count);
+            assert_eq!(count, nodes);
+        }
+    }
+
+    fn hashed_divide_total(fen: &str, depth: u8) -> u64 {
+        let table = PerftTable::new(16);
+        divide(&State::from_fen(fen), depth, 4, Some(&table))
+            .iter()
+            .map(|(_, nodes)| nodes)
+            .sum()
+    }
+
+    #[test]
+    fn hashed_perft_test() {
+        assert_eq!(hashed_divide_total(INITIAL_FEN, 5), 4865609);
+        assert_eq!(hashed_divide_total(POSITION_2, 4), 4085603);
+        assert_eq!(hashed_divide_total(POSITION_3, 5), 674624);
+    }
+
+    #[test]
+    #[ignore = "slow in debug builds"]
+    fn deep_hashed_perft_test() {
+        assert_eq!(hashed_divide_total(INITIAL_FEN, 6), 119060324);
+        assert_eq!(hashed_divide_total(POSITION_2, 5), 193690690);
+        assert_eq!(hashed_divide_total(POSITION_3, 6), 11030083);
+    }
+
+    #[test]
+    fn test_divide() {
+        let state = State::from_fen(POSITION_3);
+        let plain = divide(&state, 3, 1, None);
+        let table = PerftTable::new(1);
+        // Same moves in the same order whatever the number of threads
+        assert_eq!(plain, divide(&state, 3, 3, Some(&table)));
+        assert_eq!(plain.len(), 14);
+        assert_eq!(plain.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2812);
+    }
+}
@@ -0,0 +1,193 @@
+// Node type statistics for every depth of the perft tree, as listed in the
+// usual perft result tables: captures (en passant included), en passant
+// captures, castles, promotions, checks and checkmates.
+
+use std::{fmt::Display, ops::AddAssign};
+
+use chess_core::{
+    hash::Hasher,
+    r#move::{Move, MoveCode, MoveList},
+    position::Position,
+    state::State,
+};
+
+use crate::perft::split_root;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    pub mates: u64,
+}
+
+impl NodeStats {
+    pub const HEADER: &str = "depth        nodes     captures         e.p.      castles   promotions       checks        mates";
+}
+
+impl AddAssign for NodeStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.nodes += rhs.nodes;
+        self.captures += rhs.captures;
+        self.en_passants += rhs.en_passants;
+        self.castles += rhs.castles;
+        self.promotions += rhs.promotions;
+        self.checks += rhs.checks;
+        self.mates += rhs.mates;
+    }
+}
+
+impl Display for NodeStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>12} {:>12} {:>12} {:>12} {:>12} {:>12} {:>12}",
+            self.nodes,
+            self.captures,
+            self.en_passants,
+            self.castles,
+            self.promotions,
+            self.checks,
+            self.mates
+        )
+    }
+}
+
+fn has_legal_move<H: Hasher>(position: &mut Position<H>, move_list: &mut MoveList) -> bool {
+    move_list.new_ply();
+    position.pseudo_legal_moves(move_list);
+    let ply_number = move_list.ply_number();
+    let found = (0..move_list.ply_size(ply_number)).any(|m| {
+        let m = move_list.r#move(ply_number, m);
+        position.make(m);
+        let legal = position.was_move_legal();
+        position.unmake(m);
+        legal
+    });
+    move_list.drop_current_ply();
+    found
+}
+
+/// Count the node reached by `move`, which was just made in `position`.
+fn count_node<H: Hasher>(
+    position: &mut Position<H>,
+    move_list: &mut MoveList,
+    r#move: Move,
+    stats: &mut NodeStats,
+) {
+    let code = r#move.code();
+    stats.nodes += 1;
+    stats.captures += code.is_capture() as u64;
+    stats.en_passants += (code == MoveCode::EnPassant) as u64;
+    stats.castles += code.as_castle().is_some() as u64;
+    stats.promotions += code.as_promotion().is_some() as u64;
+    if position.state.get().is_check() {
+        stats.checks += 1;
+        stats.mates += !has_legal_move(position, move_list) as u64;
+    }
+}
+
+/// Add the nodes below `position` to `stats`, the first entry for its children.
+fn collect<H: Hasher>(
+    position: &mut Position<H>,
+    move_list: &mut MoveList,
+    stats: &mut [NodeStats],
+) {
+    let Some((children, below)) = stats.split_first_mut() else {
+        return;
+    };
+    move_list.new_ply();
+    position.pseudo_legal_moves(move_list);
+    let ply_number = move_list.ply_number();
+    for m in 0..move_list.ply_size(ply_number) {
+        let m = move_list.r#move(ply_number, m);
+        position.make(m);
+        if position.was_move_legal() {
+            count_node(position, move_list, m, children);
+            collect(position, move_list, below);
+        }
+        position.unmake(m);
+    }
+    move_list.drop_current_ply();
+}
+
+/// Statistics of the nodes at each depth from 1 to `depth`.
+pub fn node_stats(state: &State, depth: u8, threads: usize) -> Vec<NodeStats> {
+    let depth = depth as usize;
+    let mut stats = vec![NodeStats::default(); depth];
+    if depth == 0 {
+        return stats;
+    }
+    let subtrees = split_root(state, threads, |position, r#move| {
+        let move_list = &mut MoveList::new();
+        let mut stats = vec![NodeStats::default(); depth];
+        count_node(position, move_list, r#move, &mut stats[0]);
+        collect(position, move_list, &mut stats[1..]);
+        stats
+    });
+    for (_, subtree) in subtrees {
+        for (total, s) in stats.iter_mut().zip(subtree) {
+            *total += s;
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(
+        nodes: u64,
+        captures: u64,
+        en_passants: u64,
+        castles: u64,
+        checks: u64,
+        mates: u64,
+    ) -> NodeStats {
+        NodeStats {
+            nodes,
+            captures,
+            en_passants,
+            castles,
+            promotions: 0,
+            checks,
+            mates,
+        }
+    }
+
+    #[test]
+    fn test_node_stats() {
+        let state = State::default();
+        assert_eq!(
+            node_stats(&state, 4, 2),
+            [
+                stats(20, 0, 0, 0, 0, 0),
+                stats(400, 0, 0, 0, 0, 0),
+                stats(8902, 34, 0, 0, 12, 0),
+                stats(197281, 1576, 0, 0, 469, 8),
+            ]
+        );
+
+        let state =
+            State::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        assert_eq!(
+            node_stats(&state, 3, 4),
+            [
+                stats(48, 8, 0, 2, 0, 0),
+                stats(2039, 351, 1, 91, 3, 0),
+                stats(97862, 17102, 45, 3162, 993, 1),
+            ]
+        );
+
+        let state = State::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1");
+        let stats = node_stats(&state, 2, 1);
+        assert_eq!((stats[0].nodes, stats[1].nodes), (24, 496));
+        // The g2 pawn promotes on g1, f1 or h1
+        assert_eq!(stats[0].promotions, 12);
+        assert!(node_stats(&state, 0, 1).is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+// Perft suites are EPD files with the expected node counts as operations,
+//   rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400
+
+use chess_core::epd::{Epd, parse_epd_file};
+
+pub struct SuiteCase {
+    pub epd: Epd,
+    /// Depths and node counts, by increasing depth.
+    pub expected: Vec<(u8, u64)>,
+}
+
+fn parse_case(epd: Epd) -> Result<SuiteCase, &'static str> {
+    let mut expected = epd
+        .operations
+        .iter()
+        .filter_map(|(opcode, operands)| Some((opcode.strip_prefix('D')?, operands)))
+        .map(|(depth, operands)| {
+            let depth = depth.parse().map_err(|_| "Perft depth malformed")?;
+            let nodes = match operands.as_slice() {
+                [nodes] => nodes.parse().map_err(|_| "Perft node count malformed")?,
+                _ => return Err("Perft depth needs a single node count"),
+            };
+            Ok((depth, nodes))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if expected.iter().any(|(depth, _)| *depth == 0) {
+        return Err("Perft depth must be at least 1");
+    }
+    expected.sort();
+    Ok(SuiteCase { epd, expected })
+}
+
+pub fn parse_suite(text: &str) -> Result<Vec<SuiteCase>, &'static str> {
+    parse_epd_file(text)?.into_iter().map(parse_case).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_suite() {
+        let suite = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D2 400 ;D1 20\n\n\
+                     8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - ;D1 14 ;id \"position 3\"\n";
+        let cases = parse_suite(suite).unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].expected, [(1, 20), (2, 400)]);
+        assert_eq!(cases[1].expected, [(1, 14)]);
+        assert_eq!(cases[1].epd.id(), Some("position 3"));
+
+        assert!(parse_suite("8/8/8/8/8/8/8/K1k5 w - - ;D1 x").is_err());
+        assert!(parse_suite("8/8/8/8/8/8/8/K1k5 w - - ;Dx 3").is_err());
+        assert!(parse_suite("8/8/8/8/8/8/8/K1k5 w - - ;D0 1").is_err());
+    }
+}
@@ -0,0 +1,401 @@
+// Time controls and the clock of a game. A time control is a list of periods
+// written as in the PGN `TimeControl` tag, separated by colons:
+//   - `300` gives 5 minutes for the rest of the game, sudden death
+//   - `40/5400` gives 90 minutes for the next 40 moves
+//   - `+30` after the time adds a Fischer increment of 30 seconds per move,
+//     `d5` a simple delay of 5 seconds, `b5` a Bronstein delay
+// so `40/5400+30:1800+30` gives 90 minutes for 40 moves, then 30 minutes for
+// the rest of the game, with 30 seconds added per move throughout. The time
+// of a period is added to what is left of the previous one, and a last period
+// with a number of moves repeats. Delays aren't part of the PGN standard, and
+// sandclock controls (`*180`) aren't supported.
+//
+// A simple delay is waited before the clock starts to run on each move. A
+// Bronstein delay gives back the time used on the move, up to the delay.
+
+use std::{fmt::Display, time::Duration};
+
+use crate::{color::Color, pgn::PgnGame};
+
+/// Moves assumed left in the game when the time control doesn't tell.
+pub const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bonus {
+    Increment(Duration),
+    Bronstein(Duration),
+    Delay(Duration),
+}
+
+impl Bonus {
+    fn duration(self) -> Duration {
+        match self {
+            Bonus::Increment(time) | Bonus::Bronstein(time) | Bonus::Delay(time) => time,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Period {
+    /// Moves to play in the period, `None` for the rest of the game.
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub bonus: Option<Bonus>,
+}
+
+/// Periods in order, none for a game without time control.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeControl {
+    pub periods: Vec<Period>,
+}
+
+fn seconds(text: &str) -> Result<Duration, &'static str> {
+    text.parse()
+        .map(Duration::from_secs)
+        .map_err(|_| "Time control malformed")
+}
+
+impl Period {
+    fn parse(text: &str) -> Result<Self, &'static str> {
+        let (moves, rest) = match text.split_once('/') {
+            Some((moves, rest)) => {
+                let moves = moves.parse().map_err(|_| "Time control malformed")?;
+                if moves == 0 {
+                    return Err("Time control period needs moves");
+                }
+                (Some(moves), rest)
+            }
+            None => (None, text),
+        };
+        let bonus_start = rest.find(['+', 'd', 'b']).unwrap_or(rest.len());
+        let (time, bonus) = rest.split_at(bonus_start);
+        let bonus = match bonus.split_at_checked(1) {
+            Some(("+", time)) => Some(Bonus::Increment(seconds(time)?)),
+            Some(("b", time)) => Some(Bonus::Bronstein(seconds(time)?)),
+            Some(("d", time)) => Some(Bonus::Delay(seconds(time)?)),
+            _ => None,
+        };
+        Ok(Period {
+            moves,
+            time: seconds(time)?,
+            bonus,
+        })
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(moves) = self.moves {
+            write!(f, "{}/", moves)?;
+        }
+        write!(f, "{}", self.time.as_secs())?;
+        match self.bonus {
+            Some(Bonus::Increment(time)) => write!(f, "+{}", time.as_secs()),
+            Some(Bonus::Bronstein(time)) => write!(f, "b{}", time.as_secs()),
+            Some(Bonus::Delay(time)) => write!(f, "d{}", time.as_secs()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TimeControl {
+    /// Parse a time control as written in the PGN tag, `-` for none.
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let text = text.trim();
+        if text == "-" {
+            return Ok(TimeControl::default());
+        }
+        if text.starts_with('*') {
+            return Err("Sandclock time controls are not supported");
+        }
+        let periods = text
+            .split(':')
+            .map(Period::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TimeControl { periods })
+    }
+
+    /// Sudden death with a Fischer increment, like 5+3 blitz.
+    pub fn fischer(time: Duration, increment: Duration) -> Self {
+        TimeControl {
+            periods: vec![Period {
+                moves: None,
+                time,
+                bonus: Some(Bonus::Increment(increment)).filter(|_| !increment.is_zero()),
+            }],
+        }
+    }
+
+    pub fn is_untimed(&self) -> bool {
+        self.periods.is_empty()
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_untimed() {
+            return write!(f, "-");
+        }
+        for (i, period) in self.periods.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{}", period)?;
+        }
+        Ok(())
+    }
+}
+
+impl PgnGame {
+    /// Time control of the `TimeControl` tag, `None` when missing or unknown.
+    pub fn time_control(&self) -> Result<Option<TimeControl>, &'static str> {
+        match self.tag("TimeControl") {
+            None | Some("?") => Ok(None),
+            Some(text) => TimeControl::parse(text).map(Some),
+        }
+    }
+}
+
+/// Time to spend on a move with `left` on the clock, `bonus` added per move
+/// and `moves_to_go` before more time is added, when known.
+pub fn move_budget(left: Duration, bonus: Duration, moves_to_go: Option<u32>) -> Duration {
+    let moves = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    (left / moves + bonus / 2).min(left / 2)
+}
+
+/// State of both clocks. The clock doesn't follow the game: each call says
+/// which side it is about, so taking back a move doesn't give its time back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clock {
+    control: TimeControl,
+    /// Time left to each side, white first.
+    remaining: [Duration; 2],
+    /// Period each side is in, and the moves it played in it.
+    periods: [usize; 2],
+    moves: [u32; 2],
+    flagged: Option<Color>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Result<Self, &'static str> {
+        let first = control
+            .periods
+            .first()
+            .ok_or("An untimed game has no clock")?;
+        Ok(Clock {
+            remaining: [first.time; 2],
+            periods: [0; 2],
+            moves: [0; 2],
+            flagged: None,
+            control,
+        })
+    }
+
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// Time left to `color` between its moves.
+    pub fn remaining(&self, color: Color) -> Duration {
+        self.remaining[color as usize]
+    }
+
+    /// Side whose time ran out, if any.
+    pub fn flagged(&self) -> Option<Color> {
+        self.flagged
+    }
+
+    fn period(&self, color: Color) -> &Period {
+        &self.control.periods[self.periods[color as usize]]
+    }
+
+    /// Time taken from the clock of `color` after thinking `elapsed` on a move.
+    fn spent(&self, color: Color, elapsed: Duration) -> Duration {
+        match self.period(color).bonus {
+            Some(Bonus::Delay(delay)) => elapsed.saturating_sub(delay),
+            _ => elapsed,
+        }
+    }
+
+    /// Time left to `color` when it has been thinking `elapsed` on its move.
+    pub fn time_left(&self, color: Color, elapsed: Duration) -> Duration {
+        self.remaining(color)
+            .saturating_sub(self.spent(color, elapsed))
+    }
+
+    /// Flag `color` if its time ran out after thinking `elapsed` on its
+    /// move, and return the side whose time ran out, if any.
+    pub fn check(&mut self, color: Color, elapsed: Duration) -> Option<Color> {
+        if self.flagged.is_none() && self.spent(color, elapsed) > self.remaining(color) {
+            self.remaining[color as usize] = Duration::ZERO;
+            self.flagged = Some(color);
+        }
+        self.flagged
+    }
+
+    /// `color` played a move after thinking `elapsed`: its time is taken
+    /// from its clock and the bonus and the time of a new period added.
+    /// Returns the side whose time ran out, if any.
+    pub fn press(&mut self, color: Color, elapsed: Duration) -> Option<Color> {
+        if self.check(color, elapsed).is_some() {
+            return self.flagged;
+        }
+        let side = color as usize;
+        let period = *self.period(color);
+        self.remaining[side] -= self.spent(color, elapsed);
+        match period.bonus {
+            Some(Bonus::Increment(increment)) => self.remaining[side] += increment,
+            Some(Bonus::Bronstein(delay)) => self.remaining[side] += elapsed.min(delay),
+            _ => {}
+        }
+        self.moves[side] += 1;
+        if period.moves == Some(self.moves[side]) {
+            // The last period repeats
+            let next = (self.periods[side] + 1).min(self.control.periods.len() - 1);
+            self.periods[side] = next;
+            self.moves[side] = 0;
+            self.remaining[side] += self.control.periods[next].time;
+        }
+        None
+    }
+
+    /// Time `color` should spend on its next move, for the search.
+    pub fn budget(&self, color: Color) -> Duration {
+        let period = self.period(color);
+        let moves_to_go = period.moves.map(|moves| moves - self.moves[color as usize]);
+        let left = self.remaining(color);
+        match period.bonus {
+            // A simple delay is free time on this move, not time banked for later
+            Some(Bonus::Delay(delay)) => move_budget(left, Duration::ZERO, moves_to_go) + delay,
+            bonus => move_budget(
+                left,
+                bonus.map_or(Duration::ZERO, Bonus::duration),
+                moves_to_go,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::parse_pgn;
+
+    use super::*;
+
+    const fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_parse() {
+        let control = TimeControl::parse("40/5400+30:1800+30").unwrap();
+        assert_eq!(
+            control.periods,
+            [
+                Period {
+                    moves: Some(40),
+                    time: secs(5400),
+                    bonus: Some(Bonus::Increment(secs(30)))
+                },
+                Period {
+                    moves: None,
+                    time: secs(1800),
+                    bonus: Some(Bonus::Increment(secs(30)))
+                }
+            ]
+        );
+        for text in [
+            "40/5400+30:1800+30",
+            "300",
+            "300d5",
+            "900b10",
+            "40/7200:3600",
+            "-",
+        ] {
+            assert_eq!(TimeControl::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(
+            TimeControl::fischer(secs(180), secs(2)).to_string(),
+            "180+2"
+        );
+        assert!(TimeControl::parse("-").unwrap().is_untimed());
+        for text in ["", "5+", "0/300", "x/300", "300+3:", "*180", "300s5"] {
+            assert!(TimeControl::parse(text).is_err(), "{}", text);
+        }
+
+        let game = &parse_pgn("[TimeControl \"180+2\"]\n\n*").unwrap()[0];
+        assert_eq!(
+            game.time_control(),
+            Ok(Some(TimeControl::fischer(secs(180), secs(2))))
+        );
+        let game = &parse_pgn("[TimeControl \"?\"]\n\n*").unwrap()[0];
+        assert_eq!(game.time_control(), Ok(None));
+    }
+
+    #[test]
+    fn test_bonus() {
+        let mut clock = Clock::new(TimeControl::parse("60+2").unwrap()).unwrap();
+        assert_eq!(clock.press(Color::White, secs(10)), None);
+        assert_eq!(clock.remaining(Color::White), secs(52));
+        assert_eq!(clock.remaining(Color::Black), secs(60));
+
+        let mut clock = Clock::new(TimeControl::parse("60b5").unwrap()).unwrap();
+        clock.press(Color::White, secs(3));
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        clock.press(Color::White, secs(10));
+        assert_eq!(clock.remaining(Color::White), secs(55));
+
+        let mut clock = Clock::new(TimeControl::parse("60d5").unwrap()).unwrap();
+        assert_eq!(clock.time_left(Color::Black, secs(64)), secs(1));
+        clock.press(Color::Black, secs(3));
+        assert_eq!(clock.remaining(Color::Black), secs(60));
+        clock.press(Color::Black, secs(10));
+        assert_eq!(clock.remaining(Color::Black), secs(55));
+        assert!(Clock::new(TimeControl::default()).is_err());
+    }
+
+    #[test]
+    fn test_periods() {
+        let mut clock = Clock::new(TimeControl::parse("2/100:50+10").unwrap()).unwrap();
+        clock.press(Color::White, secs(20));
+        assert_eq!(clock.remaining(Color::White), secs(80));
+        // The second period starts after the second move, its increment after the third
+        clock.press(Color::White, secs(20));
+        assert_eq!(clock.remaining(Color::White), secs(110));
+        clock.press(Color::White, secs(20));
+        assert_eq!(clock.remaining(Color::White), secs(100));
+
+        // A last period with moves repeats
+        let mut clock = Clock::new(TimeControl::parse("1/100").unwrap()).unwrap();
+        clock.press(Color::White, secs(30));
+        assert_eq!(clock.remaining(Color::White), secs(170));
+        clock.press(Color::White, secs(30));
+        assert_eq!(clock.remaining(Color::White), secs(240));
+    }
+
+    #[test]
+    fn test_flag() {
+        let mut clock = Clock::new(TimeControl::parse("10+5").unwrap()).unwrap();
+        assert_eq!(clock.check(Color::White, secs(10)), None);
+        assert_eq!(clock.check(Color::White, secs(11)), Some(Color::White));
+        assert_eq!(clock.remaining(Color::White), Duration::ZERO);
+        // The first flag stays
+        assert_eq!(clock.press(Color::Black, secs(20)), Some(Color::White));
+        assert_eq!(clock.remaining(Color::Black), secs(10));
+
+        let mut clock = Clock::new(TimeControl::parse("10").unwrap()).unwrap();
+        assert_eq!(clock.press(Color::Black, secs(12)), Some(Color::Black));
+    }
+
+    #[test]
+    fn test_budget() {
+        assert_eq!(move_budget(secs(60), Duration::ZERO, None), secs(2));
+        assert_eq!(move_budget(secs(10), secs(2), Some(1)), secs(5));
+        let mut clock = Clock::new(TimeControl::parse("40/4000+10:60").unwrap()).unwrap();
+        assert_eq!(clock.budget(Color::White), secs(105));
+        clock.press(Color::White, secs(10));
+        assert_eq!(clock.budget(Color::White), secs(4000) / 39 + secs(5));
+        let clock = Clock::new(TimeControl::parse("300d3").unwrap()).unwrap();
+        assert_eq!(clock.budget(Color::Black), secs(10) + secs(3));
+    }
+}
@@ -0,0 +1,197 @@
+// Classification of openings by their ECO code and name. The table, in
+// `eco.tsv`, gives a code, a name and the SAN moves of each line from the
+// starting position. Lines are found by the Polyglot key of the position they
+// reach, so a game reaching it in another move order still matches. The key
+// only counts the en passant square when a capture is possible, so a line
+// ending with a double pawn push matches its transpositions too.
+//
+// The table holds the common lines of each ECO volume, not the whole code.
+
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+use crate::{
+    hash::{HashedState, polyglot::PolyglotHasher, polyglot::polyglot_key},
+    r#move::{Move, san::from_san},
+    pgn::PgnGame,
+    position::Position,
+    state::State,
+};
+
+const TABLE: &str = include_str!("eco.tsv");
+
+/// A named opening line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+    /// SAN moves from the starting position, separated by spaces.
+    pub moves: &'static str,
+}
+
+struct EcoTable {
+    openings: Vec<Opening>,
+    /// Index in `openings` by the key of the position reached by the line.
+    by_key: HashMap<u64, usize>,
+}
+
+impl EcoTable {
+    fn new() -> Self {
+        let mut openings = Vec::new();
+        let mut by_key = HashMap::new();
+        for line in TABLE.lines().filter(|line| !line.is_empty()) {
+            let mut fields = line.split('\t');
+            let (Some(eco), Some(name), Some(moves)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                panic!("ECO table line needs a code, a name and moves: {}", line);
+            };
+            let mut position =
+                Position::new(HashedState::new(State::default(), PolyglotHasher::new()));
+            for san in moves.split_whitespace() {
+                let r#move = from_san(&mut position, san)
+                    .unwrap_or_else(|e| panic!("{} in ECO table line {}", e, line));
+                position.make(r#move);
+            }
+            // The first line reaching a position names it
+            by_key
+                .entry(position.state.get_hash())
+                .or_insert(openings.len());
+            openings.push(Opening { eco, name, moves });
+        }
+        EcoTable { openings, by_key }
+    }
+
+    fn get(&self, key: u64) -> Option<&Opening> {
+        self.by_key.get(&key).map(|index| &self.openings[*index])
+    }
+}
+
+lazy_static! {
+    static ref ECO_TABLE: EcoTable = EcoTable::new();
+}
+
+/// Every line of the table.
+pub fn openings() -> &'static [Opening] {
+    &ECO_TABLE.openings
+}
+
+/// The opening whose line reaches exactly this position.
+pub fn classify(state: &State) -> Option<&'static Opening> {
+    ECO_TABLE.get(polyglot_key(state))
+}
+
+/// The opening of the last position of the game found in the table, the
+/// deepest named line the game went through.
+pub fn classify_game(start: &State, moves: &[Move]) -> Option<&'static Opening> {
+    let mut position = Position::new(HashedState::new(start.clone(), PolyglotHasher::new()));
+    let mut opening = ECO_TABLE.get(position.state.get_hash());
+    for r#move in moves {
+        position.make(*r#move);
+        opening = ECO_TABLE.get(position.state.get_hash()).or(opening);
+    }
+    opening
+}
+
+impl PgnGame {
+    /// The opening of the game, from its moves up to the first illegal one.
+    /// Fails if the `FEN` tag is malformed.
+    pub fn opening(&self) -> Result<Option<&'static Opening>, &'static str> {
+        let start = self.starting_state()?;
+        let mut position = Position::new(HashedState::new(start.clone(), PolyglotHasher::new()));
+        let mut moves = Vec::new();
+        for san in &self.moves {
+            let Ok(r#move) = from_san(&mut position, san) else {
+                break;
+            };
+            position.make(r#move);
+            moves.push(r#move);
+        }
+        Ok(classify_game(&start, &moves))
+    }
+
+    /// Add the `ECO` and `Opening` tags of the game's opening, unless the
+    /// game already has them.
+    pub fn fill_opening_tags(&mut self) -> Result<(), &'static str> {
+        let Some(opening) = self.opening()? else {
+            return Ok(());
+        };
+        for (name, value) in [("ECO", opening.eco), ("Opening", opening.name)] {
+            if self.tag(name).is_none() {
+                self.tags.push((name.to_string(), value.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::parse_pgn;
+
+    use super::*;
+
+    fn game_opening(moves: &str) -> Option<(&'static str, &'static str)> {
+        parse_pgn(moves).unwrap()[0]
+            .opening()
+            .unwrap()
+            .map(|opening| (opening.eco, opening.name))
+    }
+
+    #[test]
+    fn test_table() {
+        // Every line is legal and reaches its own position
+        assert_eq!(openings().len(), TABLE.lines().count());
+        assert_eq!(ECO_TABLE.by_key.len(), openings().len());
+        assert_eq!(classify(&State::default()), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            game_opening("1. e4 c5 *"),
+            Some(("B20", "Sicilian Defense"))
+        );
+        // The deepest line, after the game left the table
+        assert_eq!(
+            game_opening("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 d6 5. c3 *"),
+            Some(("C70", "Ruy Lopez: Morphy Defense"))
+        );
+        // Transpositions, the last one with a double push
+        assert_eq!(
+            game_opening("1. Nf3 d5 2. d4 Nf6 3. c4 e6 4. Nc3 c6 *"),
+            Some(("D43", "Semi-Slav Defense"))
+        );
+        assert_eq!(
+            game_opening("1. c4 e6 2. d4 d5 *"),
+            Some(("D30", "Queen's Gambit Declined"))
+        );
+        assert_eq!(game_opening("1. a3 *"), None);
+        // Moves after an illegal one are left out
+        assert_eq!(
+            game_opening("1. d4 d5 2. Ke3 *"),
+            Some(("D00", "Queen's Pawn Game"))
+        );
+    }
+
+    #[test]
+    fn test_fill_opening_tags() {
+        let mut game = parse_pgn("[ECO \"D06\"]\n\n1. d4 d5 2. c4 e6 *").unwrap()[0].clone();
+        assert_eq!(game.fill_opening_tags(), Ok(()));
+        assert_eq!(game.tag("ECO"), Some("D06"));
+        assert_eq!(game.tag("Opening"), Some("Queen's Gambit Declined"));
+        assert!(
+            game.to_string()
+                .contains("[Opening \"Queen's Gambit Declined\"]")
+        );
+
+        let mut game = parse_pgn("1. a3 *").unwrap()[0].clone();
+        assert_eq!(game.fill_opening_tags(), Ok(()));
+        assert!(game.tags.is_empty());
+
+        let mut game = parse_pgn("[FEN \"8/8 w - - 0 1\"]\n\n*").unwrap()[0].clone();
+        assert!(game.opening().is_err());
+        assert!(game.fill_opening_tags().is_err());
+    }
+}
@@ -0,0 +1,235 @@
+// Extended Position Description: the first four fields of a FEN followed by
+// operations, each an opcode and its operands ended by a semicolon, as in
+//   r1b1k2r/pp3ppp/8/8/8/8/PPP2PPP/R3KB1R w KQkq - bm Bb5+; id "WAC.042";
+// Operands with spaces or semicolons are quoted. Some suites, like perft suites,
+// also keep the halfmove and fullmove numbers after the board.
+
+use std::fmt::Display;
+
+use crate::state::State;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epd {
+    pub state: State,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// Split operations on the semicolons outside quotes, and operands on whitespace.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, &'static str> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if quoted => word.push(c),
+            c if c.is_whitespace() || c == ';' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                if c == ';' && !words.is_empty() {
+                    operations.push(std::mem::take(&mut words));
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quoted {
+        return Err("Unterminated string operand");
+    }
+    if in_word {
+        words.push(word);
+    }
+    // The last semicolon is sometimes left out
+    if !words.is_empty() {
+        operations.push(words);
+    }
+
+    operations
+        .into_iter()
+        .map(|mut words| {
+            let opcode = words.remove(0);
+            if !opcode.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return Err("Opcode must start with a letter");
+            }
+            Ok((opcode, words))
+        })
+        .collect()
+}
+
+fn needs_quotes(opcode: &str, operand: &str) -> bool {
+    let comment = opcode.len() == 2
+        && opcode.starts_with('c')
+        && opcode.ends_with(|c: char| c.is_ascii_digit());
+    opcode == "id"
+        || comment
+        || operand.is_empty()
+        || operand.contains(|c: char| c.is_whitespace() || c == ';')
+}
+
+/// Remove the first whitespace separated field of `rest` and return it.
+fn next_field<'a>(rest: &mut &'a str) -> &'a str {
+    let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    *rest = tail.trim_start();
+    field
+}
+
+impl Epd {
+    pub fn parse(line: &str) -> Result<Self, &'static str> {
+        let mut rest = line.trim();
+        let board = (0..4).map(|_| next_field(&mut rest)).collect::<Vec<_>>();
+        if board.iter().any(|field| field.is_empty()) {
+            return Err(
+                "EPD needs a board, a side to move, castling rights and an en passant square",
+            );
+        }
+
+        // Halfmove and fullmove numbers, which an opcode can't start with
+        let mut halfmove = "0";
+        for i in 0..2 {
+            let field = rest.split_whitespace().next().unwrap_or("");
+            if field.is_empty() || !field.chars().all(|c| c.is_ascii_digit()) {
+                break;
+            }
+            next_field(&mut rest);
+            if i == 0 {
+                halfmove = field;
+            }
+        }
+
+        let fen = format!("{} {}", board.join(" "), halfmove);
+        Ok(Epd {
+            state: State::try_from_fen(&fen)?,
+            operations: parse_operations(rest)?,
+        })
+    }
+
+    /// Operands of the first operation with `opcode`.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Single operand of the operation with `opcode`, like the `id` or a comment.
+    pub fn operand(&self, opcode: &str) -> Option<&str> {
+        self.operation(opcode)
+            .and_then(|operands| operands.first())
+            .map(String::as_str)
+    }
+
+    /// Replace the operands of `opcode`, or add the operation at the end.
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(op, _)| op == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operand("id")
+    }
+
+    pub fn best_moves(&self) -> &[String] {
+        self.operation("bm").unwrap_or_default()
+    }
+
+    pub fn avoid_moves(&self) -> &[String] {
+        self.operation("am").unwrap_or_default()
+    }
+}
+
+impl Display for Epd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The EPD is the FEN without the move numbers
+        let fen = self.state.to_fen();
+        let fields = fen.split_whitespace().take(4).collect::<Vec<_>>();
+        write!(f, "{}", fields.join(" "))?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                if needs_quotes(opcode, operand) {
+                    write!(f, " \"{}\"", operand)?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse every position of a suite, skipping blank lines.
+pub fn parse_epd_file(text: &str) -> Result<Vec<Epd>, &'static str> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(Epd::parse)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_epd() {
+        let epd = Epd::parse(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"mate; in 3\"; acd 12;",
+        )
+        .unwrap();
+        assert_eq!(epd.state.flags.active_color(), crate::color::Color::White);
+        assert_eq!(epd.best_moves(), ["Qg6"]);
+        assert!(epd.avoid_moves().is_empty());
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.operand("c0"), Some("mate; in 3"));
+        assert_eq!(epd.operand("acd"), Some("12"));
+
+        // Several operands and no final semicolon
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - bm Ra8+ Kd2 ; am O-O-O").unwrap();
+        assert_eq!(epd.best_moves(), ["Ra8+", "Kd2"]);
+        assert_eq!(epd.avoid_moves(), ["O-O-O"]);
+
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K2 w - - bm Ra8+;").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w - - c0 \"open").is_err());
+    }
+
+    #[test]
+    fn test_parse_perft_epd() {
+        let epd =
+            Epd::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400")
+                .unwrap();
+        assert_eq!(epd.state, State::default());
+        assert_eq!(epd.operand("D1"), Some("20"));
+        assert_eq!(epd.operand("D2"), Some("400"));
+    }
+
+    #[test]
+    fn test_write_epd() {
+        let line = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"a b\";";
+        let mut epd = Epd::parse(line).unwrap();
+        assert_eq!(epd.to_string(), line);
+        epd.set_operation("acd", vec!["7".to_string()]);
+        epd.set_operation("bm", vec!["Qg6".to_string(), "Qh7".to_string()]);
+        let written = epd.to_string();
+        assert!(written.ends_with("bm Qg6 Qh7; id \"WAC.001\"; c0 \"a b\"; acd 7;"));
+        assert_eq!(Epd::parse(&written).unwrap(), epd);
+    }
+
+    #[test]
+    fn test_parse_epd_file() {
+        let suite = "4k3/8/8/8/8/8/8/R3K3 w Q - bm Ra8+;\n\n8/8/8/8/8/8/8/K1k5 b - - id \"x\";\n";
+        assert_eq!(parse_epd_file(suite).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,603 @@
+// Index of the positions of a PGN collection, to explore it offline. Every
+// game is replayed and each position it goes through is recorded under its
+// Polyglot key, with the game, the ply and the move played next, so the index
+// answers which games reach a position and which moves were played from it
+// with what results. The material of the positions is recorded too, once per
+// game and material, to find games reaching an ending like KRP against KR.
+//
+// Games are numbered in the order of the PGN file, games with an illegal move
+// keep their number but have no positions. The index is stored on disk as:
+//   - magic "CEPX" and version byte
+//   - number of games (u32), then the result of each game (u8)
+//   - number of positions (u32), then 16 byte entries sorted by key: the
+//     Polyglot key (u64), the game (u32), the ply (u16) and the move played
+//     next (u16), 0 after the last move
+//   - number of materials (u32), then 14 byte entries sorted by material:
+//     the material (u64), the game (u32) and the first ply with it (u16)
+// The Polyglot key only counts the en passant square when a pawn can take on
+// it, so a double push matches its transpositions as long as it can't be
+// taken. All numbers are little endian.
+
+use std::{fmt::Display, fs, io, path::Path};
+
+use crate::{
+    color::Color,
+    hash::{
+        HashedState,
+        polyglot::{PolyglotHasher, polyglot_key},
+    },
+    r#move::{Move, san::from_san},
+    pgn::{PgnGame, parse_pgn},
+    position::Position,
+    state::{State, chess_board::PieceType},
+};
+
+const MAGIC: &[u8; 4] = b"CEPX";
+const VERSION: u8 = 2;
+
+const POSITION_SIZE: usize = 16;
+const MATERIAL_SIZE: usize = 14;
+
+/// Pieces counted in a material, in the order of the letters.
+const MATERIAL_PIECES: [(PieceType, char); 5] = [
+    (PieceType::Queen, 'Q'),
+    (PieceType::Rook, 'R'),
+    (PieceType::Bishop, 'B'),
+    (PieceType::Knight, 'N'),
+    (PieceType::Pawn, 'P'),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// Unfinished games, and games with an illegal move.
+    Unknown,
+}
+
+impl GameResult {
+    /// Result of a PGN result token.
+    pub fn from_pgn(result: &str) -> Self {
+        match result {
+            "1-0" => GameResult::WhiteWins,
+            "0-1" => GameResult::BlackWins,
+            "1/2-1/2" => GameResult::Draw,
+            _ => GameResult::Unknown,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, &'static str> {
+        match byte {
+            0 => Ok(GameResult::WhiteWins),
+            1 => Ok(GameResult::BlackWins),
+            2 => Ok(GameResult::Draw),
+            3 => Ok(GameResult::Unknown),
+            _ => Err("Invalid game result in index"),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Pieces of both sides besides the kings, e.g. KRP against KR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Material {
+    /// Counts of the pieces of white then black, in the order of `MATERIAL_PIECES`.
+    counts: [[u8; 5]; 2],
+}
+
+impl Material {
+    pub fn from_state(state: &State) -> Self {
+        let mut counts = [[0; 5]; 2];
+        for (color, side) in [(Color::White, 0), (Color::Black, 1)] {
+            for (i, (piece, _)) in MATERIAL_PIECES.iter().enumerate() {
+                counts[side][i] = state.boards[color][*piece].count_ones().min(15) as u8;
+            }
+        }
+        Material { counts }
+    }
+
+    /// Parse a material like `KRPvKR` or `KRP vs KR`, white first.
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let text = text.replace(' ', "");
+        let (white, black) = text
+            .split_once("vs")
+            .or_else(|| text.split_once('v'))
+            .ok_or("Material must give both sides, as in KRPvKR")?;
+        let mut counts = [[0; 5]; 2];
+        for (side, pieces) in [white, black].into_iter().enumerate() {
+            let pieces = pieces
+                .strip_prefix('K')
+                .ok_or("Each side of a material starts with its king")?;
+            for c in pieces.chars() {
+                let i = MATERIAL_PIECES
+                    .iter()
+                    .position(|(_, letter)| *letter == c)
+                    .ok_or("Unknown piece in material")?;
+                counts[side][i] += 1;
+            }
+        }
+        Ok(Material { counts })
+    }
+
+    /// The same material with the colors swapped.
+    pub fn swapped(self) -> Self {
+        let [white, black] = self.counts;
+        Material {
+            counts: [black, white],
+        }
+    }
+
+    fn to_bits(self) -> u64 {
+        self.counts
+            .iter()
+            .flatten()
+            .fold(0, |bits, count| bits << 4 | *count as u64)
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        let mut counts = [[0; 5]; 2];
+        for (i, count) in counts.iter_mut().flatten().rev().enumerate() {
+            *count = (bits >> (4 * i) & 0xF) as u8;
+        }
+        Material { counts }
+    }
+}
+
+impl Display for Material {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (side, counts) in self.counts.iter().enumerate() {
+            if side == 1 {
+                write!(f, "v")?;
+            }
+            write!(f, "K")?;
+            for (count, (_, letter)) in counts.iter().zip(MATERIAL_PIECES) {
+                for _ in 0..*count {
+                    write!(f, "{}", letter)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct PositionEntry {
+    key: u64,
+    game: u32,
+    ply: u16,
+    /// Bits of the move played next, 0 after the last move.
+    next: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct MaterialEntry {
+    material: u64,
+    game: u32,
+    ply: u16,
+}
+
+/// A game reaching a position or a material, at `ply` plies from its start.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameHit {
+    /// Number of the game in the PGN collection, from 0.
+    pub game: u32,
+    pub ply: u16,
+}
+
+/// How a move played from a position turned out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveStats {
+    pub r#move: Move,
+    pub games: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32,
+}
+
+impl MoveStats {
+    /// Percentages of white wins, draws and black wins, among the games with a result.
+    pub fn percentages(&self) -> [f64; 3] {
+        let decided = (self.white_wins + self.draws + self.black_wins).max(1) as f64;
+        [self.white_wins, self.draws, self.black_wins].map(|n| 100.0 * n as f64 / decided)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PositionIndex {
+    results: Vec<GameResult>,
+    positions: Vec<PositionEntry>,
+    materials: Vec<MaterialEntry>,
+}
+
+/// Bytes of an index not read yet.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], &'static str> {
+        let (taken, rest) = self.0.split_at_checked(size).ok_or("Truncated index")?;
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn count(&mut self) -> Result<usize, &'static str> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    }
+}
+
+/// Entries of a sorted slice whose field `key` equals `value`.
+fn equal_range<T>(entries: &[T], value: u64, key: impl Fn(&T) -> u64) -> &[T] {
+    let start = entries.partition_point(|e| key(e) < value);
+    let end = entries.partition_point(|e| key(e) <= value);
+    &entries[start..end]
+}
+
+impl PositionIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn game_count(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn position_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn result(&self, game: u32) -> Option<GameResult> {
+        self.results.get(game as usize).copied()
+    }
+
+    /// Positions and materials of the game, `Err` if it has an illegal move.
+    fn replay(
+        game: &PgnGame,
+        number: u32,
+    ) -> Result<(Vec<PositionEntry>, Vec<MaterialEntry>), &'static str> {
+        let state = game.starting_state()?;
+        let mut position = Position::new(HashedState::new(state, PolyglotHasher::new()));
+        let (mut positions, mut materials) = (Vec::new(), Vec::<MaterialEntry>::new());
+        for ply in 0..=game.moves.len() {
+            let r#move = match game.moves.get(ply) {
+                Some(san) => Some(from_san(&mut position, san)?),
+                None => None,
+            };
+            positions.push(PositionEntry {
+                key: position.state.get_hash(),
+                game: number,
+                ply: ply.min(u16::MAX as usize) as u16,
+                next: r#move.map_or(0, |m| m.into_bits()),
+            });
+            let material = Material::from_state(position.state.get()).to_bits();
+            if materials.iter().all(|m| m.material != material) {
+                materials.push(MaterialEntry {
+                    material,
+                    game: number,
+                    ply: ply.min(u16::MAX as usize) as u16,
+                });
+            }
+            if let Some(r#move) = r#move {
+                position.make(r#move);
+            }
+        }
+        Ok((positions, materials))
+    }
+
+    /// Add every game of a PGN collection, returns the number of games left
+    /// without positions because of an illegal move.
+    pub fn add_pgn(&mut self, pgn: &str) -> Result<usize, &'static str> {
+        let mut skipped = 0;
+        for game in parse_pgn(pgn)? {
+            let number = self.results.len() as u32;
+            match Self::replay(&game, number) {
+                Ok((positions, materials)) => {
+                    self.results.push(GameResult::from_pgn(&game.result));
+                    self.positions.extend(positions);
+                    self.materials.extend(materials);
+                }
+                Err(_) => {
+                    self.results.push(GameResult::Unknown);
+                    skipped += 1;
+                }
+            }
+        }
+        self.positions.sort_unstable();
+        self.materials.sort_unstable();
+        Ok(skipped)
+    }
+
+    /// Games reaching `state`, each once at the first ply it is reached.
+    pub fn games(&self, state: &State) -> Vec<GameHit> {
+        let mut hits: Vec<GameHit> = Vec::new();
+        for entry in equal_range(&self.positions, polyglot_key(state), |e| e.key) {
+            // Entries of a key are sorted by game then ply
+            if hits.last().is_none_or(|hit| hit.game != entry.game) {
+                hits.push(GameHit {
+                    game: entry.game,
+                    ply: entry.ply,
+                });
+            }
+        }
+        hits
+    }
+
+    /// Moves played from `state` with the results of their games, the most
+    /// played first. A game repeating the position counts once per move.
+    pub fn move_stats(&self, state: &State) -> Vec<MoveStats> {
+        let mut position = Position::new(HashedState::new(state.clone(), PolyglotHasher::new()));
+        let legal_moves = position.legal_moves();
+        let mut entries = equal_range(&self.positions, polyglot_key(state), |e| e.key)
+            .iter()
+            .filter(|e| e.next != 0)
+            .map(|e| (e.next, e.game))
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        entries.dedup();
+
+        let mut stats: Vec<MoveStats> = Vec::new();
+        for (next, game) in entries {
+            // Moves that aren't legal here come from a key collision
+            let Some(r#move) = legal_moves.iter().find(|m| m.into_bits() == next) else {
+                continue;
+            };
+            let index = match stats.iter().position(|s| s.r#move == *r#move) {
+                Some(index) => index,
+                None => {
+                    stats.push(MoveStats {
+                        r#move: *r#move,
+                        games: 0,
+                        white_wins: 0,
+                        draws: 0,
+                        black_wins: 0,
+                    });
+                    stats.len() - 1
+                }
+            };
+            let s = &mut stats[index];
+            s.games += 1;
+            match self.result(game) {
+                Some(GameResult::WhiteWins) => s.white_wins += 1,
+                Some(GameResult::BlackWins) => s.black_wins += 1,
+                Some(GameResult::Draw) => s.draws += 1,
+                _ => {}
+            }
+        }
+        stats.sort_by_key(|s| std::cmp::Reverse(s.games));
+        stats
+    }
+
+    /// Games reaching `material`, with either color as white when `either_color`.
+    pub fn material_games(&self, material: Material, either_color: bool) -> Vec<GameHit> {
+        let mut materials = vec![material];
+        if either_color && material.swapped() != material {
+            materials.push(material.swapped());
+        }
+        let mut hits = materials
+            .into_iter()
+            .flat_map(|material| {
+                equal_range(&self.materials, material.to_bits(), |e| e.material)
+                    .iter()
+                    .map(|e| GameHit {
+                        game: e.game,
+                        ply: e.ply,
+                    })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by_key(|hit| (hit.game, hit.ply));
+        hits.dedup_by_key(|hit| hit.game);
+        hits
+    }
+
+    /// Materials reached in the collection, with the number of games reaching each.
+    pub fn materials(&self) -> Vec<(Material, usize)> {
+        self.materials
+            .chunk_by(|a, b| a.material == b.material)
+            .map(|chunk| (Material::from_bits(chunk[0].material), chunk.len()))
+            .collect()
+    }
+
+    /// Encode the index in the on-disk format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.results.len() as u32).to_le_bytes());
+        bytes.extend(self.results.iter().map(|result| result.to_byte()));
+        bytes.extend_from_slice(&(self.positions.len() as u32).to_le_bytes());
+        for e in &self.positions {
+            bytes.extend_from_slice(&e.key.to_le_bytes());
+            bytes.extend_from_slice(&e.game.to_le_bytes());
+            bytes.extend_from_slice(&e.ply.to_le_bytes());
+            bytes.extend_from_slice(&e.next.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.materials.len() as u32).to_le_bytes());
+        for e in &self.materials {
+            bytes.extend_from_slice(&e.material.to_le_bytes());
+            bytes.extend_from_slice(&e.game.to_le_bytes());
+            bytes.extend_from_slice(&e.ply.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decode an index written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        let header = bytes.get(..5).ok_or("Truncated index header")?;
+        if &header[..4] != MAGIC {
+            return Err("Not a position index");
+        }
+        if header[4] != VERSION {
+            return Err("Unsupported index version");
+        }
+        let mut reader = Reader(&bytes[5..]);
+
+        let games = reader.count()?;
+        let results = reader
+            .take(games)?
+            .iter()
+            .map(|byte| GameResult::from_byte(*byte))
+            .collect::<Result<Vec<_>, _>>()?;
+        let positions = reader.count()?;
+        let positions = reader
+            .take(positions * POSITION_SIZE)?
+            .chunks_exact(POSITION_SIZE)
+            .map(|e| PositionEntry {
+                key: u64::from_le_bytes(e[0..8].try_into().unwrap()),
+                game: u32::from_le_bytes(e[8..12].try_into().unwrap()),
+                ply: u16::from_le_bytes(e[12..14].try_into().unwrap()),
+                next: u16::from_le_bytes(e[14..16].try_into().unwrap()),
+            })
+            .collect::<Vec<_>>();
+        let materials = reader.count()?;
+        let materials = reader
+            .take(materials * MATERIAL_SIZE)?
+            .chunks_exact(MATERIAL_SIZE)
+            .map(|e| MaterialEntry {
+                material: u64::from_le_bytes(e[0..8].try_into().unwrap()),
+                game: u32::from_le_bytes(e[8..12].try_into().unwrap()),
+                ply: u16::from_le_bytes(e[12..14].try_into().unwrap()),
+            })
+            .collect::<Vec<_>>();
+        if !reader.0.is_empty() {
+            return Err("Trailing data after the index");
+        }
+        if !positions.is_sorted() || !materials.is_sorted() {
+            return Err("Index entries are not sorted");
+        }
+        Ok(PositionIndex {
+            results,
+            positions,
+            materials,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::r#move::san::to_san;
+
+    use super::*;
+
+    const PGN: &str = "[Result \"1-0\"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 1-0
+
+[Result \"0-1\"]
+
+1. Nf3 Nc6 2. e4 e5 3. Bb5 0-1
+
+[Result \"1/2-1/2\"]
+
+1. e4 c5 1/2-1/2
+
+1. e4 Ke7 2. Kf3 *
+
+[FEN \"4k3/8/8/8/8/8/3RP3/4K3 w - - 0 1\"]
+[Result \"1-0\"]
+
+1. Rd7 Kf8 2. Rd1 1-0
+";
+
+    fn index() -> PositionIndex {
+        let mut index = PositionIndex::new();
+        // The fourth game has an illegal move
+        assert_eq!(index.add_pgn(PGN), Ok(1));
+        index
+    }
+
+    fn state_after(moves: &str) -> State {
+        let game = &parse_pgn(moves).unwrap()[0];
+        game.replay(PolyglotHasher::new(), |_, _| {})
+            .unwrap()
+            .state
+            .get()
+            .clone()
+    }
+
+    #[test]
+    fn test_games() {
+        let index = index();
+        assert_eq!(index.game_count(), 5);
+        assert_eq!(index.result(3), Some(GameResult::Unknown));
+
+        // Reached by a transposition in the second game
+        let hits = index.games(&state_after("1. e4 e5 2. Nf3 Nc6 3. Bb5"));
+        assert_eq!(
+            hits,
+            [GameHit { game: 0, ply: 5 }, GameHit { game: 1, ply: 5 }]
+        );
+        // No pawn can take on e6, the transposition matches
+        let hits = index.games(&state_after("1. e4 e5 2. Nf3 Nc6"));
+        assert_eq!(
+            hits,
+            [GameHit { game: 0, ply: 4 }, GameHit { game: 1, ply: 4 }]
+        );
+        assert_eq!(index.games(&State::default()).len(), 3);
+        assert!(index.games(&state_after("1. d4")).is_empty());
+    }
+
+    #[test]
+    fn test_move_stats() {
+        let index = index();
+        let mut position = Position::new(HashedState::new(State::default(), PolyglotHasher::new()));
+        let stats = index.move_stats(&State::default());
+        let moves = stats
+            .iter()
+            .map(|s| (to_san(&mut position, s.r#move), s.games))
+            .collect::<Vec<_>>();
+        assert_eq!(moves, [("e4".to_string(), 2), ("Nf3".to_string(), 1)]);
+        assert_eq!((stats[0].white_wins, stats[0].draws), (1, 1));
+        assert_eq!(stats[0].percentages(), [50.0, 50.0, 0.0]);
+        assert_eq!(stats[1].percentages(), [0.0, 0.0, 100.0]);
+        assert!(
+            index
+                .move_stats(&state_after("1. e4 e5 2. Nf3 Nc6 3. Bb5"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_material() {
+        let material = Material::parse("KRP vs K").unwrap();
+        assert_eq!(material.to_string(), "KRPvK");
+        assert_eq!(Material::parse("KvKRP"), Ok(material.swapped()));
+        assert_eq!(Material::from_bits(material.to_bits()), material);
+        assert!(Material::parse("KRP").is_err());
+        assert!(Material::parse("KXvK").is_err());
+
+        let index = index();
+        assert_eq!(
+            index.material_games(material, false),
+            [GameHit { game: 4, ply: 0 }]
+        );
+        assert!(index.material_games(material.swapped(), false).is_empty());
+        assert_eq!(index.material_games(material.swapped(), true).len(), 1);
+        let full = Material::from_state(&State::default());
+        assert_eq!(index.material_games(full, false).len(), 3);
+        assert!(index.materials().contains(&(material, 1)));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let index = index();
+        let bytes = index.to_bytes();
+        assert_eq!(PositionIndex::from_bytes(&bytes), Ok(index));
+        assert!(PositionIndex::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(PositionIndex::from_bytes(b"CETB\x01").is_err());
+        assert_eq!(
+            PositionIndex::from_bytes(&PositionIndex::new().to_bytes()),
+            Ok(PositionIndex::new())
+        );
+    }
+}
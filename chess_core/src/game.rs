@@ -0,0 +1,377 @@
+// A game is a position with the moves that led to it. The history is needed by
+// the rules ending a game that a position alone doesn't tell, like threefold
+// repetition, and to write the game as PGN.
+
+use std::fmt::Display;
+
+use crate::{
+    color::Color,
+    eco::classify_game,
+    hash::{HashedState, NoopHasher, zobrist::ZobristHasher},
+    r#move::{Move, san::to_san},
+    pgn::PgnGame,
+    position::Position,
+    state::{State, chess_board::PieceType},
+};
+
+/// How a game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The winner checkmated the other side.
+    Checkmate(Color),
+    Stalemate,
+    InsufficientMaterial,
+    /// A hundred plies without a capture or a pawn move.
+    FiftyMoves,
+    /// The same position occurred three times.
+    Repetition,
+    /// The time of the loser ran out.
+    Timeout(Color),
+    /// The time of a side ran out, but the other has too little material to mate.
+    TimeoutVsInsufficientMaterial,
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            Outcome::Checkmate(color) | Outcome::Timeout(color) => Some(*color),
+            _ => None,
+        }
+    }
+
+    /// Result as written in PGN.
+    pub fn result(&self) -> &'static str {
+        match self.winner() {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Outcome::Checkmate(Color::White) => "white mates",
+            Outcome::Checkmate(Color::Black) => "black mates",
+            Outcome::Stalemate => "stalemate",
+            Outcome::InsufficientMaterial => "insufficient material",
+            Outcome::FiftyMoves => "fifty move rule",
+            Outcome::Repetition => "threefold repetition",
+            Outcome::Timeout(Color::White) => "white wins on time",
+            Outcome::Timeout(Color::Black) => "black wins on time",
+            Outcome::TimeoutVsInsufficientMaterial => "timeout vs insufficient material",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Minor pieces and other pieces of `color`, besides the king.
+fn material(state: &State, color: Color) -> (u32, u32) {
+    let pieces = &state.boards[color];
+    let minors = (pieces[PieceType::Knight] | pieces[PieceType::Bishop]).count_ones();
+    let others =
+        (pieces[PieceType::Pawn] | pieces[PieceType::Rook] | pieces[PieceType::Queen]).count_ones();
+    (minors, others)
+}
+
+/// Kings alone or with a single minor piece can't mate.
+pub fn is_insufficient_material(state: &State) -> bool {
+    let [white, black] = Color::as_array().map(|color| material(state, color));
+    white.1 + black.1 == 0 && white.0 + black.0 <= 1
+}
+
+pub struct Game {
+    start: State,
+    position: Position<ZobristHasher>,
+    moves: Vec<Move>,
+    /// Hash of every position of the game, the current one last.
+    hashes: Vec<u64>,
+    /// Side whose time ran out.
+    flagged: Option<Color>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(State::default())
+    }
+}
+
+impl Game {
+    pub fn new(start: State) -> Self {
+        let position = Position::new(HashedState::new(start.clone(), ZobristHasher::new()));
+        Game {
+            start,
+            hashes: vec![position.state.get_hash()],
+            position,
+            moves: Vec::new(),
+            flagged: None,
+        }
+    }
+
+    pub fn start(&self) -> &State {
+        &self.start
+    }
+
+    pub fn position(&self) -> &Position<ZobristHasher> {
+        &self.position
+    }
+
+    pub fn state(&self) -> &State {
+        self.position.state.get()
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn legal_moves(&mut self) -> Vec<Move> {
+        self.position.legal_moves()
+    }
+
+    /// Play a legal move.
+    pub fn make(&mut self, r#move: Move) {
+        self.position.make(r#move);
+        self.moves.push(r#move);
+        self.hashes.push(self.position.state.get_hash());
+    }
+
+    /// Take back the last move, if any.
+    pub fn undo(&mut self) -> Option<Move> {
+        let r#move = self.moves.pop()?;
+        self.position.unmake(r#move);
+        self.hashes.pop();
+        self.flagged = None;
+        Some(r#move)
+    }
+
+    /// Number of times the current position occurred, this time included.
+    pub fn repetitions(&self) -> usize {
+        let current = self.hashes.last().unwrap();
+        // Positions before the last capture or pawn move can't come back
+        let reversible = self.state().halfmove as usize + 1;
+        self.hashes
+            .iter()
+            .rev()
+            .take(reversible)
+            .filter(|hash| *hash == current)
+            .count()
+    }
+
+    /// The time of `color` ran out, from a clock. Ignored once the game is over.
+    pub fn flag(&mut self, color: Color) {
+        if self.outcome().is_none() {
+            self.flagged = Some(color);
+        }
+    }
+
+    pub fn outcome(&mut self) -> Option<Outcome> {
+        if let Some(color) = self.flagged {
+            // A king alone or with a single minor piece can't mate
+            let (minors, others) = material(self.state(), !color);
+            return Some(if others == 0 && minors <= 1 {
+                Outcome::TimeoutVsInsufficientMaterial
+            } else {
+                Outcome::Timeout(!color)
+            });
+        }
+        if self.position.legal_moves().is_empty() {
+            return Some(if self.state().is_check() {
+                Outcome::Checkmate(!self.state().flags.active_color())
+            } else {
+                Outcome::Stalemate
+            });
+        }
+        let state = self.state();
+        if is_insufficient_material(state) {
+            Some(Outcome::InsufficientMaterial)
+        } else if state.halfmove >= 100 {
+            Some(Outcome::FiftyMoves)
+        } else if self.repetitions() >= 3 {
+            Some(Outcome::Repetition)
+        } else {
+            None
+        }
+    }
+
+    /// The moves of the game in SAN.
+    pub fn san_moves(&self) -> Vec<String> {
+        let mut position = Position::new(HashedState::new(self.start.clone(), NoopHasher {}));
+        self.moves
+            .iter()
+            .map(|m| {
+                let san = to_san(&mut position, *m);
+                position.make(*m);
+                san
+            })
+            .collect()
+    }
+
+    /// The game as PGN, with the result, the starting position and the
+    /// opening as tags.
+    pub fn to_pgn(&mut self) -> PgnGame {
+        let result = self
+            .outcome()
+            .map_or("*", |outcome| outcome.result())
+            .to_string();
+        let mut tags = vec![("Result".to_string(), result.clone())];
+        if self.flagged.is_some() {
+            tags.push(("Termination".to_string(), "time forfeit".to_string()));
+        }
+        if self.start != State::default() {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), self.start.to_fen()));
+        }
+        if let Some(opening) = classify_game(&self.start, &self.moves) {
+            tags.push(("ECO".to_string(), opening.eco.to_string()));
+            tags.push(("Opening".to_string(), opening.name.to_string()));
+        }
+        PgnGame {
+            tags,
+            moves: self.san_moves(),
+            annotations: Vec::new(),
+            result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(game: &mut Game, moves: &str) {
+        for m in moves.split_whitespace() {
+            let r#move = game
+                .legal_moves()
+                .into_iter()
+                .find(|m2| m2.matches_perft_string(m))
+                .unwrap();
+            game.make(r#move);
+        }
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mut game = Game::default();
+        play(&mut game, "f2f3 e7e5 g2g4");
+        assert_eq!(game.outcome(), None);
+        play(&mut game, "d8h4");
+        assert_eq!(game.outcome(), Some(Outcome::Checkmate(Color::Black)));
+        assert_eq!(game.outcome().unwrap().result(), "0-1");
+
+        let mut game = Game::new(State::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"));
+        assert_eq!(game.outcome(), Some(Outcome::Stalemate));
+
+        let mut game = Game::new(State::from_fen("7k/8/6K1/8/8/8/8/6N1 b - - 0 1"));
+        assert_eq!(game.outcome(), Some(Outcome::InsufficientMaterial));
+
+        let mut game = Game::new(State::from_fen("7k/8/6K1/8/8/8/8/R7 b - - 99 1"));
+        assert_eq!(game.outcome(), None);
+        play(&mut game, "h8g8");
+        assert_eq!(game.outcome(), Some(Outcome::FiftyMoves));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut game = Game::default();
+        play(&mut game, "e2e4");
+        game.flag(Color::Black);
+        assert_eq!(game.outcome(), Some(Outcome::Timeout(Color::White)));
+        assert_eq!(game.outcome().unwrap().result(), "1-0");
+        assert_eq!(game.to_pgn().tag("Termination"), Some("time forfeit"));
+        assert!(game.undo().is_some());
+        assert_eq!(game.outcome(), None);
+
+        let mut game = Game::new(State::from_fen("7k/8/6K1/8/8/8/8/6R1 w - - 0 1"));
+        game.flag(Color::White);
+        assert_eq!(game.outcome(), Some(Outcome::TimeoutVsInsufficientMaterial));
+        assert_eq!(game.outcome().unwrap().result(), "1/2-1/2");
+
+        // A single knight or bishop can't mate either, two can
+        for (fen, outcome) in [
+            (
+                "6nk/8/6K1/8/8/8/8/6R1 w - - 0 1",
+                Outcome::TimeoutVsInsufficientMaterial,
+            ),
+            (
+                "6bk/8/6K1/8/8/8/8/6R1 w - - 0 1",
+                Outcome::TimeoutVsInsufficientMaterial,
+            ),
+            (
+                "5bnk/8/6K1/8/8/8/8/6R1 w - - 0 1",
+                Outcome::Timeout(Color::Black),
+            ),
+            (
+                "6pk/8/6K1/8/8/8/8/6R1 w - - 0 1",
+                Outcome::Timeout(Color::Black),
+            ),
+        ] {
+            let mut game = Game::new(State::from_fen(fen));
+            game.flag(Color::White);
+            assert_eq!(game.outcome(), Some(outcome), "{}", fen);
+        }
+
+        // Too late once the game is over
+        let mut game = Game::new(State::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"));
+        game.flag(Color::Black);
+        assert_eq!(game.outcome(), Some(Outcome::Stalemate));
+    }
+
+    #[test]
+    fn test_repetition() {
+        let mut game = Game::default();
+        play(&mut game, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+        assert_eq!(game.repetitions(), 2);
+        assert_eq!(game.outcome(), None);
+        play(&mut game, "f6g8");
+        assert_eq!(game.repetitions(), 3);
+        assert_eq!(game.outcome(), Some(Outcome::Repetition));
+
+        assert!(game.undo().is_some());
+        assert_eq!(game.outcome(), None);
+        assert_eq!(game.moves().len(), 7);
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        for (fen, insufficient) in [
+            ("8/8/8/4k3/8/8/8/4K3 w - - 0 1", true),
+            ("8/8/8/4k3/8/8/8/3BK3 b - - 0 1", true),
+            ("8/8/8/4k3/8/8/8/3NK3 w - - 0 1", true),
+            ("8/8/8/4k3/8/8/8/2NNK3 w - - 0 1", false),
+            ("8/8/8/4k3/8/8/8/3RK3 w - - 0 1", false),
+            ("8/8/8/4k3/8/8/4P3/4K3 w - - 0 1", false),
+        ] {
+            assert_eq!(
+                is_insufficient_material(&State::from_fen(fen)),
+                insufficient,
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_pgn() {
+        let mut game = Game::default();
+        play(&mut game, "f2f3 e7e5 g2g4 d8h4");
+        let pgn = game.to_pgn();
+        assert_eq!(pgn.moves, ["f3", "e5", "g4", "Qh4#"]);
+        assert_eq!(pgn.result, "0-1");
+        assert_eq!(pgn.tag("Result"), Some("0-1"));
+        assert_eq!(pgn.tag("FEN"), None);
+        assert_eq!(pgn.tag("ECO"), None);
+
+        let mut game = Game::default();
+        play(&mut game, "e2e4 e7e5 g1f3 b8c6 f1b5");
+        let pgn = game.to_pgn();
+        assert_eq!(pgn.tag("ECO"), Some("C60"));
+        assert_eq!(pgn.tag("Opening"), Some("Ruy Lopez"));
+
+        let fen = "7k/8/6K1/8/8/8/8/6R1 w - - 0 1";
+        let mut game = Game::new(State::from_fen(fen));
+        let pgn = game.to_pgn();
+        assert_eq!(pgn.result, "*");
+        assert_eq!(pgn.tag("FEN"), Some(fen));
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     state::{State, bitboard::BitBoard, chess_board::PieceType},
 };
 
+pub mod polyglot;
 pub mod zobrist;
 
 /// Hasher holds a hash of the current game state.
@@ -70,6 +71,10 @@ impl<H: Hasher> HashedState<H> {
         self.hasher.get()
     }
 
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
     /// Remove a piece and update the hash.
     pub fn remove_piece(&mut self, square: Square, piece: PieceType, color: Color) {
         self.state.boards[color][piece].unset(square);
@@ -115,10 +120,10 @@ impl<H: Hasher> HashedState<H> {
     }
 
     pub fn increment_halfmove(&mut self) {
-        self.state.halfmove += 1;
+        self.state.halfmove = self.state.halfmove.saturating_add(1);
     }
 
-    pub fn decrement_halfmove(&mut self) {
-        self.state.halfmove -= 1;
+    pub fn set_halfmove(&mut self, halfmove: u8) {
+        self.state.halfmove = halfmove;
     }
 }
@@ -6,6 +6,7 @@
 use std::array;
 
 use itertools::Itertools;
+use lazy_static::lazy_static;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 
@@ -122,18 +123,30 @@ impl ZobristNumbers {
     }
 }
 
+lazy_static! {
+    // The numbers only depend on the seed, so every hasher shares them
+    static ref ZOBRIST_NUMBERS: ZobristNumbers = ZobristNumbers::new();
+}
+
 pub struct ZobristHasher {
-    zobrist_numbers: ZobristNumbers,
+    zobrist_numbers: &'static ZobristNumbers,
     hash: u64,
+    pawn_hash: u64,
 }
 
 impl ZobristHasher {
     pub fn new() -> Self {
         Self {
-            zobrist_numbers: ZobristNumbers::new(),
+            zobrist_numbers: &ZOBRIST_NUMBERS,
             hash: 0,
+            pawn_hash: 0,
         }
     }
+
+    /// Hash of the pawns only, used to key pawn structure caches.
+    pub fn pawn_hash(&self) -> u64 {
+        self.pawn_hash
+    }
 }
 
 impl Default for ZobristHasher {
@@ -145,13 +158,18 @@ impl Default for ZobristHasher {
 impl Hasher for ZobristHasher {
     fn init(&mut self, state: &State) {
         self.hash = 0;
+        self.pawn_hash = 0;
         for (color, piece) in Color::as_array()
             .into_iter()
             .cartesian_product(PieceType::as_array())
         {
-            let mut b = state.boards[color][piece].clone();
+            let mut b = state.boards[color][piece];
             while let Some(lsb) = b.pop_first_square() {
-                self.hash ^= self.zobrist_numbers.board.get(color, piece)[lsb.get() as usize]
+                let number = self.zobrist_numbers.board.get(color, piece)[lsb.get() as usize];
+                self.hash ^= number;
+                if piece == PieceType::Pawn {
+                    self.pawn_hash ^= number;
+                }
             }
         }
 
@@ -174,7 +192,11 @@ impl Hasher for ZobristHasher {
     }
 
     fn consume_piece(&mut self, color: Color, piece: PieceType, square: crate::square::Square) {
-        self.hash ^= self.zobrist_numbers.board.get(color, piece)[square.get() as usize]
+        let number = self.zobrist_numbers.board.get(color, piece)[square.get() as usize];
+        self.hash ^= number;
+        if piece == PieceType::Pawn {
+            self.pawn_hash ^= number;
+        }
     }
 
     fn get(&self) -> u64 {
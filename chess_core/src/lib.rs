@@ -1,9 +1,18 @@
+pub mod clock;
 pub mod color;
+pub mod eco;
+pub mod epd;
+pub mod explorer;
+pub mod game;
 pub mod hash;
 pub mod r#move;
+pub mod pgn;
 pub mod position;
+#[cfg(feature = "serde")]
+mod serialization;
 pub mod square;
 pub mod state;
+pub mod tablebase;
 
 pub trait Insert<T> {
     fn insert(&mut self, value: T);
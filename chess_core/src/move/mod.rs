@@ -4,6 +4,7 @@ use std::fmt::Display;
 
 mod move_generator;
 mod move_maps;
+pub mod san;
 
 pub use move_generator::MoveGenerator;
 
@@ -105,15 +106,15 @@ impl MoveCode {
     }
 
     pub fn is_capture(&self) -> bool {
-        match *self {
+        matches!(
+            *self,
             Capture
-            | EnPassant
-            | KnightPromotionCapture
-            | BishopPromotionCapture
-            | RookPromotionCapture
-            | QueenPromotionCapture => true,
-            _ => false,
-        }
+                | EnPassant
+                | KnightPromotionCapture
+                | BishopPromotionCapture
+                | RookPromotionCapture
+                | QueenPromotionCapture
+        )
     }
 
     pub fn as_promotion(&self) -> Option<PieceType> {
@@ -136,10 +137,7 @@ impl MoveCode {
     }
 
     pub fn is_quiet(&self) -> bool {
-        match *self {
-            QuietMove | DoublePawnPush | KingCastle | QueenCastle => true,
-            _ => false,
-        }
+        matches!(*self, QuietMove | DoublePawnPush | KingCastle | QueenCastle)
     }
 
     pub fn from_castle(side: CastleSide) -> MoveCode {
@@ -175,6 +173,12 @@ impl AddMove for Vec<Move> {
     }
 }
 
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MoveList {
     pub fn new() -> MoveList {
         MoveList {
@@ -30,7 +30,7 @@ impl<'a> MoveGenerator<'a> {
     }
 
     fn knight_moves<T: Insert<Move>>(&self, out: &mut T) {
-        let mut temp_knights = self.0.active_boards()[PieceType::Knight].clone();
+        let mut temp_knights = self.0.active_boards()[PieceType::Knight];
         let friendly_occupation = self.0.active_boards().union();
         let enemy_occupation = self.0.inactive_boards().union();
 
@@ -79,7 +79,7 @@ impl<'a> MoveGenerator<'a> {
     }
 
     fn rank_file_moves<T: Insert<Move>>(&self, out: &mut T, piece: PieceType) {
-        let mut board = self.0.active_boards()[piece].clone();
+        let mut board = self.0.active_boards()[piece];
         while let Some(square) = board.pop_first_square() {
             for (move_map, direction) in MOVE_MAPS.directions() {
                 self.direction_moves(out, square, move_map[square], direction);
@@ -229,6 +229,42 @@ impl<'a> MoveGenerator<'a> {
 }
 
 impl State {
+    /// Squares attacked by a `piece` of `color` standing on `square`.
+    /// Sliding pieces are blocked by any occupied square, which is included in the result.
+    pub fn piece_attacks(&self, piece: PieceType, color: Color, square: Square) -> BitBoard {
+        let occupied = self.boards.white.union() | self.boards.black.union();
+        match piece {
+            PieceType::Pawn => MOVE_MAPS.attack_pawn(color)[square],
+            PieceType::Knight => MOVE_MAPS.knight[square],
+            PieceType::Bishop => MOVE_MAPS.bishop_attacks(square, occupied),
+            PieceType::Rook => MOVE_MAPS.rook_attacks(square, occupied),
+            PieceType::Queen => {
+                MOVE_MAPS.bishop_attacks(square, occupied)
+                    | MOVE_MAPS.rook_attacks(square, occupied)
+            }
+            PieceType::King => MOVE_MAPS.king[square],
+        }
+    }
+
+    /// Union of the squares attacked by every `piece` of `color`.
+    pub fn attacks(&self, piece: PieceType, color: Color) -> BitBoard {
+        let mut pieces = self.boards[color][piece];
+        let mut attacks = BitBoard::EMPTY;
+        while let Some(square) = pieces.pop_first_square() {
+            attacks |= self.piece_attacks(piece, color, square);
+        }
+        attacks
+    }
+
+    /// Union of the squares attacked by every piece of `color`.
+    pub fn all_attacks(&self, color: Color) -> BitBoard {
+        PieceType::as_array()
+            .into_iter()
+            .fold(BitBoard::EMPTY, |attacks, piece| {
+                attacks | self.attacks(piece, color)
+            })
+    }
+
     pub fn was_move_legal(&self) -> bool {
         !self.is_square_attacked(
             self.inactive_boards().king.get_first_square().unwrap(),
@@ -238,7 +274,7 @@ impl State {
 
     /// Checks if the king of the active player is in check
     pub fn is_check(&self) -> bool {
-        !self.is_square_attacked(
+        self.is_square_attacked(
             self.active_boards().king.get_first_square().unwrap(),
             !self.flags.active_color(),
         )
@@ -348,7 +384,49 @@ fn capture_in_decreasing_direction(
 
 #[cfg(test)]
 mod tests {
-    use crate::{hash::NoopHasher, r#move::MoveList, position::Position};
+    use crate::{
+        color::Color,
+        hash::NoopHasher,
+        r#move::MoveList,
+        position::Position,
+        square::Square,
+        state::{State, bitboard::BitBoard, chess_board::PieceType},
+    };
+
+    fn squares(names: &[&str]) -> BitBoard {
+        names.iter().fold(BitBoard::EMPTY, |bb, name| {
+            bb | Square::try_from(*name).unwrap().into()
+        })
+    }
+
+    #[test]
+    fn test_piece_attacks() {
+        let state = State::from_fen("4k3/8/8/1p6/8/3B1n2/8/R3K3 w - - 0 1");
+        let d3 = Square::try_from("d3").unwrap();
+        assert_eq!(
+            state.piece_attacks(PieceType::Bishop, Color::White, d3),
+            squares(&["c2", "b1", "e2", "f1", "c4", "b5", "e4", "f5", "g6", "h7"])
+        );
+        let a1 = Square::try_from("a1").unwrap();
+        assert_eq!(
+            state.piece_attacks(PieceType::Rook, Color::White, a1),
+            squares(&["b1", "c1", "d1", "e1"]) | (BitBoard::file(0) & !squares(&["a1"]))
+        );
+        assert_eq!(
+            state.attacks(PieceType::Pawn, Color::Black),
+            squares(&["a4", "c4"])
+        );
+        assert!(
+            state
+                .all_attacks(Color::Black)
+                .get(Square::try_from("e1").unwrap())
+        );
+        assert!(
+            !state
+                .all_attacks(Color::White)
+                .get(Square::try_from("f3").unwrap())
+        );
+    }
 
     #[test]
     fn test_pseudo_legal_moves_from_starting_position() {
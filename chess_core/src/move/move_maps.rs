@@ -66,6 +66,19 @@ impl Square {
 }
 
 impl MoveMap {
+    /// Squares of the ray from `square` up to and including the first occupied square.
+    fn ray_attacks(&self, square: Square, direction: Direction, occupied: BitBoard) -> BitBoard {
+        let ray = self[square];
+        let blocker = match direction {
+            Direction::Increasing => (ray & occupied).get_first_square(),
+            Direction::Decreasing => (ray & occupied).get_last_square(),
+        };
+        match blocker {
+            Some(blocker) => ray & !self[blocker],
+            None => ray,
+        }
+    }
+
     fn from_offsets(offsets: Vec<Offset>) -> Self {
         let mut res = MoveMap::default();
         for offset in offsets {
@@ -179,6 +192,24 @@ impl MoveMaps {
         ]
     }
 
+    /// Squares attacked by a bishop on `square` given the occupied squares.
+    pub fn bishop_attacks(&self, square: Square, occupied: BitBoard) -> BitBoard {
+        self.diagonals()
+            .into_iter()
+            .fold(BitBoard::EMPTY, |attacks, (map, direction)| {
+                attacks | map.ray_attacks(square, direction, occupied)
+            })
+    }
+
+    /// Squares attacked by a rook on `square` given the occupied squares.
+    pub fn rook_attacks(&self, square: Square, occupied: BitBoard) -> BitBoard {
+        self.directions()
+            .into_iter()
+            .fold(BitBoard::EMPTY, |attacks, (map, direction)| {
+                attacks | map.ray_attacks(square, direction, occupied)
+            })
+    }
+
     pub fn passive_pawn(&self, color: Color) -> &MoveMap {
         match color {
             Color::White => &self.white_pawn_passive,
@@ -0,0 +1,244 @@
+// Standard algebraic notation, as used in PGN files: the piece letter, just
+// enough of the source square to tell identical pieces apart, `x` for
+// captures, the target square, `=Q` for promotions and `+` or `#` for checks.
+
+use std::fmt;
+
+use crate::{
+    hash::Hasher,
+    position::Position,
+    square::{CastleSide, Square},
+    state::chess_board::PieceType,
+};
+
+use super::{Move, MoveCode};
+
+/// Why `from_san` found no move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanError {
+    /// Not SAN, with the reason.
+    Invalid(&'static str),
+    /// No legal move matches.
+    Illegal,
+    /// Several legal moves match.
+    Ambiguous,
+}
+
+impl From<SanError> for &'static str {
+    fn from(error: SanError) -> Self {
+        match error {
+            SanError::Invalid(message) => message,
+            SanError::Illegal => "Illegal SAN move",
+            SanError::Ambiguous => "Ambiguous SAN move",
+        }
+    }
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str((*self).into())
+    }
+}
+
+fn piece_from_char(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+fn moved_piece<H: Hasher>(position: &Position<H>, r#move: Move) -> PieceType {
+    position.state.get().piece_at(r#move.from()).unwrap().1
+}
+
+/// SAN of a legal `move` in `position`, e.g. `Nbd7`, `exf6`, `O-O` or `e8=Q+`.
+pub fn to_san<H: Hasher>(position: &mut Position<H>, r#move: Move) -> String {
+    let mut san = match r#move.code().as_castle() {
+        Some(CastleSide::King) => "O-O".to_string(),
+        Some(CastleSide::Queen) => "O-O-O".to_string(),
+        None => {
+            let piece = moved_piece(position, r#move);
+            let mut san = String::new();
+            if piece == PieceType::Pawn {
+                if r#move.code().is_capture() {
+                    san.push(r#move.from().to_string().remove(0));
+                }
+            } else {
+                san.push(char::from(piece));
+                // Other pieces of the same type that could go to the same square
+                let others = position
+                    .legal_moves()
+                    .into_iter()
+                    .filter(|m| {
+                        m.to() == r#move.to()
+                            && m.from() != r#move.from()
+                            && moved_piece(position, *m) == piece
+                    })
+                    .map(|m| m.from())
+                    .collect::<Vec<_>>();
+                let from = r#move.from().to_string();
+                if !others.is_empty() {
+                    if others.iter().all(|s| s.file() != r#move.from().file()) {
+                        san.push_str(&from[..1]);
+                    } else if others.iter().all(|s| s.rank() != r#move.from().rank()) {
+                        san.push_str(&from[1..]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+            if r#move.code().is_capture() {
+                san.push('x');
+            }
+            san.push_str(&r#move.to().to_string());
+            if let Some(promotion) = r#move.code().as_promotion() {
+                san.push('=');
+                san.push(char::from(promotion));
+            }
+            san
+        }
+    };
+
+    position.make(r#move);
+    if position.state.get().is_check() {
+        san.push(if position.legal_moves().is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    position.unmake(r#move);
+    san
+}
+
+/// Find the legal move written `san` in `position`. Check marks and annotations
+/// like `!?` are ignored, castles can be written with zeros.
+pub fn from_san<H: Hasher>(position: &mut Position<H>, san: &str) -> Result<Move, SanError> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = position.legal_moves();
+
+    let castle = match san {
+        "O-O" | "0-0" => Some(CastleSide::King),
+        "O-O-O" | "0-0-0" => Some(CastleSide::Queen),
+        _ => None,
+    };
+    if let Some(side) = castle {
+        return legal_moves
+            .into_iter()
+            .find(|m| m.code() == MoveCode::from_castle(side))
+            .ok_or(SanError::Illegal);
+    }
+
+    let mut chars = san.chars().collect::<Vec<_>>();
+    let piece = match chars.first().copied().and_then(piece_from_char) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => PieceType::Pawn,
+    };
+
+    // Promotion, with or without the `=`
+    let promotion = match chars.last().copied().and_then(piece_from_char) {
+        Some(promotion) if piece == PieceType::Pawn => {
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+            Some(promotion)
+        }
+        _ => None,
+    };
+
+    if chars.len() < 2 {
+        return Err(SanError::Invalid("SAN move malformed"));
+    }
+    let to = chars
+        .split_off(chars.len() - 2)
+        .into_iter()
+        .collect::<String>();
+    let to = Square::try_from(to.as_str()).map_err(SanError::Invalid)?;
+
+    // What remains is the disambiguation, possibly followed by the capture mark
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in chars.into_iter().filter(|c| *c != 'x') {
+        match c {
+            'a'..='h' => from_file = Some(c as u8 - b'a'),
+            '1'..='8' => from_rank = Some(c as u8 - b'1'),
+            _ => return Err(SanError::Invalid("SAN move malformed")),
+        }
+    }
+
+    let mut candidates = legal_moves.into_iter().filter(|m| {
+        m.to() == to
+            && m.code().as_castle().is_none()
+            && moved_piece(position, *m) == piece
+            && m.code().as_promotion() == promotion
+            && from_file.is_none_or(|file| m.from().file() == file)
+            && from_rank.is_none_or(|rank| m.from().rank() == rank)
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(m), None) => Ok(m),
+        (Some(_), Some(_)) => Err(SanError::Ambiguous),
+        (None, _) => Err(SanError::Illegal),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::NoopHasher;
+
+    use super::*;
+
+    fn round_trip(fen: &str, san: &str) -> String {
+        let mut position = Position::from_fen(fen, NoopHasher {});
+        let m = from_san(&mut position, san).unwrap();
+        to_san(&mut position, m)
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(round_trip(start, "e4"), "e4");
+        assert_eq!(round_trip(start, "Nf3"), "Nf3");
+
+        // Castles, captures, en passant and promotions
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+        assert_eq!(round_trip(fen, "O-O"), "O-O");
+        assert_eq!(round_trip(fen, "0-0-0"), "O-O-O");
+        assert_eq!(round_trip(fen, "exd6"), "exd6");
+        assert_eq!(round_trip(fen, "bxa8=Q+"), "bxa8=Q+");
+        assert_eq!(round_trip(fen, "b8N"), "b8=N");
+    }
+
+    #[test]
+    fn test_san_disambiguation() {
+        // Knights on b1 and f1 both reach d2, rooks on a1 and a5 both reach a3
+        let fen = "4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1";
+        assert_eq!(round_trip(fen, "Nbd2"), "Nbd2");
+        assert_eq!(round_trip(fen, "Nfd2"), "Nfd2");
+        assert_eq!(round_trip(fen, "R1a3"), "R1a3");
+        assert_eq!(round_trip(fen, "Nc3"), "Nc3");
+
+        let mut position = Position::from_fen(fen, NoopHasher {});
+        assert_eq!(from_san(&mut position, "Nd2"), Err(SanError::Ambiguous));
+        assert_eq!(from_san(&mut position, "Nd3"), Err(SanError::Illegal));
+        assert_eq!(
+            from_san(&mut position, "Z"),
+            Err(SanError::Invalid("SAN move malformed"))
+        );
+        assert_eq!(from_san(&mut position, "O-O"), Err(SanError::Illegal));
+        assert_eq!(SanError::Ambiguous.to_string(), "Ambiguous SAN move");
+    }
+
+    #[test]
+    fn test_san_checkmate() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        assert_eq!(round_trip(fen, "Ra8"), "Ra8#");
+    }
+}
@@ -0,0 +1,382 @@
+// Reading and writing of PGN game collections. Only the main line is read:
+// comments, variations and numeric annotation glyphs are skipped. Annotations
+// of the moves can be written, as glyphs and comments after each move.
+
+use std::fmt::Display;
+
+use crate::{
+    color::Color,
+    hash::{HashedState, Hasher},
+    r#move::{Move, san::from_san},
+    position::Position,
+    state::State,
+};
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Movetext lines are wrapped before this length, as the export format asks.
+const LINE_LENGTH: usize = 80;
+
+/// A game read from a PGN file, with its moves in SAN.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    /// Annotations of the moves at the same index, only written. Empty when
+    /// the moves are not annotated.
+    pub annotations: Vec<Annotation>,
+    pub result: String,
+}
+
+/// Written after a move: numeric annotation glyphs, like 2 for `$2`, a
+/// mistake, and a comment.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Annotation {
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn annotation(&self, index: usize) -> Option<&Annotation> {
+        self.annotations.get(index)
+    }
+
+    /// Position the game starts from, given by the `FEN` tag if any.
+    pub fn starting_state(&self) -> Result<State, &'static str> {
+        self.tag("FEN")
+            .map_or(Ok(State::default()), State::try_from_fen)
+    }
+
+    /// Play the game from its starting position, calling `visit` before each move.
+    pub fn replay<H: Hasher>(
+        &self,
+        hasher: H,
+        mut visit: impl FnMut(&mut Position<H>, Move),
+    ) -> Result<Position<H>, &'static str> {
+        let mut position = Position::new(HashedState::new(self.starting_state()?, hasher));
+        for san in &self.moves {
+            let r#move = from_san(&mut position, san)?;
+            visit(&mut position, r#move);
+            position.make(r#move);
+        }
+        Ok(position)
+    }
+}
+
+/// Export format: the tags, a blank line, then the numbered moves and the result.
+impl Display for PgnGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+
+        let mut number = self
+            .tag("FEN")
+            .and_then(|fen| fen.split_whitespace().nth(5))
+            .and_then(|number| number.parse::<u32>().ok())
+            .unwrap_or(1);
+        // A game with a bad FEN tag is still written, numbered from white
+        let mut color = self
+            .starting_state()
+            .map_or(Color::White, |state| state.flags.active_color());
+        let mut tokens = Vec::new();
+        for (i, san) in self.moves.iter().enumerate() {
+            // A black move gets its number back after a comment
+            let commented = i > 0 && self.annotation(i - 1).is_some_and(|a| a.comment.is_some());
+            match color {
+                Color::White => tokens.push(format!("{}. {}", number, san)),
+                Color::Black if i == 0 || commented => {
+                    tokens.push(format!("{}... {}", number, san))
+                }
+                Color::Black => tokens.push(san.clone()),
+            }
+            if let Some(annotation) = self.annotation(i) {
+                tokens.extend(annotation.nags.iter().map(|nag| format!("${}", nag)));
+                if let Some(comment) = &annotation.comment {
+                    tokens.push(format!("{{{}}}", comment.replace('}', "")));
+                }
+            }
+            if color == Color::Black {
+                number += 1;
+            }
+            color = !color;
+        }
+        tokens.push(if self.result.is_empty() {
+            "*".to_string()
+        } else {
+            self.result.clone()
+        });
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() >= LINE_LENGTH {
+                writeln!(f)?;
+                line_length = 0;
+            }
+            if line_length > 0 {
+                write!(f, " ")?;
+                line_length += 1;
+            }
+            write!(f, "{}", token)?;
+            line_length += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+/// Split the movetext into tokens, dropping comments and variations.
+fn movetext_tokens(movetext: &str) -> Result<Vec<String>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut depth = 0;
+    let mut chars = movetext.chars();
+    while let Some(c) = chars.next() {
+        if !c.is_whitespace() && !matches!(c, '{' | ';' | '(' | ')') {
+            token.push(c);
+            continue;
+        }
+        if depth == 0 && !token.is_empty() {
+            tokens.push(std::mem::take(&mut token));
+        }
+        token.clear();
+        match c {
+            '{' => {
+                chars.find(|c| *c == '}').ok_or("Unterminated comment")?;
+            }
+            ';' => {
+                chars.find(|c| *c == '\n');
+            }
+            '(' => depth += 1,
+            ')' => {
+                if depth == 0 {
+                    return Err("Unbalanced variation");
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err("Unbalanced variation");
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Strip the move number in front of a SAN move, as in `12.e4` or `12...e5`.
+fn strip_move_number(token: &str) -> &str {
+    let rest = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() < token.len() && rest.starts_with('.') {
+        rest.trim_start_matches('.')
+    } else {
+        token
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), &'static str> {
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or("Tag malformed")?;
+    let (name, value) = inner.split_once(' ').ok_or("Tag malformed")?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or("Tag value malformed")?;
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(match c {
+            '\\' => chars.next().ok_or("Tag value malformed")?,
+            c => c,
+        });
+    }
+    Ok((name.to_string(), unescaped))
+}
+
+/// Parse every game of a PGN collection.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, &'static str> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::default();
+    let mut movetext = String::new();
+
+    let mut finish = |game: &mut PgnGame, movetext: &mut String| -> Result<(), &'static str> {
+        for token in movetext_tokens(movetext)? {
+            if RESULTS.contains(&token.as_str()) {
+                // The result ends the game, even without tags before the next one
+                game.result = token;
+                games.push(std::mem::take(game));
+            } else if !token.starts_with('$') {
+                // Anything else than a numeric annotation glyph is a move
+                let san = strip_move_number(&token);
+                if !san.is_empty() {
+                    game.moves.push(san.to_string());
+                }
+            }
+        }
+        if !game.tags.is_empty() || !game.moves.is_empty() {
+            games.push(std::mem::take(game));
+        }
+        movetext.clear();
+        Ok(())
+    };
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            // Tags after some movetext start the next game
+            if !movetext.trim().is_empty() {
+                finish(&mut game, &mut movetext)?;
+            }
+            game.tags.push(parse_tag(line)?);
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    finish(&mut game, &mut movetext)?;
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::NoopHasher;
+
+    use super::*;
+
+    const PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {A comment} Nc6 (2... d6 3. d4) 3. Bb5 $1 a6;line comment
+4.Ba4 Nf6 5. O-O 1-0
+
+[Event "Second"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1. e4 Kd7 2. e5 *
+"#;
+
+    #[test]
+    fn test_parse_pgn() {
+        let games = parse_pgn(PGN).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(
+            games[0].moves,
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"]
+        );
+        assert_eq!(games[0].result, "1-0");
+        assert_eq!(games[1].moves, ["e4", "Kd7", "e5"]);
+        assert_eq!(games[1].result, "*");
+
+        assert!(parse_pgn("1. e4 {unterminated").is_err());
+        assert!(parse_pgn("1. e4 (1. d4 *").is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let games = parse_pgn(PGN).unwrap();
+        let mut count = 0;
+        let position = games[0].replay(NoopHasher {}, |_, _| count += 1).unwrap();
+        assert_eq!(count, 9);
+        assert_eq!(
+            position.state.get().boards.to_fen(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1"
+        );
+
+        let position = games[1].replay(NoopHasher {}, |_, _| {}).unwrap();
+        assert_eq!(
+            position.state.get().boards.to_fen(),
+            "8/3k4/8/4P3/8/8/8/4K3"
+        );
+
+        let mut game = games[1].clone();
+        game.moves.push("Ke9".to_string());
+        assert!(game.replay(NoopHasher {}, |_, _| {}).is_err());
+
+        // A bad FEN tag is an error, not a panic
+        let game = &parse_pgn("[FEN \"8/8 w - - 0 1\"]\n\n1. e4 *").unwrap()[0];
+        assert!(game.starting_state().is_err());
+        assert!(game.replay(NoopHasher {}, |_, _| {}).is_err());
+        assert!(game.to_string().contains("1. e4 *"));
+    }
+
+    #[test]
+    fn test_write_pgn() {
+        let games = parse_pgn(PGN).unwrap();
+        for game in &games {
+            assert_eq!(
+                &parse_pgn(&game.to_string()).unwrap()[..],
+                std::slice::from_ref(game)
+            );
+        }
+        assert!(
+            games[0]
+                .to_string()
+                .ends_with("\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O 1-0\n")
+        );
+
+        let game = PgnGame {
+            tags: vec![
+                ("Event".to_string(), "\"Quoted\" \\ event".to_string()),
+                (
+                    "FEN".to_string(),
+                    "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12".to_string(),
+                ),
+            ],
+            moves: ["Kd7", "e4", "Ke6"].map(str::to_string).to_vec(),
+            annotations: Vec::new(),
+            result: "*".to_string(),
+        };
+        let text = game.to_string();
+        assert!(text.starts_with("[Event \"\\\"Quoted\\\" \\\\ event\"]\n"));
+        assert!(text.ends_with("\n\n12... Kd7 13. e4 Ke6 *\n"));
+        assert_eq!(parse_pgn(&text).unwrap(), [game]);
+
+        // Long games are wrapped
+        let mut game = games[0].clone();
+        game.moves = ["Nf3", "Nf6", "Ng1", "Ng8"]
+            .repeat(10)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let text = game.to_string();
+        assert!(text.lines().all(|line| line.len() < LINE_LENGTH));
+        assert_eq!(parse_pgn(&text).unwrap()[0].moves, game.moves);
+
+        let mut game = games[1].clone();
+        game.annotations = vec![
+            Annotation {
+                nags: vec![1],
+                comment: None,
+            },
+            Annotation {
+                nags: vec![2],
+                comment: Some("Kd8} was better".to_string()),
+            },
+        ];
+        let text = game.to_string();
+        assert!(text.ends_with("\n\n1. e4 $1 Kd7 $2 {Kd8 was better} 2. e5 *\n"));
+        assert_eq!(parse_pgn(&text).unwrap()[0].moves, game.moves);
+
+        game.annotations.swap(0, 1);
+        assert!(
+            game.to_string()
+                .ends_with("\n\n1. e4 $2 {Kd8 was better} 1... Kd7 $1 2. e5 *\n")
+        );
+    }
+}
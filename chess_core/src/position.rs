@@ -4,8 +4,8 @@ use crate::{
     Insert,
     color::Color,
     hash::{HashedState, Hasher},
-    r#move::{Move, MoveCode, MoveGenerator},
-    square::{CastleSide, SquareFinder},
+    r#move::{Move, MoveCode, MoveGenerator, MoveList},
+    square::{CastleSide, Square, SquareFinder},
     state::{State, bitboard::BitBoard, chess_board::PieceType, flags::StateFlags},
 };
 
@@ -14,6 +14,19 @@ pub struct Position<H: Hasher> {
     stack: Vec<IrreversibleInfo>,
 }
 
+/// What a legal move does, for user interfaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveInfo {
+    pub piece: PieceType,
+    /// A pawn when capturing en passant.
+    pub captured: Option<PieceType>,
+    pub promotion: Option<PieceType>,
+    pub castle: Option<CastleSide>,
+    pub en_passant: bool,
+    /// Whether the move gives check.
+    pub check: bool,
+}
+
 impl<H: Hasher + Default> Default for Position<H> {
     fn default() -> Self {
         Self {
@@ -43,13 +56,85 @@ impl<H: Hasher> Position<H> {
         self.state.get().was_move_legal()
     }
 
+    /// Legal moves of the side to move.
+    pub fn legal_moves(&mut self) -> Vec<Move> {
+        let mut move_list = MoveList::new();
+        move_list.new_ply();
+        self.pseudo_legal_moves(&mut move_list);
+        move_list
+            .current_ply()
+            .iter()
+            .copied()
+            .filter(|m| {
+                self.make(*m);
+                let legal = self.was_move_legal();
+                self.unmake(*m);
+                legal
+            })
+            .collect()
+    }
+
+    /// Squares the piece on `from` can legally move to, empty without a piece
+    /// of the side to move there.
+    pub fn legal_destinations(&mut self, from: Square) -> BitBoard {
+        let mut destinations = BitBoard::EMPTY;
+        for r#move in self.legal_moves() {
+            if r#move.from() == from {
+                destinations.set(r#move.to());
+            }
+        }
+        destinations
+    }
+
+    /// Squares of the pieces of the side to move that have a legal move.
+    pub fn movable_squares(&mut self) -> BitBoard {
+        let mut squares = BitBoard::EMPTY;
+        for r#move in self.legal_moves() {
+            squares.set(r#move.from());
+        }
+        squares
+    }
+
+    /// Pieces a pawn moving from `from` to `to` can promote to, the queen
+    /// first, empty when that is not a legal promotion.
+    pub fn promotion_choices(&mut self, from: Square, to: Square) -> Vec<PieceType> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|m| m.from() == from && m.to() == to)
+            .filter_map(|m| m.code().as_promotion())
+            .collect()
+    }
+
+    /// What the legal move `move` does.
+    pub fn move_info(&mut self, r#move: Move) -> MoveInfo {
+        let state = self.state.get();
+        let en_passant = r#move.code() == MoveCode::EnPassant;
+        let captured = if en_passant {
+            Some(PieceType::Pawn)
+        } else {
+            state.piece_at(r#move.to()).map(|(_, piece)| piece)
+        };
+        let piece = state.piece_at(r#move.from()).unwrap().1;
+        self.make(r#move);
+        let check = self.state.get().is_check();
+        self.unmake(r#move);
+        MoveInfo {
+            piece,
+            captured,
+            promotion: r#move.code().as_promotion(),
+            castle: r#move.code().as_castle(),
+            en_passant,
+            check,
+        }
+    }
+
     pub fn make(&mut self, r#move: Move) {
         let color = self.state.get().flags.active_color();
 
         let moved_piece = PieceType::as_array()
             .into_iter()
             .find(|piece| {
-                !(self.state.get().active_boards()[*piece].clone() & BitBoard::from(r#move.from()))
+                !(self.state.get().active_boards()[*piece] & BitBoard::from(r#move.from()))
                     .is_empty()
             })
             .unwrap();
@@ -58,7 +143,7 @@ impl<H: Hasher> Position<H> {
             Some(PieceType::Pawn)
         } else if r#move.code().is_capture() {
             PieceType::as_array().into_iter().find(|piece| {
-                !(self.state.get().inactive_boards()[*piece].clone() & BitBoard::from(r#move.to()))
+                !(self.state.get().inactive_boards()[*piece] & BitBoard::from(r#move.to()))
                     .is_empty()
             })
         } else {
@@ -88,13 +173,7 @@ impl<H: Hasher> Position<H> {
                     self.state.set_castle_right(color, side, false);
                 }
             }
-            if moved_piece == PieceType::Rook {
-                for side in CastleSide::as_array() {
-                    if r#move.from() == SquareFinder(color).castle_rook_target(side) {
-                        self.state.set_castle_right(color, side, false);
-                    }
-                }
-            }
+            self.remove_rook_castle_rights(r#move, moved_piece, captured_piece, color);
 
             if r#move.code() == MoveCode::EnPassant {
                 self.state.remove_piece(
@@ -118,7 +197,35 @@ impl<H: Hasher> Position<H> {
         }
 
         self.state.toggle_color();
-        self.state.increment_halfmove();
+        // The halfmove clock counts the moves since the last capture or pawn move
+        if moved_piece == PieceType::Pawn || captured_piece.is_some() {
+            self.state.set_halfmove(0);
+        } else {
+            self.state.increment_halfmove();
+        }
+    }
+
+    /// Remove the castling rights of a rook that leaves or is captured on its
+    /// starting square.
+    fn remove_rook_castle_rights(
+        &mut self,
+        r#move: Move,
+        moved_piece: PieceType,
+        captured_piece: Option<PieceType>,
+        color: Color,
+    ) {
+        for side in CastleSide::as_array() {
+            if moved_piece == PieceType::Rook
+                && r#move.from() == SquareFinder(color).castle_rook_source(side)
+            {
+                self.state.set_castle_right(color, side, false);
+            }
+            if captured_piece == Some(PieceType::Rook)
+                && r#move.to() == SquareFinder(!color).castle_rook_source(side)
+            {
+                self.state.set_castle_right(!color, side, false);
+            }
+        }
     }
 
     pub fn unmake(&mut self, r#move: Move) {
@@ -142,9 +249,8 @@ impl<H: Hasher> Position<H> {
             PieceType::as_array()
                 .into_iter()
                 .find(|piece| {
-                    !(self.state.get().active_boards()[*piece].clone()
-                        & BitBoard::from(r#move.to()))
-                    .is_empty()
+                    !(self.state.get().active_boards()[*piece] & BitBoard::from(r#move.to()))
+                        .is_empty()
                 })
                 .unwrap()
         };
@@ -173,7 +279,7 @@ impl<H: Hasher> Position<H> {
                 self.state.add_piece(r#move.to(), captured_piece, !color);
             }
         }
-        self.state.decrement_halfmove();
+        self.state.set_halfmove(info.halfmove);
     }
 
     fn castle(&mut self, side: CastleSide) {
@@ -222,7 +328,6 @@ impl<H: Hasher> Position<H> {
 
 /// Irreversible information needed to unmake a move
 struct IrreversibleInfo {
-    #[allow(unused)]
     halfmove: u8,
     en_passant: BitBoard,
     flags: StateFlags,
@@ -235,7 +340,7 @@ impl IrreversibleInfo {
             halfmove: state.halfmove,
             en_passant: state.en_passant,
             flags: state.flags.clone(),
-            captured_piece: captured_piece,
+            captured_piece,
         }
     }
 }
@@ -243,7 +348,6 @@ impl IrreversibleInfo {
 mod tests {
     use crate::{
         hash::{NoopHasher, zobrist::ZobristHasher},
-        r#move::MoveList,
         square::Square,
     };
     use pretty_assertions::assert_eq;
@@ -292,6 +396,70 @@ mod tests {
         dbg!(pos.state.get());
     }
 
+    #[test]
+    fn test_move_queries() {
+        let square = |s: &str| Square::try_from(s).unwrap();
+        let squares = |board: BitBoard| {
+            let mut board = board;
+            std::iter::from_fn(|| board.pop_first_square())
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+        let mut pos =
+            Position::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1", NoopHasher {});
+        assert_eq!(squares(pos.legal_destinations(square("e5"))), ["d6", "e6"]);
+        assert_eq!(
+            squares(pos.legal_destinations(square("e1"))),
+            ["c1", "d1", "f1", "g1", "d2", "e2", "f2"]
+        );
+        assert!(pos.legal_destinations(square("a8")).is_empty());
+        assert!(pos.legal_destinations(square("c3")).is_empty());
+        assert_eq!(
+            squares(pos.movable_squares()),
+            ["a1", "e1", "h1", "e5", "b7"]
+        );
+        assert_eq!(
+            pos.promotion_choices(square("b7"), square("a8")),
+            [
+                PieceType::Queen,
+                PieceType::Rook,
+                PieceType::Bishop,
+                PieceType::Knight
+            ]
+        );
+        assert!(pos.promotion_choices(square("e5"), square("e6")).is_empty());
+
+        let find = |pos: &mut Position<NoopHasher>, m: &str| {
+            pos.legal_moves()
+                .into_iter()
+                .find(|legal| legal.to_string() == m)
+                .unwrap()
+        };
+        let m = find(&mut pos, "e5d6");
+        assert_eq!(
+            pos.move_info(m),
+            MoveInfo {
+                piece: PieceType::Pawn,
+                captured: Some(PieceType::Pawn),
+                promotion: None,
+                castle: None,
+                en_passant: true,
+                check: false,
+            }
+        );
+        let m = find(&mut pos, "b7a8Q");
+        let info = pos.move_info(m);
+        assert_eq!(info.captured, Some(PieceType::Rook));
+        assert_eq!(info.promotion, Some(PieceType::Queen));
+        assert!(info.check);
+        let m = find(&mut pos, "e1g1");
+        assert_eq!(pos.move_info(m).castle, Some(CastleSide::King));
+        assert_eq!(
+            pos.state.get().to_fen(),
+            "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1"
+        );
+    }
+
     #[test]
     fn test_make_unmake_move() {
         let fens = [
@@ -335,6 +503,13 @@ mod tests {
                     m,
                     moved_gs
                 );
+                assert_eq!(
+                    position.state.hasher().pawn_hash(),
+                    new_hasher.pawn_hash(),
+                    "Move: {}\nBoard: {:#?}",
+                    m,
+                    moved_gs
+                );
                 recursize_test_make_unmake_move(position, move_list, depth - 1);
                 position.unmake(m);
                 assert_eq!(
@@ -361,4 +536,68 @@ mod tests {
         }
         move_list.drop_current_ply();
     }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut pos = Position::<NoopHasher>::default();
+        assert_eq!(pos.legal_moves().len(), 20);
+
+        // Only the king can move out of check
+        let mut pos = Position::from_fen("4k3/8/8/8/8/8/3q4/4K2R w K - 0 1", NoopHasher {});
+        let moves = pos.legal_moves();
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|m| m.from() == Square::new(0, 4).unwrap()));
+    }
+
+    #[test]
+    fn test_halfmove_clock() {
+        let mut pos = Position::from_fen("4k3/4p3/8/8/8/8/8/R3K3 w Q - 7 1", NoopHasher {});
+        let moves = ["a1a5", "e8d8", "a5a6", "e7e6", "a6e6"].map(|m| {
+            let m = pos
+                .legal_moves()
+                .into_iter()
+                .find(|m2| m2.matches_perft_string(m))
+                .unwrap();
+            pos.make(m);
+            (m, pos.state.get().halfmove)
+        });
+        // Pawn moves and captures reset the clock
+        assert_eq!(moves.map(|(_, halfmove)| halfmove), [8, 9, 10, 0, 0]);
+        for (m, _) in moves.iter().rev() {
+            pos.unmake(*m);
+        }
+        assert_eq!(pos.state.get().halfmove, 7);
+    }
+
+    #[test]
+    fn test_rook_castle_rights() {
+        let castle_rights = |fen: &str, r#move: &str| {
+            let mut pos = Position::from_fen(fen, NoopHasher {});
+            let m = pos
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.matches_perft_string(r#move))
+                .unwrap();
+            pos.make(m);
+            let rights = pos
+                .state
+                .get()
+                .to_fen()
+                .split(' ')
+                .nth(2)
+                .unwrap()
+                .to_string();
+            pos.unmake(m);
+            assert_eq!(pos.state.get().to_fen(), fen);
+            rights
+        };
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(castle_rights(fen, "h1h2"), "Qkq");
+        // The rook leaving a1 and the one captured on a8 both lose their right
+        assert_eq!(castle_rights(fen, "a1a8"), "Kk");
+        assert_eq!(castle_rights(fen, "h1h8"), "Qq");
+        // A rook leaving the square it castles to keeps the rights
+        let fen = "r3k2r/8/8/8/8/8/8/R2RK2R w KQkq - 0 1";
+        assert_eq!(castle_rights(fen, "d1d2"), "KQkq");
+    }
 }
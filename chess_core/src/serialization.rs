@@ -0,0 +1,449 @@
+// Serde support of the core types, behind the `serde` feature. Human readable
+// formats like JSON get the notation of the types:
+//   - squares as "e4", moves in UCI notation as "e7e8q"
+//   - colors as "white" or "black", piece types as their SAN letter, "N"
+//   - bitboards as the list of their squares, states as FEN
+// Other formats get the bits of the types, and a compact form of the state:
+// the occupied squares (u64), a nibble per occupied square from a1 to h8
+// with the color (bit 3) and the piece type, then the flags, the en passant
+// square (255 for none) and the halfmove clock.
+//
+// UCI notation doesn't tell captures, castles and double pushes apart from
+// other moves, so a move read from it is a quiet move or a promotion. Match
+// it against the legal moves of its position to get the full move, the
+// compact form keeps the whole move.
+
+use std::fmt;
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, SeqAccess, Visitor},
+};
+
+use crate::{
+    color::Color,
+    r#move::{Move, MoveCode},
+    square::{CastleSide, Square},
+    state::{
+        State,
+        bitboard::BitBoard,
+        chess_board::{ChessBoard, PieceType},
+        flags::StateFlags,
+    },
+};
+
+/// Read a value from its notation in human readable formats.
+fn parse<'de, D: Deserializer<'de>, T>(
+    deserializer: D,
+    from_str: impl FnOnce(&str) -> Result<T, &'static str>,
+) -> Result<T, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    from_str(&text).map_err(de::Error::custom)
+}
+
+/// Read a value from its bits in other formats.
+fn decode<'de, D: Deserializer<'de>, B: Deserialize<'de>, T>(
+    deserializer: D,
+    from_bits: impl FnOnce(B) -> Result<T, &'static str>,
+) -> Result<T, D::Error> {
+    from_bits(B::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_u8(self.get())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            parse(deserializer, |text| Square::try_from(text))
+        } else {
+            decode(deserializer, |bits: u8| Square::try_from(bits))
+        }
+    }
+}
+
+fn color_from_str(text: &str) -> Result<Color, &'static str> {
+    match text {
+        "white" => Ok(Color::White),
+        "black" => Ok(Color::Black),
+        _ => Err("Color must be white or black"),
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(match self {
+                Color::White => "white",
+                Color::Black => "black",
+            })
+        } else {
+            serializer.serialize_u8(self.into_bits())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            parse(deserializer, color_from_str)
+        } else {
+            decode(deserializer, |bits: u8| match bits {
+                0 | 1 => Ok(Color::from_bits(bits)),
+                _ => Err("Color bits out of range"),
+            })
+        }
+    }
+}
+
+fn piece_from_str(text: &str) -> Result<PieceType, &'static str> {
+    PieceType::as_array()
+        .into_iter()
+        .find(|piece| text.len() == 1 && text.starts_with(char::from(*piece)))
+        .ok_or("Piece type must be one of P, N, B, R, Q and K")
+}
+
+impl Serialize for PieceType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_char(char::from(*self))
+        } else {
+            serializer.serialize_u8(*self as u8)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PieceType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            parse(deserializer, piece_from_str)
+        } else {
+            decode(deserializer, |bits: u8| {
+                PieceType::as_array()
+                    .get(bits as usize)
+                    .copied()
+                    .ok_or("Piece type bits out of range")
+            })
+        }
+    }
+}
+
+/// A quiet move or a promotion from its UCI notation.
+fn move_from_uci(text: &str) -> Result<Move, &'static str> {
+    let (from, to, promotion) = match (text.get(..2), text.get(2..4), text.get(4..)) {
+        (Some(from), Some(to), Some(promotion)) if promotion.len() <= 1 => (from, to, promotion),
+        _ => return Err("Move must be in UCI notation"),
+    };
+    let code = match promotion {
+        "" => MoveCode::QuietMove,
+        "n" => MoveCode::KnightPromotion,
+        "b" => MoveCode::BishopPromotion,
+        "r" => MoveCode::RookPromotion,
+        "q" => MoveCode::QueenPromotion,
+        _ => return Err("Promotion piece must be one of n, b, r and q"),
+    };
+    Ok(Move::new(
+        Square::try_from(from)?,
+        Square::try_from(to)?,
+        code,
+    ))
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string().to_lowercase())
+        } else {
+            serializer.serialize_u16(self.into_bits())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            parse(deserializer, move_from_uci)
+        } else {
+            decode(deserializer, |bits: u16| {
+                // The codes stop at 13, a queen promotion with capture
+                if bits >> 12 <= MoveCode::QueenPromotionCapture as u16 {
+                    Ok(Move::from_bits(bits))
+                } else {
+                    Err("Move code out of range")
+                }
+            })
+        }
+    }
+}
+
+impl Serialize for BitBoard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut board = *self;
+            serializer.collect_seq(std::iter::from_fn(|| board.pop_first_square()))
+        } else {
+            serializer.serialize_u64(self.into_bits())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BitBoard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let squares = Vec::<Square>::deserialize(deserializer)?;
+            Ok(squares
+                .into_iter()
+                .fold(BitBoard::EMPTY, |mut board, square| {
+                    board.set(square);
+                    board
+                }))
+        } else {
+            u64::deserialize(deserializer).map(BitBoard::from)
+        }
+    }
+}
+
+const NO_EN_PASSANT: u8 = 255;
+
+fn state_to_bytes(state: &State) -> Vec<u8> {
+    let mut occupied = BitBoard::EMPTY;
+    let mut nibbles = Vec::new();
+    for square in Square::iter() {
+        if let Some((color, piece)) = state.piece_at(square) {
+            occupied.set(square);
+            nibbles.push((color.into_bits() << 3) | piece as u8);
+        }
+    }
+    let mut bytes = occupied.into_bits().to_le_bytes().to_vec();
+    bytes.extend(
+        nibbles
+            .chunks(2)
+            .map(|pair| pair[0] | pair.get(1).map_or(0, |nibble| nibble << 4)),
+    );
+    let en_passant = state.en_passant.get_first_square();
+    bytes.push(state.flags.clone().into_bits());
+    bytes.push(en_passant.map_or(NO_EN_PASSANT, |square| square.get()));
+    bytes.push(state.halfmove);
+    bytes
+}
+
+fn state_from_bytes(bytes: &[u8]) -> Result<State, &'static str> {
+    let (occupied, rest) = bytes
+        .split_first_chunk::<8>()
+        .ok_or("State bytes truncated")?;
+    let mut occupied = BitBoard::from(u64::from_le_bytes(*occupied));
+    let [pieces @ .., flags, en_passant, halfmove] = rest else {
+        return Err("State bytes truncated");
+    };
+    // Two nibbles to a byte, the last one padded when the count is odd
+    if pieces.len() != (occupied.count_ones() as usize).div_ceil(2) {
+        return Err("State bytes don't match the occupied squares");
+    }
+
+    let mut boards = ChessBoard::EMPTY;
+    let nibbles = pieces.iter().flat_map(|byte| [byte & 0xF, byte >> 4]);
+    for (square, nibble) in std::iter::from_fn(|| occupied.pop_first_square()).zip(nibbles) {
+        let piece = PieceType::as_array()
+            .get((nibble & 0x7) as usize)
+            .copied()
+            .ok_or("State piece out of range")?;
+        boards[Color::from_bits(nibble >> 3)][piece].set(square);
+    }
+    if boards.white.king.count_ones() != 1 || boards.black.king.count_ones() != 1 {
+        return Err("State needs one king of each color");
+    }
+    let flags = StateFlags::from_bits(*flags);
+
+    // The square a pawn of the other side just skipped
+    let en_passant = match *en_passant {
+        NO_EN_PASSANT => BitBoard::EMPTY,
+        square => {
+            let square = Square::try_from(square)?;
+            let rank = match flags.active_color() {
+                Color::White => 5,
+                Color::Black => 2,
+            };
+            if square.rank() != rank {
+                return Err("State en passant square not behind a double pawn push");
+            }
+            BitBoard::from(square)
+        }
+    };
+
+    // Castling needs the king and the rook on their starting squares
+    for color in Color::as_array() {
+        let back_rank = if color == Color::White { 0 } else { 7 };
+        let at = |file: u8| Square::try_from(back_rank * 8 + file).unwrap();
+        for (side, rook_file) in [(CastleSide::King, 7), (CastleSide::Queen, 0)] {
+            if flags.castle_right(color, side)
+                && !(boards[color].king.get(at(4)) && boards[color].rook.get(at(rook_file)))
+            {
+                return Err("State castling rights don't match the king and rooks");
+            }
+        }
+    }
+
+    Ok(State {
+        boards,
+        en_passant,
+        flags,
+        halfmove: *halfmove,
+    })
+}
+
+struct StateBytesVisitor;
+
+impl<'de> Visitor<'de> for StateBytesVisitor {
+    type Value = State;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the bytes of a state")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<State, E> {
+        state_from_bytes(bytes).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<State, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
+
+impl Serialize for State {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_fen())
+        } else {
+            serializer.serialize_bytes(&state_to_bytes(self))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for State {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            parse(deserializer, State::try_from_fen)
+        } else {
+            deserializer.deserialize_bytes(StateBytesVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+
+    use crate::{hash::NoopHasher, position::Position};
+
+    use super::*;
+
+    fn json<T: Serialize + DeserializeOwned + PartialEq + fmt::Debug>(value: T) -> String {
+        let text = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&text).unwrap(), value);
+        text
+    }
+
+    fn binary<T: Serialize + DeserializeOwned + PartialEq + fmt::Debug>(value: T) -> Vec<u8> {
+        let bytes = bincode::serialize(&value).unwrap();
+        assert_eq!(bincode::deserialize::<T>(&bytes).unwrap(), value);
+        bytes
+    }
+
+    #[test]
+    fn test_human_readable() {
+        let e4 = Square::try_from("e4").unwrap();
+        assert_eq!(json(e4), "\"e4\"");
+        assert_eq!(json(Color::Black), "\"black\"");
+        assert_eq!(json(PieceType::Knight), "\"N\"");
+        let e7 = Square::try_from("e7").unwrap();
+        let e8 = Square::try_from("e8").unwrap();
+        assert_eq!(
+            json(Move::new(e7, e8, MoveCode::QueenPromotion)),
+            "\"e7e8q\""
+        );
+        let mut board = BitBoard::from(e4);
+        board.set(e7);
+        assert_eq!(json(board), "[\"e4\",\"e7\"]");
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 3 1";
+        assert_eq!(json(State::from_fen(fen)), format!("\"{}\"", fen));
+
+        assert!(serde_json::from_str::<Square>("\"i9\"").is_err());
+        assert!(serde_json::from_str::<Color>("\"red\"").is_err());
+        assert!(serde_json::from_str::<PieceType>("\"NB\"").is_err());
+        assert!(serde_json::from_str::<Move>("\"e7e8k\"").is_err());
+        assert!(serde_json::from_str::<Move>("\"e7\"").is_err());
+        assert!(serde_json::from_str::<Move>("[\"e1\",\"g1\",\"king_castle\"]").is_err());
+        assert!(serde_json::from_str::<State>("\"8/8 w\"").is_err());
+    }
+
+    #[test]
+    fn test_uci_moves() {
+        // A capture comes back as a quiet move, to match with the legal moves
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+        let mut position = Position::from_fen(fen, NoopHasher {});
+        for legal in position.legal_moves() {
+            let text = serde_json::to_string(&legal).unwrap();
+            assert_eq!(text, format!("\"{}\"", legal.to_string().to_lowercase()));
+            let read: Move = serde_json::from_str(&text).unwrap();
+            assert_eq!((read.from(), read.to()), (legal.from(), legal.to()));
+            assert_eq!(read.code().as_promotion(), legal.code().as_promotion());
+            assert!(read.code().is_quiet() || read.code().as_promotion().is_some());
+        }
+        let e1 = Square::try_from("e1").unwrap();
+        let g1 = Square::try_from("g1").unwrap();
+        assert_eq!(json(Move::new(e1, g1, MoveCode::QuietMove)), "\"e1g1\"");
+    }
+
+    #[test]
+    fn test_binary() {
+        assert_eq!(binary(Square::try_from("h8").unwrap()), [63]);
+        assert_eq!(binary(Color::White), [0]);
+        assert_eq!(binary(PieceType::Queen), [4]);
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+        let mut position = Position::from_fen(fen, NoopHasher {});
+        for legal in position.legal_moves() {
+            binary(legal);
+        }
+        binary(BitBoard::FULL);
+
+        // The length, 8 bytes of occupied squares, 5 for 9 pieces, then the
+        // flags, the en passant square and the clock
+        let state = State::from_fen(fen);
+        assert_eq!(binary(state.clone()).len(), 8 + 8 + 5 + 3);
+        binary(State::default());
+        let bytes = state_to_bytes(&state);
+        assert_eq!(state_from_bytes(&bytes), Ok(state));
+        assert!(state_from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(state_from_bytes(&bytes[1..]).is_err());
+
+        // En passant on d3 with white to move, castling without the h8 rook
+        let mut invalid = bytes.clone();
+        let en_passant = invalid.len() - 2;
+        invalid[en_passant] = Square::try_from("d3").unwrap().get();
+        assert!(state_from_bytes(&invalid).is_err());
+        let state = State::from_fen("r3k3/8/8/8/8/8/8/R3K2R w KQq - 0 1");
+        let bytes = state_to_bytes(&state);
+        assert_eq!(state_from_bytes(&bytes), Ok(state));
+        let flags = bytes.len() - 3;
+        let mut invalid = bytes.clone();
+        invalid[flags] = State::from_fen("r3k3/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+            .flags
+            .into_bits();
+        assert!(state_from_bytes(&invalid).is_err());
+
+        assert!(bincode::deserialize::<Color>(&[2]).is_err());
+        assert!(bincode::deserialize::<Move>(&0xF000_u16.to_le_bytes()).is_err());
+        assert!(bincode::deserialize::<Square>(&[64]).is_err());
+    }
+}
@@ -64,7 +64,7 @@ impl Square {
     }
 
     pub fn iter() -> impl Iterator<Item = Square> {
-        (0..64).map(|x| Square(x))
+        (0..64).map(Square)
     }
 
     pub const fn from_bits(bits: u8) -> Self {
@@ -78,7 +78,7 @@ impl Square {
 
 impl Display for Square {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        const A: u8 = 'a' as u8;
+        const A: u8 = b'a';
         write!(f, "{}{}", (self.file() + A) as char, self.rank() + 1)
     }
 }
@@ -93,14 +93,12 @@ impl TryFrom<&str> for Square {
     type Error = &'static str;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        const A: u8 = 'a' as u8;
-        const H: u8 = 'h' as u8;
-        const ZERO: u8 = '0' as u8;
-        const EIGHT: u8 = '8' as u8;
+        const A: u8 = b'a';
+        const H: u8 = b'h';
+        const ONE: u8 = b'1';
+        const EIGHT: u8 = b'8';
         match *value.as_bytes() {
-            [file @ A..=H, rank @ ZERO..EIGHT] => {
-                Ok(Square::new_unchecked(rank - ZERO - 1, file - A))
-            }
+            [file @ A..=H, rank @ ONE..=EIGHT] => Ok(Square::new_unchecked(rank - ONE, file - A)),
             _ => Err("Square string malformed."),
         }
     }
@@ -128,7 +126,7 @@ impl SquareFinder {
         match piece {
             PieceType::Queen => Some(square!(self, 0, 3)),
             PieceType::King => Some(square!(self, 0, 4)),
-            _ => return None,
+            _ => None,
         }
     }
 
@@ -198,7 +196,7 @@ impl SquareFinder {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CastleSide {
     King,
     Queen,
@@ -29,6 +29,10 @@ impl BitBoard {
     pub const EMPTY: Self = Self(0);
     pub const FULL: Self = Self(0xFFFF_FFFF_FFFF_FFFF);
 
+    pub const fn into_bits(self) -> u64 {
+        self.0
+    }
+
     pub fn is_empty(&self) -> bool {
         *self == BitBoard::EMPTY
     }
@@ -101,6 +105,39 @@ impl BitBoard {
         BitBoard(0xFF_u64 << (number * 8))
     }
 
+    /// Squares one file to the east, without wrapping around the board.
+    pub fn east(self) -> Self {
+        BitBoard((self.0 << 1) & !0x0101_0101_0101_0101_u64)
+    }
+
+    /// Squares one file to the west, without wrapping around the board.
+    pub fn west(self) -> Self {
+        BitBoard((self.0 >> 1) & !0x8080_8080_8080_8080_u64)
+    }
+
+    /// The board with every set square extended towards rank 8.
+    pub fn north_fill(self) -> Self {
+        let mut b = self.0;
+        b |= b << 8;
+        b |= b << 16;
+        b |= b << 32;
+        BitBoard(b)
+    }
+
+    /// The board with every set square extended towards rank 1.
+    pub fn south_fill(self) -> Self {
+        let mut b = self.0;
+        b |= b >> 8;
+        b |= b >> 16;
+        b |= b >> 32;
+        BitBoard(b)
+    }
+
+    /// The board with every set square extended over its whole file.
+    pub fn file_fill(self) -> Self {
+        self.north_fill() | self.south_fill()
+    }
+
     pub fn count_ones(&self) -> u32 {
         self.0.count_ones()
     }
@@ -200,6 +237,18 @@ mod tests {
         // assert_eq!(0.get_lsb(), 64);
     }
 
+    #[test]
+    fn test_fills_and_shifts() {
+        let e4 = BitBoard::from(Square::try_from("e4").unwrap());
+        assert_eq!(e4.north_fill(), BitBoard::file(4) & !BitBoard(0xFF_FFFF));
+        assert_eq!(e4.south_fill(), BitBoard::file(4) & BitBoard(0xFFFF_FFFF));
+        assert_eq!(e4.file_fill(), BitBoard::file(4));
+        assert_eq!(BitBoard::file(7).east(), BitBoard::EMPTY);
+        assert_eq!(BitBoard::file(0).west(), BitBoard::EMPTY);
+        assert_eq!(BitBoard::file(3).east(), BitBoard::file(4));
+        assert_eq!(BitBoard::file(3).west(), BitBoard::file(2));
+    }
+
     #[test]
     fn test_get_msb() {
         let bb = BitBoard(0b1100);
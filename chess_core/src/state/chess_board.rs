@@ -144,13 +144,26 @@ impl ChessBoard {
     };
 
     pub fn from_fen(board: &str) -> Self {
+        Self::try_from_fen(board).unwrap()
+    }
+
+    /// Read the board field of a FEN, which needs eight full ranks and a
+    /// single king of each color.
+    pub fn try_from_fen(board: &str) -> Result<Self, &'static str> {
         let mut boards = ChessBoard::EMPTY;
 
-        for (line, rank) in board.split('/').rev().zip(0_u8..) {
+        let lines = board.split('/').collect::<Vec<_>>();
+        if lines.len() != 8 {
+            return Err("FEN board needs 8 ranks");
+        }
+        for (line, rank) in lines.into_iter().rev().zip(0_u8..) {
             let mut file = 0_u8;
             for c in line.chars() {
-                if c.is_ascii_digit() {
-                    file += c.to_digit(10).unwrap() as u8;
+                if file >= 8 {
+                    return Err("FEN rank has more than 8 squares");
+                }
+                if let Some(empty) = c.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                    file += empty as u8;
                 } else {
                     let color_board = if c.is_uppercase() {
                         &mut boards.white
@@ -164,14 +177,20 @@ impl ChessBoard {
                         'r' => &mut color_board.rook,
                         'q' => &mut color_board.queen,
                         'k' => &mut color_board.king,
-                        _ => panic!("Invalid piece type"),
+                        _ => return Err("Invalid piece type"),
                     };
                     bb.set(Square::new_unchecked(rank, file));
                     file += 1;
                 }
             }
+            if file != 8 {
+                return Err("FEN rank must have 8 squares");
+            }
+        }
+        if boards.white.king.count_ones() != 1 || boards.black.king.count_ones() != 1 {
+            return Err("FEN board needs one king of each color");
         }
-        boards
+        Ok(boards)
     }
 
     pub fn to_fen(&self) -> String {
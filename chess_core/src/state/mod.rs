@@ -7,7 +7,7 @@ use crate::{
     square::Square,
     state::{
         bitboard::BitBoard,
-        chess_board::{ChessBoard, ChessBoardSide},
+        chess_board::{ChessBoard, ChessBoardSide, PieceType},
         flags::StateFlags,
     },
 };
@@ -42,27 +42,44 @@ impl Default for State {
 
 impl State {
     pub fn from_fen(fen: &str) -> Self {
+        Self::try_from_fen(fen).unwrap()
+    }
+
+    pub fn try_from_fen(fen: &str) -> Result<Self, &'static str> {
         let mut split = fen.split_whitespace();
-        let board_str = split.next().unwrap();
-        let active_color = split.next().unwrap();
-        let castling = split.next().unwrap();
-        let en_passant = split.next().unwrap();
+        let (Some(board_str), Some(active_color), Some(castling), Some(en_passant)) =
+            (split.next(), split.next(), split.next(), split.next())
+        else {
+            return Err(
+                "FEN needs a board, a side to move, castling rights and an en passant square",
+            );
+        };
         let halfmove = split.next().unwrap_or("0");
 
         // let _fullmove = split.next().unwrap();
-        let boards = ChessBoard::from_fen(board_str);
-        let flags = StateFlags::from_fen(active_color.chars().nth(0).unwrap(), castling);
+        let boards = ChessBoard::try_from_fen(board_str)?;
+        let active_color = match active_color {
+            "w" => 'w',
+            "b" => 'b',
+            _ => return Err("FEN side to move must be w or b"),
+        };
+        if castling != "-" && !castling.chars().all(|c| "KQkq".contains(c)) {
+            return Err("FEN castling rights malformed");
+        }
+        let flags = StateFlags::from_fen(active_color, castling);
         let en_passant = match en_passant {
             "-" => BitBoard::EMPTY,
-            s => BitBoard::from(Square::try_from(s).unwrap()),
+            s => BitBoard::from(Square::try_from(s)?),
         };
-        let halfmove: u8 = halfmove.parse().unwrap();
-        State {
+        let halfmove: u8 = halfmove
+            .parse()
+            .map_err(|_| "FEN halfmove clock malformed")?;
+        Ok(State {
             boards,
             en_passant,
             flags,
             halfmove,
-        }
+        })
     }
 
     pub fn to_fen(&self) -> String {
@@ -76,6 +93,16 @@ impl State {
         format!("{} {} {} {} 1", board_str, flags, en_passant, self.halfmove)
     }
 
+    /// The color and type of the piece on `square`, if any.
+    pub fn piece_at(&self, square: Square) -> Option<(Color, PieceType)> {
+        Color::as_array().into_iter().find_map(|color| {
+            PieceType::as_array()
+                .into_iter()
+                .find(|piece| self.boards[color][*piece].get(square))
+                .map(|piece| (color, piece))
+        })
+    }
+
     pub fn inactive_boards(&self) -> &ChessBoardSide {
         match self.flags.active_color() {
             Color::White => &self.boards.black,
@@ -125,6 +152,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_try_from_fen() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(State::try_from_fen(fen), Ok(State::default()));
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1",
+            "rnbqxbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQxq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+        ] {
+            assert!(State::try_from_fen(fen).is_err(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_piece_at() {
+        let gs = State::default();
+        let square = |s: &str| Square::try_from(s).unwrap();
+        assert_eq!(
+            gs.piece_at(square("e1")),
+            Some((Color::White, PieceType::King))
+        );
+        assert_eq!(
+            gs.piece_at(square("d8")),
+            Some((Color::Black, PieceType::Queen))
+        );
+        assert_eq!(gs.piece_at(square("e4")), None);
+    }
+
     #[test]
     fn test_to_fen() {
         let fens = [
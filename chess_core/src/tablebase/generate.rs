@@ -0,0 +1,367 @@
+// Retrograde analysis: start from the checkmates and walk backwards with
+// un-moves, one ply at a time.
+//   - A position is won in n plies if some move leads to a position lost in n - 1.
+//   - A position is lost in n plies once every move leads to a position won in
+//     at most n - 1. Each position counts its remaining unresolved successors and
+//     is lost when the count reaches zero.
+// Symmetric positions share an index, so several moves can lead to the same
+// successor and several un-moves to the same predecessor. Successors are
+// counted once per index, and each resolved position is passed once to each
+// of its predecessors.
+// Captures and promotions leave the table, their outcome is looked up in the
+// tables generated before this one. Positions never reached are draws.
+
+use crate::{
+    color::Color,
+    hash::{HashedState, NoopHasher},
+    r#move::MoveList,
+    position::Position,
+    square::Square,
+    state::{State, bitboard::BitBoard, chess_board::PieceType},
+};
+
+use super::{DRAW, Dtm, EndgameTable, EndgameTables, INVALID, MAX_PLIES, UNKNOWN, normalize};
+
+/// Materials reached from `pieces` by a capture of the lone king or a promotion.
+pub(super) fn dependencies(pieces: &[PieceType]) -> Vec<Vec<PieceType>> {
+    let mut dependencies = Vec::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        let mut captured = pieces.to_vec();
+        captured.remove(i);
+        dependencies.push(captured);
+        if *piece == PieceType::Pawn {
+            for promotion in [
+                PieceType::Queen,
+                PieceType::Rook,
+                PieceType::Bishop,
+                PieceType::Knight,
+            ] {
+                let mut promoted = pieces.to_vec();
+                promoted[i] = promotion;
+                dependencies.push(normalize(promoted));
+            }
+        }
+    }
+    dependencies
+}
+
+/// Squares a pawn of `color` standing on `to` could have been pushed from.
+fn pawn_push_sources(color: Color, to: Square, occupied: BitBoard) -> BitBoard {
+    let (start_rank, single, double) = match color {
+        Color::White => (1, to.get().checked_sub(8), to.get().checked_sub(16)),
+        Color::Black => (
+            6,
+            Some(to.get() + 8).filter(|s| *s < 64),
+            Some(to.get() + 16).filter(|s| *s < 64),
+        ),
+    };
+    let mut sources = BitBoard::EMPTY;
+    let Some(single) = single.map(Square::from_bits) else {
+        return sources;
+    };
+    // Pawns never stand on their back rank
+    if occupied.get(single) || single.rank() == 0 || single.rank() == 7 {
+        return sources;
+    }
+    sources.set(single);
+    if let Some(double) = double.map(Square::from_bits)
+        && double.rank() == start_rank
+        && !occupied.get(double)
+    {
+        sources.set(double);
+    }
+    sources
+}
+
+/// Positions one ply before `state`, where the side not to move made a move that
+/// neither captured nor promoted.
+fn unmoves(state: &State, out: &mut Vec<State>) {
+    let mover = !state.flags.active_color();
+    let occupied = state.boards.white.union() | state.boards.black.union();
+    for piece in PieceType::as_array() {
+        let mut pieces = state.boards[mover][piece];
+        while let Some(to) = pieces.pop_first_square() {
+            // Moves are reversible, except for pawns which only go forward
+            let mut sources = match piece {
+                PieceType::Pawn => pawn_push_sources(mover, to, occupied),
+                _ => state.piece_attacks(piece, mover, to) & !occupied,
+            };
+            while let Some(from) = sources.pop_first_square() {
+                let mut previous = state.clone();
+                previous.boards[mover][piece].unset(to);
+                previous.boards[mover][piece].set(from);
+                previous.en_passant = BitBoard::EMPTY;
+                previous.flags.toggle_color();
+                out.push(previous);
+            }
+        }
+    }
+}
+
+/// Generate the table for `pieces`. `tables` must hold the tables reached by
+/// captures and promotions that still have mating material.
+pub(super) fn generate(
+    pieces: &[PieceType],
+    tables: &EndgameTables,
+) -> Result<EndgameTable, &'static str> {
+    let mut table = EndgameTable::new(pieces.to_vec(), Vec::new());
+    let size = table.size();
+    table.values = vec![INVALID; size];
+    // Unresolved successors of each position
+    let mut remaining = vec![0u8; size];
+    // Positions won or lost through a capture or promotion, by ply
+    let mut exit_wins = vec![Vec::new(); MAX_PLIES as usize + 2];
+    let mut exit_losses = vec![Vec::new(); MAX_PLIES as usize + 2];
+    let mut mated = Vec::new();
+
+    let mut move_list = MoveList::new();
+    let mut children = Vec::new();
+    for (index, remaining) in remaining.iter_mut().enumerate() {
+        let Some(state) = table.state_at(index) else {
+            continue;
+        };
+        if !state.was_move_legal() {
+            continue;
+        }
+        let in_check = state.is_check();
+        let mut position = Position::new(HashedState::new(state, NoopHasher {}));
+        move_list.new_ply();
+        position.pseudo_legal_moves(&mut move_list);
+        let ply_number = move_list.ply_number();
+
+        let mut legal_moves = 0;
+        let mut exit_moves = 0;
+        children.clear();
+        let mut fastest_exit_win = None;
+        for i in 0..move_list.ply_size(ply_number) {
+            let m = move_list.r#move(ply_number, i);
+            position.make(m);
+            if position.was_move_legal() {
+                legal_moves += 1;
+                let child = position.state.get();
+                if table.covers(child) {
+                    children.push(table.state_index(child));
+                } else {
+                    match tables.probe(child).ok_or("Missing table for an exit")? {
+                        Dtm::Loss(plies) => {
+                            fastest_exit_win =
+                                Some(fastest_exit_win.map_or(plies + 1, |f: u8| f.min(plies + 1)));
+                        }
+                        Dtm::Win(plies) => {
+                            exit_moves += 1;
+                            exit_losses[plies as usize + 1].push(index);
+                        }
+                        // Never resolved, so the position can't be lost
+                        Dtm::Draw => exit_moves += 1,
+                    }
+                }
+            }
+            position.unmake(m);
+        }
+        move_list.drop_current_ply();
+        children.sort_unstable();
+        children.dedup();
+
+        *remaining = exit_moves + children.len() as u8;
+        table.values[index] = if legal_moves > 0 {
+            if let Some(plies) = fastest_exit_win {
+                exit_wins[plies as usize].push(index);
+            }
+            UNKNOWN
+        } else if in_check {
+            mated.push(index);
+            Dtm::Loss(0).to_value()
+        } else {
+            DRAW
+        };
+    }
+
+    let mut previous = mated;
+    let mut predecessors = Vec::new();
+    let mut indices = Vec::new();
+    for plies in 1..=MAX_PLIES {
+        let mut current = Vec::new();
+        let win = plies % 2 == 1;
+        let mut resolve = |index: usize, values: &mut Vec<u8>, current: &mut Vec<usize>| {
+            if values[index] != UNKNOWN {
+                return;
+            }
+            if !win {
+                remaining[index] -= 1;
+                if remaining[index] > 0 {
+                    return;
+                }
+            }
+            values[index] = if win {
+                Dtm::Win(plies).to_value()
+            } else {
+                Dtm::Loss(plies).to_value()
+            };
+            current.push(index);
+        };
+
+        for &index in &previous {
+            let state = table.state_at(index).unwrap();
+            predecessors.clear();
+            unmoves(&state, &mut predecessors);
+            indices.clear();
+            indices.extend(predecessors.iter().map(|p| table.state_index(p)));
+            indices.sort_unstable();
+            indices.dedup();
+            for &predecessor in &indices {
+                resolve(predecessor, &mut table.values, &mut current);
+            }
+        }
+        let exits = if win {
+            &exit_wins[plies as usize]
+        } else {
+            &exit_losses[plies as usize]
+        };
+        for &index in exits {
+            resolve(index, &mut table.values, &mut current);
+        }
+
+        previous = current;
+        let pending = exit_wins[plies as usize + 1..]
+            .iter()
+            .chain(&exit_losses[plies as usize + 1..])
+            .any(|exits| !exits.is_empty());
+        if previous.is_empty() && !pending {
+            break;
+        }
+    }
+
+    for value in table.values.iter_mut() {
+        if *value == UNKNOWN {
+            *value = DRAW;
+        }
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fens(states: &[State]) -> Vec<String> {
+        let mut fens = states.iter().map(|s| s.to_fen()).collect::<Vec<_>>();
+        fens.sort();
+        fens
+    }
+
+    #[test]
+    fn test_unmoves() {
+        let mut out = Vec::new();
+        // White just moved, either the king or the pawn
+        let state = State::from_fen("8/8/8/8/P7/8/8/K6k b - - 0 1");
+        unmoves(&state, &mut out);
+        let mut expected = [
+            "8/8/8/8/8/8/P7/K6k w - - 0 1",
+            "8/8/8/8/8/P7/8/K6k w - - 0 1",
+            "8/8/8/8/P7/8/8/1K5k w - - 0 1",
+            "8/8/8/8/P7/8/1K6/7k w - - 0 1",
+            "8/8/8/8/P7/8/K7/7k w - - 0 1",
+        ];
+        expected.sort();
+        assert_eq!(fens(&out), expected);
+
+        // Sliding un-moves stop at blockers
+        out.clear();
+        let state = State::from_fen("8/8/8/8/8/8/8/KR5k b - - 0 1");
+        unmoves(&state, &mut out);
+        let rook_sources = out
+            .iter()
+            .filter(|s| s.boards.white.king == state.boards.white.king)
+            .count();
+        assert_eq!(rook_sources, 7 + 5);
+    }
+
+    /// Check every entry of the table against the values of its successors.
+    fn assert_consistent(tables: &EndgameTables, key: &str) {
+        let table = tables.get(key).unwrap();
+        for index in 0..table.size() {
+            let Some(state) = table.state_at(index) else {
+                continue;
+            };
+            let stored = Dtm::from_value(table.values[index]);
+            if !state.was_move_legal() {
+                assert_eq!(stored, None, "{}", state.to_fen());
+                continue;
+            }
+            let in_check = state.is_check();
+            let mut position = Position::new(HashedState::new(state, NoopHasher {}));
+            let mut fastest_win = None;
+            let mut slowest_loss = Some(0);
+            for m in position.legal_moves() {
+                position.make(m);
+                match tables.probe(position.state.get()).unwrap() {
+                    Dtm::Loss(plies) => {
+                        fastest_win = Some(fastest_win.map_or(plies + 1, |f: u8| f.min(plies + 1)))
+                    }
+                    Dtm::Win(plies) => slowest_loss = slowest_loss.map(|s: u8| s.max(plies + 1)),
+                    Dtm::Draw => slowest_loss = None,
+                }
+                position.unmake(m);
+            }
+            let expected = match (fastest_win, slowest_loss) {
+                (Some(plies), _) => Dtm::Win(plies),
+                (None, Some(0)) if !in_check => Dtm::Draw,
+                (None, Some(plies)) => Dtm::Loss(plies),
+                (None, None) => Dtm::Draw,
+            };
+            assert_eq!(stored, Some(expected), "{}", position.state.get().to_fen());
+        }
+    }
+
+    #[test]
+    fn test_one_ply_lookahead() {
+        let tables = EndgameTables::generate(&["KPK"]).unwrap();
+        for key in ["KQK", "KRK", "KPK"] {
+            assert_consistent(&tables, key);
+        }
+        // A symmetric position the opposing king can flee from on both sides
+        assert_eq!(
+            tables.probe(&State::from_fen("8/8/8/8/8/2k5/1Q6/K7 b - - 0 1")),
+            Some(Dtm::Loss(12))
+        );
+    }
+
+    #[test]
+    fn test_pawn_push_sources() {
+        let square = |name| Square::try_from(name).unwrap();
+        let a2 = BitBoard::from(square("a2"));
+        assert_eq!(
+            pawn_push_sources(Color::White, square("a3"), BitBoard::EMPTY),
+            a2
+        );
+        assert_eq!(
+            pawn_push_sources(Color::White, square("a2"), BitBoard::EMPTY),
+            BitBoard::EMPTY
+        );
+        assert_eq!(
+            pawn_push_sources(Color::White, square("a4"), a2),
+            BitBoard::from(square("a3"))
+        );
+        assert_eq!(
+            pawn_push_sources(Color::Black, square("h5"), BitBoard::EMPTY),
+            BitBoard::from(square("h6")) | square("h7").into()
+        );
+    }
+
+    #[test]
+    fn test_dependencies() {
+        use PieceType::*;
+        assert_eq!(dependencies(&[Queen]), vec![vec![]]);
+        assert_eq!(
+            dependencies(&[Rook, Pawn]),
+            vec![
+                vec![Pawn],
+                vec![Rook],
+                vec![Queen, Rook],
+                vec![Rook, Rook],
+                vec![Rook, Bishop],
+                vec![Rook, Knight],
+            ]
+        );
+    }
+}
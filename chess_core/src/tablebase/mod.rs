@@ -0,0 +1,712 @@
+// Distance to mate tables for a lone king against a king and one or two pieces.
+//
+// The strong side is always stored as white: positions where black has the
+// pieces are mirrored vertically with the colors swapped before lookup.
+// Every position is indexed by the side to move, both kings and the extra
+// pieces, one square per piece. Symmetric positions share an index, which
+// restricts the white king to 10 squares without pawns and 32 with pawns.
+//
+// Tables are stored on disk as a small header followed by the values packed
+// with as few bits as the number of distinct values needs:
+//   - magic "CETB" and version byte
+//   - material key length (u8) and key, e.g. "KBNK"
+//   - number of distinct values (u8) and the values
+//   - the index of each position's value in that list, packed little endian
+
+mod generate;
+
+use std::{fs, io, path::Path};
+
+use crate::{
+    color::Color,
+    square::Square,
+    state::{
+        State,
+        bitboard::BitBoard,
+        chess_board::{ChessBoard, ChessBoardSide, PieceType},
+        flags::StateFlags,
+    },
+};
+
+const MAGIC: &[u8; 4] = b"CETB";
+const VERSION: u8 = 1;
+
+/// Stored value of drawn positions, other legal positions store the plies to mate + 1.
+const DRAW: u8 = 0;
+/// Stored value of illegal positions.
+const INVALID: u8 = 255;
+/// Value of positions not resolved yet during generation.
+const UNKNOWN: u8 = 254;
+/// Longest distance to mate that can be stored, in plies.
+pub const MAX_PLIES: u8 = 252;
+
+const MAX_PIECES: usize = 2;
+
+/// Distance to mate in plies with perfect play, from the point of view of the side to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dtm {
+    /// The side to move mates in this many plies.
+    Win(u8),
+    /// The side to move is mated in this many plies, 0 when already checkmated.
+    Loss(u8),
+    Draw,
+}
+
+impl Dtm {
+    fn from_value(value: u8) -> Option<Dtm> {
+        match value {
+            INVALID => None,
+            DRAW => Some(Dtm::Draw),
+            v if (v - 1) % 2 == 1 => Some(Dtm::Win(v - 1)),
+            v => Some(Dtm::Loss(v - 1)),
+        }
+    }
+
+    fn to_value(self) -> u8 {
+        match self {
+            Dtm::Win(plies) | Dtm::Loss(plies) => plies + 1,
+            Dtm::Draw => DRAW,
+        }
+    }
+}
+
+/// Letters of the pieces in material keys, strongest first.
+const PIECE_ORDER: [(PieceType, char); 5] = [
+    (PieceType::Queen, 'Q'),
+    (PieceType::Rook, 'R'),
+    (PieceType::Bishop, 'B'),
+    (PieceType::Knight, 'N'),
+    (PieceType::Pawn, 'P'),
+];
+
+/// Extra pieces of the strong side from a key like `KBNK`, in key order.
+fn parse_key(key: &str) -> Result<Vec<PieceType>, &'static str> {
+    let pieces = key
+        .strip_prefix('K')
+        .and_then(|key| key.strip_suffix('K'))
+        .ok_or("Material key must start and end with a king")?
+        .chars()
+        .map(|c| {
+            PIECE_ORDER
+                .iter()
+                .find(|(_, name)| *name == c)
+                .map(|(piece, _)| *piece)
+                .ok_or("Unknown piece in material key")
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if pieces.is_empty() || pieces.len() > MAX_PIECES {
+        return Err("Tables hold one or two pieces besides the kings");
+    }
+    Ok(normalize(pieces))
+}
+
+/// Sort pieces in key order.
+fn normalize(mut pieces: Vec<PieceType>) -> Vec<PieceType> {
+    pieces.sort_by_key(|piece| PIECE_ORDER.iter().position(|(p, _)| p == piece));
+    pieces
+}
+
+fn material_key(pieces: &[PieceType]) -> String {
+    let mut key = String::from("K");
+    for piece in pieces {
+        key.push(char::from(*piece));
+    }
+    key.push('K');
+    key
+}
+
+/// Pieces of `color` other than the king, in key order.
+fn side_pieces(state: &State, color: Color) -> Vec<PieceType> {
+    let mut pieces = Vec::new();
+    for (piece, _) in PIECE_ORDER {
+        for _ in 0..state.boards[color][piece].count_ones() {
+            pieces.push(piece);
+        }
+    }
+    pieces
+}
+
+/// The strong side and its pieces, if the other side only has its king.
+/// With kings alone, white is considered the strong side.
+fn strong_side(state: &State) -> Option<(Color, Vec<PieceType>)> {
+    let white = side_pieces(state, Color::White);
+    let black = side_pieces(state, Color::Black);
+    match (white.is_empty(), black.is_empty()) {
+        (_, true) => Some((Color::White, white)),
+        (true, false) => Some((Color::Black, black)),
+        _ => None,
+    }
+}
+
+/// One of the 8 symmetries of the board. Tables with pawns only use the
+/// left-right flip since pawns move up the board.
+#[derive(Clone, Copy)]
+struct Symmetry {
+    flip_file: bool,
+    flip_rank: bool,
+    transpose: bool,
+}
+
+impl Symmetry {
+    fn all(pawns: bool) -> Vec<Symmetry> {
+        let mut symmetries = Vec::new();
+        for bits in 0..8 {
+            let symmetry = Symmetry {
+                flip_file: bits & 1 != 0,
+                flip_rank: bits & 2 != 0,
+                transpose: bits & 4 != 0,
+            };
+            if !pawns || !(symmetry.flip_rank || symmetry.transpose) {
+                symmetries.push(symmetry);
+            }
+        }
+        symmetries
+    }
+
+    fn apply(self, square: Square) -> Square {
+        let (mut rank, mut file) = (square.rank(), square.file());
+        if self.flip_file {
+            file = 7 - file;
+        }
+        if self.flip_rank {
+            rank = 7 - rank;
+        }
+        if self.transpose {
+            (rank, file) = (file, rank);
+        }
+        Square::new_unchecked(rank, file)
+    }
+}
+
+/// Squares the white king is restricted to: the a1-d1-d4 triangle without
+/// pawns, the a to d files with pawns. Every position is brought there by a symmetry.
+fn king_squares(pawns: bool) -> Vec<Square> {
+    Square::iter()
+        .filter(|square| square.file() < 4 && (pawns || square.rank() <= square.file()))
+        .collect()
+}
+
+/// A generated distance to mate table.
+pub struct EndgameTable {
+    key: String,
+    pieces: Vec<PieceType>,
+    symmetries: Vec<Symmetry>,
+    king_squares: Vec<Square>,
+    /// Position of each square in `king_squares`.
+    king_slots: [Option<u8>; 64],
+    values: Vec<u8>,
+}
+
+impl EndgameTable {
+    fn new(pieces: Vec<PieceType>, values: Vec<u8>) -> Self {
+        let pawns = pieces.contains(&PieceType::Pawn);
+        let king_squares = king_squares(pawns);
+        let mut king_slots = [None; 64];
+        for (slot, square) in king_squares.iter().enumerate() {
+            king_slots[square.get() as usize] = Some(slot as u8);
+        }
+        EndgameTable {
+            key: material_key(&pieces),
+            pieces,
+            symmetries: Symmetry::all(pawns),
+            king_squares,
+            king_slots,
+            values,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Number of pieces on the board, kings included.
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len() + 2
+    }
+
+    fn size(&self) -> usize {
+        2 * self.king_squares.len() * 64usize.pow(1 + self.pieces.len() as u32)
+    }
+
+    /// Index of a position of this table with white as the strong side. Of all
+    /// the symmetric positions with the white king on one of `king_squares`,
+    /// the one with the smallest index is used.
+    fn state_index(&self, state: &State) -> usize {
+        let white_king = state.boards.white.king.get_first_square().unwrap();
+        let black_king = state.boards.black.king.get_first_square().unwrap();
+        let stm = state.flags.active_color() as usize;
+
+        let mut best = usize::MAX;
+        for symmetry in &self.symmetries {
+            let Some(slot) = self.king_slots[symmetry.apply(white_king).get() as usize] else {
+                continue;
+            };
+            let mut index = (stm * self.king_squares.len() + slot as usize) * 64
+                + symmetry.apply(black_king).get() as usize;
+            let mut previous: Option<(PieceType, Square)> = None;
+            let mut squares = [0u8; MAX_PIECES];
+            for (i, piece) in self.pieces.iter().enumerate() {
+                // Identical pieces are taken in square order
+                let mut board = BitBoard::EMPTY;
+                let mut pieces = state.boards.white[*piece];
+                while let Some(square) = pieces.pop_first_square() {
+                    board.set(symmetry.apply(square));
+                }
+                if let Some((previous_piece, previous_square)) = previous
+                    && previous_piece == *piece
+                {
+                    board.unset(previous_square);
+                }
+                let square = board.get_first_square().unwrap();
+                squares[i] = square.get();
+                previous = Some((*piece, square));
+            }
+            for square in &squares[..self.pieces.len()] {
+                index = index * 64 + *square as usize;
+            }
+            best = best.min(index);
+        }
+        best
+    }
+
+    /// Position at `index`, `None` if pieces overlap, pawns stand on the back
+    /// ranks or another index is used for the position.
+    fn state_at(&self, index: usize) -> Option<State> {
+        let mut rest = index;
+        let mut squares = [Square::from_bits(0); MAX_PIECES];
+        for square in squares[..self.pieces.len()].iter_mut().rev() {
+            *square = Square::from_bits((rest % 64) as u8);
+            rest /= 64;
+        }
+        let black_king = Square::from_bits((rest % 64) as u8);
+        rest /= 64;
+        let white_king = self.king_squares[rest % self.king_squares.len()];
+        let stm = if rest / self.king_squares.len() == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+
+        if white_king == black_king {
+            return None;
+        }
+        let mut white = ChessBoardSide::EMPTY;
+        let mut black = ChessBoardSide::EMPTY;
+        white.king.set(white_king);
+        black.king.set(black_king);
+        let mut occupied = BitBoard::from(white_king) | black_king.into();
+        for (piece, square) in self.pieces.iter().zip(&squares) {
+            if occupied.get(*square) {
+                return None;
+            }
+            if *piece == PieceType::Pawn && (square.rank() == 0 || square.rank() == 7) {
+                return None;
+            }
+            occupied.set(*square);
+            white[*piece].set(*square);
+        }
+
+        let mut flags = StateFlags::from_fen('w', "-");
+        if stm == Color::Black {
+            flags.toggle_color();
+        }
+        let state = State {
+            boards: ChessBoard { white, black },
+            en_passant: BitBoard::EMPTY,
+            flags,
+            halfmove: 0,
+        };
+        (self.state_index(&state) == index).then_some(state)
+    }
+
+    /// Whether the table covers `state`.
+    pub fn covers(&self, state: &State) -> bool {
+        strong_side(state).is_some_and(|(_, pieces)| pieces == self.pieces)
+    }
+
+    /// Distance to mate of the position, `None` if the table doesn't cover it
+    /// or the position is illegal.
+    pub fn probe(&self, state: &State) -> Option<Dtm> {
+        let (color, pieces) = strong_side(state)?;
+        if pieces != self.pieces {
+            return None;
+        }
+        let index = match color {
+            Color::White => self.state_index(state),
+            Color::Black => self.state_index(&mirror(state)),
+        };
+        Dtm::from_value(self.values[index])
+    }
+
+    /// Encode the table in the on-disk format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut palette = self.values.clone();
+        palette.sort_unstable();
+        palette.dedup();
+        let mut codes = [0u8; 256];
+        for (code, value) in palette.iter().enumerate() {
+            codes[*value as usize] = code as u8;
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.key.len() as u8);
+        bytes.extend_from_slice(self.key.as_bytes());
+        bytes.push(palette.len() as u8);
+        bytes.extend_from_slice(&palette);
+
+        let bits = code_bits(palette.len());
+        let mut buffer = 0u32;
+        let mut buffered = 0;
+        for value in &self.values {
+            buffer |= (codes[*value as usize] as u32) << buffered;
+            buffered += bits;
+            while buffered >= 8 {
+                bytes.push(buffer as u8);
+                buffer >>= 8;
+                buffered -= 8;
+            }
+        }
+        if buffered > 0 {
+            bytes.push(buffer as u8);
+        }
+        bytes
+    }
+
+    /// Decode a table written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        let header = bytes.get(..6).ok_or("Truncated table header")?;
+        if &header[..4] != MAGIC {
+            return Err("Not an endgame table");
+        }
+        if header[4] != VERSION {
+            return Err("Unsupported table version");
+        }
+        let key_end = 6 + header[5] as usize;
+        let key = bytes.get(6..key_end).ok_or("Truncated table header")?;
+        let key = std::str::from_utf8(key).map_err(|_| "Invalid material key")?;
+        let mut table = EndgameTable::new(parse_key(key)?, Vec::new());
+
+        let palette_len = *bytes.get(key_end).ok_or("Truncated table header")? as usize;
+        let palette = bytes
+            .get(key_end + 1..key_end + 1 + palette_len)
+            .ok_or("Truncated table header")?;
+        let bits = code_bits(palette_len);
+        let data = &bytes[key_end + 1 + palette_len..];
+        let size = table.size();
+        if data.len() != (size * bits as usize).div_ceil(8) {
+            return Err("Table data doesn't match the material");
+        }
+
+        let mut values = Vec::with_capacity(size);
+        let mut data = data.iter();
+        let mut buffer = 0u32;
+        let mut buffered = 0;
+        for _ in 0..size {
+            while buffered < bits {
+                buffer |= (*data.next().unwrap() as u32) << buffered;
+                buffered += 8;
+            }
+            let code = (buffer & ((1 << bits) - 1)) as usize;
+            values.push(*palette.get(code).ok_or("Invalid table value")?);
+            buffer >>= bits;
+            buffered -= bits;
+        }
+        table.values = values;
+        Ok(table)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Bits needed to store a code for each of `palette_len` values.
+fn code_bits(palette_len: usize) -> u32 {
+    (usize::BITS - palette_len.saturating_sub(1).leading_zeros()).max(1)
+}
+
+/// Flip the board vertically and swap the colors, keeping the same outcome.
+fn mirror(state: &State) -> State {
+    let mut boards = ChessBoard {
+        white: ChessBoardSide::EMPTY,
+        black: ChessBoardSide::EMPTY,
+    };
+    for color in Color::as_array() {
+        for piece in PieceType::as_array() {
+            let mut board = state.boards[color][piece];
+            while let Some(square) = board.pop_first_square() {
+                boards[!color][piece].set(square.mirror());
+            }
+        }
+    }
+    let mut flags = state.flags.clone();
+    flags.toggle_color();
+    State {
+        boards,
+        en_passant: BitBoard::EMPTY,
+        flags,
+        halfmove: state.halfmove,
+    }
+}
+
+/// A set of tables, generated together since promotions lead from one table to another.
+#[derive(Default)]
+pub struct EndgameTables {
+    tables: Vec<EndgameTable>,
+}
+
+impl EndgameTables {
+    /// Generate the tables for the material keys and every table they depend on.
+    pub fn generate(keys: &[&str]) -> Result<Self, &'static str> {
+        let mut tables = EndgameTables::default();
+        for key in keys {
+            tables.generate_table(&parse_key(key)?)?;
+        }
+        Ok(tables)
+    }
+
+    fn generate_table(&mut self, pieces: &[PieceType]) -> Result<(), &'static str> {
+        if self.get(&material_key(pieces)).is_some() || !has_mating_material(pieces) {
+            return Ok(());
+        }
+        for dependency in generate::dependencies(pieces) {
+            self.generate_table(&dependency)?;
+        }
+        let table = generate::generate(pieces, self)?;
+        self.tables.push(table);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, table: EndgameTable) {
+        self.tables.retain(|t| t.key != table.key);
+        self.tables.push(table);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&EndgameTable> {
+        self.tables.iter().find(|table| table.key == key)
+    }
+
+    pub fn tables(&self) -> &[EndgameTable] {
+        &self.tables
+    }
+
+    /// Distance to mate of the position in the table covering it. Positions
+    /// without mating material are draws and don't need a table.
+    pub fn probe(&self, state: &State) -> Option<Dtm> {
+        let (_, pieces) = strong_side(state)?;
+        if !has_mating_material(&pieces) {
+            return Some(Dtm::Draw);
+        }
+        self.get(&material_key(&pieces))?.probe(state)
+    }
+
+    /// Write every table to `directory`, one `<key>.cetb` file per table.
+    pub fn save(&self, directory: impl AsRef<Path>) -> io::Result<()> {
+        for table in &self.tables {
+            table.save(directory.as_ref().join(format!("{}.cetb", table.key)))?;
+        }
+        Ok(())
+    }
+
+    /// Load every `.cetb` table in `directory`.
+    pub fn load(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let mut tables = EndgameTables::default();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "cetb")
+            {
+                tables.insert(EndgameTable::load(path)?);
+            }
+        }
+        Ok(tables)
+    }
+}
+
+/// A lone minor piece can't force mate, neither can nothing at all.
+fn has_mating_material(pieces: &[PieceType]) -> bool {
+    match pieces {
+        [] | [PieceType::Bishop] | [PieceType::Knight] => false,
+        // Two knights can't force mate either, but mates still exist
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dtm(tables: &EndgameTables, fen: &str) -> Option<Dtm> {
+        tables.probe(&State::from_fen(fen))
+    }
+
+    fn longest_win(table: &EndgameTable) -> Option<u8> {
+        table
+            .values
+            .iter()
+            .filter_map(|value| match Dtm::from_value(*value) {
+                Some(Dtm::Win(plies)) => Some(plies),
+                _ => None,
+            })
+            .max()
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("KQK"), Ok(vec![PieceType::Queen]));
+        assert_eq!(
+            parse_key("KNBK"),
+            Ok(vec![PieceType::Bishop, PieceType::Knight])
+        );
+        assert!(parse_key("KK").is_err());
+        assert!(parse_key("KQRPK").is_err());
+        assert!(parse_key("KXK").is_err());
+        assert!(parse_key("QK").is_err());
+        assert_eq!(material_key(&parse_key("KNBK").unwrap()), "KBNK");
+    }
+
+    #[test]
+    fn test_dtm_value() {
+        for dtm in [
+            Dtm::Win(1),
+            Dtm::Win(19),
+            Dtm::Loss(0),
+            Dtm::Loss(18),
+            Dtm::Draw,
+        ] {
+            assert_eq!(Dtm::from_value(dtm.to_value()), Some(dtm));
+        }
+        assert_eq!(Dtm::from_value(INVALID), None);
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let table = EndgameTable::new(vec![PieceType::Bishop, PieceType::Knight], Vec::new());
+        assert_eq!(table.size(), 2 * 10 * 64 * 64 * 64);
+        let state = State::from_fen("8/8/8/4k3/8/2N5/8/B3K3 b - - 0 1");
+        let index = table.state_index(&state);
+        let canonical = table.state_at(index).unwrap();
+        assert_eq!(table.state_index(&canonical), index);
+        // Symmetric positions share the index
+        let flipped = State::from_fen("8/8/8/3k4/8/5N2/8/3K3B b - - 0 1");
+        assert_eq!(table.state_index(&flipped), index);
+        let transposed = State::from_fen("8/8/8/K3k3/8/2N5/8/B7 b - - 0 1");
+        assert_eq!(table.state_index(&transposed), index);
+
+        // With pawns the board is only flipped left to right
+        let pawns = EndgameTable::new(vec![PieceType::Pawn], Vec::new());
+        assert_eq!(pawns.size(), 2 * 32 * 64 * 64);
+        let state = State::from_fen("8/8/8/4k3/8/2P5/8/6K1 w - - 0 1");
+        let flipped = State::from_fen("8/8/8/3k4/8/5P2/8/1K6 w - - 0 1");
+        let index = pawns.state_index(&state);
+        assert_eq!(pawns.state_index(&flipped), index);
+        assert_eq!(pawns.state_at(index), Some(flipped));
+    }
+
+    #[test]
+    fn test_kqk() {
+        let tables = EndgameTables::generate(&["KQK"]).unwrap();
+        // Already mated
+        assert_eq!(
+            dtm(&tables, "k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Loss(0))
+        );
+        // Mate in one
+        assert_eq!(
+            dtm(&tables, "k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        // Stalemate
+        assert_eq!(
+            dtm(&tables, "k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // Black takes the hanging queen
+        assert_eq!(
+            dtm(&tables, "8/8/8/8/8/8/1kQ5/4K3 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // Same position with the colors swapped
+        assert_eq!(
+            dtm(&tables, "K7/1q6/1k6/8/8/8/8/8 w - - 0 1"),
+            Some(Dtm::Loss(0))
+        );
+        // Illegal, the side not to move is in check
+        assert_eq!(dtm(&tables, "k7/8/1K6/8/8/8/8/Q7 w - - 0 1"), None);
+
+        // The longest win takes 10 moves
+        assert_eq!(longest_win(tables.get("KQK").unwrap()), Some(19));
+    }
+
+    #[test]
+    fn test_kpk() {
+        let tables = EndgameTables::generate(&["KPK"]).unwrap();
+        assert!(tables.get("KQK").is_some());
+        assert!(tables.get("KRK").is_some());
+        // Black king can't catch the pawn
+        assert!(matches!(
+            dtm(&tables, "8/8/8/8/P7/8/8/4K2k w - - 0 1"),
+            Some(Dtm::Win(_))
+        ));
+        // Black king keeps the opposition in front of the pawn
+        assert_eq!(
+            dtm(&tables, "8/4k3/8/4P3/4K3/8/8/8 w - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // The king in front of its pawn on the sixth rank wins whoever moves
+        assert!(matches!(
+            dtm(&tables, "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some(Dtm::Win(_))
+        ));
+        assert!(matches!(
+            dtm(&tables, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Loss(_))
+        ));
+        // Rook pawn with the defending king in the corner
+        assert_eq!(
+            dtm(&tables, "7k/8/6KP/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // Insufficient material is a draw without a table
+        assert_eq!(
+            dtm(&tables, "8/8/8/4k3/8/8/8/3BK3 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let tables = EndgameTables::generate(&["KRK"]).unwrap();
+        let table = tables.get("KRK").unwrap();
+        // The longest win takes 16 moves
+        assert_eq!(longest_win(table), Some(31));
+        let bytes = table.to_bytes();
+        // KRK values fit in 6 bits
+        assert!(bytes.len() < table.values.len() * 6 / 8 + 64);
+        let decoded = EndgameTable::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.key(), "KRK");
+        assert!(decoded.values == table.values);
+
+        assert!(EndgameTable::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(EndgameTable::from_bytes(&bytes[..bytes.len() - 2]).is_err());
+        assert!(EndgameTable::from_bytes(b"CETB").is_err());
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(EndgameTable::from_bytes(&wrong_magic).is_err());
+    }
+
+    #[test]
+    #[ignore = "takes minutes without optimizations"]
+    fn test_kbnk() {
+        let tables = EndgameTables::generate(&["KBNK"]).unwrap();
+        // The longest win takes 33 moves
+        assert_eq!(longest_win(tables.get("KBNK").unwrap()), Some(65));
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs;
+
+use chess_core::{
+    color::Color,
+    hash::{HashedState, zobrist::ZobristHasher},
+    pgn::{PgnGame, parse_pgn},
+    position::Position,
+};
+use chess_engines::alpha_beta::{
+    analysis::{Classification, GameAnalysis, analyse_game, annotate},
+    evaluation::SimpleEval,
+    search::SearchContext,
+};
+
+// Review the games of a PGN file: every move is searched, classified from the
+// winning chances it lost, and written back with its evaluation as an
+// `[%eval]` comment and a glyph on inaccuracies, mistakes and blunders. The
+// annotated games go to stdout, the accuracy of each side to stderr.
+
+const DEFAULT_DEPTH: u8 = 4;
+
+fn analyse(game: &PgnGame, depth: u8) -> Result<GameAnalysis, &'static str> {
+    let mut moves = Vec::new();
+    game.replay(ZobristHasher::new(), |_, m| moves.push(m))?;
+    let state = HashedState::new(game.starting_state()?, ZobristHasher::new());
+    let mut context = SearchContext::new(Position::new(state), SimpleEval::default(), None);
+    Ok(analyse_game(&mut context, &moves, depth))
+}
+
+/// One line per side, e.g. `white 87.3% (1 inaccuracy, 0 mistakes, 1 blunder)`.
+fn summary(analysis: &GameAnalysis, color: Color) -> String {
+    let name = match color {
+        Color::White => "white",
+        Color::Black => "black",
+    };
+    let accuracy = analysis
+        .accuracy(color)
+        .map_or("-".to_string(), |accuracy| format!("{:.1}%", accuracy));
+    let counts = [
+        (Classification::Inaccuracy, "inaccurac", "y", "ies"),
+        (Classification::Mistake, "mistake", "", "s"),
+        (Classification::Blunder, "blunder", "", "s"),
+    ]
+    .map(|(classification, stem, one, many)| {
+        let count = analysis.count(color, classification);
+        format!("{} {}{}", count, stem, if count == 1 { one } else { many })
+    });
+    format!("{} {} ({})", name, accuracy, counts.join(", "))
+}
+
+fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let (file, depth) = match &args[..] {
+        [_, file] => (file, DEFAULT_DEPTH),
+        [_, file, depth] => match depth.parse() {
+            Ok(depth) => (file, depth),
+            Err(_) => {
+                eprintln!("error: depth {} is not a number", depth);
+                std::process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("usage: chess_annotate <games.pgn> [depth]");
+            eprintln!("       annotated games go to stdout, the depth is 4 by default");
+            std::process::exit(1);
+        }
+    };
+
+    let games = fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|text| parse_pgn(&text).map_err(str::to_string))
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}: {}", file, e);
+            std::process::exit(1);
+        });
+    for (i, game) in games.iter().enumerate() {
+        let analysis = match analyse(game, depth) {
+            Ok(analysis) => analysis,
+            Err(e) => {
+                eprintln!("game {}: {}", i + 1, e);
+                continue;
+            }
+        };
+        let mut annotated = annotate(game, &analysis);
+        annotated.tags.push((
+            "Annotator".to_string(),
+            format!("chess_annotate depth {}", depth),
+        ));
+        println!("{}", annotated);
+        eprintln!(
+            "game {}: {}, {}",
+            i + 1,
+            summary(&analysis, Color::White),
+            summary(&analysis, Color::Black)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let pgn = "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0";
+        let analysis = analyse(&parse_pgn(pgn).unwrap()[0], 2).unwrap();
+        let black = summary(&analysis, Color::Black);
+        assert!(black.starts_with("black "));
+        assert!(black.ends_with(", 1 blunder)"));
+        assert!(
+            summary(&GameAnalysis::default(), Color::White).starts_with("white - (0 inaccuracies")
+        );
+
+        let illegal = &parse_pgn("1. e4 e5 2. Ke3 *").unwrap()[0];
+        assert!(analyse(illegal, 1).is_err());
+    }
+}
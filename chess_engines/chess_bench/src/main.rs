@@ -0,0 +1,303 @@
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use chess_core::{
+    hash::{HashedState, Hasher, zobrist::ZobristHasher},
+    r#move::MoveList,
+    position::Position,
+    state::State,
+};
+use chess_engines::alpha_beta::{evaluation::SimpleEval, search::SearchContext};
+
+// Benchmark of move generation and search over a fixed set of positions at a
+// fixed depth. The node counts only change with the behaviour of the
+// generator, the evaluation or the search, so their signature tells apart
+// functional changes from speed changes. A run can be saved as a JSON baseline
+// and later runs compared with it.
+
+const POSITIONS: [&str; 7] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - - 0 1",
+];
+
+const USAGE: &str = "usage: chess_bench [options]
+options: --perft-depth <n>    perft depth, 4 by default
+         --search-depth <n>   search depth, 5 by default
+         --save <file.json>   save the results as a baseline
+         --compare <file.json>  compare with a baseline, failing on a changed
+                              signature or a slowdown
+         --tolerance <percent>  slowdown allowed by --compare, 5 by default";
+
+/// Results of a whole run, as saved in a baseline.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Baseline {
+    perft_depth: u8,
+    search_depth: u8,
+    perft_nodes: u64,
+    search_nodes: u64,
+    /// Hash of the node counts of every position, in hexadecimal.
+    signature: String,
+    perft_nps: u64,
+    search_nps: u64,
+}
+
+struct Options {
+    perft_depth: u8,
+    search_depth: u8,
+    save: Option<String>,
+    compare: Option<String>,
+    tolerance: f64,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, &'static str> {
+    let mut options = Options {
+        perft_depth: 4,
+        search_depth: 5,
+        save: None,
+        compare: None,
+        tolerance: 5.0,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or("Option needs a value")?;
+        match arg.as_str() {
+            "--perft-depth" => {
+                options.perft_depth = value.parse().map_err(|_| "Depth malformed")?;
+            }
+            "--search-depth" => {
+                options.search_depth = value.parse().map_err(|_| "Depth malformed")?;
+            }
+            "--save" => options.save = Some(value.clone()),
+            "--compare" => options.compare = Some(value.clone()),
+            "--tolerance" => {
+                options.tolerance = value.parse().map_err(|_| "Tolerance malformed")?;
+            }
+            _ => return Err("Unknown option"),
+        }
+    }
+    Ok(options)
+}
+
+fn perft<H: Hasher>(position: &mut Position<H>, move_list: &mut MoveList, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut nodes = 0;
+    move_list.new_ply();
+    position.pseudo_legal_moves(move_list);
+    let ply_number = move_list.ply_number();
+    for m in 0..move_list.ply_size(ply_number) {
+        let m = move_list.r#move(ply_number, m);
+        position.make(m);
+        if position.was_move_legal() {
+            nodes += perft(position, move_list, depth - 1);
+        }
+        position.unmake(m);
+    }
+    move_list.drop_current_ply();
+    nodes
+}
+
+fn position(fen: &str) -> Position<ZobristHasher> {
+    Position::new(HashedState::new(State::from_fen(fen), ZobristHasher::new()))
+}
+
+/// Nodes visited by an iterative deepening search to `depth`, and the best move.
+fn search(fen: &str, depth: u8) -> (u64, String) {
+    let mut context = SearchContext::new(position(fen), SimpleEval::default(), Some(1));
+    let mut pv = Vec::new();
+    for depth in 1..=depth {
+        context.max_depth = depth;
+        (_, pv) = context.search(pv);
+    }
+    let best_move = pv.last().map_or("none".to_string(), |m| m.to_string());
+    (context.nodes, best_move)
+}
+
+/// FNV-1a hash of the node counts.
+fn signature(counts: &[u64]) -> u64 {
+    counts
+        .iter()
+        .flat_map(|count| count.to_le_bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+fn nodes_per_second(nodes: u64, time: Duration) -> u64 {
+    (nodes as f64 / time.as_secs_f64().max(1e-9)) as u64
+}
+
+fn run(perft_depth: u8, search_depth: u8) -> Baseline {
+    let (mut perft_nodes, mut perft_time) = (0, Duration::ZERO);
+    let (mut search_nodes, mut search_time) = (0, Duration::ZERO);
+    let mut counts = Vec::new();
+    println!("position         perft nodes    search nodes  best move");
+    for (i, fen) in POSITIONS.iter().enumerate() {
+        let start = Instant::now();
+        let nodes = perft(&mut position(fen), &mut MoveList::new(), perft_depth);
+        perft_time += start.elapsed();
+        perft_nodes += nodes;
+
+        let start = Instant::now();
+        let (searched, best_move) = search(fen, search_depth);
+        search_time += start.elapsed();
+        search_nodes += searched;
+
+        println!("{:>8} {:>19} {:>15}  {}", i + 1, nodes, searched, best_move);
+        counts.extend([nodes, searched]);
+    }
+
+    Baseline {
+        perft_depth,
+        search_depth,
+        perft_nodes,
+        search_nodes,
+        signature: format!("{:016x}", signature(&counts)),
+        perft_nps: nodes_per_second(perft_nodes, perft_time),
+        search_nps: nodes_per_second(search_nodes, search_time),
+    }
+}
+
+/// Differences with the baseline that should fail the comparison.
+fn compare(baseline: &Baseline, bench: &Baseline, tolerance: f64) -> Vec<String> {
+    if (baseline.perft_depth, baseline.search_depth) != (bench.perft_depth, bench.search_depth) {
+        return vec![format!(
+            "baseline was run at perft depth {} and search depth {}",
+            baseline.perft_depth, baseline.search_depth
+        )];
+    }
+    let mut problems = Vec::new();
+    if baseline.signature != bench.signature {
+        problems.push(format!(
+            "signature changed from {} to {}, the generator or the search behave differently",
+            baseline.signature, bench.signature
+        ));
+    }
+    for (name, before, after) in [
+        ("perft", baseline.perft_nps, bench.perft_nps),
+        ("search", baseline.search_nps, bench.search_nps),
+    ] {
+        let change = (after as f64 / before as f64 - 1.0) * 100.0;
+        println!("{} nps: {} -> {} ({:+.1}%)", name, before, after, change);
+        if change < -tolerance {
+            problems.push(format!("{} is {:.1}% slower", name, -change));
+        }
+    }
+    problems
+}
+
+fn main() {
+    let args: Vec<_> = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    // A bad baseline fails before the run
+    let baseline = options.compare.as_ref().map(|path| {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<Baseline>(&text).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                eprintln!("error: {}: {}", path, e);
+                std::process::exit(1);
+            })
+    });
+
+    let bench = run(options.perft_depth, options.search_depth);
+    println!();
+    println!("perft nodes  {}", bench.perft_nodes);
+    println!("search nodes {}", bench.search_nodes);
+    println!("signature    {}", bench.signature);
+    println!("perft nps    {}", bench.perft_nps);
+    println!("search nps   {}", bench.search_nps);
+
+    if let Some(path) = &options.save {
+        let saved = serde_json::to_string_pretty(&bench)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            eprintln!("error: {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(baseline) = baseline {
+        println!();
+        let problems = compare(&baseline, &bench, options.tolerance);
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        if !problems.is_empty() {
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bench(signature: &str, perft_nps: u64, search_nps: u64) -> Baseline {
+        Baseline {
+            perft_depth: 4,
+            search_depth: 5,
+            perft_nodes: 1000,
+            search_nodes: 100,
+            signature: signature.to_string(),
+            perft_nps,
+            search_nps,
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let baseline = bench("01", 1000, 100);
+        assert!(compare(&baseline, &bench("01", 960, 120), 5.0).is_empty());
+        assert_eq!(compare(&baseline, &bench("02", 1000, 100), 5.0).len(), 1);
+        assert_eq!(compare(&baseline, &bench("01", 900, 90), 5.0).len(), 2);
+
+        let mut other_depth = bench("01", 1000, 100);
+        other_depth.search_depth = 6;
+        assert_eq!(compare(&baseline, &other_depth, 5.0).len(), 1);
+    }
+
+    #[test]
+    fn test_baseline_json() {
+        let baseline = bench("00ff", 1000, 100);
+        let json = serde_json::to_string(&baseline).unwrap();
+        assert!(json.contains("\"signature\":\"00ff\""));
+        assert_eq!(serde_json::from_str::<Baseline>(&json).unwrap(), baseline);
+    }
+
+    #[test]
+    fn test_bench_is_repeatable() {
+        let first = run(2, 2);
+        assert_eq!(first.perft_nodes, 400 + 2039 + 191 + 264 + 1486 + 2079 + 15);
+        assert_eq!(run(2, 2).signature, first.signature);
+        assert_ne!(run(1, 2).signature, first.signature);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = ["--perft-depth", "3", "--compare", "base.json"].map(str::to_string);
+        let options = parse_options(&args).unwrap();
+        assert_eq!((options.perft_depth, options.search_depth), (3, 5));
+        assert_eq!(options.compare.as_deref(), Some("base.json"));
+        assert!(parse_options(&["--perft-depth".to_string()]).is_err());
+        assert!(parse_options(&["--depth".to_string(), "3".to_string()]).is_err());
+    }
+}
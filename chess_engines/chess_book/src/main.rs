@@ -0,0 +1,35 @@
+use std::fs;
+
+use chess_engines::book::builder::BookBuilder;
+
+// Build a Polyglot opening book from a PGN game collection.
+
+/// Plies of each game added to the book by default.
+const DEFAULT_MAX_PLY: usize = 20;
+
+fn main() {
+    let args: Vec<_> = std::env::args().collect::<Vec<_>>();
+    let (pgn, output, max_ply, min_count) = match &args[..] {
+        [_, pgn, output] => (pgn, output, DEFAULT_MAX_PLY, 1),
+        [_, pgn, output, max_ply] => (pgn, output, max_ply.parse().unwrap(), 1),
+        [_, pgn, output, max_ply, min_count] => (
+            pgn,
+            output,
+            max_ply.parse().unwrap(),
+            min_count.parse().unwrap(),
+        ),
+        _ => {
+            eprintln!("usage: chess_book <games.pgn> <book.bin> [max_ply] [min_count]");
+            std::process::exit(1);
+        }
+    };
+
+    let mut builder = BookBuilder::new(max_ply, min_count);
+    let skipped = builder.add_pgn(&fs::read_to_string(pgn).unwrap()).unwrap();
+    if skipped > 0 {
+        eprintln!("skipped {} games with illegal moves", skipped);
+    }
+    let book = builder.build();
+    eprintln!("{} entries", book.len());
+    book.save(output).unwrap();
+}
@@ -0,0 +1,172 @@
+use std::fs;
+
+use chess_core::{
+    epd::{Epd, parse_epd_file},
+    hash::{HashedState, Hasher, zobrist::ZobristHasher},
+    r#move::{
+        Move,
+        san::{from_san, to_san},
+    },
+    position::Position,
+    state::State,
+};
+use chess_engines::alpha_beta::{evaluation::SimpleEval, search::SearchContext};
+use chrono::Duration;
+
+// Run a test suite: search every position and compare the move found with the
+// best moves (`bm`) and the moves to avoid (`am`). STS suites also give points
+// for good but not best moves in the first comment, like `c0 "Nf3=10, e4=5"`,
+// other suites score a point per solved position.
+
+enum Limit {
+    Time(Duration),
+    Depth(u8),
+}
+
+fn main() {
+    let args: Vec<_> = std::env::args().collect::<Vec<_>>();
+    let (suite, limit) = match &args[..] {
+        [_, suite, kind, value] if kind == "time" => (
+            suite,
+            Limit::Time(Duration::milliseconds(value.parse().unwrap())),
+        ),
+        [_, suite, kind, value] if kind == "depth" => (suite, Limit::Depth(value.parse().unwrap())),
+        _ => {
+            eprintln!("usage: chess_epd <suite.epd> time <milliseconds>");
+            eprintln!("       chess_epd <suite.epd> depth <plies>");
+            std::process::exit(1);
+        }
+    };
+
+    let positions = parse_epd_file(&fs::read_to_string(suite).unwrap()).unwrap();
+    let (mut solved, mut points, mut max_points) = (0, 0, 0);
+    for (i, epd) in positions.iter().enumerate() {
+        let id = epd
+            .id()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("#{}", i + 1));
+        let mut position = position(epd);
+        let Some(r#move) = search(position.state.get().clone(), &limit) else {
+            println!("{:<16} no legal move", id);
+            continue;
+        };
+        let played = to_san(&mut position, r#move);
+        let (position_points, position_max) = score(epd, &mut position, r#move);
+        if position_points == position_max {
+            solved += 1;
+        }
+        points += position_points;
+        max_points += position_max;
+
+        let expected = [("bm", epd.best_moves()), ("am", epd.avoid_moves())]
+            .iter()
+            .filter(|(_, moves)| !moves.is_empty())
+            .map(|(opcode, moves)| format!("{} {}", opcode, moves.join(" ")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "{:<16} {:<8} {:>2}/{:<2} {}",
+            id, played, position_points, position_max, expected
+        );
+    }
+    println!(
+        "solved {}/{}, score {}/{}",
+        solved,
+        positions.len(),
+        points,
+        max_points
+    );
+}
+
+fn position(epd: &Epd) -> Position<ZobristHasher> {
+    Position::new(HashedState::new(epd.state.clone(), ZobristHasher::new()))
+}
+
+fn search(state: State, limit: &Limit) -> Option<Move> {
+    let position = Position::new(HashedState::new(state, ZobristHasher::new()));
+    let (_, pv) = match limit {
+        Limit::Time(time) => {
+            SearchContext::new(position, SimpleEval::default(), None).iterative_deepen(*time)
+        }
+        Limit::Depth(depth) => {
+            SearchContext::new(position, SimpleEval::default(), Some(*depth)).search(Vec::new())
+        }
+    };
+    pv.last().copied()
+}
+
+/// Moves and points of an STS comment, `None` when the comment has no points.
+fn sts_points(epd: &Epd) -> Option<Vec<(String, u32)>> {
+    epd.operand("c0")?
+        .split(',')
+        .map(|entry| {
+            let (san, points) = entry.trim().split_once('=')?;
+            Some((san.to_string(), points.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Points scored by `move` and the most points available for the position.
+fn score<H: Hasher>(epd: &Epd, position: &mut Position<H>, r#move: Move) -> (u32, u32) {
+    // Moves that are illegal or malformed in the suite never match
+    let mut matches = |san: &str| from_san(position, san) == Ok(r#move);
+
+    if let Some(points) = sts_points(epd) {
+        let max = points.iter().map(|(_, p)| *p).max().unwrap_or(0);
+        let scored = points
+            .iter()
+            .find(|(san, _)| matches(san))
+            .map_or(0, |(_, p)| *p);
+        return (scored, max);
+    }
+
+    let best = epd.best_moves().is_empty() || epd.best_moves().iter().any(|san| matches(san));
+    let avoided = !epd.avoid_moves().iter().any(|san| matches(san));
+    ((best && avoided) as u32, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(line: &str, san: &str) -> (u32, u32) {
+        let epd = Epd::parse(line).unwrap();
+        let mut position = position(&epd);
+        let r#move = from_san(&mut position, san).unwrap();
+        score(&epd, &mut position, r#move)
+    }
+
+    #[test]
+    fn test_score() {
+        let line = "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate\";";
+        assert_eq!(scored(line, "Ra8"), (1, 1));
+        assert_eq!(scored(line, "Ra7"), (0, 1));
+
+        let line = "6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra7 Ra6;";
+        assert_eq!(scored(line, "Ra8"), (1, 1));
+        assert_eq!(scored(line, "Ra6"), (0, 1));
+
+        let line = "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; c0 \"Ra8=10, Ra7=3, Kf1=1\";";
+        assert_eq!(scored(line, "Ra8"), (10, 10));
+        assert_eq!(scored(line, "Ra7"), (3, 10));
+        assert_eq!(scored(line, "Ra2"), (0, 10));
+    }
+
+    #[test]
+    fn test_sts_points() {
+        let epd = Epd::parse("8/8/8/8/8/8/8/K1k5 w - - c0 \"Kb1=10, Ka2=4\";").unwrap();
+        assert_eq!(
+            sts_points(&epd),
+            Some(vec![("Kb1".to_string(), 10), ("Ka2".to_string(), 4)])
+        );
+        let epd = Epd::parse("8/8/8/8/8/8/8/K1k5 w - - c0 \"just a comment\";").unwrap();
+        assert_eq!(sts_points(&epd), None);
+    }
+
+    #[test]
+    fn test_search() {
+        let epd = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#;").unwrap();
+        let r#move = search(epd.state.clone(), &Limit::Depth(2)).unwrap();
+        assert_eq!(to_san(&mut position(&epd), r#move), "Ra8#");
+    }
+}
@@ -0,0 +1,603 @@
+mod player;
+mod sprt;
+mod uci;
+
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    sync::Arc,
+};
+
+use chess_core::{
+    color::Color,
+    epd::parse_epd_file,
+    game::{Game, Outcome},
+    hash::zobrist::ZobristHasher,
+    r#move::Move,
+    pgn::{PgnGame, parse_pgn},
+    state::State,
+};
+use chess_engines::alpha_beta::evaluation::{EvalParams, nnue::Network};
+use chrono::Duration;
+
+use player::{Eval, Limit, Player, SearchPlayer};
+use sprt::{Decision, Score, Sprt};
+use uci::UciPlayer;
+
+// Engine against engine matches. Every opening is played twice with the colours
+// swapped, the games are adjudicated by the rules or after a maximum number of
+// plies, and an optional SPRT stops the match once the Elo gain of the first
+// engine over the second is accepted or rejected.
+
+const USAGE: &str = "usage: chess_match <engine> <engine> [options]
+engines: comma separated settings, like depth=5,eval=tuned.cfg or uci=stockfish,time=100
+         depth=<plies>      search to a fixed depth, 4 by default
+         time=<ms>          search for a fixed time per move
+         eval=<file.cfg>    evaluation parameters of our search
+         nnue=<file>        network evaluating the positions of our search
+         uci=<command>      external UCI engine instead of our search
+         name=<name>        name in the results and the PGN
+options: --openings <file.epd|file.pgn>  starting positions, the initial one by default
+         --rounds <n>       pairs of games, one for every opening by default
+         --max-plies <n>    plies after the opening before a draw is adjudicated, 400 by default
+         --pgn <file>       write the games to a PGN file
+         --sprt <elo0>,<elo1>  stop when a gain of elo0 or elo1 is accepted
+         --alpha <p>, --beta <p>  error rates of the SPRT, 0.05 by default";
+
+struct Options {
+    openings: Option<String>,
+    rounds: Option<usize>,
+    max_plies: usize,
+    pgn: Option<String>,
+    sprt: Option<Sprt>,
+}
+
+/// Split the arguments into the engines and the options.
+fn parse_options(args: &[String]) -> Result<(Vec<&str>, Options), &'static str> {
+    let mut engines = Vec::new();
+    let mut options = Options {
+        openings: None,
+        rounds: None,
+        max_plies: 400,
+        pgn: None,
+        sprt: None,
+    };
+    let (mut alpha, mut beta) = (0.05, 0.05);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            engines.push(arg.as_str());
+            continue;
+        }
+        let value = args.next().ok_or("Option needs a value")?;
+        match arg.as_str() {
+            "--openings" => options.openings = Some(value.clone()),
+            "--rounds" => options.rounds = Some(value.parse().map_err(|_| "Rounds malformed")?),
+            "--max-plies" => options.max_plies = value.parse().map_err(|_| "Plies malformed")?,
+            "--pgn" => options.pgn = Some(value.clone()),
+            "--sprt" => {
+                let (elo0, elo1) = value.split_once(',').ok_or("SPRT needs elo0,elo1")?;
+                options.sprt = Some(Sprt {
+                    elo0: elo0.parse().map_err(|_| "Elo malformed")?,
+                    elo1: elo1.parse().map_err(|_| "Elo malformed")?,
+                    alpha: 0.0,
+                    beta: 0.0,
+                });
+            }
+            "--alpha" => alpha = value.parse().map_err(|_| "Alpha malformed")?,
+            "--beta" => beta = value.parse().map_err(|_| "Beta malformed")?,
+            _ => return Err("Unknown option"),
+        }
+    }
+    if !(0.0 < alpha && alpha < 1.0 && 0.0 < beta && beta < 1.0) {
+        return Err("Error rates must be between 0 and 1");
+    }
+    if let Some(sprt) = &mut options.sprt {
+        if sprt.elo0 >= sprt.elo1 {
+            return Err("SPRT needs elo0 below elo1");
+        }
+        (sprt.alpha, sprt.beta) = (alpha, beta);
+    }
+    Ok((engines, options))
+}
+
+/// Create a player from its settings.
+fn parse_engine(spec: &str) -> Result<Box<dyn Player>, &'static str> {
+    let (mut limit, mut eval, mut uci, mut name) = (Limit::Depth(4), None, None, None);
+    for setting in spec.split(',') {
+        let (key, value) = setting.split_once('=').ok_or("Engine setting malformed")?;
+        match key {
+            "depth" => limit = Limit::Depth(value.parse().map_err(|_| "Depth malformed")?),
+            "time" => {
+                let time = value.parse().map_err(|_| "Time malformed")?;
+                limit = Limit::Time(Duration::milliseconds(time));
+            }
+            "eval" => {
+                let config = fs::read_to_string(value).map_err(|_| "Cannot read the eval file")?;
+                eval = Some(Eval::Simple(Box::new(EvalParams::from_config(&config)?)));
+            }
+            "nnue" => {
+                let network = Network::load(value).map_err(|_| "Cannot load the network")?;
+                eval = Some(Eval::Nnue(Arc::new(network)));
+            }
+            "uci" => uci = Some(value),
+            "name" => name = Some(value.to_string()),
+            _ => return Err("Unknown engine setting"),
+        }
+    }
+    match (uci, eval) {
+        (Some(_), Some(_)) => Err("UCI engines use their own evaluation"),
+        (Some(command), None) => {
+            let mut player = UciPlayer::start(command, limit)?;
+            if let Some(name) = name {
+                player.set_name(name);
+            }
+            Ok(Box::new(player))
+        }
+        (None, eval) => Ok(Box::new(SearchPlayer::new(
+            name.unwrap_or_else(|| spec.to_string()),
+            eval.unwrap_or(Eval::Simple(Box::default())),
+            limit,
+        ))),
+    }
+}
+
+/// A starting position and the moves played from it before the engines take over.
+#[derive(Clone)]
+struct Opening {
+    start: State,
+    moves: Vec<Move>,
+}
+
+impl Opening {
+    fn game(&self) -> Game {
+        let mut game = Game::new(self.start.clone());
+        for r#move in &self.moves {
+            game.make(*r#move);
+        }
+        game
+    }
+}
+
+fn parse_openings(path: &str, text: &str) -> Result<Vec<Opening>, &'static str> {
+    let openings = if path.ends_with(".pgn") {
+        parse_pgn(text)?
+            .iter()
+            .map(|game| {
+                let mut moves = Vec::new();
+                game.replay(ZobristHasher::new(), |_, r#move| moves.push(r#move))?;
+                Ok(Opening {
+                    start: game.starting_state()?,
+                    moves,
+                })
+            })
+            .collect::<Result<Vec<_>, &'static str>>()?
+    } else {
+        parse_epd_file(text)?
+            .into_iter()
+            .map(|epd| Opening {
+                start: epd.state,
+                moves: Vec::new(),
+            })
+            .collect()
+    };
+    if openings.is_empty() {
+        return Err("No opening found");
+    }
+    Ok(openings)
+}
+
+/// Why a game stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Ending {
+    Rules(Outcome),
+    /// The player of this colour failed to give a legal move.
+    Forfeit(Color, &'static str),
+    MaxPlies,
+}
+
+impl Ending {
+    fn winner(&self) -> Option<Color> {
+        match self {
+            Ending::Rules(outcome) => outcome.winner(),
+            Ending::Forfeit(color, _) => Some(!*color),
+            Ending::MaxPlies => None,
+        }
+    }
+
+    /// Score of white.
+    fn white_score(&self) -> f64 {
+        match self.winner() {
+            Some(Color::White) => 1.0,
+            Some(Color::Black) => 0.0,
+            None => 0.5,
+        }
+    }
+
+    fn result(&self) -> &'static str {
+        match self {
+            Ending::Rules(outcome) => outcome.result(),
+            Ending::Forfeit(Color::White, _) => "0-1",
+            Ending::Forfeit(Color::Black, _) => "1-0",
+            Ending::MaxPlies => "1/2-1/2",
+        }
+    }
+}
+
+impl Display for Ending {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ending::Rules(outcome) => write!(f, "{}", outcome),
+            Ending::Forfeit(Color::White, reason) => write!(f, "white forfeits: {}", reason),
+            Ending::Forfeit(Color::Black, reason) => write!(f, "black forfeits: {}", reason),
+            Ending::MaxPlies => write!(f, "adjudicated draw"),
+        }
+    }
+}
+
+/// Play a game from `opening`, white first in `players`.
+fn play_game(players: [&mut dyn Player; 2], opening: &Opening, max_plies: usize) -> (Game, Ending) {
+    let mut game = opening.game();
+    let [white, black] = players;
+    for (color, player) in [(Color::White, &mut *white), (Color::Black, &mut *black)] {
+        if let Err(e) = player.new_game() {
+            return (game, Ending::Forfeit(color, e));
+        }
+    }
+    for _ in 0..max_plies {
+        if let Some(outcome) = game.outcome() {
+            return (game, Ending::Rules(outcome));
+        }
+        let color = game.state().flags.active_color();
+        let player = match color {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+        let r#move = match player.best_move(&mut game) {
+            Ok(r#move) if game.legal_moves().contains(&r#move) => r#move,
+            Ok(_) => return (game, Ending::Forfeit(color, "illegal move")),
+            Err(e) => return (game, Ending::Forfeit(color, e)),
+        };
+        game.make(r#move);
+    }
+    match game.outcome() {
+        Some(outcome) => (game, Ending::Rules(outcome)),
+        None => (game, Ending::MaxPlies),
+    }
+}
+
+fn to_pgn(game: &mut Game, ending: Ending, round: usize, names: [&str; 2]) -> PgnGame {
+    let mut pgn = game.to_pgn();
+    pgn.result = ending.result().to_string();
+    pgn.tags.retain(|(name, _)| name != "Result");
+    let tags = [
+        ("Event", "chess_match".to_string()),
+        ("Round", round.to_string()),
+        ("White", names[0].to_string()),
+        ("Black", names[1].to_string()),
+        ("Result", pgn.result.clone()),
+    ];
+    pgn.tags
+        .splice(0..0, tags.map(|(name, value)| (name.to_string(), value)));
+    pgn.tags
+        .push(("Termination".to_string(), ending.to_string()));
+    pgn
+}
+
+/// Play the match and return the score of the first engine.
+fn run_match(
+    engines: [&mut dyn Player; 2],
+    openings: &[Opening],
+    options: &Options,
+    mut pgn: Option<&mut dyn Write>,
+) -> io::Result<Score> {
+    let [first, second] = engines;
+    let mut score = Score::default();
+    let rounds = options.rounds.unwrap_or(openings.len());
+    for round in 1..=rounds {
+        let opening = &openings[(round - 1) % openings.len()];
+        for first_is_white in [true, false] {
+            let names = if first_is_white {
+                [first.name().to_string(), second.name().to_string()]
+            } else {
+                [second.name().to_string(), first.name().to_string()]
+            };
+            let players: [&mut dyn Player; 2] = if first_is_white {
+                [&mut *first, &mut *second]
+            } else {
+                [&mut *second, &mut *first]
+            };
+            let (mut game, ending) = play_game(players, opening, options.max_plies);
+            let white_score = ending.white_score();
+            score.add(if first_is_white {
+                white_score
+            } else {
+                1.0 - white_score
+            });
+            if let Some(pgn) = &mut pgn {
+                let names = [names[0].as_str(), names[1].as_str()];
+                writeln!(pgn, "{}", to_pgn(&mut game, ending, round, names))?;
+            }
+            print!(
+                "game {}: {} - {} {} ({}), score {}",
+                score.games(),
+                names[0],
+                names[1],
+                ending.result(),
+                ending,
+                score
+            );
+            if let Some((elo, margin)) = score.elo() {
+                print!(", elo {:+.1} +/- {:.1}", elo, margin);
+            }
+            if let Some(sprt) = &options.sprt {
+                let (lower, upper) = sprt.bounds();
+                print!(", llr {:.2} ({:.2}, {:.2})", sprt.llr(&score), lower, upper);
+                match sprt.decide(&score) {
+                    Decision::Continue => {}
+                    decision => {
+                        println!();
+                        println!(
+                            "{}",
+                            if decision == Decision::AcceptH1 {
+                                format!(
+                                    "H1 accepted: {} gains at least {} elo",
+                                    first.name(),
+                                    sprt.elo1
+                                )
+                            } else {
+                                format!(
+                                    "H0 accepted: {} gains at most {} elo",
+                                    first.name(),
+                                    sprt.elo0
+                                )
+                            }
+                        );
+                        return Ok(score);
+                    }
+                }
+            }
+            println!();
+        }
+    }
+    Ok(score)
+}
+
+fn main() {
+    let args: Vec<_> = std::env::args().skip(1).collect::<Vec<_>>();
+    let (engines, options) = match parse_options(&args) {
+        Ok((engines, options)) if engines.len() == 2 => (engines, options),
+        Ok(_) => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let [mut first, mut second] = [engines[0], engines[1]].map(|spec| match parse_engine(spec) {
+        Ok(player) => player,
+        Err(e) => {
+            eprintln!("error: {}: {}", spec, e);
+            std::process::exit(1);
+        }
+    });
+    let openings = match &options.openings {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_openings(path, &text).map_err(str::to_string))
+            .unwrap_or_else(|e| {
+                eprintln!("error: {}: {}", path, e);
+                std::process::exit(1);
+            }),
+        None => vec![Opening {
+            start: State::default(),
+            moves: Vec::new(),
+        }],
+    };
+
+    let mut pgn = options
+        .pgn
+        .as_ref()
+        .map(|path| match fs::File::create(path) {
+            Ok(file) => io::BufWriter::new(file),
+            Err(e) => {
+                eprintln!("error: {}: {}", path, e);
+                std::process::exit(1);
+            }
+        });
+    let score = run_match(
+        [first.as_mut(), second.as_mut()],
+        &openings,
+        &options,
+        pgn.as_mut().map(|pgn| pgn as &mut dyn Write),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    });
+    println!("{}: {} in {} games", first.name(), score, score.games());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays the first legal move, or always fails.
+    struct FirstMove(bool);
+
+    /// Plays the first legal move, but fails in every third game.
+    struct Flaky(u32);
+
+    impl Player for Flaky {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn new_game(&mut self) -> Result<(), &'static str> {
+            self.0 += 1;
+            Ok(())
+        }
+
+        fn best_move(&mut self, game: &mut Game) -> Result<Move, &'static str> {
+            if self.0.is_multiple_of(3) {
+                Err("no move")
+            } else {
+                Ok(game.legal_moves()[0])
+            }
+        }
+    }
+
+    impl Player for FirstMove {
+        fn name(&self) -> &str {
+            "first"
+        }
+
+        fn best_move(&mut self, game: &mut Game) -> Result<Move, &'static str> {
+            if self.0 {
+                Ok(game.legal_moves()[0])
+            } else {
+                Err("no move")
+            }
+        }
+    }
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = args("depth=3 uci=stockfish,time=50 --sprt 0,5 --alpha 0.1 --max-plies 20");
+        let (engines, options) = parse_options(&args).unwrap();
+        assert_eq!(engines, ["depth=3", "uci=stockfish,time=50"]);
+        assert_eq!(options.max_plies, 20);
+        assert_eq!(
+            options.sprt,
+            Some(Sprt {
+                elo0: 0.0,
+                elo1: 5.0,
+                alpha: 0.1,
+                beta: 0.05
+            })
+        );
+        assert!(parse_options(&self::args("a b --sprt 5,0")).is_err());
+        assert!(parse_options(&self::args("a b --beta 1")).is_err());
+        assert!(parse_options(&self::args("a b --rounds")).is_err());
+
+        assert_eq!(parse_engine("depth=2,name=two").unwrap().name(), "two");
+        assert!(parse_engine("depth=x").is_err());
+        assert!(parse_engine("speed=2").is_err());
+        assert!(parse_engine("uci=stockfish,eval=a.cfg").is_err());
+    }
+
+    #[test]
+    fn test_parse_openings() {
+        let epd = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";\n\
+                   8/8/8/4k3/8/8/4P3/4K3 w - -\n";
+        let openings = parse_openings("suite.epd", epd).unwrap();
+        assert_eq!(openings.len(), 2);
+        assert!(openings[0].moves.is_empty());
+
+        let pgn = "[Event \"A\"]\n\n1. e4 e5 2. Nf3 *\n\n[Event \"B\"]\n\n1. d4 *\n";
+        let openings = parse_openings("book.pgn", pgn).unwrap();
+        assert_eq!(openings.len(), 2);
+        let game = openings[0].game();
+        assert_eq!(game.moves().len(), 3);
+        assert_eq!(game.state().flags.active_color(), Color::Black);
+
+        assert!(parse_openings("book.pgn", "1. e4 Ke3 *").is_err());
+        assert!(parse_openings("suite.epd", "").is_err());
+    }
+
+    #[test]
+    fn test_play_game() {
+        let opening = Opening {
+            start: State::default(),
+            moves: Vec::new(),
+        };
+        let (game, ending) =
+            play_game([&mut FirstMove(true), &mut FirstMove(false)], &opening, 100);
+        assert_eq!(ending, Ending::Forfeit(Color::Black, "no move"));
+        assert_eq!(ending.result(), "1-0");
+        assert_eq!(game.moves().len(), 1);
+
+        let (game, ending) = play_game([&mut FirstMove(true), &mut FirstMove(true)], &opening, 6);
+        assert_eq!(ending, Ending::MaxPlies);
+        assert_eq!(game.moves().len(), 6);
+
+        // Mate in one for white
+        let opening = Opening {
+            start: State::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"),
+            moves: Vec::new(),
+        };
+        let mut search = SearchPlayer::new(
+            "search".to_string(),
+            Eval::Simple(Box::default()),
+            Limit::Depth(2),
+        );
+        let (mut game, ending) = play_game([&mut search, &mut FirstMove(true)], &opening, 10);
+        assert_eq!(ending, Ending::Rules(Outcome::Checkmate(Color::White)));
+        let pgn = to_pgn(&mut game, ending, 3, ["search", "first"]);
+        assert_eq!(pgn.moves, ["Ra8#"]);
+        assert_eq!(pgn.tag("Round"), Some("3"));
+        assert_eq!(pgn.tag("Result"), Some("1-0"));
+        assert_eq!(pgn.tag("Termination"), Some("white mates"));
+        assert!(pgn.tag("FEN").is_some());
+    }
+
+    #[test]
+    fn test_run_match() {
+        let openings =
+            parse_openings("book.pgn", "1. e4 e5 *\n\n1. d4 d5 *\n\n1. c4 c5 *\n").unwrap();
+        let (_, mut options) = parse_options(&args("--max-plies 4")).unwrap();
+        let mut pgn = Vec::new();
+        let score = run_match(
+            [&mut FirstMove(true), &mut FirstMove(false)],
+            &openings,
+            &options,
+            Some(&mut pgn),
+        )
+        .unwrap();
+        // The first engine wins every game, as white and as black
+        assert_eq!(
+            score,
+            Score {
+                wins: 6,
+                draws: 0,
+                losses: 0
+            }
+        );
+        let games = parse_pgn(&String::from_utf8(pgn).unwrap()).unwrap();
+        assert_eq!(games.len(), 6);
+        assert_eq!(games[0].result, "1-0");
+        assert_eq!(games[1].result, "0-1");
+        assert_eq!(games[1].tag("Black"), Some("first"));
+        assert_eq!(games[1].moves[..2], ["e4", "e5"]);
+
+        // The SPRT stops as soon as the gain is clear, which takes more than
+        // one result
+        options.rounds = Some(1000);
+        options.sprt = Some(Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        });
+        let score = run_match(
+            [&mut FirstMove(true), &mut Flaky(0)],
+            &openings,
+            &options,
+            None,
+        )
+        .unwrap();
+        assert_eq!(score.losses, 0);
+        assert!(score.wins > 0 && score.draws > 0);
+        assert!(score.games() < 2000);
+        let sprt = options.sprt.unwrap();
+        assert_eq!(sprt.decide(&score), Decision::AcceptH1);
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use chrono::Duration;
+
+use chess_core::{
+    game::Game,
+    hash::{HashedState, zobrist::ZobristHasher},
+    r#move::Move,
+    position::Position,
+};
+use chess_engines::alpha_beta::{
+    evaluation::{EvalParams, Evaluator, NnueEval, SimpleEval, nnue::Network},
+    search::SearchContext,
+};
+
+/// How long a player thinks on every move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    Depth(u8),
+    Time(Duration),
+}
+
+/// One side of a match.
+pub trait Player {
+    fn name(&self) -> &str;
+
+    /// Called before every game.
+    fn new_game(&mut self) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// The move to play in `game`, which has at least one legal move. An error
+    /// or an illegal move loses the game.
+    fn best_move(&mut self, game: &mut Game) -> Result<Move, &'static str>;
+}
+
+pub enum Eval {
+    Simple(Box<EvalParams>),
+    Nnue(Arc<Network>),
+}
+
+/// Our own search, with a fresh context for every move.
+pub struct SearchPlayer {
+    name: String,
+    eval: Eval,
+    limit: Limit,
+}
+
+impl SearchPlayer {
+    pub fn new(name: String, eval: Eval, limit: Limit) -> Self {
+        SearchPlayer { name, eval, limit }
+    }
+}
+
+fn search<E: Evaluator>(mut context: SearchContext<E>, limit: Limit) -> Vec<Move> {
+    match limit {
+        Limit::Depth(depth) => {
+            context.max_depth = depth;
+            context.search(Vec::new()).1
+        }
+        Limit::Time(time) => context.iterative_deepen(time).1,
+    }
+}
+
+impl Player for SearchPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn best_move(&mut self, game: &mut Game) -> Result<Move, &'static str> {
+        let position = Position::new(HashedState::new(game.state().clone(), ZobristHasher::new()));
+        let pv = match &self.eval {
+            Eval::Simple(params) => search(
+                SearchContext::new(position, SimpleEval::new(params.as_ref().clone()), None),
+                self.limit,
+            ),
+            Eval::Nnue(network) => search(
+                SearchContext::new(position, NnueEval::new(network.clone()), None),
+                self.limit,
+            ),
+        };
+        pv.last().copied().ok_or("Search found no move")
+    }
+}
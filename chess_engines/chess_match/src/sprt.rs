@@ -0,0 +1,173 @@
+// Sequential probability ratio test between two Elo hypotheses. After every
+// game the log-likelihood ratio of "the engine is elo1 stronger" against "the
+// engine is elo0 stronger" is compared with bounds set by the accepted error
+// rates, so a match stops as soon as the result is clear. The ratio uses the
+// normal approximation of the mean game score.
+
+use std::fmt::Display;
+
+/// Wins, draws and losses of the tested engine.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Score {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Add a game scored 1, 0.5 or 0.
+    pub fn add(&mut self, score: f64) {
+        if score > 0.5 {
+            self.wins += 1;
+        } else if score < 0.5 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Variance of the score of a single game.
+    fn variance(&self) -> f64 {
+        let mean = self.mean();
+        (self.wins as f64 * (1.0 - mean).powi(2)
+            + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2))
+            / self.games() as f64
+    }
+
+    /// Elo difference and the half width of its 95% confidence interval,
+    /// `None` until the score is neither 0 nor 1.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let mean = self.mean();
+        if !(mean > 0.0 && mean < 1.0) {
+            return None;
+        }
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = elo((mean - 1.96 * deviation).max(f64::EPSILON));
+        let high = elo((mean + 1.96 * deviation).min(1.0 - f64::EPSILON));
+        Some((elo(mean), (high - low) / 2.0))
+    }
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// Expected score against an opponent `elo` points weaker.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Elo difference giving the expected score `score`.
+fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Continue,
+    /// The gain is at most elo0.
+    AcceptH0,
+    /// The gain is at least elo1.
+    AcceptH1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// Probability of accepting H1 when H0 holds.
+    pub alpha: f64,
+    /// Probability of accepting H0 when H1 holds.
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// Log-likelihood ratio bounds below which H0 and above which H1 is accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn llr(&self, score: &Score) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let variance = score.variance();
+        if variance == 0.0 {
+            // All games had the same result, there is nothing to tell yet
+            return 0.0;
+        }
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        (s1 - s0) * (2.0 * score.mean() - s0 - s1) / (2.0 * variance / score.games() as f64)
+    }
+
+    pub fn decide(&self, score: &Score) -> Decision {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            Decision::AcceptH0
+        } else if llr >= upper {
+            Decision::AcceptH1
+        } else {
+            Decision::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPRT: Sprt = Sprt {
+        elo0: 0.0,
+        elo1: 10.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+
+    fn score(wins: u32, draws: u32, losses: u32) -> Score {
+        Score {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    #[test]
+    fn test_elo() {
+        assert_eq!(score(0, 0, 0).elo(), None);
+        assert_eq!(score(3, 0, 0).elo(), None);
+        let (elo, margin) = score(50, 0, 50).elo().unwrap();
+        assert!(elo.abs() < 1e-9);
+        assert!(margin > 0.0);
+        let (elo, _) = score(60, 30, 10).elo().unwrap();
+        assert!((elo - 190.85).abs() < 0.01, "{}", elo);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let (lower, upper) = SPRT.bounds();
+        assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+
+        assert_eq!(SPRT.llr(&Score::default()), 0.0);
+        assert_eq!(SPRT.decide(&score(0, 40, 0)), Decision::Continue);
+        assert_eq!(SPRT.decide(&score(10, 80, 10)), Decision::Continue);
+        assert_eq!(SPRT.decide(&score(600, 800, 400)), Decision::AcceptH1);
+        assert_eq!(SPRT.decide(&score(400, 800, 600)), Decision::AcceptH0);
+        // A score between the hypotheses takes longer to decide
+        assert!(SPRT.llr(&score(210, 400, 190)).abs() < SPRT.llr(&score(250, 400, 150)));
+    }
+}
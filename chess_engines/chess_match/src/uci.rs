@@ -0,0 +1,203 @@
+// Client side of the UCI protocol, to play against external engines. The
+// engine gets the starting position and every move of the game, so it can
+// detect repetitions itself.
+
+use std::{
+    io::{BufRead, BufReader, Lines, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use chess_core::{game::Game, r#move::Move};
+
+use crate::player::{Limit, Player};
+
+pub struct UciPlayer {
+    name: String,
+    limit: Limit,
+    process: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl UciPlayer {
+    /// Start the engine and wait until it is ready. The command is split on
+    /// whitespace into the program and its arguments.
+    pub fn start(command: &str, limit: Limit) -> Result<Self, &'static str> {
+        let mut words = command.split_whitespace();
+        let mut process = Command::new(words.next().ok_or("Engine command is empty")?)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|_| "Cannot start the engine")?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = BufReader::new(process.stdout.take().unwrap()).lines();
+        let mut player = UciPlayer {
+            name: command.to_string(),
+            limit,
+            process,
+            stdin,
+            stdout,
+        };
+
+        player.send("uci")?;
+        loop {
+            let line = player.read_line()?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                player.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        player.is_ready()?;
+        Ok(player)
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), &'static str> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| "Cannot write to the engine")
+    }
+
+    fn read_line(&mut self) -> Result<String, &'static str> {
+        match self.stdout.next() {
+            Some(Ok(line)) => Ok(line),
+            _ => Err("Engine closed its output"),
+        }
+    }
+
+    fn is_ready(&mut self) -> Result<(), &'static str> {
+        self.send("isready")?;
+        while self.read_line()?.trim() != "readyok" {}
+        Ok(())
+    }
+}
+
+/// The `position` command for the current position of `game`.
+fn position_command(game: &Game) -> String {
+    let mut command = format!("position fen {}", game.start().to_fen());
+    if !game.moves().is_empty() {
+        command.push_str(" moves");
+        for r#move in game.moves() {
+            command.push(' ');
+            command.push_str(&r#move.to_string().to_lowercase());
+        }
+    }
+    command
+}
+
+fn go_command(limit: Limit) -> String {
+    match limit {
+        Limit::Depth(depth) => format!("go depth {}", depth),
+        Limit::Time(time) => format!("go movetime {}", time.num_milliseconds()),
+    }
+}
+
+/// The move of a `bestmove` line, `None` for any other line.
+fn parse_best_move(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    (words.next() == Some("bestmove")).then(|| words.next().unwrap_or_default())
+}
+
+impl Player for UciPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_game(&mut self) -> Result<(), &'static str> {
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    fn best_move(&mut self, game: &mut Game) -> Result<Move, &'static str> {
+        self.send(&position_command(game))?;
+        self.send(&go_command(self.limit))?;
+        let best_move = loop {
+            if let Some(r#move) = parse_best_move(&self.read_line()?) {
+                break r#move.to_string();
+            }
+        };
+        game.legal_moves()
+            .into_iter()
+            .find(|m| m.to_string().eq_ignore_ascii_case(&best_move))
+            .ok_or("Engine played an illegal move")
+    }
+}
+
+impl Drop for UciPlayer {
+    fn drop(&mut self) {
+        if self.send("quit").is_err() || self.process.wait().is_err() {
+            let _ = self.process.kill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chess_core::state::State;
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        let mut game = Game::default();
+        assert_eq!(
+            position_command(&game),
+            "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        let r#move = game.legal_moves()[0];
+        game.make(r#move);
+        assert!(position_command(&game).ends_with(&format!(" moves {}", r#move)));
+
+        let game = Game::new(State::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1"));
+        assert!(position_command(&game).starts_with("position fen 8/P6k/"));
+
+        assert_eq!(go_command(Limit::Depth(6)), "go depth 6");
+        assert_eq!(
+            go_command(Limit::Time(Duration::milliseconds(250))),
+            "go movetime 250"
+        );
+        assert_eq!(parse_best_move("bestmove a7a8q ponder h7h6"), Some("a7a8q"));
+        assert_eq!(parse_best_move("info depth 3 pv e2e4"), None);
+    }
+
+    #[test]
+    fn test_uci_player() {
+        // An engine answering e2e4 to everything
+        let script = std::env::temp_dir().join("chess_match_test_engine.sh");
+        fs::write(
+            &script,
+            "while read line; do
+                case $line in
+                    uci) echo 'id name Fake'; echo uciok;;
+                    isready) echo readyok;;
+                    go*) echo 'info depth 1'; echo 'bestmove e2e4';;
+                    quit) exit;;
+                esac
+            done",
+        )
+        .unwrap();
+        let command = format!("sh {}", script.display());
+        let mut player = UciPlayer::start(&command, Limit::Depth(1)).unwrap();
+        assert_eq!(player.name(), "Fake");
+        player.new_game().unwrap();
+
+        let mut game = Game::default();
+        let r#move = player.best_move(&mut game).unwrap();
+        assert_eq!(r#move.to_string(), "e2e4");
+        game.make(r#move);
+        assert_eq!(
+            player.best_move(&mut game),
+            Err("Engine played an illegal move")
+        );
+        drop(player);
+        fs::remove_file(script).unwrap();
+    }
+}
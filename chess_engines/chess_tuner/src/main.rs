@@ -0,0 +1,286 @@
+use std::{fs, io, thread};
+
+use chess_core::{
+    color::Color,
+    hash::{HashedState, zobrist::ZobristHasher},
+    state::State,
+};
+use chess_engines::alpha_beta::evaluation::{EvalParams, Evaluator, SimpleEval};
+
+// Texel tuning: the evaluation of quiet positions is mapped to an expected
+// game result with a sigmoid, and the parameters are adjusted one at a time
+// to minimise the mean squared error against the actual game results.
+
+/// Parameter changes tried first, halved every time a full pass finds no improvement.
+const INITIAL_STEP: i32 = 8;
+
+fn main() {
+    let args: Vec<_> = std::env::args().collect::<Vec<_>>();
+    let (positions, output, iterations) = match &args[..] {
+        [_, positions, output] => (positions, output, 100),
+        [_, positions, output, iterations] => (positions, output, iterations.parse().unwrap()),
+        _ => {
+            eprintln!("usage: chess_tuner <positions> <output.rs|output.cfg> [iterations]");
+            std::process::exit(1);
+        }
+    };
+
+    let samples = load_samples(positions).unwrap();
+    eprintln!("loaded {} positions", samples.len());
+
+    let params = EvalParams::default();
+    let k = fit_k(&samples, &params);
+    eprintln!(
+        "k = {:.3}, error = {:.6}",
+        k,
+        mean_squared_error(&samples, &params, k)
+    );
+
+    let params = local_search(&samples, params, k, iterations);
+    let content = if output.ends_with(".rs") {
+        params.to_rust("TUNED_PARAMS")
+    } else {
+        params.to_config()
+    };
+    fs::write(output, content).unwrap();
+}
+
+/// A quiet position and the result of the game it was taken from.
+struct Sample {
+    state: State,
+    /// Pawn hash of the position, for the pawn structure cache.
+    pawn_key: u64,
+    /// 1 for a white win, 0.5 for a draw, 0 for a black win.
+    result: f64,
+}
+
+/// Parse a line made of a FEN followed by the game result. The result is the
+/// last token and can be written `1-0`, `1/2-1/2`, `0-1` or `[1.0]`, `[0.5]`, `[0.0]`,
+/// optionally quoted and followed by a `;` as in EPD files.
+fn parse_sample(line: &str) -> Result<Sample, &'static str> {
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    if tokens.len() < 5 {
+        return Err("Expected a FEN followed by a result");
+    }
+    let result = tokens[tokens.len() - 1].trim_matches(|c| matches!(c, '"' | ';' | '[' | ']'));
+    let result = match result {
+        "1-0" | "1.0" | "1" => 1.0,
+        "1/2-1/2" | "0.5" => 0.5,
+        "0-1" | "0.0" | "0" => 0.0,
+        _ => return Err("Unknown game result"),
+    };
+    // Clocks and EPD opcodes are not needed for a static evaluation
+    let fen = tokens[..4].join(" ");
+    let state = HashedState::new(State::from_fen(&fen), ZobristHasher::new());
+    Ok(Sample {
+        pawn_key: state.hasher().pawn_hash(),
+        state: state.get().clone(),
+        result,
+    })
+}
+
+fn load_samples(path: &str) -> io::Result<Vec<Sample>> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            parse_sample(line).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+            })
+        })
+        .collect()
+}
+
+/// Expected result for a score in centipawns, from white's point of view.
+fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+/// Evaluation of the sample from white's point of view.
+fn white_score(evaluator: &mut SimpleEval, sample: &Sample) -> f64 {
+    let score = evaluator.evaluate(&sample.state, sample.pawn_key) as f64;
+    match sample.state.flags.active_color() {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+fn mean_squared_error(samples: &[Sample], params: &EvalParams, k: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = samples.len().div_ceil(threads);
+    let total: f64 = thread::scope(|scope| {
+        samples
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut evaluator = SimpleEval::new(params.clone());
+                    chunk
+                        .iter()
+                        .map(|sample| {
+                            let error =
+                                sample.result - sigmoid(white_score(&mut evaluator, sample), k);
+                            error * error
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum()
+    });
+    total / samples.len() as f64
+}
+
+/// Scaling constant of the sigmoid that best fits the untuned evaluation,
+/// found by refining a grid search one decimal at a time.
+fn fit_k(samples: &[Sample], params: &EvalParams) -> f64 {
+    // Scores don't depend on k, so they are computed once
+    let mut evaluator = SimpleEval::new(params.clone());
+    let scores = samples
+        .iter()
+        .map(|sample| (white_score(&mut evaluator, sample), sample.result))
+        .collect::<Vec<_>>();
+    let error = |k: f64| {
+        scores
+            .iter()
+            .map(|(score, result)| (result - sigmoid(*score, k)).powi(2))
+            .sum::<f64>()
+    };
+
+    let (mut low, mut high, mut step) = (0.0, 10.0, 1.0);
+    let mut best = 1.0;
+    for _ in 0..5 {
+        let mut k = low;
+        let mut best_error = f64::MAX;
+        while k <= high + 1e-9 {
+            let e = error(k);
+            if e < best_error {
+                best_error = e;
+                best = k;
+            }
+            k += step;
+        }
+        low = (best - step).max(0.0);
+        high = best + step;
+        step /= 10.0;
+    }
+    best
+}
+
+/// Move each parameter by `step` in both directions, keeping changes that lower
+/// the error, until a full pass finds nothing better with a step of 1.
+fn local_search(samples: &[Sample], params: EvalParams, k: f64, iterations: usize) -> EvalParams {
+    let mut values = params.to_vec();
+    let names = EvalParams::names();
+    let mut best_error = mean_squared_error(samples, &params, k);
+    let mut step = INITIAL_STEP;
+
+    for iteration in 0..iterations {
+        let mut improved = false;
+        for i in 0..values.len() {
+            for delta in [step, -step] {
+                values[i] += delta;
+                let candidate = EvalParams::from_slice(&values).unwrap();
+                let error = mean_squared_error(samples, &candidate, k);
+                if error < best_error {
+                    best_error = error;
+                    improved = true;
+                    eprintln!("{} = {}", names[i], values[i]);
+                    break;
+                }
+                values[i] -= delta;
+            }
+        }
+        eprintln!(
+            "iteration {}, step {}, error = {:.6}",
+            iteration + 1,
+            step,
+            best_error
+        );
+        if !improved {
+            if step == 1 {
+                break;
+            }
+            step /= 2;
+        }
+    }
+    EvalParams::from_slice(&values).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [&str; 7] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 [0.5]",
+        "4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1 [1.0]",
+        "4k3/ppp5/8/8/8/8/8/4K3 b - - 0 1 [0.0]",
+        "3qk3/8/8/8/8/8/8/4K3 w - - 0 1 \"0-1\";",
+        "4k3/8/8/8/8/8/8/3RK3 b - - c9 \"1-0\";",
+        "4k3/p7/8/8/8/8/P7/4K3 w - - 1/2-1/2",
+        // An extra pawn is not always enough, this keeps k finite
+        "4k3/8/8/8/8/8/P7/4K3 b - - 0 1 [0.5]",
+    ];
+
+    fn samples() -> Vec<Sample> {
+        SAMPLES
+            .iter()
+            .map(|line| parse_sample(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_sample() {
+        let results = samples().iter().map(|s| s.result).collect::<Vec<_>>();
+        assert_eq!(results, [0.5, 1.0, 0.0, 0.0, 1.0, 0.5, 0.5]);
+        assert_eq!(samples()[2].state.flags.active_color(), Color::Black);
+        assert!(parse_sample("4k3/8/8/8/8/8/8/4K3 w - - 2-0").is_err());
+        assert!(parse_sample("1-0").is_err());
+    }
+
+    #[test]
+    fn test_sigmoid() {
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!((sigmoid(400.0, 1.0) - 10.0 / 11.0).abs() < 1e-9);
+        assert!((sigmoid(-400.0, 1.0) - 1.0 / 11.0).abs() < 1e-9);
+        assert!(sigmoid(100.0, 2.0) > sigmoid(100.0, 1.0));
+    }
+
+    #[test]
+    fn test_white_score() {
+        let samples = samples();
+        let mut evaluator = SimpleEval::default();
+        // Material decides the sign whoever is to move
+        assert!(white_score(&mut evaluator, &samples[1]) > 0.0);
+        assert!(white_score(&mut evaluator, &samples[2]) < 0.0);
+        assert!(white_score(&mut evaluator, &samples[4]) > 0.0);
+    }
+
+    #[test]
+    fn test_fit_k() {
+        let samples = samples();
+        let params = EvalParams::default();
+        let k = fit_k(&samples, &params);
+        assert!(k > 0.0 && k < 10.0, "k = {}", k);
+        // The fitted constant beats its neighbours
+        let error = mean_squared_error(&samples, &params, k);
+        assert!(error <= mean_squared_error(&samples, &params, k + 0.1));
+        assert!(error <= mean_squared_error(&samples, &params, (k - 0.1).max(0.0)));
+    }
+
+    #[test]
+    fn test_local_search() {
+        let samples = samples();
+        let params = EvalParams::default();
+        let k = 1.0;
+        let before = mean_squared_error(&samples, &params, k);
+        let tuned = local_search(&samples, params.clone(), k, 2);
+        assert!(mean_squared_error(&samples, &tuned, k) < before);
+        assert_ne!(tuned, params);
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+use chess_core::{
+    clock::move_budget,
+    game::Game,
+    hash::{HashedState, zobrist::ZobristHasher},
+    r#move::Move,
+    position::Position,
+    state::State,
+};
+use chess_engines::alpha_beta::{
+    evaluation::SimpleEval,
+    search::SearchContext,
+    strength::{MAX_ELO, MAX_SKILL, MIN_ELO, Strength, choose_move},
+};
+use chrono::Local;
+
+// UCI front end of the alpha-beta search, for GUIs and match runners. Searches
+// run on the input thread, so `stop` is not supported and `go infinite` stops
+// at the default depth.
+
+/// Depth of a search given neither a depth nor a time.
+const DEFAULT_DEPTH: u8 = 6;
+const DEFAULT_ELO: u32 = 1500;
+
+/// Limits of a `go` command.
+#[derive(Debug, Default, PartialEq)]
+struct GoLimits {
+    depth: Option<u8>,
+    nodes: Option<u64>,
+    move_time: Option<Duration>,
+    time_left: [Option<Duration>; 2],
+    increment: [Option<Duration>; 2],
+    moves_to_go: Option<u32>,
+}
+
+impl GoLimits {
+    fn parse(words: &[&str]) -> Result<Self, &'static str> {
+        let mut limits = GoLimits::default();
+        let mut words = words.iter();
+        let milliseconds = |value: Option<&&str>| {
+            value
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_millis)
+                .ok_or("Time malformed")
+        };
+        while let Some(word) = words.next() {
+            match *word {
+                "depth" => {
+                    let depth = words.next().and_then(|depth| depth.parse().ok());
+                    limits.depth = Some(depth.ok_or("Depth malformed")?);
+                }
+                "nodes" => {
+                    let nodes = words.next().and_then(|nodes| nodes.parse().ok());
+                    limits.nodes = Some(nodes.ok_or("Nodes malformed")?);
+                }
+                "movestogo" => {
+                    let moves = words.next().and_then(|moves| moves.parse().ok());
+                    limits.moves_to_go = Some(moves.ok_or("Moves to go malformed")?);
+                }
+                "movetime" => limits.move_time = Some(milliseconds(words.next())?),
+                "wtime" => limits.time_left[0] = Some(milliseconds(words.next())?),
+                "btime" => limits.time_left[1] = Some(milliseconds(words.next())?),
+                "winc" => limits.increment[0] = Some(milliseconds(words.next())?),
+                "binc" => limits.increment[1] = Some(milliseconds(words.next())?),
+                // Searched like a plain `go`
+                "infinite" | "ponder" => {}
+                _ => return Err("Unknown go parameter"),
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Time to spend on the move by the side with index `side`, 0 for white.
+    fn budget(&self, side: usize) -> Option<Duration> {
+        if let Some(time) = self.move_time {
+            return Some(time);
+        }
+        let increment = self.increment[side].unwrap_or_default();
+        Some(move_budget(
+            self.time_left[side]?,
+            increment,
+            self.moves_to_go,
+        ))
+    }
+}
+
+struct Engine {
+    game: Game,
+    skill_level: u8,
+    limit_strength: bool,
+    elo: u32,
+}
+
+impl Engine {
+    fn new() -> Self {
+        Engine {
+            game: Game::default(),
+            skill_level: MAX_SKILL,
+            limit_strength: false,
+            elo: DEFAULT_ELO,
+        }
+    }
+
+    /// Strength to play at, `None` for full strength.
+    fn strength(&self) -> Option<Strength> {
+        if self.limit_strength {
+            Some(Strength::from_elo(self.elo))
+        } else if self.skill_level < MAX_SKILL {
+            Some(Strength::from_skill(self.skill_level))
+        } else {
+            None
+        }
+    }
+
+    fn set_option(&mut self, words: &[&str]) -> Result<(), &'static str> {
+        let text = words.join(" ");
+        let text = text.strip_prefix("name ").ok_or("Option name missing")?;
+        let (name, value) = text.split_once(" value ").unwrap_or((text, ""));
+        match name.to_lowercase().as_str() {
+            "skill level" => {
+                self.skill_level = value.parse().map_err(|_| "Skill level malformed")?;
+            }
+            "uci_limitstrength" => {
+                self.limit_strength = value.parse().map_err(|_| "Expected true or false")?;
+            }
+            "uci_elo" => self.elo = value.parse().map_err(|_| "Elo malformed")?,
+            _ => return Err("Unknown option"),
+        }
+        Ok(())
+    }
+
+    fn set_position(&mut self, words: &[&str]) -> Result<(), &'static str> {
+        let (state, rest) = match words {
+            ["startpos", rest @ ..] => (State::default(), rest),
+            ["fen", rest @ ..] => {
+                let end = rest
+                    .iter()
+                    .position(|w| *w == "moves")
+                    .unwrap_or(rest.len());
+                (State::try_from_fen(&rest[..end].join(" "))?, &rest[end..])
+            }
+            _ => return Err("Expected startpos or fen"),
+        };
+        let mut game = Game::new(state);
+        let moves = match rest {
+            ["moves", moves @ ..] => moves,
+            [] => &[],
+            _ => return Err("Expected moves"),
+        };
+        for r#move in moves {
+            let r#move = game
+                .legal_moves()
+                .into_iter()
+                .find(|m| m.to_string().eq_ignore_ascii_case(r#move))
+                .ok_or("Illegal move")?;
+            game.make(r#move);
+        }
+        self.game = game;
+        Ok(())
+    }
+
+    fn search(&mut self, limits: &GoLimits, out: &mut impl Write) -> io::Result<Option<Move>> {
+        let position = Position::new(HashedState::new(
+            self.game.state().clone(),
+            ZobristHasher::new(),
+        ));
+        let mut context = SearchContext::new(position, SimpleEval::default(), None);
+        let side = self.game.state().flags.active_color() as usize;
+        let budget = limits.budget(side);
+        let start = Instant::now();
+        context.node_limit = limits.nodes;
+        context.deadline = budget.map(|budget| Local::now() + budget);
+
+        if let Some(strength) = self.strength() {
+            let strength = Strength {
+                depth: limits
+                    .depth
+                    .map_or(strength.depth, |d| d.min(strength.depth)),
+                ..strength
+            };
+            return Ok(choose_move(&mut context, &strength));
+        }
+
+        let max_depth = limits.depth.unwrap_or(if budget.is_some() {
+            u8::MAX
+        } else {
+            DEFAULT_DEPTH
+        });
+        let mut pv = Vec::new();
+        for depth in 1..=max_depth {
+            context.max_depth = depth;
+            let (score, new_pv) = context.search(pv.clone());
+            if context.aborted() {
+                // Out of nodes or time, the previous depth is kept
+                break;
+            }
+            pv = new_pv;
+            let elapsed = start.elapsed();
+            writeln!(
+                out,
+                "info depth {} score cp {} nodes {} time {} pv {}",
+                depth,
+                score,
+                context.nodes,
+                elapsed.as_millis(),
+                pv.iter()
+                    .rev()
+                    .map(|m| m.to_string().to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?;
+            // The next depth takes longer than all the previous ones
+            let out_of_time = budget.is_some_and(|budget| elapsed * 2 > budget);
+            if pv.is_empty() || out_of_time {
+                break;
+            }
+        }
+        match pv.last() {
+            Some(r#move) => Ok(Some(*r#move)),
+            // No legal move, or a position the search cut short
+            None => Ok(self.game.legal_moves().first().copied()),
+        }
+    }
+
+    /// Answer a command, `false` once the engine should quit.
+    fn handle(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let result = match words[..] {
+            ["uci"] => {
+                writeln!(out, "id name chess_engines")?;
+                writeln!(out, "id author the chess_engines authors")?;
+                writeln!(
+                    out,
+                    "option name Skill Level type spin default {} min 0 max {}",
+                    MAX_SKILL, MAX_SKILL
+                )?;
+                writeln!(
+                    out,
+                    "option name UCI_LimitStrength type check default false"
+                )?;
+                writeln!(
+                    out,
+                    "option name UCI_Elo type spin default {} min {} max {}",
+                    DEFAULT_ELO, MIN_ELO, MAX_ELO
+                )?;
+                writeln!(out, "uciok")?;
+                Ok(())
+            }
+            ["isready"] => {
+                writeln!(out, "readyok")?;
+                Ok(())
+            }
+            ["ucinewgame"] => {
+                self.game = Game::default();
+                Ok(())
+            }
+            ["setoption", ref rest @ ..] => self.set_option(rest),
+            ["position", ref rest @ ..] => self.set_position(rest),
+            ["go", ref rest @ ..] => match GoLimits::parse(rest) {
+                Ok(limits) => {
+                    let best_move = self.search(&limits, out)?;
+                    let best_move =
+                        best_move.map_or("0000".to_string(), |m| m.to_string().to_lowercase());
+                    writeln!(out, "bestmove {}", best_move)?;
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+            ["quit"] => return Ok(false),
+            [] => Ok(()),
+            _ => Err("Unknown command"),
+        };
+        if let Err(e) = result {
+            writeln!(out, "info string error: {}: {}", e, line.trim())?;
+        }
+        out.flush()?;
+        Ok(true)
+    }
+}
+
+fn main() {
+    let mut engine = Engine::new();
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        if !engine.handle(&line.unwrap(), &mut stdout).unwrap() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of the engine for the commands.
+    fn run(engine: &mut Engine, commands: &str) -> String {
+        let mut out = Vec::new();
+        for line in commands.lines() {
+            engine.handle(line, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    fn best_move(output: &str) -> &str {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix("bestmove "))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_handshake() {
+        let engine = &mut Engine::new();
+        let output = run(engine, "uci\nisready");
+        assert!(output.contains("option name UCI_Elo type spin default 1500 min 800 max 2200"));
+        assert!(output.ends_with("uciok\nreadyok\n"));
+        assert!(run(engine, "hello").starts_with("info string error: Unknown command"));
+        assert!(!engine.handle("quit", &mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn test_position() {
+        let engine = &mut Engine::new();
+        run(engine, "position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(
+            engine.game.state().to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 1"
+        );
+        assert_eq!(engine.game.moves().len(), 3);
+
+        run(
+            engine,
+            "position fen 8/P6k/8/8/8/8/8/K7 w - - 0 1 moves a7a8q",
+        );
+        assert_eq!(engine.game.state().to_fen(), "Q7/7k/8/8/8/8/8/K7 b - - 0 1");
+
+        // A bad command keeps the previous position
+        let output = run(engine, "position startpos moves e2e5");
+        assert!(output.contains("Illegal move"));
+        assert_eq!(engine.game.moves().len(), 1);
+    }
+
+    #[test]
+    fn test_go() {
+        let engine = &mut Engine::new();
+        let output = run(
+            engine,
+            "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2",
+        );
+        assert!(output.contains("info depth 2 "));
+        assert_eq!(best_move(&output), "a1a8");
+
+        let output = run(
+            engine,
+            "position fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1\ngo movetime 10",
+        );
+        assert_eq!(best_move(&output), "0000");
+
+        assert_eq!(
+            GoLimits::parse(&["wtime", "60000", "btime", "1000", "binc", "100"]),
+            Ok(GoLimits {
+                time_left: [Some(Duration::from_secs(60)), Some(Duration::from_secs(1))],
+                increment: [None, Some(Duration::from_millis(100))],
+                ..GoLimits::default()
+            })
+        );
+        let limits = GoLimits::parse(&["wtime", "60000", "btime", "1000", "binc", "100"]).unwrap();
+        assert_eq!(limits.budget(0), Some(Duration::from_secs(2)));
+        assert_eq!(limits.budget(1).unwrap().as_millis(), 83);
+        assert!(GoLimits::parse(&["depth"]).is_err());
+        assert!(GoLimits::parse(&["mate", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_limit_strength() {
+        let engine = &mut Engine::new();
+        assert_eq!(engine.strength(), None);
+        run(engine, "setoption name Skill Level value 3");
+        assert_eq!(engine.strength(), Some(Strength::from_skill(3)));
+        run(
+            engine,
+            "setoption name UCI_LimitStrength value true\nsetoption name UCI_Elo value 2000",
+        );
+        assert_eq!(engine.strength(), Some(Strength::from_elo(2000)));
+        assert!(run(engine, "setoption name UCI_Elo value high").contains("Elo malformed"));
+
+        // The limits of the go command apply on top of the strength
+        for go in [
+            "go depth 3",
+            "go nodes 100",
+            "go movetime 10",
+            "go wtime 100 btime 100",
+        ] {
+            let output = run(engine, &format!("position startpos\n{}", go));
+            let r#move = best_move(&output);
+            assert!(
+                engine
+                    .game
+                    .legal_moves()
+                    .iter()
+                    .any(|m| m.to_string().eq_ignore_ascii_case(r#move))
+            );
+        }
+    }
+}
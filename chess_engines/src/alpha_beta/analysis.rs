@@ -0,0 +1,361 @@
+// Review of a played game. Every position of the game is searched to the same
+// depth, and the score of the best move against the score after the move
+// played tells how much the move lost. Losses are measured in winning chances
+// rather than centipawns, so that going from +12 to +8 in a won position is
+// not a blunder. The accuracy of a move falls quickly with the chances lost,
+// with the constants of the formula Lichess uses, and the accuracy of a side
+// is the average over its moves.
+
+use std::fmt::Display;
+
+use chess_core::{
+    color::Color,
+    r#move::{Move, san::to_san},
+    pgn::{Annotation, PgnGame},
+};
+
+use super::{
+    evaluation::Evaluator,
+    search::{MATE_SCORE, SearchContext},
+};
+
+/// Largest evaluation in centipawns, tablebase wins scored around
+/// `TB_WIN_SCORE` are shown as this.
+const MAX_CENTIPAWNS: i32 = 10000;
+
+/// Winning chances lost, in percentage points, from which a move is an
+/// inaccuracy, a mistake or a blunder.
+const INACCURACY: f64 = 5.0;
+const MISTAKE: f64 = 10.0;
+const BLUNDER: f64 = 15.0;
+
+/// Evaluation of a position from white's side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eval {
+    Centipawns(i32),
+    /// The winner and its moves to mate, 0 when the other side is checkmated.
+    Mate(Color, u32),
+}
+
+impl Eval {
+    /// Evaluation of a search `score` for the side to move `color`, mates are
+    /// counted along the principal variation `pv`.
+    fn from_score(score: i32, pv: &[Move], color: Color) -> Self {
+        if score.abs() < MATE_SCORE {
+            let score = score.clamp(-MAX_CENTIPAWNS, MAX_CENTIPAWNS);
+            let score = if color == Color::White { score } else { -score };
+            return Eval::Centipawns(score);
+        }
+        // The mating side plays the last move of the variation
+        if score > 0 {
+            Eval::Mate(color, pv.len().div_ceil(2) as u32)
+        } else {
+            Eval::Mate(!color, pv.len() as u32 / 2)
+        }
+    }
+
+    /// Winning chances of `color` in percent.
+    pub fn win_percent(&self, color: Color) -> f64 {
+        let white = match *self {
+            Eval::Centipawns(cp) => 100.0 / (1.0 + (-0.00368208 * cp as f64).exp()),
+            Eval::Mate(Color::White, _) => 100.0,
+            Eval::Mate(Color::Black, _) => 0.0,
+        };
+        match color {
+            Color::White => white,
+            Color::Black => 100.0 - white,
+        }
+    }
+}
+
+/// As in the `[%eval]` command of PGN comments: pawns, or `#` and the moves
+/// to mate, negative when black mates.
+impl Display for Eval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Eval::Centipawns(cp) => write!(f, "{:.2}", *cp as f64 / 100.0),
+            Eval::Mate(Color::White, moves) => write!(f, "#{}", moves),
+            Eval::Mate(Color::Black, moves) => write!(f, "#-{}", moves),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Classification {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    fn from_loss(loss: f64, best: bool) -> Self {
+        match loss {
+            _ if best => Classification::Best,
+            loss if loss >= BLUNDER => Classification::Blunder,
+            loss if loss >= MISTAKE => Classification::Mistake,
+            loss if loss >= INACCURACY => Classification::Inaccuracy,
+            _ => Classification::Good,
+        }
+    }
+
+    /// Numeric annotation glyph: `?!`, `?` or `??`.
+    pub fn nag(&self) -> Option<u8> {
+        match self {
+            Classification::Inaccuracy => Some(6),
+            Classification::Mistake => Some(2),
+            Classification::Blunder => Some(4),
+            Classification::Best | Classification::Good => None,
+        }
+    }
+}
+
+impl Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Classification::Best => "best",
+            Classification::Good => "good",
+            Classification::Inaccuracy => "inaccuracy",
+            Classification::Mistake => "mistake",
+            Classification::Blunder => "blunder",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveAnalysis {
+    /// Side that played the move.
+    pub color: Color,
+    pub r#move: Move,
+    pub san: String,
+    /// Evaluation of the position after the move.
+    pub eval: Eval,
+    /// Best move found before the move was played, and the evaluation it keeps.
+    pub best_move: Move,
+    pub best_san: String,
+    pub best_eval: Eval,
+    /// Winning chances lost by the move, in percentage points.
+    pub loss: f64,
+    /// From 0 to 100.
+    pub accuracy: f64,
+    pub classification: Classification,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameAnalysis {
+    pub moves: Vec<MoveAnalysis>,
+}
+
+impl GameAnalysis {
+    /// Average accuracy of the moves of `color`, `None` if it played none.
+    pub fn accuracy(&self, color: Color) -> Option<f64> {
+        let accuracies = self
+            .moves
+            .iter()
+            .filter(|m| m.color == color)
+            .map(|m| m.accuracy)
+            .collect::<Vec<_>>();
+        if accuracies.is_empty() {
+            return None;
+        }
+        Some(accuracies.iter().sum::<f64>() / accuracies.len() as f64)
+    }
+
+    /// Number of moves of `color` with the classification.
+    pub fn count(&self, color: Color, classification: Classification) -> usize {
+        self.moves
+            .iter()
+            .filter(|m| m.color == color && m.classification == classification)
+            .count()
+    }
+}
+
+/// Accuracy of a move losing `loss` percentage points of winning chances.
+fn move_accuracy(loss: f64) -> f64 {
+    (103.1668 * (-0.04354 * loss).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+/// Evaluation and best move of the position of `context`, searched to `depth`.
+fn search_position<E: Evaluator>(context: &mut SearchContext<E>, depth: u8) -> (Eval, Vec<Move>) {
+    let color = context.position.state.get().flags.active_color();
+    if context.position.legal_moves().is_empty() {
+        let score = if context.position.state.get().is_check() {
+            -MATE_SCORE
+        } else {
+            0
+        };
+        return (Eval::from_score(score, &[], color), Vec::new());
+    }
+    let mut result = (0, Vec::new());
+    for depth in 1..=depth.max(1) {
+        context.max_depth = depth;
+        result = context.search(result.1);
+    }
+    let (score, pv) = result;
+    (Eval::from_score(score, &pv, color), pv)
+}
+
+/// Analyse the legal `moves` played from the position of `context`, searching
+/// every position to `depth`. The position is left as it was.
+pub fn analyse_game<E: Evaluator>(
+    context: &mut SearchContext<E>,
+    moves: &[Move],
+    depth: u8,
+) -> GameAnalysis {
+    let prev_depth = context.max_depth;
+    let mut analysis = GameAnalysis::default();
+    let (mut best_eval, mut pv) = search_position(context, depth);
+    for r#move in moves {
+        let color = context.position.state.get().flags.active_color();
+        let san = to_san(&mut context.position, *r#move);
+        // The variation comes best move last
+        let best_move = pv.last().copied().unwrap_or(*r#move);
+        let best_san = to_san(&mut context.position, best_move);
+        context.make(*r#move);
+        let (eval, next_pv) = search_position(context, depth);
+
+        let loss = (best_eval.win_percent(color) - eval.win_percent(color)).max(0.0);
+        analysis.moves.push(MoveAnalysis {
+            color,
+            r#move: *r#move,
+            san,
+            eval,
+            best_move,
+            best_san,
+            best_eval,
+            loss,
+            accuracy: move_accuracy(loss),
+            classification: Classification::from_loss(loss, best_move == *r#move),
+        });
+        (best_eval, pv) = (eval, next_pv);
+    }
+    for r#move in moves.iter().rev() {
+        context.unmake(*r#move);
+    }
+    context.max_depth = prev_depth;
+    analysis
+}
+
+/// `pgn` with the evaluation after every move as an `[%eval]` comment, and the
+/// glyph and the best move after inaccuracies, mistakes and blunders.
+pub fn annotate(pgn: &PgnGame, analysis: &GameAnalysis) -> PgnGame {
+    let annotations = analysis
+        .moves
+        .iter()
+        .map(|m| {
+            let mut comment = match m.eval {
+                // No evaluation once the game is over
+                Eval::Mate(_, 0) => Vec::new(),
+                eval => vec![format!("[%eval {}]", eval)],
+            };
+            if m.classification.nag().is_some() {
+                let mut name = m.classification.to_string();
+                name[..1].make_ascii_uppercase();
+                comment.push(format!("{}. {} was best.", name, m.best_san));
+            }
+            Annotation {
+                nags: m.classification.nag().into_iter().collect(),
+                comment: (!comment.is_empty()).then(|| comment.join(" ")),
+            }
+        })
+        .collect();
+    PgnGame {
+        annotations,
+        ..pgn.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::{
+        hash::{HashedState, NoopHasher, zobrist::ZobristHasher},
+        r#move::MoveCode,
+        pgn::parse_pgn,
+        position::Position,
+        square::Square,
+    };
+
+    use super::*;
+    use crate::alpha_beta::{evaluation::SimpleEval, search::TB_WIN_SCORE};
+
+    const SCHOLARS_MATE: &str = "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0";
+
+    fn analyse(pgn: &PgnGame, depth: u8) -> GameAnalysis {
+        let mut moves = Vec::new();
+        pgn.replay(NoopHasher {}, |_, m| moves.push(m)).unwrap();
+        let position = Position::new(HashedState::new(
+            pgn.starting_state().unwrap(),
+            ZobristHasher::new(),
+        ));
+        let mut context = SearchContext::new(position, SimpleEval::default(), None);
+        let analysis = analyse_game(&mut context, &moves, depth);
+        assert_eq!(context.position.state.get(), &pgn.starting_state().unwrap());
+        analysis
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(Eval::Centipawns(35).to_string(), "0.35");
+        assert_eq!(Eval::Centipawns(-120).to_string(), "-1.20");
+        assert_eq!(Eval::Mate(Color::Black, 2).to_string(), "#-2");
+        assert_eq!(Eval::Centipawns(0).win_percent(Color::Black), 50.0);
+        assert!(Eval::Centipawns(300).win_percent(Color::White) > 75.0);
+        assert_eq!(Eval::Mate(Color::White, 3).win_percent(Color::Black), 0.0);
+
+        let square = |name| Square::try_from(name).unwrap();
+        let m = Move::new(square("e2"), square("e4"), MoveCode::QuietMove);
+        // Mated in one: the other side plays the last of two moves
+        assert_eq!(
+            Eval::from_score(-MATE_SCORE, &[m, m], Color::Black),
+            Eval::Mate(Color::White, 1)
+        );
+        assert_eq!(
+            Eval::from_score(MATE_SCORE, &[m, m, m], Color::Black),
+            Eval::Mate(Color::Black, 2)
+        );
+        assert_eq!(
+            Eval::from_score(-40, &[m], Color::Black),
+            Eval::Centipawns(40)
+        );
+        assert_eq!(
+            Eval::from_score(TB_WIN_SCORE - 3, &[m, m, m], Color::Black),
+            Eval::Centipawns(-MAX_CENTIPAWNS)
+        );
+        assert_eq!(
+            Eval::from_score(-TB_WIN_SCORE + 2, &[m, m], Color::Black).to_string(),
+            "100.00"
+        );
+        assert!(move_accuracy(0.0) > 99.9);
+        assert!(move_accuracy(20.0) < 50.0);
+    }
+
+    #[test]
+    fn test_analyse_game() {
+        let pgn = &parse_pgn(SCHOLARS_MATE).unwrap()[0];
+        let analysis = analyse(pgn, 2);
+        assert_eq!(analysis.moves.len(), 7);
+        let blunder = &analysis.moves[5];
+        assert_eq!(blunder.san, "Nf6");
+        assert_eq!(blunder.classification, Classification::Blunder);
+        assert_eq!(blunder.eval, Eval::Mate(Color::White, 1));
+        assert_ne!(blunder.best_san, "Nf6");
+
+        let mate = &analysis.moves[6];
+        assert_eq!(mate.classification, Classification::Best);
+        assert_eq!(mate.eval, Eval::Mate(Color::White, 0));
+        assert_eq!(analysis.count(Color::Black, Classification::Blunder), 1);
+        assert!(analysis.accuracy(Color::Black) < analysis.accuracy(Color::White));
+
+        let annotated = annotate(pgn, &analysis).to_string();
+        assert!(annotated.contains("3... Nf6 $4"));
+        assert!(annotated.contains(&format!(
+            "{{[%eval #1] Blunder. {} was best.}}",
+            blunder.best_san
+        )));
+        assert!(annotated.trim_end().ends_with("4. Qxf7# 1-0"));
+        assert_eq!(parse_pgn(&annotated).unwrap()[0].moves, pgn.moves);
+        assert_eq!(GameAnalysis::default().accuracy(Color::White), None);
+    }
+}
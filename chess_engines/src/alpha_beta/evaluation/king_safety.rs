@@ -0,0 +1,264 @@
+// King safety is scored from attack maps built with the move maps:
+// enemy pieces hitting the squares around the king, the pawn shield in
+// front of the king, enemy pawns storming it, open files next to it and
+// checks the enemy could give from squares we don't control.
+// Attacks on the king zone have their own end game weights, the other terms
+// count for a fixed fraction in the end game. The middle game and end game
+// scores are blended with the game phase so king safety fades as material
+// comes off.
+
+use chess_core::{
+    color::Color,
+    square::Square,
+    state::{State, bitboard::BitBoard, chess_board::PieceType},
+};
+
+use super::params::EvalParams;
+
+/// Weight of each attacking piece type in the king zone, indexed by piece type.
+pub const KING_ATTACKER_WEIGHT: [i32; 6] = [0, 2, 2, 3, 5, 0];
+/// End game weight of each attacking piece type in the king zone.
+pub const KING_ATTACKER_WEIGHT_EG: [i32; 6] = [0, 1, 1, 2, 3, 0];
+/// Bonus for a friendly pawn in front of the king, indexed by relative rank.
+pub const PAWN_SHELTER_BONUS: [i32; 8] = [0, 30, 20, 8, 0, 0, 0, 0];
+/// Penalty for an enemy pawn in front of the king, indexed by relative rank.
+pub const PAWN_STORM_PENALTY: [i32; 8] = [0, 0, 40, 30, 15, 5, 0, 0];
+/// Penalty for a check the enemy could give safely, indexed by piece type.
+pub const SAFE_CHECK_PENALTY: [i32; 6] = [0, 30, 20, 40, 35, 0];
+pub const SEMI_OPEN_FILE_PENALTY: i32 = 20;
+pub const OPEN_FILE_PENALTY: i32 = 35;
+
+/// Phase of each piece type, a full board of pieces adds up to `MAX_PHASE`.
+const PIECE_PHASE: [i32; 6] = [0, 1, 1, 2, 4, 0];
+pub const MAX_PHASE: i32 = 24;
+
+/// Terms without an end game weight count for this fraction of their middle game weight.
+const END_GAME_DIVISOR: i32 = 4;
+
+/// Game phase from `MAX_PHASE` (all pieces on the board) to 0 (pawns and kings only).
+pub fn game_phase(state: &State) -> i32 {
+    let phase = PieceType::as_array()
+        .into_iter()
+        .map(|piece| {
+            let count =
+                state.boards.white[piece].count_ones() + state.boards.black[piece].count_ones();
+            PIECE_PHASE[piece as usize] * count as i32
+        })
+        .sum::<i32>();
+    phase.min(MAX_PHASE)
+}
+
+/// Blend middle game and end game scores by phase.
+pub fn taper(mg: i32, eg: i32, phase: i32) -> i32 {
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+fn relative_rank(color: Color, square: Square) -> u8 {
+    match color {
+        Color::White => square.rank(),
+        Color::Black => 7 - square.rank(),
+    }
+}
+
+/// Middle game and end game safety of `color`'s king, negative when in danger.
+struct KingSafety {
+    mg: i32,
+    eg: i32,
+}
+
+impl KingSafety {
+    fn new(state: &State, color: Color, params: &EvalParams) -> Self {
+        let Some(king) = state.boards[color].king.get_first_square() else {
+            return KingSafety { mg: 0, eg: 0 };
+        };
+        let enemy = !color;
+        let enemy_occupation = state.boards[enemy].union();
+
+        // King zone: squares around the king and the rank in front of those
+        let around = state.piece_attacks(PieceType::King, color, king) | king.into();
+        let zone = around
+            | match color {
+                Color::White => around << 8,
+                Color::Black => around >> 8,
+            };
+
+        // Pieces attacking the zone
+        let mut attackers = 0;
+        let (mut attack_units, mut attack_units_eg) = (0, 0);
+        for piece in [
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+        ] {
+            let mut pieces = state.boards[enemy][piece];
+            while let Some(square) = pieces.pop_first_square() {
+                let hits = state.piece_attacks(piece, enemy, square) & zone;
+                if !hits.is_empty() {
+                    attackers += 1;
+                    let hits = hits.count_ones() as i32;
+                    attack_units += params.king_attacker_weight[piece as usize] * hits;
+                    attack_units_eg += params.king_attacker_weight_eg[piece as usize] * hits;
+                }
+            }
+        }
+
+        // Checks from squares we don't defend
+        let defended = state.all_attacks(color);
+        let mut safe_checks = 0;
+        for piece in [
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+        ] {
+            let checks = state.piece_attacks(piece, color, king)
+                & state.attacks(piece, enemy)
+                & !defended
+                & !enemy_occupation;
+            safe_checks += params.safe_check_penalty[piece as usize] * checks.count_ones() as i32;
+        }
+
+        // A lone attacker is rarely dangerous
+        let attack_penalty = |units: i32| if attackers >= 2 { units * units / 4 } else { 0 };
+
+        // Pawn shelter, storm and open files on the king's file and adjacent files
+        let mut shelter = 0;
+        let king_rank = relative_rank(color, king);
+        let min_file = king.file().saturating_sub(1);
+        let max_file = (king.file() + 1).min(7);
+        for file in min_file..=max_file {
+            let file_board = BitBoard::file(file);
+            let ours = state.boards[color].pawn & file_board;
+            let theirs = state.boards[enemy].pawn & file_board;
+
+            let in_front = |pawns: BitBoard| {
+                let mut pawns = pawns;
+                let mut nearest: Option<u8> = None;
+                while let Some(square) = pawns.pop_first_square() {
+                    let rank = relative_rank(color, square);
+                    if rank > king_rank && nearest.is_none_or(|n| rank < n) {
+                        nearest = Some(rank);
+                    }
+                }
+                nearest
+            };
+
+            if let Some(rank) = in_front(ours) {
+                shelter += params.pawn_shelter_bonus[rank as usize];
+            }
+            if let Some(rank) = in_front(theirs) {
+                shelter -= params.pawn_storm_penalty[rank as usize];
+            }
+            if ours.is_empty() {
+                shelter -= if theirs.is_empty() {
+                    params.open_file_penalty
+                } else {
+                    params.semi_open_file_penalty
+                };
+            }
+        }
+
+        KingSafety {
+            mg: shelter - attack_penalty(attack_units) - safe_checks,
+            eg: (shelter - safe_checks) / END_GAME_DIVISOR - attack_penalty(attack_units_eg),
+        }
+    }
+}
+
+/// King safety score from the point of view of the side to move.
+pub fn king_safety_score(state: &State, params: &EvalParams) -> i32 {
+    let white = KingSafety::new(state, Color::White, params);
+    let black = KingSafety::new(state, Color::Black, params);
+    let score = taper(white.mg - black.mg, white.eg - black.eg, game_phase(state));
+    match state.flags.active_color() {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn safety(fen: &str, color: Color) -> i32 {
+        KingSafety::new(&State::from_fen(fen), color, &EvalParams::default()).mg
+    }
+
+    #[test]
+    fn test_game_phase() {
+        assert_eq!(game_phase(&State::default()), MAX_PHASE);
+        assert_eq!(
+            game_phase(&State::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1")),
+            0
+        );
+        assert_eq!(taper(100, 20, MAX_PHASE), 100);
+        assert_eq!(taper(100, 20, 0), 20);
+        assert_eq!(taper(100, 20, MAX_PHASE / 2), 60);
+    }
+
+    #[test]
+    fn test_symmetric_position() {
+        assert_eq!(
+            king_safety_score(&State::default(), &EvalParams::default()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_pawn_shelter() {
+        let sheltered = safety("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1", Color::White);
+        let advanced = safety("4k3/8/8/8/8/5PPP/8/6K1 w - - 0 1", Color::White);
+        let missing = safety("4k3/8/8/8/8/8/5PP1/6K1 w - - 0 1", Color::White);
+        assert_eq!(sheltered, 3 * PAWN_SHELTER_BONUS[1]);
+        assert_eq!(advanced, 3 * PAWN_SHELTER_BONUS[2]);
+        assert_eq!(missing, 2 * PAWN_SHELTER_BONUS[1] - OPEN_FILE_PENALTY);
+    }
+
+    #[test]
+    fn test_pawn_storm() {
+        let calm = safety("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1", Color::White);
+        let storm = safety("4k3/8/8/8/8/6p1/5PPP/6K1 w - - 0 1", Color::White);
+        assert_eq!(calm - storm, PAWN_STORM_PENALTY[2]);
+        let semi_open = safety("4k3/8/8/7p/8/8/5PP1/6K1 w - - 0 1", Color::White);
+        assert_eq!(
+            semi_open,
+            2 * PAWN_SHELTER_BONUS[1] - PAWN_STORM_PENALTY[4] - SEMI_OPEN_FILE_PENALTY
+        );
+    }
+
+    #[test]
+    fn test_king_zone_attackers() {
+        // Queen and rook bearing down on the castled king
+        let attacked = safety("4k3/8/8/8/6q1/8/5PPP/5rK1 w - - 0 1", Color::White);
+        let single = safety("4k3/8/8/8/8/8/5PPP/5rK1 w - - 0 1", Color::White);
+        assert!(attacked < single, "{} >= {}", attacked, single);
+
+        // The end game weighs the attack with its own table
+        let state = State::from_fen("4k3/8/8/8/6q1/8/5PPP/5rK1 w - - 0 1");
+        let params = EvalParams {
+            king_attacker_weight_eg: [0; 6],
+            ..EvalParams::default()
+        };
+        let weighted = KingSafety::new(&state, Color::White, &EvalParams::default());
+        let unweighted = KingSafety::new(&state, Color::White, &params);
+        assert_eq!(weighted.mg, unweighted.mg);
+        assert!(weighted.eg < unweighted.eg);
+    }
+
+    #[test]
+    fn test_safe_checks() {
+        // The knight can check from f3 or h3, only f3 is defended by e2
+        let fen = "4k3/8/8/6n1/8/8/4P2P/6K1 w - - 0 1";
+        let state = State::from_fen(fen);
+        let king = state.boards.white.king.get_first_square().unwrap();
+        let checks = state.piece_attacks(PieceType::Knight, Color::White, king)
+            & state.attacks(PieceType::Knight, Color::Black);
+        assert_eq!(checks.count_ones(), 2);
+        let without_knight = safety("4k3/8/8/8/8/8/4P2P/6K1 w - - 0 1", Color::White);
+        assert_eq!(
+            without_knight - safety(fen, Color::White),
+            SAFE_CHECK_PENALTY[PieceType::Knight as usize]
+        );
+    }
+}
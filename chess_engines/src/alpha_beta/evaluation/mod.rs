@@ -1 +1,31 @@
-pub mod simple_eval;
\ No newline at end of file
+pub mod king_safety;
+pub mod nnue;
+pub mod params;
+pub mod pawn_structure;
+pub mod simple_eval;
+
+use chess_core::{r#move::Move, state::State};
+
+pub use nnue::NnueEval;
+pub use params::EvalParams;
+pub use simple_eval::SimpleEval;
+
+/// An evaluator scores a position from the point of view of the side to move.
+///
+/// The search notifies the evaluator of every move it makes and unmakes so that
+/// implementations can keep incremental state up to date. Stateless evaluators
+/// only need to implement `evaluate`.
+pub trait Evaluator {
+    /// Called with the root position before the evaluator is used.
+    fn init(&mut self, _state: &State) {}
+
+    /// Called before `r#move` is made. `state` is the position before the move.
+    fn make(&mut self, _state: &State, _move: Move) {}
+
+    /// Called after `r#move` is unmade. `state` is the position before the move.
+    fn unmake(&mut self, _state: &State, _move: Move) {}
+
+    /// Full evaluation of the position in centipawns, positive if the side to move is better.
+    /// `pawn_key` identifies the pawn structure, for caching evaluations of it.
+    fn evaluate(&mut self, state: &State, pawn_key: u64) -> i32;
+}
@@ -0,0 +1,508 @@
+// Efficiently updatable neural network evaluation.
+//
+// Architecture: (768 -> HIDDEN) x 2 -> 1
+// Each input feature is a (piece color, piece type, square) triple seen from one
+// perspective. The black perspective mirrors the board vertically and swaps colors,
+// so both perspectives share the same feature weights. The hidden layer of each
+// perspective is kept in an int16 accumulator that is updated incrementally when
+// pieces are added or removed, instead of being recomputed from scratch.
+//
+// The output is computed from the clipped ReLU of the side to move's accumulator
+// followed by the other side's accumulator.
+//
+// Network file format, all values little-endian:
+//
+// | Offset            | Type                      | Content                                  |
+// |-------------------|---------------------------|------------------------------------------|
+// | 0                 | [u8; 4]                   | Magic bytes `CNNU`                       |
+// | 4                 | u32                       | Format version, currently 1              |
+// | 8                 | u32                       | Hidden layer size, must equal `HIDDEN`   |
+// | 12                | [i16; 768 * HIDDEN]       | Feature weights, feature major           |
+// | ..                | [i16; HIDDEN]             | Feature biases                           |
+// | ..                | [i16; 2 * HIDDEN]         | Output weights, side to move first       |
+// | ..                | i16                       | Output bias                              |
+//
+// Feature index for a perspective is `side * 384 + piece * 64 + square` where side
+// is 0 for the perspective's own pieces, piece follows `PieceType` order and square
+// is mirrored for the black perspective.
+// Weights are quantized with `QA` for the feature layer and `QB` for the output layer.
+
+use std::{path::Path, sync::Arc};
+
+use boxarray::boxarray;
+use chess_core::{
+    color::Color,
+    r#move::{Move, MoveCode},
+    square::{Square, SquareFinder},
+    state::{State, chess_board::PieceType},
+};
+
+use super::Evaluator;
+
+pub const HIDDEN: usize = 256;
+pub const FEATURES: usize = 768;
+pub const QA: i32 = 255;
+pub const QB: i32 = 64;
+pub const SCALE: i32 = 400;
+/// Evaluations are clamped to this many centipawns, below tablebase wins and mates.
+pub const MAX_EVAL: i32 = 30000;
+
+const MAGIC: &[u8; 4] = b"CNNU";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 12;
+const FILE_SIZE: usize = HEADER_SIZE + 2 * (FEATURES * HIDDEN + HIDDEN + 2 * HIDDEN + 1);
+
+/// Index of a feature from the point of view of `perspective`.
+pub fn feature_index(perspective: Color, color: Color, piece: PieceType, square: Square) -> usize {
+    let (side, square) = match perspective {
+        Color::White => (color as usize, square),
+        Color::Black => ((!color) as usize, square.mirror()),
+    };
+    side * 384 + piece as usize * 64 + square.get() as usize
+}
+
+/// Network weights, see the module documentation for the file layout.
+pub struct Network {
+    feature_weights: Box<[[i16; HIDDEN]; FEATURES]>,
+    feature_bias: [i16; HIDDEN],
+    output_weights: [[i16; HIDDEN]; 2],
+    output_bias: i16,
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+            return Err("Not a network file.");
+        }
+        let read_u32 =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        if read_u32(4) != VERSION {
+            return Err("Unsupported network version.");
+        }
+        if read_u32(8) as usize != HIDDEN {
+            return Err("Network hidden layer size does not match.");
+        }
+        if bytes.len() != FILE_SIZE {
+            return Err("Network file has the wrong size.");
+        }
+
+        let mut values = bytes[HEADER_SIZE..]
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut network = Network::zeroed();
+        for feature in network.feature_weights.iter_mut() {
+            feature.fill_with(|| values.next().unwrap());
+        }
+        network.feature_bias.fill_with(|| values.next().unwrap());
+        for side in network.output_weights.iter_mut() {
+            side.fill_with(|| values.next().unwrap());
+        }
+        network.output_bias = values.next().unwrap();
+        Ok(network)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FILE_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(HIDDEN as u32).to_le_bytes());
+        let values = self
+            .feature_weights
+            .iter()
+            .flatten()
+            .chain(self.feature_bias.iter())
+            .chain(self.output_weights.iter().flatten())
+            .chain(std::iter::once(&self.output_bias));
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(&bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    fn zeroed() -> Self {
+        Network {
+            feature_weights: boxarray(0),
+            feature_bias: [0; HIDDEN],
+            output_weights: [[0; HIDDEN]; 2],
+            output_bias: 0,
+        }
+    }
+
+    /// Compute both perspectives' accumulators from scratch.
+    pub fn refresh(&self, state: &State) -> Accumulator {
+        let mut accumulator = Accumulator([self.feature_bias; 2]);
+        for color in Color::as_array() {
+            for piece in PieceType::as_array() {
+                let mut board = state.boards[color][piece];
+                while let Some(square) = board.pop_first_square() {
+                    accumulator.add(self, color, piece, square);
+                }
+            }
+        }
+        accumulator
+    }
+
+    /// Evaluate an accumulator in centipawns from the point of view of `side_to_move`.
+    pub fn evaluate(&self, accumulator: &Accumulator, side_to_move: Color) -> i32 {
+        let us = &accumulator.0[side_to_move as usize];
+        let them = &accumulator.0[(!side_to_move) as usize];
+
+        #[cfg(target_arch = "x86_64")]
+        let (ours, theirs) = if is_x86_feature_detected!("avx2") {
+            // SAFETY: avx2 support was checked just above
+            unsafe {
+                (
+                    simd::crelu_dot(us, &self.output_weights[0]),
+                    simd::crelu_dot(them, &self.output_weights[1]),
+                )
+            }
+        } else {
+            (
+                scalar_crelu_dot(us, &self.output_weights[0]),
+                scalar_crelu_dot(them, &self.output_weights[1]),
+            )
+        };
+        #[cfg(not(target_arch = "x86_64"))]
+        let (ours, theirs) = (
+            scalar_crelu_dot(us, &self.output_weights[0]),
+            scalar_crelu_dot(them, &self.output_weights[1]),
+        );
+
+        // Each dot product fits an i32 but their sum and its scaling may not
+        let sum = ours as i64 + theirs as i64 + self.output_bias as i64 * QA as i64;
+        let score = sum * SCALE as i64 / (QA * QB) as i64;
+        score.clamp(-MAX_EVAL as i64, MAX_EVAL as i64) as i32
+    }
+}
+
+/// Sum of clipped ReLU of the accumulator times the output weights.
+fn scalar_crelu_dot(accumulator: &[i16; HIDDEN], weights: &[i16; HIDDEN]) -> i32 {
+    accumulator
+        .iter()
+        .zip(weights)
+        .map(|(a, w)| (*a as i32).clamp(0, QA) * *w as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    use super::{HIDDEN, QA};
+
+    /// AVX2 version of `scalar_crelu_dot`, 16 values at a time.
+    ///
+    /// # Safety
+    /// The CPU must support avx2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn crelu_dot(accumulator: &[i16; HIDDEN], weights: &[i16; HIDDEN]) -> i32 {
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(QA as i16);
+        let mut sum = _mm256_setzero_si256();
+        for i in (0..HIDDEN).step_by(16) {
+            // SAFETY: i + 16 <= HIDDEN so both loads are in bounds
+            let (a, w) = unsafe {
+                (
+                    _mm256_loadu_si256(accumulator.as_ptr().add(i) as *const __m256i),
+                    _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i),
+                )
+            };
+            let clipped = _mm256_min_epi16(_mm256_max_epi16(a, zero), max);
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+        }
+        let mut lanes = [0_i32; 8];
+        // SAFETY: lanes is 32 bytes long
+        unsafe { _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum) };
+        lanes.iter().sum()
+    }
+}
+
+/// Hidden layer values for the white and black perspectives.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Accumulator([[i16; HIDDEN]; 2]);
+
+impl Accumulator {
+    fn add(&mut self, network: &Network, color: Color, piece: PieceType, square: Square) {
+        for perspective in Color::as_array() {
+            let weights =
+                &network.feature_weights[feature_index(perspective, color, piece, square)];
+            for (value, weight) in self.0[perspective as usize].iter_mut().zip(weights) {
+                *value = value.wrapping_add(*weight);
+            }
+        }
+    }
+
+    fn remove(&mut self, network: &Network, color: Color, piece: PieceType, square: Square) {
+        for perspective in Color::as_array() {
+            let weights =
+                &network.feature_weights[feature_index(perspective, color, piece, square)];
+            for (value, weight) in self.0[perspective as usize].iter_mut().zip(weights) {
+                *value = value.wrapping_sub(*weight);
+            }
+        }
+    }
+
+    /// Apply the feature changes of `r#move` made from `state`.
+    fn apply_move(&mut self, network: &Network, state: &State, r#move: Move) {
+        let color = state.flags.active_color();
+        let Some((_, moved_piece)) = state.piece_at(r#move.from()) else {
+            return;
+        };
+
+        if r#move.code() == MoveCode::EnPassant {
+            let captured = SquareFinder(color).en_passant_capture(r#move.to().file());
+            self.remove(network, !color, PieceType::Pawn, captured);
+        } else if r#move.code().is_capture()
+            && let Some((_, captured_piece)) = state.piece_at(r#move.to())
+        {
+            self.remove(network, !color, captured_piece, r#move.to());
+        }
+
+        self.remove(network, color, moved_piece, r#move.from());
+        let placed_piece = r#move.code().as_promotion().unwrap_or(moved_piece);
+        self.add(network, color, placed_piece, r#move.to());
+
+        if let Some(side) = r#move.code().as_castle() {
+            let finder = SquareFinder(color);
+            self.remove(
+                network,
+                color,
+                PieceType::Rook,
+                finder.castle_rook_source(side),
+            );
+            self.add(
+                network,
+                color,
+                PieceType::Rook,
+                finder.castle_rook_target(side),
+            );
+        }
+    }
+}
+
+/// Neural network evaluation with a stack of incrementally updated accumulators.
+pub struct NnueEval {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+}
+
+impl NnueEval {
+    pub fn new(network: Arc<Network>) -> Self {
+        NnueEval {
+            network,
+            stack: Vec::with_capacity(128),
+        }
+    }
+}
+
+impl Evaluator for NnueEval {
+    fn init(&mut self, state: &State) {
+        self.stack.clear();
+        self.stack.push(self.network.refresh(state));
+    }
+
+    fn make(&mut self, state: &State, r#move: Move) {
+        let mut accumulator = match self.stack.last() {
+            Some(accumulator) => accumulator.clone(),
+            None => self.network.refresh(state),
+        };
+        accumulator.apply_move(&self.network, state, r#move);
+        self.stack.push(accumulator);
+    }
+
+    fn unmake(&mut self, _state: &State, _move: Move) {
+        self.stack.pop();
+    }
+
+    fn evaluate(&mut self, state: &State, _pawn_key: u64) -> i32 {
+        let side_to_move = state.flags.active_color();
+        match self.stack.last() {
+            Some(accumulator) => self.network.evaluate(accumulator, side_to_move),
+            None => self
+                .network
+                .evaluate(&self.network.refresh(state), side_to_move),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::{
+        hash::{NoopHasher, zobrist::ZobristHasher},
+        r#move::MoveList,
+        position::Position,
+    };
+
+    use super::*;
+    use crate::alpha_beta::search::SearchContext;
+
+    /// Deterministic small weights from a xorshift generator.
+    fn test_network() -> Network {
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % 129) as i16 - 64
+        };
+        let mut network = Network::zeroed();
+        for feature in network.feature_weights.iter_mut() {
+            feature.fill_with(&mut next);
+        }
+        network.feature_bias.fill_with(&mut next);
+        for side in network.output_weights.iter_mut() {
+            side.fill_with(&mut next);
+        }
+        network.output_bias = next();
+        network
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let network = test_network();
+        let bytes = network.to_bytes();
+        assert_eq!(bytes.len(), FILE_SIZE);
+        let loaded = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(Network::from_bytes(&bad_magic).is_err());
+        assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut bad_size = bytes.clone();
+        bad_size[8] = 1;
+        assert!(Network::from_bytes(&bad_size).is_err());
+    }
+
+    #[test]
+    fn test_feature_index() {
+        let e2 = Square::try_from("e2").unwrap();
+        let e7 = Square::try_from("e7").unwrap();
+        // A white pawn on e2 looks like a black pawn on e7 from the other side
+        assert_eq!(
+            feature_index(Color::White, Color::White, PieceType::Pawn, e2),
+            feature_index(Color::Black, Color::Black, PieceType::Pawn, e7)
+        );
+        assert!(feature_index(Color::Black, Color::White, PieceType::King, e7) < FEATURES);
+    }
+
+    #[test]
+    fn test_symmetric_evaluation() {
+        let network = test_network();
+        for (fen, mirrored) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+                "rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            ),
+            (
+                "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+                "4k3/4p3/8/8/8/8/8/4K3 b - - 0 1",
+            ),
+        ] {
+            let state = State::from_fen(fen);
+            let mirrored = State::from_fen(mirrored);
+            assert_eq!(
+                network.evaluate(&network.refresh(&state), state.flags.active_color()),
+                network.evaluate(&network.refresh(&mirrored), mirrored.flags.active_color())
+            );
+        }
+    }
+
+    #[test]
+    fn test_incremental_matches_refresh() {
+        let network = Arc::new(test_network());
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let mut position = Position::from_fen(fen, NoopHasher {});
+            let mut eval = NnueEval::new(network.clone());
+            eval.init(position.state.get());
+            walk(&mut position, &mut eval, &mut MoveList::new(), 2);
+            assert_eq!(eval.stack.len(), 1);
+        }
+    }
+
+    fn walk(
+        position: &mut Position<NoopHasher>,
+        eval: &mut NnueEval,
+        move_list: &mut MoveList,
+        depth: u8,
+    ) {
+        if depth == 0 {
+            return;
+        }
+        move_list.new_ply();
+        position.pseudo_legal_moves(move_list);
+        let ply_number = move_list.ply_number();
+        for i in 0..move_list.ply_size(ply_number) {
+            let m = move_list.r#move(ply_number, i);
+            eval.make(position.state.get(), m);
+            position.make(m);
+            if position.was_move_legal() {
+                assert_eq!(
+                    *eval.stack.last().unwrap(),
+                    eval.network.refresh(position.state.get()),
+                    "Move: {}",
+                    m
+                );
+                walk(position, eval, move_list, depth - 1);
+            }
+            position.unmake(m);
+            eval.unmake(position.state.get(), m);
+        }
+        move_list.drop_current_ply();
+    }
+
+    #[test]
+    fn test_search_with_nnue() {
+        let position = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            ZobristHasher::new(),
+        );
+        let eval = NnueEval::new(Arc::new(test_network()));
+        let mut search_ctx = SearchContext::new(position, eval, Some(2));
+        let (_, pv) = search_ctx.search(Vec::new());
+        assert!(!pv.is_empty());
+        assert_eq!(search_ctx.evaluator.stack.len(), 1);
+    }
+
+    #[test]
+    fn test_extreme_weights() {
+        let mut network = Network::zeroed();
+        let saturated = Accumulator([[i16::MAX; HIDDEN]; 2]);
+        network.output_weights = [[i16::MAX; HIDDEN]; 2];
+        network.output_bias = i16::MAX;
+        assert_eq!(network.evaluate(&saturated, Color::White), MAX_EVAL);
+        network.output_weights = [[i16::MIN; HIDDEN]; 2];
+        network.output_bias = i16::MIN;
+        assert_eq!(network.evaluate(&saturated, Color::Black), -MAX_EVAL);
+        // Small outputs are left alone
+        network.output_weights = [[0; HIDDEN]; 2];
+        network.output_bias = 1;
+        assert_eq!(network.evaluate(&saturated, Color::White), SCALE / QB);
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let network = test_network();
+        let state =
+            State::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let accumulator = network.refresh(&state);
+        let scalar = scalar_crelu_dot(&accumulator.0[0], &network.output_weights[0]);
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: avx2 support was checked just above
+            let simd = unsafe { simd::crelu_dot(&accumulator.0[0], &network.output_weights[0]) };
+            assert_eq!(simd, scalar);
+        }
+        assert_ne!(scalar, 0);
+    }
+}
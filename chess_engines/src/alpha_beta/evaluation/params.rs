@@ -0,0 +1,247 @@
+// Every weight of the hand-written evaluation in one place, so they can be
+// read and written as a flat vector by the tuner and saved to or loaded from
+// a plain text config file with one `name = value` line per parameter.
+
+use super::{king_safety, pawn_structure};
+
+/// Weights used by `SimpleEval`, defaults to the hand picked values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    /// Material value indexed by piece type, king excluded.
+    pub piece_values: [i32; 5],
+    pub mobility: i32,
+    pub doubled_pawn: i32,
+    pub isolated_pawn: i32,
+    pub backward_pawn: i32,
+    pub chain_pawn: i32,
+    pub phalanx_pawn: i32,
+    pub candidate_pawn: i32,
+    pub knight_outpost: i32,
+    pub bishop_outpost: i32,
+    pub passed_pawn_bonus: [i32; 8],
+    pub king_attacker_weight: [i32; 6],
+    pub king_attacker_weight_eg: [i32; 6],
+    pub pawn_shelter_bonus: [i32; 8],
+    pub pawn_storm_penalty: [i32; 8],
+    pub safe_check_penalty: [i32; 6],
+    pub semi_open_file_penalty: i32,
+    pub open_file_penalty: i32,
+}
+
+pub const MOBILITY_COEF: i32 = 5;
+pub const PIECE_VALUES: [i32; 5] = [100, 300, 300, 500, 900];
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            piece_values: PIECE_VALUES,
+            mobility: MOBILITY_COEF,
+            doubled_pawn: pawn_structure::DOUBLED_PAWN_COEF,
+            isolated_pawn: pawn_structure::ISOLATED_PAWN_COEF,
+            backward_pawn: pawn_structure::BACKWARD_PAWN_COEF,
+            chain_pawn: pawn_structure::CHAIN_PAWN_COEF,
+            phalanx_pawn: pawn_structure::PHALANX_PAWN_COEF,
+            candidate_pawn: pawn_structure::CANDIDATE_PAWN_COEF,
+            knight_outpost: pawn_structure::KNIGHT_OUTPOST_COEF,
+            bishop_outpost: pawn_structure::BISHOP_OUTPOST_COEF,
+            passed_pawn_bonus: pawn_structure::PASSED_PAWN_BONUS,
+            king_attacker_weight: king_safety::KING_ATTACKER_WEIGHT,
+            king_attacker_weight_eg: king_safety::KING_ATTACKER_WEIGHT_EG,
+            pawn_shelter_bonus: king_safety::PAWN_SHELTER_BONUS,
+            pawn_storm_penalty: king_safety::PAWN_STORM_PENALTY,
+            safe_check_penalty: king_safety::SAFE_CHECK_PENALTY,
+            semi_open_file_penalty: king_safety::SEMI_OPEN_FILE_PENALTY,
+            open_file_penalty: king_safety::OPEN_FILE_PENALTY,
+        }
+    }
+}
+
+impl EvalParams {
+    /// Named groups of parameters, in vector order.
+    fn groups(&mut self) -> [(&'static str, &mut [i32]); 18] {
+        [
+            ("piece_values", &mut self.piece_values),
+            ("mobility", std::slice::from_mut(&mut self.mobility)),
+            ("doubled_pawn", std::slice::from_mut(&mut self.doubled_pawn)),
+            (
+                "isolated_pawn",
+                std::slice::from_mut(&mut self.isolated_pawn),
+            ),
+            (
+                "backward_pawn",
+                std::slice::from_mut(&mut self.backward_pawn),
+            ),
+            ("chain_pawn", std::slice::from_mut(&mut self.chain_pawn)),
+            ("phalanx_pawn", std::slice::from_mut(&mut self.phalanx_pawn)),
+            (
+                "candidate_pawn",
+                std::slice::from_mut(&mut self.candidate_pawn),
+            ),
+            (
+                "knight_outpost",
+                std::slice::from_mut(&mut self.knight_outpost),
+            ),
+            (
+                "bishop_outpost",
+                std::slice::from_mut(&mut self.bishop_outpost),
+            ),
+            ("passed_pawn_bonus", &mut self.passed_pawn_bonus),
+            ("king_attacker_weight", &mut self.king_attacker_weight),
+            ("king_attacker_weight_eg", &mut self.king_attacker_weight_eg),
+            ("pawn_shelter_bonus", &mut self.pawn_shelter_bonus),
+            ("pawn_storm_penalty", &mut self.pawn_storm_penalty),
+            ("safe_check_penalty", &mut self.safe_check_penalty),
+            (
+                "semi_open_file_penalty",
+                std::slice::from_mut(&mut self.semi_open_file_penalty),
+            ),
+            (
+                "open_file_penalty",
+                std::slice::from_mut(&mut self.open_file_penalty),
+            ),
+        ]
+    }
+
+    /// All parameters as a flat vector.
+    pub fn to_vec(&self) -> Vec<i32> {
+        self.clone()
+            .groups()
+            .into_iter()
+            .flat_map(|(_, values)| values.to_vec())
+            .collect()
+    }
+
+    /// Parameters from a flat vector in the order of `to_vec`.
+    pub fn from_slice(values: &[i32]) -> Result<Self, &'static str> {
+        let mut params = EvalParams::default();
+        if values.len() != params.to_vec().len() {
+            return Err("Wrong number of parameters");
+        }
+        let mut values = values.iter();
+        for (_, group) in params.groups() {
+            for value in group.iter_mut() {
+                *value = *values.next().unwrap();
+            }
+        }
+        Ok(params)
+    }
+
+    /// Name of each entry of the vector, array entries get their index appended.
+    pub fn names() -> Vec<String> {
+        EvalParams::default()
+            .groups()
+            .into_iter()
+            .flat_map(|(name, values)| {
+                let len = values.len();
+                (0..len).map(move |i| {
+                    if len == 1 {
+                        name.to_string()
+                    } else {
+                        format!("{}[{}]", name, i)
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Config file with one `name = value` line per parameter group.
+    pub fn to_config(&self) -> String {
+        let mut config = String::new();
+        for (name, values) in self.clone().groups() {
+            let values = values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            config.push_str(&format!("{} = {}\n", name, values));
+        }
+        config
+    }
+
+    /// Parse a config written by `to_config`. Missing groups keep their default
+    /// value, empty lines and lines starting with `#` are ignored.
+    pub fn from_config(config: &str) -> Result<Self, &'static str> {
+        let mut params = EvalParams::default();
+        for line in config.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, values) = line.split_once('=').ok_or("Missing '=' in config line")?;
+            let values = values
+                .split(',')
+                .map(|v| v.trim().parse::<i32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| "Invalid parameter value")?;
+            let mut groups = params.groups();
+            let (_, group) = groups
+                .iter_mut()
+                .find(|(n, _)| *n == name.trim())
+                .ok_or("Unknown parameter")?;
+            if group.len() != values.len() {
+                return Err("Wrong number of values for parameter");
+            }
+            group.copy_from_slice(&values);
+        }
+        Ok(params)
+    }
+
+    /// Rust source defining the parameters as a constant named `name`.
+    pub fn to_rust(&self, name: &str) -> String {
+        format!("pub const {}: EvalParams = {:#?};\n", name, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_round_trip() {
+        let params = EvalParams::default();
+        let mut values = params.to_vec();
+        assert_eq!(values.len(), EvalParams::names().len());
+        assert_eq!(values[0], PIECE_VALUES[0]);
+        assert_eq!(EvalParams::from_slice(&values), Ok(params));
+
+        values[5] += 1;
+        assert_eq!(
+            EvalParams::from_slice(&values).unwrap().mobility,
+            MOBILITY_COEF + 1
+        );
+        assert!(EvalParams::from_slice(&values[1..]).is_err());
+    }
+
+    #[test]
+    fn test_names() {
+        let names = EvalParams::names();
+        assert_eq!(names[0], "piece_values[0]");
+        assert_eq!(names[5], "mobility");
+        assert_eq!(names.last().unwrap(), "open_file_penalty");
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut params = EvalParams::default();
+        params.piece_values[1] = 320;
+        params.open_file_penalty = -3;
+        let config = params.to_config();
+        assert!(config.contains("piece_values = 100, 320, 300, 500, 900\n"));
+        assert_eq!(EvalParams::from_config(&config), Ok(params));
+
+        let partial = EvalParams::from_config("# tuned\n\nmobility = 7\n").unwrap();
+        assert_eq!(partial.mobility, 7);
+        assert_eq!(partial.piece_values, PIECE_VALUES);
+
+        assert!(EvalParams::from_config("mobility = 1, 2").is_err());
+        assert!(EvalParams::from_config("unknown = 1").is_err());
+        assert!(EvalParams::from_config("mobility").is_err());
+        assert!(EvalParams::from_config("mobility = x").is_err());
+    }
+
+    #[test]
+    fn test_to_rust() {
+        let source = EvalParams::default().to_rust("TUNED");
+        assert!(source.starts_with("pub const TUNED: EvalParams = EvalParams {"));
+        assert!(source.contains("mobility: 5,"));
+    }
+}
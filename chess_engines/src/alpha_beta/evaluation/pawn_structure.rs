@@ -0,0 +1,428 @@
+// Pawn structure only changes on pawn moves and captures, so the pawn-only
+// part of the evaluation is cached in a small table keyed by the pawn hash.
+// Terms that also depend on other pieces (blockaded passed pawns, occupied
+// outposts) are computed from the cached bitboards on every evaluation.
+
+use boxarray::boxarray;
+
+use chess_core::{
+    color::Color,
+    square::Square,
+    state::{State, bitboard::BitBoard},
+};
+
+use super::params::EvalParams;
+
+pub const DOUBLED_PAWN_COEF: i32 = 40;
+pub const ISOLATED_PAWN_COEF: i32 = 40;
+pub const BACKWARD_PAWN_COEF: i32 = 20;
+pub const CHAIN_PAWN_COEF: i32 = 10;
+pub const PHALANX_PAWN_COEF: i32 = 8;
+pub const CANDIDATE_PAWN_COEF: i32 = 15;
+pub const KNIGHT_OUTPOST_COEF: i32 = 25;
+pub const BISHOP_OUTPOST_COEF: i32 = 15;
+
+/// Passed pawn bonus indexed by the rank relative to the pawn's color.
+pub const PASSED_PAWN_BONUS: [i32; 8] = [0, 10, 15, 25, 45, 75, 120, 0];
+
+const TABLE_SIZE: usize = 1 << 14;
+
+/// Shift a board one rank towards the opponent of `color`.
+fn forward(color: Color, board: BitBoard) -> BitBoard {
+    match color {
+        Color::White => board << 8,
+        Color::Black => board >> 8,
+    }
+}
+
+/// Shift a board one rank towards `color`'s own side.
+fn backward(color: Color, board: BitBoard) -> BitBoard {
+    forward(!color, board)
+}
+
+/// Every square in front of the set squares, from `color`'s point of view, included.
+fn front_fill(color: Color, board: BitBoard) -> BitBoard {
+    match color {
+        Color::White => board.north_fill(),
+        Color::Black => board.south_fill(),
+    }
+}
+
+/// Every square strictly in front of the set squares, from `color`'s point of view.
+fn front_span(color: Color, board: BitBoard) -> BitBoard {
+    forward(color, front_fill(color, board))
+}
+
+/// Squares attacked by `pawns` of `color`.
+pub fn pawn_attacks(color: Color, pawns: BitBoard) -> BitBoard {
+    forward(color, pawns.east() | pawns.west())
+}
+
+fn relative_rank(color: Color, square: Square) -> u8 {
+    match color {
+        Color::White => square.rank(),
+        Color::Black => 7 - square.rank(),
+    }
+}
+
+/// Pawn-only evaluation of one position, stored in the pawn table.
+#[derive(Clone, Copy)]
+pub struct PawnEntry {
+    pub key: u64,
+    /// Score of the pawn-only terms, from white's point of view.
+    pub score: i32,
+    pub passed: [BitBoard; 2],
+    pub outposts: [BitBoard; 2],
+}
+
+impl PawnEntry {
+    pub fn new(key: u64, state: &State, params: &EvalParams) -> Self {
+        let mut entry = PawnEntry {
+            key,
+            score: 0,
+            passed: [BitBoard::EMPTY; 2],
+            outposts: [BitBoard::EMPTY; 2],
+        };
+        for color in Color::as_array() {
+            let terms = PawnTerms::new(state, color);
+            let sign = match color {
+                Color::White => 1,
+                Color::Black => -1,
+            };
+            entry.score += sign * terms.score(params);
+            entry.passed[color as usize] = terms.passed;
+            entry.outposts[color as usize] = terms.outposts;
+        }
+        entry
+    }
+
+    /// Full pawn structure score from the point of view of the side to move.
+    pub fn score(&self, state: &State, params: &EvalParams) -> i32 {
+        let mut score = self.score;
+        for color in Color::as_array() {
+            let sign = match color {
+                Color::White => 1,
+                Color::Black => -1,
+            };
+            let enemy_occupation = state.boards[!color].union();
+
+            // Blockaded passed pawns are worth half as much
+            let mut passed = self.passed[color as usize];
+            while let Some(square) = passed.pop_first_square() {
+                if !(forward(color, square.into()) & enemy_occupation).is_empty() {
+                    score -=
+                        sign * params.passed_pawn_bonus[relative_rank(color, square) as usize] / 2;
+                }
+            }
+
+            let outposts = self.outposts[color as usize];
+            let pieces = &state.boards[color];
+            score += sign
+                * (params.knight_outpost * (outposts & pieces.knight).count_ones() as i32
+                    + params.bishop_outpost * (outposts & pieces.bishop).count_ones() as i32);
+        }
+        match state.flags.active_color() {
+            Color::White => score,
+            Color::Black => -score,
+        }
+    }
+}
+
+/// Pawn structure features of one side.
+struct PawnTerms {
+    color: Color,
+    doubled: i32,
+    isolated: i32,
+    backward: i32,
+    chain: i32,
+    phalanx: i32,
+    candidate: i32,
+    passed: BitBoard,
+    outposts: BitBoard,
+}
+
+impl PawnTerms {
+    fn new(state: &State, color: Color) -> Self {
+        let ours = state.boards[color].pawn;
+        let theirs = state.boards[!color].pawn;
+        let our_attacks = pawn_attacks(color, ours);
+        let their_attacks = pawn_attacks(!color, theirs);
+
+        // Pawns with a friendly pawn behind them on the same file
+        let doubled = ours & front_span(color, ours);
+
+        // Doubled isolated pawns are only counted once per file
+        let isolated = ours & !(ours.file_fill().east() | ours.file_fill().west());
+        let isolated_files = (isolated.file_fill() & BitBoard::rank(0)).count_ones();
+
+        let their_spans = front_span(!color, theirs);
+        let passed = ours & !(their_spans | their_spans.east() | their_spans.west());
+
+        // Pawns whose stop square is controlled by an enemy pawn
+        // and that can never be defended by a friendly pawn
+        let backward_pawns = backward(
+            color,
+            forward(color, ours) & their_attacks & !front_fill(color, our_attacks),
+        ) & !isolated;
+
+        let chain = ours & our_attacks;
+        let phalanx = ours & (ours.east() | ours.west());
+
+        // Pawns on a half-open file with at least as many helpers as sentries
+        let mut candidate = 0;
+        let mut candidates = ours & !passed;
+        while let Some(square) = candidates.pop_first_square() {
+            let pawn = BitBoard::from(square);
+            if !(front_span(color, pawn) & theirs).is_empty() {
+                continue;
+            }
+            let adjacent_files = (pawn.east() | pawn.west()).file_fill();
+            let rank = BitBoard::rank(square.rank());
+            let helpers = ours & adjacent_files & front_fill(!color, rank);
+            let sentries = theirs & adjacent_files & front_span(color, rank);
+            if helpers.count_ones() >= sentries.count_ones() {
+                candidate += 1;
+            }
+        }
+
+        // Squares on the 4th to 6th rank defended by a pawn
+        // that no enemy pawn will ever be able to attack
+        let outpost_ranks = match color {
+            Color::White => BitBoard::rank(3) | BitBoard::rank(4) | BitBoard::rank(5),
+            Color::Black => BitBoard::rank(2) | BitBoard::rank(3) | BitBoard::rank(4),
+        };
+        let outposts = outpost_ranks & our_attacks & !theirs & !front_fill(!color, their_attacks);
+
+        PawnTerms {
+            color,
+            doubled: doubled.count_ones() as i32,
+            isolated: isolated_files as i32,
+            backward: backward_pawns.count_ones() as i32,
+            chain: chain.count_ones() as i32,
+            phalanx: phalanx.count_ones() as i32,
+            candidate,
+            passed,
+            outposts,
+        }
+    }
+
+    fn passed_bonus(&self, params: &EvalParams) -> i32 {
+        let mut bonus = 0;
+        let mut passed = self.passed;
+        while let Some(square) = passed.pop_first_square() {
+            bonus += params.passed_pawn_bonus[relative_rank(self.color, square) as usize];
+        }
+        bonus
+    }
+
+    fn score(&self, params: &EvalParams) -> i32 {
+        -params.doubled_pawn * self.doubled
+            - params.isolated_pawn * self.isolated
+            - params.backward_pawn * self.backward
+            + params.chain_pawn * self.chain
+            + params.phalanx_pawn * self.phalanx
+            + params.candidate_pawn * self.candidate
+            + self.passed_bonus(params)
+    }
+}
+
+/// Fixed size cache of pawn evaluations, addressed with pawn hash % size.
+pub struct PawnTable {
+    table: Box<[Option<PawnEntry>; TABLE_SIZE]>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        PawnTable {
+            table: boxarray(None),
+        }
+    }
+
+    /// Get the entry for the pawn hash, computing and storing it on a miss.
+    /// Entries are only valid for the parameters they were computed with.
+    pub fn probe(&mut self, key: u64, state: &State, params: &EvalParams) -> PawnEntry {
+        let index = key as usize % TABLE_SIZE;
+        match self.table[index] {
+            Some(entry) if entry.key == key => entry,
+            _ => {
+                let entry = PawnEntry::new(key, state, params);
+                self.table[index] = Some(entry);
+                entry
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(fen: &str, color: Color) -> PawnTerms {
+        PawnTerms::new(&State::from_fen(fen), color)
+    }
+
+    fn squares(names: &[&str]) -> BitBoard {
+        names.iter().fold(BitBoard::EMPTY, |bb, name| {
+            bb | Square::try_from(*name).unwrap().into()
+        })
+    }
+
+    #[test]
+    fn test_doubled_pawns() {
+        for (fen, color, result) in [
+            // starting position
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                Color::White,
+                0,
+            ),
+            // white has doubled pawns
+            (
+                "rnbqkbnr/pppppppp/8/8/8/1P6/1PPPPPPP/RNBQKBNR w KQkq - 0 1",
+                Color::White,
+                1,
+            ),
+            // black has doubled pawns
+            (
+                "rnbqkbnr/1ppppppp/1p6/8/8/8/PPPPPPPP/RNBQKB1R w KQkq - 0 1",
+                Color::Black,
+                1,
+            ),
+            // white has tripled pawns
+            (
+                "rnbqkbnr/pppppppp/8/8/2P5/2P5/11PPPPPP/RNBQKBNR b KQkq - 0 1",
+                Color::White,
+                2,
+            ),
+        ] {
+            assert_eq!(terms(fen, color).doubled, result, "FEN: {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_isolated_pawns() {
+        for (fen, result) in [
+            // starting position
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                0,
+            ),
+            // isolated pawn on the side
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/P1PPPPPP/RNBQKBNR w KQkq - 0 1",
+                1,
+            ),
+            // isolated pawn in the middle
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/1P1PPPPP/RNBQKBNR b KQkq - 0 1",
+                1,
+            ),
+            // doubled isolated pawns count once
+            ("4k3/8/8/8/8/P7/P7/4K3 w - - 0 1", 1),
+        ] {
+            assert_eq!(terms(fen, Color::White).isolated, result, "FEN: {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        let fen = "4k3/8/8/1P6/p7/8/3P3P/4K3 w - - 0 1";
+        assert_eq!(
+            terms(fen, Color::White).passed,
+            squares(&["b5", "d2", "h2"])
+        );
+        assert_eq!(
+            terms(fen, Color::White).passed_bonus(&EvalParams::default()),
+            PASSED_PAWN_BONUS[4] + 2 * PASSED_PAWN_BONUS[1]
+        );
+        assert_eq!(terms(fen, Color::Black).passed, squares(&["a4"]));
+        // e4 can be captured by d2 on its way
+        let fen = "4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::Black).passed, BitBoard::EMPTY);
+    }
+
+    #[test]
+    fn test_backward_pawns() {
+        // d2 can't advance safely and c3/e3 are ahead of it
+        let fen = "4k3/8/8/8/2p5/2P1P3/3P4/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).backward, 1);
+        // c3 is behind b4 and d4, c4 is controlled by b5
+        let fen = "4k3/8/8/1p6/1P1P4/2P5/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).backward, 1);
+        // d3 can be defended by c2 or e2 advancing
+        let fen = "4k3/8/8/4p3/8/3P4/2P1P3/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).backward, 0);
+        // Stop squares are not controlled
+        let fen = "4k3/8/8/8/4p3/3P1P2/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).backward, 0);
+    }
+
+    #[test]
+    fn test_chains_and_phalanxes() {
+        let fen = "4k3/8/8/8/3PP3/2P5/1P6/4K3 w - - 0 1";
+        let terms = terms(fen, Color::White);
+        assert_eq!(terms.chain, 2);
+        assert_eq!(terms.phalanx, 2);
+    }
+
+    #[test]
+    fn test_candidate_pawns() {
+        // c4 has one sentry (b6) and one helper (b3)
+        let fen = "4k3/8/1p6/8/2P5/1P6/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).candidate, 1);
+        // Two sentries for one helper
+        let fen = "4k3/8/1p1p4/8/2P5/1P6/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).candidate, 0);
+    }
+
+    #[test]
+    fn test_outposts() {
+        let fen = "4k3/p7/8/8/3P4/8/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).outposts, squares(&["c5", "e5"]));
+        // b7 can still chase a piece from c5
+        let fen = "4k3/1p6/8/8/3P4/8/8/4K3 w - - 0 1";
+        assert_eq!(terms(fen, Color::White).outposts, squares(&["e5"]));
+    }
+
+    #[test]
+    fn test_blockade_and_outpost_occupation() {
+        let params = &EvalParams::default();
+        let free = State::from_fen("4k3/8/8/3N4/1P6/8/8/4K3 w - - 0 1");
+        let blocked = State::from_fen("4k3/8/8/1n6/1P6/8/8/4K3 w - - 0 1");
+        let free_entry = PawnEntry::new(0, &free, params);
+        let blocked_entry = PawnEntry::new(0, &blocked, params);
+        assert_eq!(free_entry.score, blocked_entry.score);
+        assert_eq!(
+            free_entry.score(&free, params) - blocked_entry.score(&blocked, params),
+            PASSED_PAWN_BONUS[3] / 2
+        );
+
+        let outpost = State::from_fen("4k3/8/8/2N5/1P6/8/8/4K3 w - - 0 1");
+        let entry = PawnEntry::new(0, &outpost, params);
+        assert_eq!(
+            entry.score(&outpost, params),
+            entry.score + KNIGHT_OUTPOST_COEF
+        );
+    }
+
+    #[test]
+    fn test_pawn_table() {
+        let params = &EvalParams::default();
+        let state = State::default();
+        let mut table = PawnTable::new();
+        let entry = table.probe(42, &state, params);
+        assert_eq!(entry.key, 42);
+        assert_eq!(entry.score(&state, params), 0);
+
+        // A hit returns the cached entry even if the position is different
+        let other = State::from_fen("4k3/8/8/8/8/8/P7/4K3 w - - 0 1");
+        assert_eq!(table.probe(42, &other, params).score, entry.score);
+        assert_ne!(table.probe(43, &other, params).score, entry.score);
+    }
+}
@@ -1,3 +1,5 @@
-mod evaluation;
+pub mod analysis;
+pub mod evaluation;
 pub mod search;
+pub mod strength;
 mod transposition_table;
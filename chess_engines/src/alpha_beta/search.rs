@@ -1,35 +1,102 @@
-use chrono::{Duration, Local};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Local};
 
 use chess_core::{
-    r#move::{Move, MoveGenerator, MoveList},
-    state::{game_state::GameState, make_unmake::MakeUnmaker},
+    hash::zobrist::ZobristHasher,
+    r#move::{Move, MoveList},
+    position::Position,
+};
+
+use super::{
+    evaluation::Evaluator,
+    transposition_table::{TranspositionTable, TtEntry},
+};
+use crate::{
+    book::{BookSelection, PolyglotBook},
+    tablebase::{Tablebase, Wdl, is_probeable},
 };
 
-use super::transposition_table::{TranspositionTable, TtEntry};
+/// Score of the side to move when it is checkmated, negated.
+pub const MATE_SCORE: i32 = 100000;
+/// Score of a tablebase win, above any evaluation but below checkmate.
+pub const TB_WIN_SCORE: i32 = 90000;
+/// Nodes between two checks of the deadline.
+const DEADLINE_INTERVAL: u64 = 1024;
 
-pub struct SearchContext<'a> {
-    pub make_unmaker: MakeUnmaker<'a>,
-    pub move_generator: MoveGenerator,
+pub struct SearchContext<E: Evaluator> {
+    pub position: Position<ZobristHasher>,
+    pub evaluator: E,
     pub move_list: MoveList,
     pub transpos: TranspositionTable,
     pub max_depth: u8,
+    pub tablebase: Option<Arc<dyn Tablebase + Send + Sync>>,
+    pub book: Option<(Arc<PolyglotBook>, BookSelection)>,
+    /// Nodes visited by the alpha-beta and quiescence searches so far.
+    pub nodes: u64,
+    /// The search is abandoned once `nodes` reaches this limit.
+    pub node_limit: Option<u64>,
+    /// The search is abandoned once this instant has passed.
+    pub deadline: Option<DateTime<Local>>,
+    /// Whether the last search hit a limit, its result is then meaningless.
+    aborted: bool,
+    /// Root moves allowed by the tablebase, empty when the root is not covered.
+    root_moves: Vec<Move>,
 }
 
-impl SearchContext<'_> {
+impl<E: Evaluator> SearchContext<E> {
     const MIN_SCORE: i32 = i32::MIN + 1;
     const MAX_SCORE: i32 = i32::MAX;
 
-    pub fn new(state: &mut GameState, max_depth: Option<u8>) -> SearchContext<'_> {
+    pub fn new(position: Position<ZobristHasher>, mut evaluator: E, max_depth: Option<u8>) -> Self {
+        evaluator.init(position.state.get());
         SearchContext {
-            make_unmaker: MakeUnmaker::new(state),
-            move_generator: MoveGenerator::new(),
+            position,
+            evaluator,
             move_list: MoveList::new(),
             transpos: TranspositionTable::new(),
             max_depth: max_depth.unwrap_or(1),
+            tablebase: None,
+            book: None,
+            nodes: 0,
+            node_limit: None,
+            deadline: None,
+            aborted: false,
+            root_moves: Vec::new(),
         }
     }
 
+    /// Use `tablebase` to filter root moves and cut the search in covered positions.
+    pub fn with_tablebase(mut self, tablebase: Arc<dyn Tablebase + Send + Sync>) -> Self {
+        self.tablebase = Some(tablebase);
+        self
+    }
+
+    /// Play moves from `book` without searching while the position is in it.
+    pub fn with_book(mut self, book: Arc<PolyglotBook>, selection: BookSelection) -> Self {
+        self.book = Some((book, selection));
+        self
+    }
+
+    /// Make a move on the position and notify the evaluator.
+    pub fn make(&mut self, r#move: Move) {
+        self.evaluator.make(self.position.state.get(), r#move);
+        self.position.make(r#move);
+    }
+
+    /// Unmake a move on the position and notify the evaluator.
+    pub fn unmake(&mut self, r#move: Move) {
+        self.position.unmake(r#move);
+        self.evaluator.unmake(self.position.state.get(), r#move);
+    }
+
     pub fn iterative_deepen(&mut self, max_time: Duration) -> (i32, Vec<Move>) {
+        if let Some((book, selection)) = &self.book
+            && let Some(r#move) = book.probe(&mut self.position, *selection)
+        {
+            return (0, vec![r#move]);
+        }
+
         let mut time_taken = Duration::new(0, 0).unwrap();
         let prev_depth = self.max_depth;
 
@@ -38,7 +105,15 @@ impl SearchContext<'_> {
         while time_taken < max_time {
             let start_time = Local::now();
             let prev_pv = pv.clone();
-            (score, *pv) = self.search(prev_pv);
+            let (new_score, new_pv) = self.search(prev_pv);
+            if self.aborted {
+                break;
+            }
+            (score, *pv) = (new_score, new_pv);
+            // Without legal moves deeper searches find nothing more
+            if pv.is_empty() {
+                break;
+            }
             time_taken = Local::now() - start_time;
             self.max_depth += 1;
         }
@@ -47,18 +122,46 @@ impl SearchContext<'_> {
     }
 
     pub fn search(&mut self, prev_pv: Vec<Move>) -> (i32, Vec<Move>) {
+        self.search_window(Self::MIN_SCORE, Self::MAX_SCORE, prev_pv)
+    }
+
+    /// Search with the scores outside of `alpha..beta` cut off: a score at or
+    /// below `alpha` only tells the position is not better than it.
+    pub fn search_window(&mut self, alpha: i32, beta: i32, prev_pv: Vec<Move>) -> (i32, Vec<Move>) {
         let mut prev_pv = prev_pv;
         let mut pv = Vec::new();
-        let score =
-            self.alpha_beta_search(Self::MIN_SCORE, Self::MAX_SCORE, 0, &mut pv, &mut prev_pv);
+        self.aborted = false;
+        self.root_moves = self.tablebase_root_moves();
+        let score = self.alpha_beta_search(alpha, beta, 0, &mut pv, &mut prev_pv);
         (score, pv)
     }
 
+    /// Whether the last search was abandoned at the node limit or the deadline.
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Count a node, `false` if the search has to be abandoned.
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            self.aborted = true;
+        }
+        // Reading the clock at every node would slow the search down
+        if self.nodes.is_multiple_of(DEADLINE_INTERVAL)
+            && self
+                .deadline
+                .is_some_and(|deadline| Local::now() >= deadline)
+        {
+            self.aborted = true;
+        }
+        !self.aborted
+    }
+
     /// Add pseudo legal moves to move list and returns number and size of ply
     fn add_moves_to_list(&mut self, prev_pv: &mut Vec<Move>) -> (usize, usize) {
         self.move_list.new_ply();
-        self.move_generator
-            .get_pseudo_legal_moves(self.make_unmaker.state, &mut self.move_list);
+        self.position.pseudo_legal_moves(&mut self.move_list);
         self.move_list.order_ply(prev_pv.pop());
 
         let ply_number = self.move_list.ply_number();
@@ -74,9 +177,22 @@ impl SearchContext<'_> {
         prev_pv: &mut Vec<Move>,
     ) -> i32 {
         let mut alpha = alpha;
+        if depth > 0
+            && let Some(wdl) = self.probe_wdl()
+        {
+            pv.clear();
+            return match wdl {
+                Wdl::Win => TB_WIN_SCORE - depth as i32,
+                Wdl::Loss => -TB_WIN_SCORE + depth as i32,
+                _ => 0,
+            };
+        }
         if depth == self.max_depth {
             return self.quiesce(alpha, beta, depth, pv, prev_pv);
         }
+        if !self.visit() {
+            return 0;
+        }
 
         let (ply_number, ply_size) = self.add_moves_to_list(prev_pv);
 
@@ -86,18 +202,24 @@ impl SearchContext<'_> {
 
         for i in 0..ply_size {
             let m = self.move_list.r#move(ply_number, i);
+            if depth == 0 && !self.root_moves.is_empty() && !self.root_moves.contains(&m) {
+                continue;
+            }
 
-            self.make_unmaker.make_move(m);
-            if !self.move_generator.was_move_legal(self.make_unmaker.state) {
-                self.make_unmaker.unmake_move(m);
+            self.make(m);
+            if !self.position.was_move_legal() {
+                self.unmake(m);
                 continue;
             }
             let score = -self.alpha_beta_search(-beta, -alpha, depth + 1, &mut line, prev_pv);
-            // if let Some(tt_entry) = self.transpos.get(self.make_unmaker.zobrist_hash) {
+            // if let Some(tt_entry) = self.transpos.get(self.position.state.get_hash()) {
             //     score = -tt_entry.score;
             //     line.push(tt_entry.best_move);
             // }
-            self.make_unmaker.unmake_move(m);
+            self.unmake(m);
+            if self.aborted {
+                break;
+            }
 
             if score > best_score {
                 best_score = score;
@@ -115,10 +237,13 @@ impl SearchContext<'_> {
         }
 
         self.move_list.drop_current_ply();
+        if self.aborted {
+            return 0;
+        }
 
         if let Some(best_move) = best_move {
             self.transpos.store(TtEntry {
-                hash: self.make_unmaker.zobrist_hash,
+                hash: self.position.state.get_hash(),
                 depth,
                 score: best_score,
                 best_move,
@@ -140,6 +265,9 @@ impl SearchContext<'_> {
         pv: &mut Vec<Move>,
         prev_pv: &mut Vec<Move>,
     ) -> i32 {
+        if !self.visit() {
+            return 0;
+        }
         if depth >= self.max_depth + 4 {
             pv.clear();
             return self.evaluate();
@@ -171,18 +299,21 @@ impl SearchContext<'_> {
                 continue;
             }
             // println!("{}Exploring {}", "  ".repeat(depth as usize), m.to_pretty_string());
-            self.make_unmaker.make_move(m);
-            if !self.move_generator.was_move_legal(self.make_unmaker.state) {
-                self.make_unmaker.unmake_move(m);
+            self.make(m);
+            if !self.position.was_move_legal() {
+                self.unmake(m);
                 continue;
             }
             let score = -self.quiesce(-beta, -alpha, depth + 1, &mut line, prev_pv);
             // println!("{}{} scored {}", "  ".repeat(depth as usize), m.to_pretty_string(), score);
-            // if let Some(tt_entry) = self.transpos.get(self.make_unmaker.zobrist_hash) {
+            // if let Some(tt_entry) = self.transpos.get(self.position.state.get_hash()) {
             //     score = -tt_entry.score;
             //     line.push(tt_entry.best_move);
             // }
-            self.make_unmaker.unmake_move(m);
+            self.unmake(m);
+            if self.aborted {
+                break;
+            }
             if score > best_score {
                 best_score = score;
                 best_move = Some(m);
@@ -198,10 +329,13 @@ impl SearchContext<'_> {
             }
         }
         self.move_list.drop_current_ply();
+        if self.aborted {
+            return 0;
+        }
 
         if let Some(best_move) = best_move {
             self.transpos.store(TtEntry {
-                hash: self.make_unmaker.zobrist_hash,
+                hash: self.position.state.get_hash(),
                 depth: 0,
                 score: best_score,
                 best_move,
@@ -214,6 +348,107 @@ impl SearchContext<'_> {
 
         best_score
     }
+
+    /// Tablebase outcome of the current position.
+    fn probe_wdl(&self) -> Option<Wdl> {
+        let tablebase = self.tablebase.as_deref()?;
+        let state = self.position.state.get();
+        if !is_probeable(tablebase, state) {
+            return None;
+        }
+        tablebase.probe_wdl(state)
+    }
+
+    /// Legal root moves that keep the best tablebase outcome. Winning moves
+    /// making the fastest progress towards the next capture or pawn move are
+    /// preferred, and losing moves delaying it the most.
+    fn tablebase_root_moves(&mut self) -> Vec<Move> {
+        let Some(tablebase) = self.tablebase.clone() else {
+            return Vec::new();
+        };
+        let root = self.position.state.get();
+        if !is_probeable(tablebase.as_ref(), root) {
+            return Vec::new();
+        }
+        let pawns = root.active_boards().pawn;
+
+        self.move_list.new_ply();
+        self.position.pseudo_legal_moves(&mut self.move_list);
+        let ply_number = self.move_list.ply_number();
+        let mut ranked = Vec::new();
+        let mut covered = true;
+        for i in 0..self.move_list.ply_size(ply_number) {
+            let m = self.move_list.r#move(ply_number, i);
+            let zeroing = m.code().is_capture() || pawns.get(m.from());
+            self.position.make(m);
+            if self.position.was_move_legal() {
+                let state = self.position.state.get();
+                match tablebase.probe_wdl(state) {
+                    Some(wdl) => {
+                        let dtz = if zeroing {
+                            Some(0)
+                        } else {
+                            tablebase.probe_dtz(state).map(i32::abs)
+                        };
+                        ranked.push((m, -wdl, dtz));
+                    }
+                    None => covered = false,
+                }
+            }
+            self.position.unmake(m);
+        }
+        self.move_list.drop_current_ply();
+
+        let Some(best) = ranked.iter().map(|(_, wdl, _)| *wdl).max() else {
+            return Vec::new();
+        };
+        if !covered {
+            return Vec::new();
+        }
+        ranked.retain(|(_, wdl, _)| *wdl == best);
+        if ranked.iter().all(|(_, _, dtz)| dtz.is_some()) {
+            let distances = ranked.iter().filter_map(|(_, _, dtz)| *dtz);
+            let target = match best {
+                Wdl::Win | Wdl::CursedWin => distances.min(),
+                _ => distances.max(),
+            };
+            ranked.retain(|(_, _, dtz)| *dtz == target);
+        }
+        ranked.into_iter().map(|(m, _, _)| m).collect()
+    }
+
+    /// Mutable due to move list use but does not modify the position
+    pub fn is_checkmate(&mut self) -> bool {
+        if !self.position.state.get().is_check() {
+            return false;
+        }
+        self.move_list.new_ply();
+        self.position.pseudo_legal_moves(&mut self.move_list);
+        let ply_number = self.move_list.ply_number();
+        let mut move_found = false;
+        for i in 0..self.move_list.ply_size(ply_number) {
+            let m = self.move_list.r#move(ply_number, i);
+            self.position.make(m);
+            let legal = self.position.was_move_legal();
+            self.position.unmake(m);
+            if legal {
+                move_found = true;
+                break;
+            }
+        }
+        self.move_list.drop_current_ply();
+        !move_found
+    }
+
+    /// Mutable due to move list use but does not modify the position
+    pub fn evaluate(&mut self) -> i32 {
+        if self.is_checkmate() {
+            return -MATE_SCORE;
+        }
+        let state = &self.position.state;
+        self.evaluator
+            .evaluate(state.get(), state.hasher().pawn_hash())
+    }
 }
 
 #[cfg(test)]
@@ -228,6 +463,31 @@ mod tests {
     use chess_core::{color::Color, r#move::MoveCode, square::Square};
 
     use super::*;
+    use crate::{
+        alpha_beta::evaluation::SimpleEval,
+        book::builder::BookBuilder,
+        tablebase::{
+            Tablebase,
+            syzygy::{FIXTURES, SyzygyTablebase},
+        },
+    };
+
+    #[test]
+    fn test_is_checkmate() {
+        for (fen, result) in [
+            // starting position
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                false,
+            ),
+            // mate
+            ("8/8/8/8/8/8/5KQ1/7k b - - 0 1", true),
+        ] {
+            let position = Position::from_fen(fen, ZobristHasher::new());
+            let mut search_context = SearchContext::new(position, SimpleEval::default(), None);
+            assert_eq!(search_context.is_checkmate(), result);
+        }
+    }
 
     #[test]
     fn test_quiesce() {
@@ -238,80 +498,250 @@ mod tests {
                 (0, 0, vec![]),
             ),
             // white is up by a pawn, black has 4 more mobility
+            // h2 is a candidate passed pawn
             (
                 "rnbqkbnr/ppppppp1/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
-                (80, 80, vec![]),
+                (80, 110, vec![]),
             ),
             // white is up by a knight, black to play
             (
                 "rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
                 (-350, -300, vec![]),
             ),
-            // One capture + lots of extra mobility
+            // One capture + lots of extra mobility and safe checks against the black king
             (
-                "8/8/8/8/8/8/qQ/5k1K w - - 0 1",
+                "8/8/8/8/8/8/qQ6/5k1K w - - 0 1",
                 (
                     900,
-                    1050,
-                    vec![Move::new(Square(9), Square(8), MoveCode::Capture)],
+                    1100,
+                    vec![Move::new(
+                        Square::from_bits(9),
+                        Square::from_bits(8),
+                        MoveCode::Capture,
+                    )],
                 ),
             ),
-            // Two captures
+            // Two captures, black is left with a passed pawn on the seventh rank
             (
                 "8/8/8/8/8/1p6/qR6/5k1K w - - 0 1",
                 (
+                    -250,
                     -150,
-                    -50,
                     vec![
-                        Move::new(Square(17), Square(8), MoveCode::Capture),
-                        Move::new(Square(9), Square(8), MoveCode::Capture),
+                        Move::new(
+                            Square::from_bits(17),
+                            Square::from_bits(8),
+                            MoveCode::Capture,
+                        ),
+                        Move::new(
+                            Square::from_bits(9),
+                            Square::from_bits(8),
+                            MoveCode::Capture,
+                        ),
                     ],
                 ),
             ),
             // Capture rook with queen but get taken or capture pawn with no capture
+            // The queen then has several safe checks against the exposed black king
             (
                 "8/8/8/8/1p6/8/rQ6/r4k1K w - - 0 1",
                 (
-                    -150,
-                    -50,
-                    vec![Move::new(Square(9), Square(25), MoveCode::Capture)],
+                    -100,
+                    50,
+                    vec![Move::new(
+                        Square::from_bits(9),
+                        Square::from_bits(25),
+                        MoveCode::Capture,
+                    )],
                 ),
             ),
             // Capture + promotion sequence resulting in gain for white
             // Black is not forced to make second capture. Static eval can be considered best move.
+            // The pawn on h7 is a passed pawn about to promote.
             (
                 "k7/pp5r/6P1/3p4/4P3/8/6PP/7K w - - 0 1",
                 (
-                    50,
                     150,
+                    250,
                     vec![
-                        Move::new(Square(46), Square(55), MoveCode::Capture),
-                        // Move::new(Square(35), Square(28), MoveCode::Capture),
-                        // Move::new(Square(55), Square(63), MoveCode::QueenPromotion)
+                        Move::new(
+                            Square::from_bits(46),
+                            Square::from_bits(55),
+                            MoveCode::Capture,
+                        ),
+                        // Move::new(Square::from_bits(35), Square::from_bits(28), MoveCode::Capture),
+                        // Move::new(Square::from_bits(55), Square::from_bits(63), MoveCode::QueenPromotion)
                     ],
                 ),
             ),
         ];
         for (fen, (lower_bound, upper_bound, expected_pv)) in cases {
-            let state = &mut GameState::from_fen(fen.to_string());
+            let position = Position::from_fen(fen, ZobristHasher::new());
             let prev_pv = &mut Vec::new();
             let pv = &mut Vec::new();
-            let mut context = SearchContext::new(state, None);
+            let mut context = SearchContext::new(position, SimpleEval::default(), None);
             let score = context.quiesce(
-                SearchContext::MIN_SCORE,
-                SearchContext::MAX_SCORE,
+                SearchContext::<SimpleEval>::MIN_SCORE,
+                SearchContext::<SimpleEval>::MAX_SCORE,
                 0,
                 pv,
                 prev_pv,
             );
             assert_eq!(*prev_pv, vec![]);
-            assert_eq!(*pv, expected_pv, "State: {:?}", state);
+            assert_eq!(
+                *pv,
+                expected_pv,
+                "State: {:?}",
+                context.position.state.get()
+            );
             assert!(score >= lower_bound, "{} < {}", score, lower_bound);
             assert!(score <= upper_bound, "{} > {}", score, upper_bound);
         }
     }
 
     #[test]
+    fn test_node_limit() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let position = Position::from_fen(fen, ZobristHasher::new());
+        let mut context = SearchContext::new(position, SimpleEval::default(), Some(3));
+        let (score, pv) = context.search(Vec::new());
+        assert!(!context.aborted());
+        let nodes = context.nodes;
+
+        context.nodes = 0;
+        context.node_limit = Some(nodes / 2);
+        context.search(Vec::new());
+        assert!(context.aborted());
+        assert_eq!(context.nodes, nodes / 2);
+        assert_eq!(context.position.state.get().to_fen(), fen);
+        assert_eq!(context.move_list.ply_number(), 0);
+
+        // Searching again without the limit gives the same result
+        context.node_limit = None;
+        assert_eq!(context.search(Vec::new()), (score, pv));
+        assert!(!context.aborted());
+
+        // A deadline already passed stops the search at its next check
+        context.nodes = 0;
+        context.deadline = Some(Local::now());
+        context.search(Vec::new());
+        assert!(context.aborted());
+        assert_eq!(context.nodes, DEADLINE_INTERVAL);
+        assert_eq!(context.position.state.get().to_fen(), fen);
+    }
+
+    /// Pretends that the side with more pieces wins, for positions with up to 4 pieces.
+    struct PieceCountTablebase;
+
+    impl Tablebase for PieceCountTablebase {
+        fn max_pieces(&self) -> u32 {
+            4
+        }
+
+        fn probe_wdl(&self, state: &chess_core::state::State) -> Option<Wdl> {
+            let ours = state.active_boards().union().count_ones();
+            let theirs = state.inactive_boards().union().count_ones();
+            Some(match ours.cmp(&theirs) {
+                std::cmp::Ordering::Greater => Wdl::Win,
+                std::cmp::Ordering::Equal => Wdl::Draw,
+                std::cmp::Ordering::Less => Wdl::Loss,
+            })
+        }
+
+        fn probe_dtz(&self, _state: &chess_core::state::State) -> Option<i32> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_tablebase() {
+        // White is in check from the rook and only Kxe2 wins it
+        let position = Position::from_fen("4k3/8/8/8/8/8/4r3/4K2Q w - - 0 1", ZobristHasher::new());
+        let mut context = SearchContext::new(position, SimpleEval::default(), Some(2))
+            .with_tablebase(Arc::new(PieceCountTablebase));
+        let capture = Move::new(
+            Square::try_from("e1").unwrap(),
+            Square::try_from("e2").unwrap(),
+            MoveCode::Capture,
+        );
+        assert_eq!(context.tablebase_root_moves(), vec![capture]);
+        let (score, pv) = context.search(Vec::new());
+        assert_eq!(pv, vec![capture]);
+        assert_eq!(score, TB_WIN_SCORE - 1);
+
+        // Too many pieces for the tablebase
+        let position = Position::from_fen(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            ZobristHasher::new(),
+        );
+        let mut context = SearchContext::new(position, SimpleEval::default(), Some(1))
+            .with_tablebase(Arc::new(PieceCountTablebase));
+        assert!(context.tablebase_root_moves().is_empty());
+    }
+
+    #[test]
+    fn test_syzygy_tablebase() {
+        let tablebase = Arc::new(SyzygyTablebase::open(FIXTURES).unwrap());
+        for fen in [
+            "8/8/8/8/8/2k5/8/K6R w - - 0 1",
+            "8/8/8/8/8/8/k3P3/4K3 w - - 0 1",
+            "4K3/4P3/8/8/8/8/8/k7 w - - 0 1",
+        ] {
+            let position = Position::from_fen(fen, ZobristHasher::new());
+            let mut context = SearchContext::new(position, SimpleEval::default(), Some(2))
+                .with_tablebase(tablebase.clone());
+            let root = context.position.state.get().clone();
+            let dtz = tablebase.probe_dtz(&root).unwrap();
+            assert!(dtz > 0, "{}", fen);
+
+            // Root moves keep the win and make the fastest progress
+            let root_moves = context.tablebase_root_moves();
+            assert!(!root_moves.is_empty(), "{}", fen);
+            for m in &root_moves {
+                context.position.make(*m);
+                let child = context.position.state.get();
+                assert_eq!(tablebase.probe_wdl(child), Some(Wdl::Loss), "{}", fen);
+                let zeroing = m.code().is_capture() || root.active_boards().pawn.get(m.from());
+                if !zeroing {
+                    assert_eq!(tablebase.probe_dtz(child), Some(1 - dtz), "{}", fen);
+                }
+                context.position.unmake(*m);
+            }
+
+            let (score, pv) = context.search(Vec::new());
+            assert_eq!(score, TB_WIN_SCORE - 1, "{}", fen);
+            assert!(root_moves.contains(&pv[0]), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_book() {
+        let mut builder = BookBuilder::new(2, 1);
+        builder.add_pgn("1. e4 e5 *").unwrap();
+        let book = Arc::new(builder.build());
+
+        let mut context = SearchContext::new(Position::default(), SimpleEval::default(), None)
+            .with_book(book, BookSelection::Best);
+        let e4 = Move::new(
+            Square::try_from("e2").unwrap(),
+            Square::try_from("e4").unwrap(),
+            MoveCode::DoublePawnPush,
+        );
+        // The book answers without spending the time budget
+        let (score, pv) = context.iterative_deepen(Duration::new(1000, 0).unwrap());
+        assert_eq!((score, pv), (0, vec![e4]));
+        assert_eq!(context.nodes, 0);
+
+        // Out of book after e4 e5, no time is left to search
+        context.make(e4);
+        let (_, pv) = context.iterative_deepen(Duration::zero());
+        context.make(pv[0]);
+        let (_, pv) = context.iterative_deepen(Duration::zero());
+        assert!(pv.is_empty());
+    }
+
+    #[test]
+    #[ignore = "requires stockfish on PATH"]
     fn test_vs_stockfish() {
         // 27/01: current estimated elo: 2000
         // Lacking transpo table, good move ordering, and simple eval function
@@ -323,10 +753,11 @@ mod tests {
         let mut stockfish_stdin = stockfish_cli.stdin.take().unwrap();
         let mut bufreader = BufReader::new(stockfish_cli.stdout.as_mut().unwrap());
 
-        let mut state = GameState::from_fen(
-            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+        let position = Position::from_fen(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            ZobristHasher::new(),
         );
-        let mut search_ctx = SearchContext::new(&mut state, None);
+        let mut search_ctx = SearchContext::new(position, SimpleEval::default(), None);
 
         stockfish_stdin
             .write_all("setoption name UCI_LimitStrength value true\n".as_bytes())
@@ -339,8 +770,10 @@ mod tests {
         let mut time_sum = TimeDelta::zero();
         let mut time_count = 0;
 
-        while !search_ctx.is_checkmate() && search_ctx.make_unmaker.state.halfmove < 200 {
-            if search_ctx.make_unmaker.state.flags.active_color() == Color::White {
+        let mut plies = 0;
+        while !search_ctx.is_checkmate() && plies < 200 {
+            plies += 1;
+            if search_ctx.position.state.get().flags.active_color() == Color::White {
                 let start_time = Local::now();
                 let (score, pv) =
                     search_ctx.iterative_deepen(Duration::new(0, 500_000_000).unwrap());
@@ -353,13 +786,16 @@ mod tests {
                 dbg!(score);
                 let m = pv.last().unwrap();
                 dbg!(m);
-                search_ctx.make_unmaker.make_move(*m);
-                println!("{}", search_ctx.make_unmaker.state.to_fen());
+                search_ctx.make(*m);
+                println!("{}", search_ctx.position.state.get().to_fen());
             } else {
                 stockfish_stdin
                     .write_all(
-                        format!("position fen {}\n", search_ctx.make_unmaker.state.to_fen())
-                            .as_bytes(),
+                        format!(
+                            "position fen {}\n",
+                            search_ctx.position.state.get().to_fen()
+                        )
+                        .as_bytes(),
                     )
                     .unwrap();
                 stockfish_stdin
@@ -374,20 +810,19 @@ mod tests {
                 let move_str = buf.split_whitespace().nth(1).unwrap();
                 dbg!(move_str);
                 search_ctx.move_list.new_ply();
-                search_ctx.move_generator.get_pseudo_legal_moves(
-                    search_ctx.make_unmaker.state,
-                    &mut search_ctx.move_list,
-                );
+                search_ctx
+                    .position
+                    .pseudo_legal_moves(&mut search_ctx.move_list);
                 let m = search_ctx
                     .move_list
                     .current_ply()
-                    .into_iter()
+                    .iter()
                     .find(|m| m.matches_perft_string(move_str))
-                    .unwrap()
-                    .clone();
+                    .copied()
+                    .unwrap();
                 search_ctx.move_list.drop_current_ply();
-                search_ctx.make_unmaker.make_move(m);
-                println!("{}", search_ctx.make_unmaker.state.to_fen());
+                search_ctx.make(m);
+                println!("{}", search_ctx.position.state.get().to_fen());
             }
         }
 
@@ -397,11 +832,11 @@ mod tests {
         );
 
         if search_ctx.is_checkmate()
-            && search_ctx.make_unmaker.state.flags.active_color() == Color::White
+            && search_ctx.position.state.get().flags.active_color() == Color::White
         {
             println!("Black wins");
         } else if search_ctx.is_checkmate()
-            && search_ctx.make_unmaker.state.flags.active_color() == Color::Black
+            && search_ctx.position.state.get().flags.active_color() == Color::Black
         {
             println!("White wins");
         } else {
@@ -409,5 +844,6 @@ mod tests {
         }
 
         stockfish_cli.kill().unwrap();
+        stockfish_cli.wait().unwrap();
     }
 }
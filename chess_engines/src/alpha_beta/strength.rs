@@ -0,0 +1,224 @@
+// Weaker play for human opponents. Every legal root move is scored by a
+// search limited in depth and nodes, then a move is drawn among those scored
+// close to the best one, favouring the better ones. At the lowest levels a
+// random legal move is sometimes played instead. Skill levels go from 0 to 20;
+// the Elo scale maps onto them linearly and is a rough estimate, not measured.
+
+use rand::Rng;
+
+use chess_core::r#move::Move;
+
+use super::{evaluation::Evaluator, search::SearchContext};
+
+pub const MAX_SKILL: u8 = 20;
+pub const MIN_ELO: u32 = 800;
+pub const MAX_ELO: u32 = 2200;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Strength {
+    /// Deepest search of the root moves.
+    pub depth: u8,
+    /// Nodes the search of the root moves may visit.
+    pub nodes: u64,
+    /// Moves scored within this many centipawns of the best one can be played.
+    pub margin: i32,
+    /// Probability of playing a random legal move.
+    pub blunder: f64,
+}
+
+impl Strength {
+    /// Limits of a skill level from 0 to 20, higher levels are clamped to 20.
+    pub fn from_skill(level: u8) -> Self {
+        let level = level.min(MAX_SKILL);
+        Strength {
+            depth: 1 + level / 4,
+            nodes: 200 << (level / 2),
+            margin: (MAX_SKILL - level) as i32 * 15,
+            blunder: (10 - level.min(10)) as f64 * 0.02,
+        }
+    }
+
+    /// Limits of the skill level closest to `elo`, clamped to the supported range.
+    pub fn from_elo(elo: u32) -> Self {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        let level =
+            ((elo - MIN_ELO) * MAX_SKILL as u32 + (MAX_ELO - MIN_ELO) / 2) / (MAX_ELO - MIN_ELO);
+        Self::from_skill(level as u8)
+    }
+}
+
+/// Score of every legal root move at the deepest depth completed within the
+/// limits. A depth cut short by the node limit or a deadline is discarded, if
+/// not even the first one completes every move scores 0.
+pub fn root_scores<E: Evaluator>(
+    context: &mut SearchContext<E>,
+    strength: &Strength,
+) -> Vec<(Move, i32)> {
+    let moves = context.position.legal_moves();
+    let (prev_depth, prev_limit) = (context.max_depth, context.node_limit);
+    let limit = context.nodes + strength.nodes;
+    context.node_limit = Some(prev_limit.map_or(limit, |prev| prev.min(limit)));
+    let mut scores = moves.iter().map(|m| (*m, 0)).collect::<Vec<_>>();
+    for depth in 1..=strength.depth.max(1) {
+        // The root move is the first ply of the depth
+        context.max_depth = depth - 1;
+        let depth_scores = moves
+            .iter()
+            .map(|m| {
+                context.make(*m);
+                let (score, _) = context.search(Vec::new());
+                context.unmake(*m);
+                (!context.aborted()).then_some((*m, -score))
+            })
+            .collect::<Option<Vec<_>>>();
+        match depth_scores {
+            Some(depth_scores) => scores = depth_scores,
+            None => break,
+        }
+    }
+    context.max_depth = prev_depth;
+    context.node_limit = prev_limit;
+    scores
+}
+
+/// Pick the move to play among the scored root moves, `None` if there is none.
+fn pick(scores: &[(Move, i32)], strength: &Strength, rng: &mut impl Rng) -> Option<Move> {
+    if scores.is_empty() {
+        return None;
+    }
+    if rng.gen_bool(strength.blunder.clamp(0.0, 1.0)) {
+        return Some(scores[rng.gen_range(0..scores.len())].0);
+    }
+    let best = scores.iter().map(|(_, score)| *score).max()?;
+    // Weights fall linearly from the best score to the edge of the margin
+    let weights = scores
+        .iter()
+        .map(|(m, score)| (*m, (strength.margin + 1 - (best - score)).max(0) as u32))
+        .collect::<Vec<_>>();
+    let total = weights.iter().map(|(_, weight)| weight).sum::<u32>();
+    let mut draw = rng.gen_range(0..total);
+    weights.into_iter().find_map(|(m, weight)| {
+        if draw < weight {
+            Some(m)
+        } else {
+            draw -= weight;
+            None
+        }
+    })
+}
+
+/// The move played at `strength` in the position of `context`, `None` without legal moves.
+pub fn choose_move<E: Evaluator>(
+    context: &mut SearchContext<E>,
+    strength: &Strength,
+) -> Option<Move> {
+    let scores = root_scores(context, strength);
+    pick(&scores, strength, &mut rand::thread_rng())
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::{hash::zobrist::ZobristHasher, position::Position};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::alpha_beta::evaluation::SimpleEval;
+
+    fn search_context(fen: &str) -> SearchContext<SimpleEval> {
+        let position = Position::from_fen(fen, ZobristHasher::new());
+        SearchContext::new(position, SimpleEval::default(), None)
+    }
+
+    #[test]
+    fn test_levels() {
+        let levels = (0..=MAX_SKILL)
+            .map(Strength::from_skill)
+            .collect::<Vec<_>>();
+        for pair in levels.windows(2) {
+            assert!(pair[0].depth <= pair[1].depth && pair[0].nodes <= pair[1].nodes);
+            assert!(pair[0].margin > pair[1].margin && pair[0].blunder >= pair[1].blunder);
+        }
+        assert_eq!(levels[MAX_SKILL as usize].margin, 0);
+        assert_eq!(levels[MAX_SKILL as usize].blunder, 0.0);
+        assert_eq!(Strength::from_skill(30), levels[MAX_SKILL as usize]);
+
+        assert_eq!(Strength::from_elo(0), levels[0]);
+        assert_eq!(
+            Strength::from_elo(MAX_ELO + 100),
+            levels[MAX_SKILL as usize]
+        );
+        assert_eq!(Strength::from_elo(1500), levels[10]);
+    }
+
+    #[test]
+    fn test_root_scores() {
+        // Mate in one with Ra8
+        let mut context = search_context("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let strength = Strength::from_skill(4);
+        let scores = root_scores(&mut context, &strength);
+        assert_eq!(scores.len(), 17);
+        let (best, _) = scores.iter().max_by_key(|(_, score)| *score).unwrap();
+        assert_eq!(best.to_string(), "a1a8");
+        assert_eq!(context.max_depth, 1);
+        assert_eq!(
+            context.position.state.get().to_fen(),
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"
+        );
+
+        // The node budget cuts the deeper depths short
+        let mut context = search_context("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let strength = Strength {
+            depth: 6,
+            nodes: 1000,
+            ..strength
+        };
+        let scores = root_scores(&mut context, &strength);
+        assert_eq!(scores.len(), 17);
+        assert!(context.nodes <= strength.nodes);
+        assert_eq!(context.node_limit, None);
+        let (best, _) = scores.iter().max_by_key(|(_, score)| *score).unwrap();
+        assert_eq!(best.to_string(), "a1a8");
+
+        let mut context = search_context("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(choose_move(&mut context, &strength), None);
+    }
+
+    #[test]
+    fn test_pick() {
+        let mut context = search_context("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let moves = context.position.legal_moves();
+        let scores = moves
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (*m, i as i32 * 10))
+            .collect::<Vec<_>>();
+        let best = *moves.last().unwrap();
+        let rng = &mut StdRng::seed_from_u64(7);
+
+        let strongest = Strength::from_skill(MAX_SKILL);
+        assert!((0..20).all(|_| pick(&scores, &strongest, rng) == Some(best)));
+
+        // Within 25 centipawns of the best move, the best one most often
+        let near = Strength {
+            margin: 25,
+            blunder: 0.0,
+            ..strongest
+        };
+        let picks = (0..300)
+            .map(|_| pick(&scores, &near, rng).unwrap())
+            .collect::<Vec<_>>();
+        assert!(picks.iter().all(|m| moves[moves.len() - 3..].contains(m)));
+        let count = |m: &Move| picks.iter().filter(|p| *p == m).count();
+        assert!(count(&best) > count(&moves[moves.len() - 3]));
+
+        let blunders = Strength {
+            blunder: 1.0,
+            ..strongest
+        };
+        let picks = (0..300)
+            .map(|_| pick(&scores, &blunders, rng).unwrap())
+            .collect::<Vec<_>>();
+        assert!(picks.iter().any(|m| *m == moves[0]));
+        assert_eq!(pick(&[], &blunders, rng), None);
+    }
+}
@@ -0,0 +1,107 @@
+// Building a book from a game collection: every move played in the first plies
+// of the games is counted, and the moves played often enough make the book.
+// The weight of a move is the number of games it was played in.
+
+use std::collections::HashMap;
+
+use chess_core::{
+    hash::{HashedState, NoopHasher, polyglot::polyglot_key},
+    r#move::san::from_san,
+    pgn::{PgnGame, parse_pgn},
+    position::Position,
+};
+
+use super::{BookEntry, PolyglotBook, encode_move};
+
+pub struct BookBuilder {
+    /// Moves played after this many plies are ignored.
+    max_ply: usize,
+    /// Moves played in fewer games are left out of the book.
+    min_count: u32,
+    counts: HashMap<(u64, u16), u32>,
+}
+
+impl BookBuilder {
+    pub fn new(max_ply: usize, min_count: u32) -> Self {
+        BookBuilder {
+            max_ply,
+            min_count,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Count the moves of the first `max_ply` plies of the game. Nothing is
+    /// counted if one of them is illegal.
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<(), &'static str> {
+        let mut position = Position::new(HashedState::new(game.starting_state()?, NoopHasher {}));
+        let mut moves = Vec::new();
+        for san in game.moves.iter().take(self.max_ply) {
+            let r#move = from_san(&mut position, san)?;
+            let state = position.state.get();
+            moves.push((polyglot_key(state), encode_move(state, r#move)));
+            position.make(r#move);
+        }
+        for m in moves {
+            *self.counts.entry(m).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    /// Add every game of a PGN collection, returns the number of games skipped
+    /// because of an illegal move.
+    pub fn add_pgn(&mut self, pgn: &str) -> Result<usize, &'static str> {
+        let games = parse_pgn(pgn)?;
+        Ok(games
+            .iter()
+            .filter(|game| self.add_game(game).is_err())
+            .count())
+    }
+
+    pub fn build(&self) -> PolyglotBook {
+        PolyglotBook::new(
+            self.counts
+                .iter()
+                .filter(|(_, count)| **count >= self.min_count)
+                .map(|((key, r#move), count)| BookEntry {
+                    key: *key,
+                    r#move: *r#move,
+                    weight: (*count).min(u16::MAX as u32) as u16,
+                    learn: 0,
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::r#move::san::to_san;
+
+    use super::*;
+
+    const PGN: &str = "1. e4 e5 2. Nf3 Nc6 1-0\n\n1. e4 c5 2. Nf3 d6 0-1\n\n1. d4 d5 1/2-1/2\n\n1. e4 Ke7 2. Kf3 *";
+
+    #[test]
+    fn test_build() {
+        let mut builder = BookBuilder::new(3, 2);
+        // The last game has an illegal move
+        assert_eq!(builder.add_pgn(PGN), Ok(1));
+        let book = builder.build();
+
+        let mut position = Position::<NoopHasher>::default();
+        let moves = book
+            .moves(&mut position)
+            .into_iter()
+            .map(|(m, weight)| (to_san(&mut position, m), weight))
+            .collect::<Vec<_>>();
+        // d4 was only played once
+        assert_eq!(moves, [("e4".to_string(), 2)]);
+
+        // Nf3 is the third ply, reached by different positions
+        assert_eq!(book.len(), 1);
+
+        let mut builder = BookBuilder::new(3, 1);
+        builder.add_pgn(PGN).unwrap();
+        assert_eq!(builder.build().len(), 7);
+    }
+}
@@ -0,0 +1,266 @@
+// Polyglot opening books are arrays of 16 byte big endian entries sorted by key:
+//   - the Polyglot key of the position (8 bytes)
+//   - the move (2 bytes): target file and rank, source file and rank on 3 bits
+//     each, then the promotion piece, 1 for a knight up to 4 for a queen.
+//     Castles are written as the king taking its own rook, e.g. e1h1.
+//   - the weight of the move (2 bytes)
+//   - learning data that we keep but don't use (4 bytes)
+
+pub mod builder;
+
+use std::{fs, io, path::Path};
+
+use rand::Rng;
+
+use chess_core::{
+    hash::{Hasher, polyglot::polyglot_key},
+    r#move::Move,
+    position::Position,
+    square::SquareFinder,
+    state::{State, chess_board::PieceType},
+};
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub r#move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            r#move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.r#move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// How to pick a move among the book moves of a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookSelection {
+    /// The move with the highest weight.
+    Best,
+    /// A move drawn with a probability proportional to its weight.
+    WeightedRandom,
+}
+
+/// Polyglot encoding of a `move` played in `state`.
+pub fn encode_move(state: &State, r#move: Move) -> u16 {
+    let to = match r#move.code().as_castle() {
+        Some(side) => SquareFinder(state.flags.active_color()).castle_rook_source(side),
+        None => r#move.to(),
+    };
+    let promotion = match r#move.code().as_promotion() {
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    to.get() as u16 | (r#move.from().get() as u16) << 6 | promotion << 12
+}
+
+/// The legal move of `position` with the Polyglot encoding `raw`.
+pub fn decode_move<H: Hasher>(position: &mut Position<H>, raw: u16) -> Option<Move> {
+    position
+        .legal_moves()
+        .into_iter()
+        .find(|m| encode_move(position.state.get(), *m) == raw)
+}
+
+/// An opening book, with entries sorted by key and by decreasing weight.
+#[derive(Default, Debug)]
+pub struct PolyglotBook {
+    entries: Vec<BookEntry>,
+}
+
+impl PolyglotBook {
+    pub fn new(mut entries: Vec<BookEntry>) -> Self {
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        PolyglotBook { entries }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err("Book size is not a multiple of the entry size");
+        }
+        Ok(Self::new(
+            bytes
+                .chunks_exact(ENTRY_SIZE)
+                .map(BookEntry::from_bytes)
+                .collect(),
+        ))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(|e| e.to_bytes()).collect()
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries of the position with the Polyglot `key`.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Legal book moves of the position with their weights. Entries with moves
+    /// that aren't legal, from a key collision or a broken book, are skipped.
+    pub fn moves<H: Hasher>(&self, position: &mut Position<H>) -> Vec<(Move, u16)> {
+        self.entries(polyglot_key(position.state.get()))
+            .iter()
+            .filter_map(|e| decode_move(position, e.r#move).map(|m| (m, e.weight)))
+            .collect()
+    }
+
+    /// Pick a book move for the position, `None` when out of book.
+    pub fn probe<H: Hasher>(
+        &self,
+        position: &mut Position<H>,
+        selection: BookSelection,
+    ) -> Option<Move> {
+        select(&self.moves(position), selection, &mut rand::thread_rng())
+    }
+}
+
+fn select(moves: &[(Move, u16)], selection: BookSelection, rng: &mut impl Rng) -> Option<Move> {
+    match selection {
+        BookSelection::Best => moves
+            .iter()
+            .max_by_key(|(_, weight)| *weight)
+            .map(|(m, _)| *m),
+        BookSelection::WeightedRandom => {
+            let total = moves.iter().map(|(_, weight)| *weight as u32).sum::<u32>();
+            if total == 0 {
+                // Only moves that shouldn't be played
+                return None;
+            }
+            let mut pick = rng.gen_range(0..total);
+            moves.iter().find_map(|(m, weight)| {
+                if pick < *weight as u32 {
+                    Some(*m)
+                } else {
+                    pick -= *weight as u32;
+                    None
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::{hash::NoopHasher, r#move::san::from_san};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn entry(fen: &str, san: &str, weight: u16) -> BookEntry {
+        let mut position = Position::from_fen(fen, NoopHasher {});
+        let m = from_san(&mut position, san).unwrap();
+        BookEntry {
+            key: polyglot_key(position.state.get()),
+            r#move: encode_move(position.state.get(), m),
+            weight,
+            learn: 0,
+        }
+    }
+
+    #[test]
+    fn test_encode_move() {
+        let start = State::from_fen(START);
+        let mut position = Position::from_fen(START, NoopHasher {});
+        let e4 = from_san(&mut position, "e4").unwrap();
+        // e2 is 12, e4 is 28
+        assert_eq!(encode_move(&start, e4), 28 | 12 << 6);
+
+        // Castles are written as king takes rook
+        let fen = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let mut position = Position::from_fen(fen, NoopHasher {});
+        let castle = from_san(&mut position, "O-O-O").unwrap();
+        assert_eq!(encode_move(position.state.get(), castle), 4 << 6);
+        assert_eq!(decode_move(&mut position, 4 << 6), Some(castle));
+
+        let promotion = from_san(&mut position, "bxa8=N").unwrap();
+        let raw = encode_move(position.state.get(), promotion);
+        assert_eq!(raw, 56 | 49 << 6 | 1 << 12);
+        assert_eq!(decode_move(&mut position, raw), Some(promotion));
+        // A pawn from e2 to e4 is not legal here
+        assert_eq!(decode_move(&mut position, 28 | 12 << 6), None);
+    }
+
+    #[test]
+    fn test_book_bytes() {
+        let book = PolyglotBook::new(vec![
+            entry(START, "e4", 10),
+            entry(START, "d4", 30),
+            entry("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", "e4", 1),
+        ]);
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), 3 * ENTRY_SIZE);
+        let read = PolyglotBook::from_bytes(&bytes).unwrap();
+        assert_eq!(read.entries, book.entries);
+        assert!(PolyglotBook::from_bytes(&bytes[1..]).is_err());
+
+        // The start position key comes first in big endian
+        assert_eq!(bytes[..8], 0x463b96181691fc9c_u64.to_be_bytes());
+        let start = read.entries(0x463b96181691fc9c);
+        assert_eq!(start.len(), 2);
+        assert_eq!(start[0].weight, 30);
+    }
+
+    #[test]
+    fn test_probe() {
+        let book = PolyglotBook::new(vec![entry(START, "e4", 1), entry(START, "d4", 3)]);
+        let mut position = Position::from_fen(START, NoopHasher {});
+        let d4 = from_san(&mut position, "d4").unwrap();
+        let e4 = from_san(&mut position, "e4").unwrap();
+        assert_eq!(book.probe(&mut position, BookSelection::Best), Some(d4));
+
+        let moves = book.moves(&mut position);
+        let rng = &mut StdRng::seed_from_u64(0);
+        let picks = (0..400)
+            .filter_map(|_| select(&moves, BookSelection::WeightedRandom, rng))
+            .collect::<Vec<_>>();
+        let e4_count = picks.iter().filter(|m| **m == e4).count();
+        assert_eq!(picks.len(), 400);
+        assert!((60..140).contains(&e4_count), "{}", e4_count);
+
+        position.make(e4);
+        assert_eq!(book.probe(&mut position, BookSelection::Best), None);
+        assert_eq!(select(&[(e4, 0)], BookSelection::WeightedRandom, rng), None);
+    }
+}
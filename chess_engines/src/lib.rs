@@ -1 +1,3 @@
 pub mod alpha_beta;
+pub mod book;
+pub mod tablebase;
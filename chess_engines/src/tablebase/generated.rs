@@ -0,0 +1,55 @@
+// Tables generated by chess_core hold distances to mate, which are turned into
+// win/draw/loss. Those tables only cover one side having pieces besides the king
+// and ignore the fifty move rule, so they never give cursed wins or blessed losses.
+
+use chess_core::{
+    state::State,
+    tablebase::{Dtm, EndgameTables},
+};
+
+use super::{Tablebase, Wdl};
+
+impl Tablebase for EndgameTables {
+    fn max_pieces(&self) -> u32 {
+        self.tables()
+            .iter()
+            .map(|table| table.piece_count() as u32)
+            .max()
+            // Kings alone are a draw without any table
+            .unwrap_or(2)
+    }
+
+    fn probe_wdl(&self, state: &State) -> Option<Wdl> {
+        Some(match self.probe(state)? {
+            Dtm::Win(_) => Wdl::Win,
+            Dtm::Loss(_) => Wdl::Loss,
+            Dtm::Draw => Wdl::Draw,
+        })
+    }
+
+    /// Distance to mate isn't distance to zeroing, the search falls back on the
+    /// win/draw/loss.
+    fn probe_dtz(&self, _state: &State) -> Option<i32> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_tablebase() {
+        let tables = EndgameTables::generate(&["KQK"]).unwrap();
+        assert_eq!(tables.max_pieces(), 3);
+        for (fen, result) in [
+            ("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1", Some(Wdl::Win)),
+            ("k7/8/1K6/8/8/8/8/6Q1 b - - 0 1", Some(Wdl::Loss)),
+            ("8/8/8/4k3/8/8/8/3NK3 w - - 0 1", Some(Wdl::Draw)),
+            ("8/8/8/4k3/8/8/8/3RK3 w - - 0 1", None),
+        ] {
+            assert_eq!(tables.probe_wdl(&State::from_fen(fen)), result, "{}", fen);
+        }
+        assert_eq!(EndgameTables::default().max_pieces(), 2);
+    }
+}
@@ -0,0 +1,115 @@
+// Endgame tablebases give the exact outcome of positions with few pieces.
+// The search only relies on the `Tablebase` trait, so any source of perfect
+// endgame knowledge can be plugged in.
+
+pub mod generated;
+pub mod syzygy;
+
+use std::ops::Neg;
+
+use chess_core::{
+    color::Color,
+    square::CastleSide,
+    state::{State, chess_board::PieceType},
+};
+
+/// Win/draw/loss from the point of view of the side to move. Cursed wins and
+/// blessed losses are wins and losses that the fifty move rule turns into draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Self::Output {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+}
+
+pub trait Tablebase {
+    /// Largest number of pieces, kings included, of the available tables.
+    fn max_pieces(&self) -> u32;
+
+    /// Outcome of the position, `None` if it is not covered.
+    fn probe_wdl(&self, state: &State) -> Option<Wdl>;
+
+    /// Distance to the next zeroing move (capture or pawn move) with optimal play,
+    /// positive when the side to move wins and negative when it loses.
+    fn probe_dtz(&self, state: &State) -> Option<i32>;
+}
+
+/// Number of pieces on the board, kings included.
+pub fn piece_count(state: &State) -> u32 {
+    (state.boards.white.union() | state.boards.black.union()).count_ones()
+}
+
+/// Tables never cover positions where castling is still possible.
+pub fn is_probeable(tablebase: &dyn Tablebase, state: &State) -> bool {
+    piece_count(state) <= tablebase.max_pieces()
+        && Color::as_array().into_iter().all(|color| {
+            CastleSide::as_array()
+                .into_iter()
+                .all(|side| !state.flags.castle_right(color, side))
+        })
+}
+
+/// Material of one side in tablebase notation, strongest piece first, e.g. `KQP`.
+fn side_material(state: &State, color: Color) -> String {
+    let mut material = String::new();
+    for (piece, name) in [
+        (PieceType::King, 'K'),
+        (PieceType::Queen, 'Q'),
+        (PieceType::Rook, 'R'),
+        (PieceType::Bishop, 'B'),
+        (PieceType::Knight, 'N'),
+        (PieceType::Pawn, 'P'),
+    ] {
+        for _ in 0..state.boards[color][piece].count_ones() {
+            material.push(name);
+        }
+    }
+    material
+}
+
+/// Material key of the position with `color`'s pieces first, e.g. `KRPvKR`.
+pub fn material_key(state: &State, color: Color) -> String {
+    format!(
+        "{}v{}",
+        side_material(state, color),
+        side_material(state, !color)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material_key() {
+        let state = State::from_fen("8/8/8/4k3/8/8/3PR3/4K3 b - - 0 1");
+        assert_eq!(material_key(&state, Color::White), "KRPvK");
+        assert_eq!(material_key(&state, Color::Black), "KvKRP");
+        assert_eq!(piece_count(&state), 4);
+        assert_eq!(piece_count(&State::default()), 32);
+    }
+
+    #[test]
+    fn test_wdl_order() {
+        assert!(Wdl::Win > Wdl::CursedWin);
+        assert!(Wdl::BlessedLoss > Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+    }
+}
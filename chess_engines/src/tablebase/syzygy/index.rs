@@ -0,0 +1,179 @@
+// Maps used to turn a placement of pieces into the index of a Syzygy table.
+// Squares are numbered from a1 = 0 to h8 = 63, as in chess_core.
+//   - pawnless tables bring the leading piece to the a1-d1-d4 triangle and,
+//     while the leading pieces stand on the a1-h8 diagonal, the next one below it
+//   - tables with pawns bring the leading pawn to the a to d files, with one
+//     subtable per file
+// Pieces of the same type and color are encoded together as a combination of
+// squares, so their order doesn't matter.
+
+use lazy_static::lazy_static;
+
+/// Rank minus file: negative below the a1-h8 diagonal, positive above.
+pub(super) fn off_diagonal(square: u8) -> i8 {
+    (square >> 3) as i8 - (square & 7) as i8
+}
+
+pub(super) fn rank(square: u8) -> u8 {
+    square >> 3
+}
+
+pub(super) fn file(square: u8) -> u8 {
+    square & 7
+}
+
+fn kings_touch(a: u8, b: u8) -> bool {
+    rank(a).abs_diff(rank(b)) <= 1 && file(a).abs_diff(file(b)) <= 1
+}
+
+pub(super) struct Maps {
+    /// Squares a2 to h7, the highest for the pawn closest to the edge and the
+    /// lowest rank, which leads.
+    pub pawns: [u8; 64],
+    /// Squares below the a1-h8 diagonal.
+    pub b1h1h7: [u8; 64],
+    /// Squares of the a1-d1-d4 triangle, the diagonal last.
+    pub a1d1d4: [Option<u8>; 64],
+    /// Placements of the two kings with the first one on the triangle.
+    pub kk: [[u16; 64]; 10],
+    /// `binomial[k][n]` ways to choose k squares out of n.
+    pub binomial: [[u64; 64]; 6],
+    /// Index of the leading pawns by number of pawns and square of the first one.
+    pub lead_pawn_index: [[u64; 64]; 6],
+    /// Number of placements of the leading pawns by number of pawns and file.
+    pub lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Maps {
+    fn new() -> Self {
+        let mut b1h1h7 = [0; 64];
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                b1h1h7[square as usize] = code;
+                code += 1;
+            }
+        }
+
+        let mut a1d1d4 = [None; 64];
+        let mut diagonal = Vec::new();
+        let mut code = 0;
+        for square in 0..32 {
+            if file(square) > 3 {
+                continue;
+            }
+            match off_diagonal(square) {
+                0 => diagonal.push(square),
+                off if off < 0 => {
+                    a1d1d4[square as usize] = Some(code);
+                    code += 1;
+                }
+                _ => {}
+            }
+        }
+        for square in diagonal {
+            a1d1d4[square as usize] = Some(code);
+            code += 1;
+        }
+
+        let mut kk = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for slot in 0..10 {
+            let Some(first) = (0..32).find(|square| a1d1d4[*square as usize] == Some(slot)) else {
+                continue;
+            };
+            for second in 0..64 {
+                if kings_touch(first, second) {
+                    continue;
+                }
+                match (off_diagonal(first), off_diagonal(second)) {
+                    // With the first king on the diagonal the second is below it
+                    (0, off) if off > 0 => {}
+                    (0, 0) => both_on_diagonal.push((slot, second)),
+                    _ => {
+                        kk[slot as usize][second as usize] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (slot, second) in both_on_diagonal {
+            kk[slot as usize][second as usize] = code;
+            code += 1;
+        }
+
+        let mut binomial = [[0; 64]; 6];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                binomial[k][n] = if k > 0 { binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut pawns = [0; 64];
+        let mut lead_pawn_index = [[0; 64]; 6];
+        let mut lead_pawns_size = [[0; 4]; 6];
+        let mut available: u8 = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4u8 {
+                let mut index = 0;
+                for rank in 1..7u8 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        pawns[square as usize] = available;
+                        pawns[(square ^ 7) as usize] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    lead_pawn_index[lead_pawns][square as usize] = index;
+                    index += binomial[lead_pawns - 1][pawns[square as usize] as usize];
+                }
+                lead_pawns_size[lead_pawns][file as usize] = index;
+            }
+        }
+
+        Maps {
+            pawns,
+            b1h1h7,
+            a1d1d4,
+            kk,
+            binomial,
+            lead_pawn_index,
+            lead_pawns_size,
+        }
+    }
+}
+
+lazy_static! {
+    pub(super) static ref MAPS: Maps = Maps::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maps() {
+        // 462 placements of the kings, 48 pawn squares
+        assert_eq!(MAPS.kk.iter().flatten().max().copied(), Some(461));
+        assert_eq!(MAPS.pawns[8], 47);
+        assert_eq!(MAPS.pawns[15], 46);
+        assert_eq!(MAPS.pawns[16], 45);
+        assert_eq!(MAPS.pawns[51], 1);
+        assert_eq!(MAPS.pawns[52], 0);
+        assert_eq!(MAPS.a1d1d4[1], Some(0));
+        assert_eq!(MAPS.a1d1d4[0], Some(6));
+        assert_eq!(MAPS.a1d1d4[27], Some(9));
+        assert_eq!(MAPS.a1d1d4[4], None);
+        assert_eq!(MAPS.b1h1h7[55], 27);
+        assert_eq!(MAPS.binomial[2][62], 1891);
+        // A single leading pawn can stand on 6 ranks, two on any of the squares after
+        assert_eq!(MAPS.lead_pawns_size[1], [6; 4]);
+        assert_eq!(MAPS.lead_pawn_index[1][8 * 3 + 2], 2);
+        assert_eq!(
+            MAPS.lead_pawns_size[2][0],
+            (37..48).rev().step_by(2).sum::<u64>()
+        );
+    }
+}
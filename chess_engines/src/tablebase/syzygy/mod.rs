@@ -0,0 +1,551 @@
+// Syzygy tables are stored one file per material configuration, named after
+// the material key with the stronger side first, e.g. `KQvK.rtbw` for the
+// win/draw/loss table and `KQvK.rtbz` for the distance to zeroing table.
+// Files are found when opening the directory and parsed on their first probe.
+//
+// Tables leave out what a short search finds, so probes follow the reference
+// implementation:
+//   - WDL tables may store anything for positions where a capture is best,
+//     captures are searched first and the table only has to beat them
+//   - DTZ tables are only stored for one side to move, the other side is
+//     answered by a one ply search
+//   - the distance of positions where a capture or pawn move wins is left out
+//     and known to be 1
+// Tables don't cover castling rights, en passant is handled by searching captures.
+
+mod index;
+mod table;
+#[cfg(test)]
+mod writer;
+
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use chess_core::{
+    color::Color,
+    game::is_insufficient_material,
+    hash::{HashedState, NoopHasher},
+    position::Position,
+    state::State,
+};
+
+use super::{Tablebase, Wdl, material_key, piece_count};
+use table::{DTZ_MAGIC, Kind, Table, WDL_MAGIC};
+
+/// Tables written by the tests, see `writer`.
+#[cfg(test)]
+pub(crate) const FIXTURES: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/src/tablebase/syzygy/fixtures");
+
+const WDL_EXTENSION: &str = "rtbw";
+const DTZ_EXTENSION: &str = "rtbz";
+
+/// Files found for one material key.
+#[derive(Default, Debug)]
+pub struct TableFiles {
+    pub wdl: Option<PathBuf>,
+    pub dtz: Option<PathBuf>,
+    /// Tables parsed on first use, `None` if their file can't be read.
+    wdl_table: OnceLock<Option<Table>>,
+    dtz_table: OnceLock<Option<Table>>,
+}
+
+impl TableFiles {
+    fn load(&self, kind: Kind, key: &str) -> Option<&Table> {
+        let (path, table) = match kind {
+            Kind::Wdl => (&self.wdl, &self.wdl_table),
+            Kind::Dtz => (&self.dtz, &self.dtz_table),
+        };
+        table
+            .get_or_init(|| {
+                let bytes = fs::read(path.as_ref()?).ok()?;
+                Table::new(kind, key, bytes).ok()
+            })
+            .as_ref()
+    }
+}
+
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("bytes", &self.bytes().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Syzygy tables found in a directory.
+#[derive(Default)]
+pub struct SyzygyTablebase {
+    tables: HashMap<String, TableFiles>,
+    max_pieces: u32,
+}
+
+/// What a DTZ table gives for a position.
+enum Dtz {
+    Plies(i32),
+    /// The table stores the other side to move.
+    OtherSide,
+}
+
+/// Distance of a zeroing move with the outcome of the position after it.
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+/// A material key is two sides starting with a king, like `KRPvKR`.
+fn is_material_key(key: &str) -> bool {
+    let Some((strong, weak)) = key.split_once('v') else {
+        return false;
+    };
+    [strong, weak]
+        .iter()
+        .all(|side| side.starts_with('K') && side[1..].chars().all(|c| "QRBNP".contains(c)))
+}
+
+fn check_magic(path: &Path, magic: [u8; 4]) -> io::Result<()> {
+    let mut header = [0; 4];
+    fs::File::open(path)?.read_exact(&mut header)?;
+    if header != magic {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a syzygy table", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+impl SyzygyTablebase {
+    /// Find and validate every table in `directory`, other files are ignored.
+    pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let mut tablebase = SyzygyTablebase::default();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let (Some(key), Some(extension)) = (
+                path.file_stem().and_then(|s| s.to_str()),
+                path.extension().and_then(|s| s.to_str()),
+            ) else {
+                continue;
+            };
+            if !is_material_key(key) {
+                continue;
+            }
+            let key = key.to_string();
+            match extension {
+                WDL_EXTENSION => {
+                    check_magic(&path, WDL_MAGIC)?;
+                    tablebase.tables.entry(key.clone()).or_default().wdl = Some(path);
+                }
+                DTZ_EXTENSION => {
+                    check_magic(&path, DTZ_MAGIC)?;
+                    tablebase.tables.entry(key.clone()).or_default().dtz = Some(path);
+                }
+                _ => continue,
+            }
+            // The key has one letter per piece and the separator
+            tablebase.max_pieces = tablebase.max_pieces.max(key.len() as u32 - 1);
+        }
+        Ok(tablebase)
+    }
+
+    /// Files of the table covering the position and the color of the side
+    /// listed first in the table name.
+    pub fn table(&self, state: &State) -> Option<(&TableFiles, Color)> {
+        self.entry(state).map(|(_, files, color)| (files, color))
+    }
+
+    fn entry(&self, state: &State) -> Option<(&str, &TableFiles, Color)> {
+        Color::as_array().into_iter().find_map(|color| {
+            self.tables
+                .get_key_value(&material_key(state, color))
+                .map(|(key, files)| (key.as_str(), files, color))
+        })
+    }
+
+    /// Table of the position and whether its colors are swapped in the table:
+    /// when black has the first side of the table name, or with the same
+    /// material on both sides and black to move.
+    fn load(&self, state: &State, kind: Kind) -> Option<(&Table, bool)> {
+        let (key, files, color) = self.entry(state)?;
+        let symmetric = material_key(state, Color::White) == material_key(state, Color::Black);
+        let flip =
+            color == Color::Black || (symmetric && state.flags.active_color() == Color::Black);
+        Some((files.load(kind, key)?, flip))
+    }
+
+    /// Outcome stored in the WDL table, which may be wrong when a capture is best.
+    fn table_wdl(&self, state: &State) -> Option<Wdl> {
+        if is_insufficient_material(state) {
+            return Some(Wdl::Draw);
+        }
+        let (table, flip) = self.load(state, Kind::Wdl)?;
+        table.wdl(&table.locate(state, flip)?)
+    }
+
+    fn table_dtz(&self, state: &State, wdl: Wdl) -> Option<Dtz> {
+        let (table, flip) = self.load(state, Kind::Dtz)?;
+        match table.locate(state, flip) {
+            Some(location) => table.dtz(&location, wdl).map(Dtz::Plies),
+            None => Some(Dtz::OtherSide),
+        }
+    }
+
+    /// Outcome of the position searching captures, and pawn moves when
+    /// `pawn_moves` is set, before the table. Also tells whether a zeroing move
+    /// is best, when the DTZ table doesn't store a meaningful distance.
+    fn search(&self, state: &State, pawn_moves: bool) -> Option<(Wdl, bool)> {
+        let mut position = Position::new(HashedState::new(state.clone(), NoopHasher {}));
+        let moves = position.legal_moves();
+        let pawns = state.active_boards().pawn;
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for m in &moves {
+            let zeroing = m.code().is_capture() || (pawn_moves && pawns.get(m.from()));
+            if !zeroing {
+                continue;
+            }
+            searched += 1;
+            position.make(*m);
+            let child = self.search(position.state.get(), false);
+            position.unmake(*m);
+            let wdl = -child?.0;
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Some((wdl, true));
+                }
+            }
+        }
+
+        // With every move searched the table is not needed, it doesn't know
+        // about en passant anyway
+        let all_searched = searched > 0 && searched == moves.len();
+        let stored = if all_searched {
+            best
+        } else {
+            self.table_wdl(state)?
+        };
+        if best >= stored {
+            Some((best, best > Wdl::Draw || all_searched))
+        } else {
+            Some((stored, false))
+        }
+    }
+
+    fn dtz(&self, state: &State) -> Option<i32> {
+        let (wdl, zeroing) = self.search(state, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        let sign = (wdl as i32).signum();
+        if let Dtz::Plies(dtz) = self.table_dtz(state, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + if cursed { 100 } else { 0 }) * sign);
+        }
+
+        // Best distance of the moves keeping the outcome
+        let mut position = Position::new(HashedState::new(state.clone(), NoopHasher {}));
+        let pawns = state.active_boards().pawn;
+        let mut best: Option<i32> = None;
+        for m in position.legal_moves() {
+            let zeroing = m.code().is_capture() || pawns.get(m.from());
+            position.make(m);
+            let child = position.state.get();
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(child, false)?.0)
+            } else {
+                -self.dtz(child)?
+            };
+            if dtz == 1 && child.is_check() && position.legal_moves().is_empty() {
+                best = Some(1);
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz.signum() == sign && best.is_none_or(|best| dtz < best) {
+                best = Some(dtz);
+            }
+            position.unmake(m);
+        }
+        // Without legal moves the side to move is mated
+        Some(best.unwrap_or(-1))
+    }
+}
+
+impl Tablebase for SyzygyTablebase {
+    fn max_pieces(&self) -> u32 {
+        // Trivial draws are known without any table
+        self.max_pieces.max(3)
+    }
+
+    fn probe_wdl(&self, state: &State) -> Option<Wdl> {
+        if piece_count(state) > self.max_pieces() {
+            return None;
+        }
+        self.search(state, false).map(|(wdl, _)| wdl)
+    }
+
+    fn probe_dtz(&self, state: &State) -> Option<i32> {
+        if piece_count(state) > self.max_pieces() {
+            return None;
+        }
+        self.dtz(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::{
+        state::chess_board::PieceType,
+        tablebase::{Dtm, EndgameTables},
+    };
+
+    use super::*;
+    use writer::{outcome, positions};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("syzygy_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_open() {
+        let dir = temp_dir("open");
+        fs::write(dir.join("KQvK.rtbw"), WDL_MAGIC).unwrap();
+        fs::write(dir.join("KQvK.rtbz"), DTZ_MAGIC).unwrap();
+        fs::write(dir.join("KRPvKR.rtbw"), WDL_MAGIC).unwrap();
+        fs::write(dir.join("README.txt"), "not a table").unwrap();
+
+        let tablebase = SyzygyTablebase::open(&dir).unwrap();
+        assert_eq!(tablebase.max_pieces(), 5);
+
+        let state = State::from_fen("8/8/8/4k3/8/8/8/3QK3 w - - 0 1");
+        let (files, color) = tablebase.table(&state).unwrap();
+        assert_eq!(color, Color::White);
+        assert!(files.wdl.is_some() && files.dtz.is_some());
+
+        // Same table with colors swapped
+        let state = State::from_fen("3rk3/3p4/8/8/8/8/8/3RK3 b - - 0 1");
+        let (files, color) = tablebase.table(&state).unwrap();
+        assert_eq!(color, Color::Black);
+        assert!(files.wdl.is_some() && files.dtz.is_none());
+
+        assert!(
+            tablebase
+                .table(&State::from_fen("8/8/8/4k3/8/8/8/3RK3 w - - 0 1"))
+                .is_none()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_invalid_table() {
+        let dir = temp_dir("invalid");
+        fs::write(dir.join("KQvK.rtbw"), DTZ_MAGIC).unwrap();
+        let error = SyzygyTablebase::open(&dir).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_material_key_format() {
+        assert!(is_material_key("KQvK"));
+        assert!(is_material_key("KRPvKR"));
+        assert!(!is_material_key("KQK"));
+        assert!(!is_material_key("QKvK"));
+        assert!(!is_material_key("KXvK"));
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let tablebase = SyzygyTablebase::default();
+        for (fen, result) in [
+            ("8/8/8/4k3/8/8/8/4K3 w - - 0 1", Some(Wdl::Draw)),
+            ("8/8/8/4k3/8/8/8/3BK3 b - - 0 1", Some(Wdl::Draw)),
+            ("8/8/8/4k3/8/8/8/3NK3 w - - 0 1", Some(Wdl::Draw)),
+            ("8/8/8/4k3/8/8/8/2NNK3 w - - 0 1", None),
+            ("8/8/8/4k3/8/8/8/3RK3 w - - 0 1", None),
+        ] {
+            assert_eq!(
+                tablebase.probe_wdl(&State::from_fen(fen)),
+                result,
+                "{}",
+                fen
+            );
+        }
+    }
+
+    /// The same position with the colors swapped and the board mirrored.
+    fn mirror(state: &State) -> State {
+        let fen = state.to_fen();
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let board: Vec<String> = fields[0]
+            .split('/')
+            .rev()
+            .map(|rank| {
+                rank.chars()
+                    .map(|c| match c.is_ascii_uppercase() {
+                        true => c.to_ascii_lowercase(),
+                        false => c.to_ascii_uppercase(),
+                    })
+                    .collect()
+            })
+            .collect();
+        let stm = if fields[1] == "w" { "b" } else { "w" };
+        State::from_fen(&format!("{} {} - - 0 1", board.join("/"), stm))
+    }
+
+    #[test]
+    fn test_fixture_wdl() {
+        let tables = EndgameTables::generate(&["KQK", "KRK", "KPK"]).unwrap();
+        let tablebase = SyzygyTablebase::open(FIXTURES).unwrap();
+        assert_eq!(tablebase.max_pieces(), 4);
+        for (piece, step) in [
+            (PieceType::Queen, 101),
+            (PieceType::Rook, 101),
+            (PieceType::Pawn, 53),
+        ] {
+            let states = positions(&[piece, PieceType::King], &[Color::White, Color::Black]);
+            for (_, state) in states.step_by(step) {
+                let wdl = outcome(&tables, &state);
+                assert_eq!(tablebase.probe_wdl(&state), Some(wdl), "{}", state.to_fen());
+                let mirrored = mirror(&state);
+                assert_eq!(
+                    tablebase.probe_wdl(&mirrored),
+                    Some(wdl),
+                    "{}",
+                    mirrored.to_fen()
+                );
+            }
+        }
+
+        // Two knights can't force mate, but can mate
+        for (fen, wdl) in [
+            ("7k/4N3/3N2K1/8/8/8/8/8 w - - 0 1", Wdl::Win),
+            ("7k/4NN2/6K1/8/8/8/8/8 b - - 0 1", Wdl::Loss),
+            ("8/8/8/4k3/8/8/8/1NN1K3 w - - 0 1", Wdl::Draw),
+            ("8/8/8/4k3/8/8/8/1NN1K3 b - - 0 1", Wdl::Draw),
+        ] {
+            let state = State::from_fen(fen);
+            assert_eq!(tablebase.probe_wdl(&state), Some(wdl), "{}", fen);
+            assert_eq!(tablebase.probe_wdl(&mirror(&state)), Some(wdl), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_fixture_dtz() {
+        let tables = EndgameTables::generate(&["KQK", "KRK", "KPK"]).unwrap();
+        let tablebase = SyzygyTablebase::open(FIXTURES).unwrap();
+        // Without pawns the only zeroing move is the mate, so the distance to
+        // zeroing is the distance to mate
+        for piece in [PieceType::Queen, PieceType::Rook] {
+            let states = positions(&[piece, PieceType::King], &[Color::White, Color::Black]);
+            for (_, state) in states.step_by(101) {
+                let dtz = match tables.probe(&state).unwrap() {
+                    Dtm::Win(plies) => plies as i32,
+                    Dtm::Loss(0) => -1,
+                    Dtm::Loss(plies) => -(plies as i32),
+                    Dtm::Draw => 0,
+                };
+                assert_eq!(tablebase.probe_dtz(&state), Some(dtz), "{}", state.to_fen());
+                let mirrored = mirror(&state);
+                assert_eq!(
+                    tablebase.probe_dtz(&mirrored),
+                    Some(dtz),
+                    "{}",
+                    mirrored.to_fen()
+                );
+            }
+        }
+
+        let states = positions(
+            &[PieceType::Pawn, PieceType::King],
+            &[Color::White, Color::Black],
+        );
+        for (_, state) in states.step_by(53) {
+            let dtz = tablebase.probe_dtz(&state).unwrap();
+            let wdl = outcome(&tables, &state);
+            assert_eq!(dtz.signum(), (wdl as i32).signum(), "{}", state.to_fen());
+        }
+        for (fen, dtz) in [
+            // Promotion wins at once, without the table
+            ("8/4P3/8/8/8/8/k7/4K3 w - - 0 1", 1),
+            // The king has to step aside first, then the pawn promotes
+            ("4K3/4P3/8/8/8/8/8/k7 w - - 0 1", 3),
+            ("4K3/4P3/8/8/8/8/8/k7 b - - 0 1", -4),
+            ("8/3KP3/8/8/8/8/8/k7 b - - 0 1", -2),
+            // Black takes the pawn
+            ("8/8/8/8/8/8/3kP3/7K b - - 0 1", 0),
+        ] {
+            assert_eq!(
+                tablebase.probe_dtz(&State::from_fen(fen)),
+                Some(dtz),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn test_published_values() {
+        // Values from endgame theory rather than from the tables the fixtures
+        // were written from
+        let tablebase = SyzygyTablebase::open(FIXTURES).unwrap();
+        for (fen, wdl, dtz) in [
+            // Mate in one, and mated
+            ("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1", Wdl::Win, 1),
+            ("7k/8/6K1/8/8/8/8/R7 w - - 0 1", Wdl::Win, 1),
+            ("R6k/8/6K1/8/8/8/8/8 b - - 0 1", Wdl::Loss, -1),
+            // Stalemate
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Wdl::Draw, 0),
+            // The king on the sixth rank in front of its pawn wins whoever
+            // moves, the pawn pushes once the king stepped aside
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Win, 3),
+            ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss, -4),
+            // The defending king in front of the pawn holds, or is stalemated
+            ("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", Wdl::Draw, 0),
+            ("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1", Wdl::Draw, 0),
+            // A rook pawn with the defending king in the corner is a draw
+            ("k7/8/K7/P7/8/8/8/8 w - - 0 1", Wdl::Draw, 0),
+            // Rule of the square
+            ("7k/8/8/8/P7/8/8/K7 b - - 0 1", Wdl::Loss, -2),
+            ("8/3k4/8/8/P7/8/8/K7 b - - 0 1", Wdl::Draw, 0),
+        ] {
+            let state = State::from_fen(fen);
+            assert_eq!(tablebase.probe_wdl(&state), Some(wdl), "{}", fen);
+            assert_eq!(tablebase.probe_dtz(&state), Some(dtz), "{}", fen);
+        }
+
+        // The longest wins are mate in 10 with the queen and mate in 16 with
+        // the rook. Every position has a mirror with white's king in the
+        // a1-d1-d4 triangle.
+        let in_triangle = |state: &State| {
+            let king = state.boards.white.king.get_first_square().unwrap().get();
+            let (file, rank) = (king % 8, king / 8);
+            file < 4 && rank <= file
+        };
+        for (piece, longest) in [(PieceType::Queen, 19), (PieceType::Rook, 31)] {
+            let states = positions(&[piece, PieceType::King], &[Color::White]);
+            let max = states
+                .filter(|(_, state)| in_triangle(state))
+                .filter_map(|(_, state)| tablebase.probe_dtz(&state))
+                .max();
+            assert_eq!(max, Some(longest));
+        }
+    }
+}
@@ -0,0 +1,738 @@
+// Layout of a Syzygy table file, after the 4 byte magic:
+//   - flags: split (the two sides to move have their own subtables) and pawns
+//   - per file with pawns, or once without: the order of the groups of pieces
+//     and the pieces, one nibble per side to move
+//   - per subtable: the Huffman code and the symbol tree, or a single value
+//   - the DTZ value maps
+//   - per subtable: a sparse index into the block lengths, the block lengths
+//     and the 64 byte aligned compressed blocks
+// Values are compressed by recursive pairing: a symbol is a value or a pair
+// of symbols, and each block is a stream of canonical Huffman codes of symbols.
+// Longer codes belong to lower symbols.
+//
+// Table values aren't always the outcome of their position. WDL tables may
+// store anything for positions won by a capture and DTZ tables only store one
+// side to move, see `SyzygyTablebase` for the probing rules.
+
+use chess_core::{
+    color::Color,
+    state::{State, bitboard::BitBoard, chess_board::PieceType},
+};
+
+use super::index::{MAPS, file, off_diagonal, rank};
+use crate::tablebase::Wdl;
+
+pub(super) const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+pub(super) const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+/// Flags of the file.
+pub(super) const SPLIT: u8 = 1;
+pub(super) const HAS_PAWNS: u8 = 2;
+
+/// Flags of a subtable, all but the last only used by DTZ tables.
+pub(super) const STM: u8 = 1;
+pub(super) const MAPPED: u8 = 2;
+pub(super) const WIN_PLIES: u8 = 4;
+pub(super) const LOSS_PLIES: u8 = 8;
+pub(super) const WIDE: u8 = 16;
+pub(super) const SINGLE_VALUE: u8 = 128;
+
+/// Most pieces on the board of a table, kings included.
+const MAX_PIECES: usize = 7;
+
+/// Size of the leading group of pawnless tables, by whether it has 3 pieces
+/// or only the two kings.
+const UNIQUE_PIECES_SIZE: u64 = 31332;
+const KINGS_SIZE: u64 = 462;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum Kind {
+    Wdl,
+    Dtz,
+}
+
+/// A piece as stored in the tables: 1 to 6 for white pawn to king, plus 8 for black.
+fn piece_code(color: Color, piece: PieceType) -> u8 {
+    piece as u8 + 1 + 8 * color.into_bits()
+}
+
+/// Decoding information of one subtable.
+#[derive(Clone, Default)]
+pub(super) struct Subtable {
+    pub flags: u8,
+    /// Pieces in the order they are encoded.
+    pub pieces: [u8; MAX_PIECES],
+    /// Number of pieces of each group, zero terminated.
+    pub group_len: [usize; MAX_PIECES + 1],
+    /// Factor of each group in the index, the size of the subtable last.
+    pub group_index: [u64; MAX_PIECES + 1],
+    /// Value of a single valued subtable, shortest code length otherwise.
+    min_sym_len: u8,
+    block_size: usize,
+    span: u64,
+    sparse_index_size: usize,
+    block_length_size: usize,
+    num_blocks: usize,
+    lowest_sym: usize,
+    /// Lowest code of each length, left aligned on 64 bits.
+    base64: Vec<u64>,
+    /// Number of values of each symbol minus one.
+    symlen: Vec<u8>,
+    btree: usize,
+    sparse_index: usize,
+    block_lengths: usize,
+    data: usize,
+    /// Start of the DTZ value map by WDL outcome.
+    map_index: [usize; 4],
+}
+
+impl Subtable {
+    pub fn size(&self) -> u64 {
+        let groups = self.group_len.iter().position(|len| *len == 0).unwrap_or(0);
+        self.group_index[groups]
+    }
+}
+
+/// Where a position is stored.
+pub(super) struct Location {
+    pub side: usize,
+    pub file: usize,
+    pub index: u64,
+}
+
+/// A table file, parsed on first use.
+pub(super) struct Table {
+    kind: Kind,
+    bytes: Vec<u8>,
+    piece_count: usize,
+    has_pawns: bool,
+    /// Some piece other than the kings is alone of its type and color.
+    has_unique_pieces: bool,
+    /// Both sides have the same material.
+    symmetric: bool,
+    /// Pawns of the leading color and of the other one.
+    pawn_count: [u8; 2],
+    /// Subtables by side to move, then by file.
+    subtables: Vec<Vec<Subtable>>,
+    map: usize,
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> Result<u16, &'static str> {
+    bytes
+        .get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or("Truncated table")
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> Result<u32, &'static str> {
+    bytes
+        .get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or("Truncated table")
+}
+
+fn byte(bytes: &[u8], at: usize) -> Result<u8, &'static str> {
+    bytes.get(at).copied().ok_or("Truncated table")
+}
+
+/// Bytes of the compressed stream, zeros past the end of the file.
+fn stream(bytes: &[u8], at: usize, len: usize) -> u64 {
+    (0..len).fold(0, |value, i| {
+        (value << 8) | *bytes.get(at + i).unwrap_or(&0) as u64
+    })
+}
+
+impl Table {
+    /// Parse the file of the table named `key`, like `KRPvKR`.
+    pub fn new(kind: Kind, key: &str, bytes: Vec<u8>) -> Result<Self, &'static str> {
+        let mut table = Table::layout(kind, key, bytes)?;
+        let mut at = table.parse_pieces()?;
+        at += at & 1;
+        at = table.parse_sizes(at)?;
+        if at > table.bytes.len() {
+            return Err("Truncated table");
+        }
+        Ok(table)
+    }
+
+    /// Table of the material of `key`, before parsing its file.
+    pub fn layout(kind: Kind, key: &str, bytes: Vec<u8>) -> Result<Self, &'static str> {
+        let (white, black) = key.split_once('v').ok_or("Invalid material key")?;
+        let count = |side: &str, letter: char| side.chars().filter(|c| *c == letter).count();
+        let piece_count = white.len() + black.len();
+        if piece_count > MAX_PIECES {
+            return Err("Too many pieces for a table");
+        }
+        let has_unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1));
+        let (white_pawns, black_pawns) = (count(white, 'P') as u8, count(black, 'P') as u8);
+        // The side with fewer pawns leads, which compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+        let has_pawns = white_pawns + black_pawns > 0;
+        let symmetric = white == black;
+        let sides = if kind == Kind::Wdl && !symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if has_pawns { 4 } else { 1 };
+        Ok(Table {
+            kind,
+            bytes,
+            piece_count,
+            has_pawns,
+            has_unique_pieces,
+            symmetric,
+            pawn_count,
+            subtables: vec![vec![Subtable::default(); files]; sides],
+            map: 0,
+        })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[cfg(test)]
+    pub fn subtables(&self) -> &[Vec<Subtable>] {
+        &self.subtables
+    }
+
+    /// Pieces and groups of every subtable, returns the end of the piece lists.
+    pub fn parse_pieces(&mut self) -> Result<usize, &'static str> {
+        let flags = byte(&self.bytes, 4)?;
+        if (flags & HAS_PAWNS != 0) != self.has_pawns || (flags & SPLIT != 0) == self.symmetric {
+            return Err("Table flags don't match the material");
+        }
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut at = 5;
+        for file in 0..self.subtables[0].len() {
+            let first = byte(&self.bytes, at)?;
+            let second = if both_pawns {
+                byte(&self.bytes, at + 1)?
+            } else {
+                0xFF
+            };
+            let orders = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            at += 1 + both_pawns as usize;
+            for k in 0..self.piece_count {
+                let code = byte(&self.bytes, at + k)?;
+                for (side, subtables) in self.subtables.iter_mut().enumerate() {
+                    subtables[file].pieces[k] = if side == 1 { code >> 4 } else { code & 0xF };
+                }
+            }
+            at += self.piece_count;
+            for (side, order) in orders.into_iter().enumerate().take(self.subtables.len()) {
+                self.set_groups(side, file, order)?;
+            }
+        }
+        Ok(at)
+    }
+
+    /// Group the pieces encoded together and compute the factor of each group.
+    /// Groups are in piece order, but are multiplied in the order of the file.
+    fn set_groups(&mut self, side: usize, file: usize, order: [u8; 2]) -> Result<(), &'static str> {
+        let (has_pawns, has_unique_pieces) = (self.has_pawns, self.has_unique_pieces);
+        let both_pawns = has_pawns && self.pawn_count[1] > 0;
+        let piece_count = self.piece_count;
+        let subtable = &mut self.subtables[side][file];
+
+        let mut first_len: i32 = if has_pawns {
+            0
+        } else if has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        let mut groups = 0;
+        subtable.group_len[0] = 1;
+        for i in 1..piece_count {
+            first_len -= 1;
+            if first_len > 0 || subtable.pieces[i] == subtable.pieces[i - 1] {
+                subtable.group_len[groups] += 1;
+            } else {
+                groups += 1;
+                subtable.group_len[groups] = 1;
+            }
+        }
+        groups += 1;
+        subtable.group_len[groups] = 0;
+        if subtable.group_len.iter().any(|len| *len > 5) {
+            return Err("Too many pieces in a group");
+        }
+
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares =
+            64 - subtable.group_len[0] - if both_pawns { subtable.group_len[1] } else { 0 };
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while next < groups || k == order[0] || k == order[1] {
+            let factor = if k == order[0] {
+                subtable.group_index[0] = index;
+                if has_pawns {
+                    MAPS.lead_pawns_size[subtable.group_len[0]][file]
+                } else if has_unique_pieces {
+                    UNIQUE_PIECES_SIZE
+                } else {
+                    KINGS_SIZE
+                }
+            } else if k == order[1] {
+                subtable.group_index[1] = index;
+                MAPS.binomial[subtable.group_len[1]][48 - subtable.group_len[0]]
+            } else {
+                subtable.group_index[next] = index;
+                let factor = MAPS.binomial[subtable.group_len[next]][free_squares];
+                free_squares -= subtable.group_len[next];
+                next += 1;
+                factor
+            };
+            index = index.checked_mul(factor).ok_or("Invalid group order")?;
+            k += 1;
+        }
+        subtable.group_index[groups] = index;
+        Ok(())
+    }
+
+    /// Huffman codes, DTZ maps and offsets of the blocks, from `at`.
+    fn parse_sizes(&mut self, mut at: usize) -> Result<usize, &'static str> {
+        let (sides, files) = (self.subtables.len(), self.subtables[0].len());
+        for file in 0..files {
+            for side in 0..sides {
+                at = self.set_sizes(side, file, at)?;
+            }
+        }
+
+        if self.kind == Kind::Dtz {
+            self.map = at;
+            for file in 0..files {
+                let flags = self.subtables[0][file].flags;
+                if flags & MAPPED == 0 {
+                    continue;
+                }
+                for wdl in 0..4 {
+                    if flags & WIDE != 0 {
+                        at += at & 1;
+                        self.subtables[0][file].map_index[wdl] = at + 2;
+                        at += 2 * u16_le(&self.bytes, at)? as usize + 2;
+                    } else {
+                        self.subtables[0][file].map_index[wdl] = at + 1;
+                        at += byte(&self.bytes, at)? as usize + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let subtable = &mut self.subtables[side][file];
+                subtable.sparse_index = at;
+                at += 6 * subtable.sparse_index_size;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let subtable = &mut self.subtables[side][file];
+                subtable.block_lengths = at;
+                at += 2 * subtable.block_length_size;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let subtable = &mut self.subtables[side][file];
+                at = (at + 0x3F) & !0x3F;
+                subtable.data = at;
+                at += subtable.num_blocks * subtable.block_size;
+            }
+        }
+        Ok(at)
+    }
+
+    fn set_sizes(
+        &mut self,
+        side: usize,
+        file: usize,
+        mut at: usize,
+    ) -> Result<usize, &'static str> {
+        let bytes = &self.bytes;
+        let subtable = &mut self.subtables[side][file];
+        subtable.flags = byte(bytes, at)?;
+        at += 1;
+        if subtable.flags & SINGLE_VALUE != 0 {
+            subtable.min_sym_len = byte(bytes, at)?;
+            return Ok(at + 1);
+        }
+
+        let size = subtable.size();
+        let (block_size, span) = (byte(bytes, at)?, byte(bytes, at + 1)?);
+        if block_size >= 32 || span >= 32 {
+            return Err("Invalid block size");
+        }
+        subtable.block_size = 1 << block_size;
+        subtable.span = 1 << span;
+        subtable.sparse_index_size = size.div_ceil(subtable.span) as usize;
+        let padding = byte(bytes, at + 2)? as usize;
+        subtable.num_blocks = u32_le(bytes, at + 3)? as usize;
+        subtable.block_length_size = subtable.num_blocks + padding;
+        let max_sym_len = byte(bytes, at + 7)?;
+        subtable.min_sym_len = byte(bytes, at + 8)?;
+        if subtable.min_sym_len == 0 || max_sym_len > 64 || max_sym_len < subtable.min_sym_len {
+            return Err("Invalid code lengths");
+        }
+        at += 9;
+
+        // Codes of each length follow the ones of the next length
+        subtable.lowest_sym = at;
+        let lengths = (max_sym_len - subtable.min_sym_len + 1) as usize;
+        let mut base64 = vec![0u64; lengths];
+        for i in (0..lengths - 1).rev() {
+            let (lowest, next_lowest) =
+                (u16_le(bytes, at + 2 * i)?, u16_le(bytes, at + 2 * i + 2)?);
+            base64[i] = (base64[i + 1] + lowest as u64).wrapping_sub(next_lowest as u64) / 2;
+        }
+        for (i, base) in base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl(64 - i as u32 - subtable.min_sym_len as u32)
+                .unwrap_or(0);
+        }
+        subtable.base64 = base64;
+        at += 2 * lengths;
+
+        let symbols = u16_le(bytes, at)? as usize;
+        at += 2;
+        subtable.btree = at;
+        if bytes.len() < at + 3 * symbols {
+            return Err("Truncated table");
+        }
+        let mut symlen = vec![None; symbols];
+        for symbol in 0..symbols {
+            Self::set_symlen(bytes, at, symbol, &mut symlen)?;
+        }
+        subtable.symlen = symlen.into_iter().map(|len| len.unwrap_or(0)).collect();
+        Ok(at + 3 * symbols + (symbols & 1))
+    }
+
+    /// Left and right children of a symbol, the value as the left one for leaves.
+    fn children(bytes: &[u8], btree: usize, symbol: usize) -> (usize, usize) {
+        let lr = &bytes[btree + 3 * symbol..btree + 3 * symbol + 3];
+        let left = ((lr[1] as usize & 0xF) << 8) | lr[0] as usize;
+        let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+        (left, right)
+    }
+
+    /// Number of values of `symbol` minus one.
+    fn set_symlen(
+        bytes: &[u8],
+        btree: usize,
+        symbol: usize,
+        symlen: &mut Vec<Option<u8>>,
+    ) -> Result<u8, &'static str> {
+        if let Some(len) = symlen[symbol] {
+            return Ok(len);
+        }
+        // Visited before the children, so a cycle can't recurse forever
+        symlen[symbol] = Some(0);
+        let (left, right) = Self::children(bytes, btree, symbol);
+        if right == 0xFFF {
+            return Ok(0);
+        }
+        if left >= symlen.len() || right >= symlen.len() {
+            return Err("Invalid symbol tree");
+        }
+        let left = Self::set_symlen(bytes, btree, left, symlen)?;
+        let right = Self::set_symlen(bytes, btree, right, symlen)?;
+        let len = left
+            .checked_add(right)
+            .and_then(|len| len.checked_add(1))
+            .ok_or("Invalid symbol tree")?;
+        symlen[symbol] = Some(len);
+        Ok(len)
+    }
+
+    /// Value stored at `index` of a subtable, `None` if the file is corrupt.
+    pub fn decompress(&self, subtable: &Subtable, index: u64) -> Option<u16> {
+        if subtable.flags & SINGLE_VALUE != 0 {
+            return Some(subtable.min_sym_len as u16);
+        }
+        let bytes = &self.bytes;
+        let block_length = |block: usize| {
+            u16_le(bytes, subtable.block_lengths + 2 * block)
+                .ok()
+                .filter(|_| block < subtable.block_length_size)
+                .map(|len| len as i64)
+        };
+
+        // The sparse index gives the block and offset of the value in the
+        // middle of each span
+        let k = (index / subtable.span) as usize;
+        if k >= subtable.sparse_index_size {
+            return None;
+        }
+        let entry = subtable.sparse_index + 6 * k;
+        let mut block = u32_le(bytes, entry).ok()? as usize;
+        let mut offset = u16_le(bytes, entry + 4).ok()? as i64;
+        offset += (index % subtable.span) as i64 - (subtable.span / 2) as i64;
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Skip the symbols of the block before the value
+        let mut at = subtable.data + block * subtable.block_size;
+        let mut buffer = stream(bytes, at, 8);
+        let mut buffered = 64;
+        at += 8;
+        let min_sym_len = subtable.min_sym_len as u32;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buffer < *subtable.base64.get(len)? {
+                len += 1;
+            }
+            let code = (buffer - subtable.base64[len]) >> (64 - len as u32 - min_sym_len);
+            symbol = code as usize + u16_le(bytes, subtable.lowest_sym + 2 * len).ok()? as usize;
+            let values = *subtable.symlen.get(symbol)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            let len = len as u32 + min_sym_len;
+            buffer = buffer.checked_shl(len).unwrap_or(0);
+            buffered -= len as i32;
+            if buffered <= 32 {
+                buffered += 32;
+                buffer |= stream(bytes, at, 4) << (64 - buffered);
+                at += 4;
+            }
+        }
+
+        // Then walk down the pairs to the value
+        while subtable.symlen[symbol] != 0 {
+            let (left, right) = Self::children(bytes, subtable.btree, symbol);
+            let left_values = subtable.symlen[left] as i64 + 1;
+            if offset < left_values {
+                symbol = left;
+            } else {
+                offset -= left_values;
+                symbol = right;
+            }
+        }
+        Some(Self::children(bytes, subtable.btree, symbol).0 as u16)
+    }
+
+    /// Where the position is stored, `None` for DTZ tables storing the other
+    /// side to move. `flip` tells that the table's first side is black in the
+    /// position.
+    pub fn locate(&self, state: &State, flip: bool) -> Option<Location> {
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ state.flags.active_color().into_bits() as usize;
+
+        let mut squares = [0u8; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = BitBoard::EMPTY;
+        let mut file_index = 0;
+        if self.has_pawns {
+            // Pawns come first, of the leading color
+            let lead = self.subtables[0][0].pieces[0] ^ flip_color;
+            lead_pawns = state.boards[Color::from_bits(lead >> 3)].pawn;
+            let mut pawns = lead_pawns;
+            while let Some(square) = pawns.pop_first_square() {
+                if size == MAX_PIECES {
+                    return None;
+                }
+                squares[size] = square.get() ^ flip_squares;
+                size += 1;
+            }
+            let first = (0..size).max_by_key(|i| MAPS.pawns[squares[*i] as usize])?;
+            squares.swap(0, first);
+            file_index = file(squares[0]).min(7 - file(squares[0])) as usize;
+        }
+        let lead_count = size;
+
+        if self.kind == Kind::Dtz {
+            let flags = self.subtables[0][file_index].flags;
+            if (flags & STM) as usize != stm && (self.has_pawns || !self.symmetric) {
+                return None;
+            }
+        }
+
+        for color in Color::as_array() {
+            for piece in PieceType::as_array() {
+                let mut board = state.boards[color][piece];
+                if piece == PieceType::Pawn {
+                    board &= !lead_pawns;
+                }
+                while let Some(square) = board.pop_first_square() {
+                    if size == MAX_PIECES {
+                        return None;
+                    }
+                    squares[size] = square.get() ^ flip_squares;
+                    pieces[size] = piece_code(color, piece) ^ flip_color;
+                    size += 1;
+                }
+            }
+        }
+        if size != self.piece_count {
+            return None;
+        }
+
+        let side = stm % self.subtables.len();
+        let subtable = &self.subtables[side][file_index];
+        // Same piece order as the table
+        for i in lead_count..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|j| subtable.pieces[i] == pieces[*j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        let index = self.encode(subtable, &mut squares[..size], lead_count);
+        Some(Location {
+            side,
+            file: file_index,
+            index,
+        })
+    }
+
+    /// Index of the squares of the pieces, in the order of the subtable.
+    fn encode(&self, subtable: &Subtable, squares: &mut [u8], lead_count: usize) -> u64 {
+        // The leading piece goes to the a to d files
+        if file(squares[0]) > 3 {
+            for square in squares.iter_mut() {
+                *square ^= 7;
+            }
+        }
+
+        let mut index;
+        if self.has_pawns {
+            index = MAPS.lead_pawn_index[lead_count][squares[0] as usize];
+            squares[1..lead_count].sort_by_key(|square| MAPS.pawns[*square as usize]);
+            for (i, square) in squares.iter().enumerate().take(lead_count).skip(1) {
+                index += MAPS.binomial[i][MAPS.pawns[*square as usize] as usize];
+            }
+        } else {
+            // Then to the first four ranks, and below the diagonal
+            if rank(squares[0]) > 3 {
+                for square in squares.iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..subtable.group_len[0] {
+                match off_diagonal(squares[i]) {
+                    0 => continue,
+                    off if off > 0 => {
+                        for square in squares[i..].iter_mut() {
+                            *square = ((*square >> 3) | (*square << 3)) & 63;
+                        }
+                    }
+                    _ => {}
+                }
+                break;
+            }
+
+            index = if self.has_unique_pieces {
+                let (a, b, c) = (squares[0] as u64, squares[1] as u64, squares[2] as u64);
+                let adjust1 = (b > a) as u64;
+                let adjust2 = (c > a) as u64 + (c > b) as u64;
+                let rank = |square: u64| rank(square as u8) as u64;
+                let slot = |square: u64| MAPS.a1d1d4[square as usize].unwrap_or(0) as u64;
+                let below = |square: u64| MAPS.b1h1h7[square as usize] as u64;
+                if off_diagonal(a as u8) != 0 {
+                    (slot(a) * 63 + b - adjust1) * 62 + c - adjust2
+                } else if off_diagonal(b as u8) != 0 {
+                    (6 * 63 + rank(a) * 28 + below(b)) * 62 + c - adjust2
+                } else if off_diagonal(c as u8) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(a) * 7 * 28
+                        + (rank(b) - adjust1) * 28
+                        + below(c)
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(a) * 7 * 6
+                        + (rank(b) - adjust1) * 6
+                        + (rank(c) - adjust2)
+                }
+            } else {
+                let slot = MAPS.a1d1d4[squares[0] as usize].unwrap_or(0) as usize;
+                MAPS.kk[slot][squares[1] as usize] as u64
+            };
+        }
+
+        // Other groups as combinations of the squares left by the previous ones
+        index *= subtable.group_index[0];
+        let mut start = subtable.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut group = 1;
+        while subtable.group_len[group] != 0 {
+            let len = subtable.group_len[group];
+            squares[start..start + len].sort_unstable();
+            let mut combination = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|s| square > **s).count();
+                let square = square as usize - adjust - if remaining_pawns { 8 } else { 0 };
+                combination += MAPS.binomial[i + 1][square];
+            }
+            remaining_pawns = false;
+            index += combination * subtable.group_index[group];
+            start += len;
+            group += 1;
+        }
+        index
+    }
+
+    /// Outcome stored for the position, see `locate`.
+    pub fn wdl(&self, location: &Location) -> Option<Wdl> {
+        let subtable = &self.subtables[location.side][location.file];
+        match self.decompress(subtable, location.index)? {
+            0 => Some(Wdl::Loss),
+            1 => Some(Wdl::BlessedLoss),
+            2 => Some(Wdl::Draw),
+            3 => Some(Wdl::CursedWin),
+            4 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+
+    /// Distance to zeroing stored for the position in plies, given its outcome.
+    pub fn dtz(&self, location: &Location, wdl: Wdl) -> Option<i32> {
+        let subtable = &self.subtables[0][location.file];
+        let mut value = self.decompress(subtable, location.index)? as usize;
+        let flags = subtable.flags;
+        if flags & MAPPED != 0 {
+            let start = subtable.map_index[match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+            }];
+            value = if flags & WIDE != 0 {
+                u16_le(&self.bytes, start + 2 * value).ok()? as usize
+            } else {
+                byte(&self.bytes, start + value).ok()? as usize
+            };
+        }
+        // Distances are stored in moves unless they need the precision of plies
+        let plies = match wdl {
+            Wdl::Win => flags & WIN_PLIES != 0,
+            Wdl::Loss => flags & LOSS_PLIES != 0,
+            _ => false,
+        };
+        let value = value as i32 * if plies { 1 } else { 2 };
+        Some(value + 1)
+    }
+}
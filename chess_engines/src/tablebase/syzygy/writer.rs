@@ -0,0 +1,550 @@
+// Writes the Syzygy fixture tables of the tests from the distance to mate
+// tables of chess_core. The fixtures are regenerated with
+//   cargo test --release -p chess_engines generate_fixtures -- --ignored
+//
+// Compression is kept simple: a symbol is either a value or a run of 2^k equal
+// values, coded with a length limited canonical Huffman code. Values of
+// indices no position maps to don't matter and repeat the previous value.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use chess_core::{
+    color::Color,
+    hash::{HashedState, NoopHasher},
+    position::Position,
+    square::Square,
+    state::{
+        State,
+        bitboard::BitBoard,
+        chess_board::{ChessBoard, ChessBoardSide, PieceType},
+        flags::StateFlags,
+    },
+    tablebase::{Dtm, EndgameTables},
+};
+
+use super::{
+    FIXTURES,
+    table::{
+        DTZ_MAGIC, HAS_PAWNS, Kind, LOSS_PLIES, SINGLE_VALUE, SPLIT, Table, WDL_MAGIC, WIN_PLIES,
+    },
+};
+use crate::tablebase::Wdl;
+
+const BLOCK_SIZE_LOG2: u8 = 10;
+const SPAN_LOG2: u8 = 12;
+/// Longest run of equal values in one symbol, as a power of two.
+const MAX_RUN_LOG2: u8 = 8;
+/// Longest code the decoder can read.
+const MAX_CODE_LEN: u32 = 32;
+/// Most values in a block, its length is stored on 16 bits.
+const MAX_BLOCK_VALUES: usize = 1 << 16;
+
+/// Outcome from chess_core's tables, for positions with at most one side
+/// having pieces besides its king.
+pub(super) fn outcome(tables: &EndgameTables, state: &State) -> Wdl {
+    match tables.probe(state).expect("position covered by the tables") {
+        Dtm::Win(_) => Wdl::Win,
+        Dtm::Loss(_) => Wdl::Loss,
+        Dtm::Draw => Wdl::Draw,
+    }
+}
+
+/// White's pieces on `squares`, the black king last, `None` if the position is illegal.
+fn position_at(white_pieces: &[PieceType], squares: &[u8], stm: Color) -> Option<State> {
+    let mut white = ChessBoardSide::EMPTY;
+    let mut black = ChessBoardSide::EMPTY;
+    let mut occupied = BitBoard::EMPTY;
+    for (i, square) in squares.iter().enumerate() {
+        let square = Square::from_bits(*square);
+        if occupied.get(square) {
+            return None;
+        }
+        occupied.set(square);
+        match white_pieces.get(i) {
+            Some(PieceType::Pawn) if square.rank() == 0 || square.rank() == 7 => return None,
+            Some(piece) => white[*piece].set(square),
+            None => black.king.set(square),
+        }
+    }
+    let mut flags = StateFlags::from_fen('w', "-");
+    if stm == Color::Black {
+        flags.toggle_color();
+    }
+    let state = State {
+        boards: ChessBoard { white, black },
+        en_passant: BitBoard::EMPTY,
+        flags,
+        halfmove: 0,
+    };
+    state.was_move_legal().then_some(state)
+}
+
+/// Every legal position with white's pieces and a black king, white to move
+/// first, with the raw index of their squares.
+pub(super) fn positions(
+    white_pieces: &[PieceType],
+    stm: &[Color],
+) -> impl Iterator<Item = (usize, State)> + use<> {
+    let count = white_pieces.len() + 1;
+    let raw_size = 64usize.pow(count as u32);
+    let white_pieces = white_pieces.to_vec();
+    let stm = stm.to_vec();
+    (0..stm.len() * raw_size).filter_map(move |raw| {
+        let mut squares = [0u8; 8];
+        let mut rest = raw;
+        for square in squares[..count].iter_mut().rev() {
+            *square = (rest % 64) as u8;
+            rest /= 64;
+        }
+        position_at(&white_pieces, &squares[..count], stm[rest]).map(|state| (raw, state))
+    })
+}
+
+/// Raw index of a position with white's king and one piece against the black
+/// king, as given by `positions`.
+fn raw_index(state: &State, piece: PieceType) -> usize {
+    let square = |board: BitBoard| board.get_first_square().unwrap().get() as usize;
+    let stm = state.flags.active_color() as usize;
+    ((stm * 64 + square(state.boards.white.king)) * 64 + square(state.boards.white[piece])) * 64
+        + square(state.boards.black.king)
+}
+
+enum Successor {
+    Zeroing(Wdl),
+    Mate,
+    Move(usize),
+}
+
+/// Distance to zeroing in plies of the positions of a king and `piece`
+/// against a king by raw index, as the probe computes it: a zeroing or mating
+/// move counts 1, the winner shortens the distance and the loser lengthens it.
+pub(super) fn distances_to_zeroing(tables: &EndgameTables, piece: PieceType) -> Vec<Option<i32>> {
+    let white_pieces = [PieceType::King, piece];
+    let mut dtz = vec![None; 2 * 64usize.pow(3)];
+    let mut pending = Vec::new();
+    for (raw, state) in positions(&white_pieces, &[Color::White, Color::Black]) {
+        let wdl = outcome(tables, &state);
+        if wdl == Wdl::Draw {
+            dtz[raw] = Some(0);
+            continue;
+        }
+        let mut position = Position::new(HashedState::new(state.clone(), NoopHasher {}));
+        let pawns = state.active_boards().pawn;
+        let mut successors = Vec::new();
+        for m in position.legal_moves() {
+            position.make(m);
+            let child = position.state.get();
+            successors.push(if m.code().is_capture() || pawns.get(m.from()) {
+                Successor::Zeroing(outcome(tables, child))
+            } else if child.is_check() && position.legal_moves().is_empty() {
+                Successor::Mate
+            } else {
+                Successor::Move(raw_index(position.state.get(), piece))
+            });
+            position.unmake(m);
+        }
+        pending.push((raw, wdl, successors));
+    }
+
+    // Round n settles the positions at n plies from zeroing
+    loop {
+        let mut settled = Vec::new();
+        for (raw, wdl, successors) in &pending {
+            let distance = if *wdl == Wdl::Win {
+                successors
+                    .iter()
+                    .filter_map(|successor| match successor {
+                        Successor::Zeroing(Wdl::Loss) | Successor::Mate => Some(1),
+                        Successor::Move(child) => dtz[*child]
+                            .filter(|dtz| *dtz < 0)
+                            .map(|dtz: i32| dtz.abs() + 1),
+                        _ => None,
+                    })
+                    .min()
+            } else {
+                successors
+                    .iter()
+                    .map(|successor| match successor {
+                        Successor::Move(child) => dtz[*child].map(|dtz| dtz + 1),
+                        _ => Some(1),
+                    })
+                    .try_fold(1, |longest, distance| Some(longest.max(distance?)))
+                    .map(|distance| -distance)
+            };
+            if let Some(distance) = distance {
+                settled.push((*raw, distance));
+            }
+        }
+        if settled.is_empty() {
+            break;
+        }
+        for (raw, distance) in settled {
+            assert!(distance.abs() <= 100, "fixtures have no cursed wins");
+            dtz[raw] = Some(distance);
+        }
+        pending.retain(|(raw, _, _)| dtz[*raw].is_none());
+    }
+    assert!(pending.is_empty());
+    dtz
+}
+
+/// Compressed values of one subtable.
+struct Compressed {
+    sizes: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    blocks: Vec<u8>,
+}
+
+/// Code lengths of a Huffman code for the frequencies, flattened until the
+/// longest code can be read.
+fn code_lengths(frequencies: &[u64]) -> Vec<u32> {
+    let mut frequencies = frequencies.to_vec();
+    loop {
+        // Nodes are (weight, symbols below)
+        let mut lengths = vec![0u32; frequencies.len()];
+        let mut nodes: Vec<(u64, Vec<usize>)> = frequencies
+            .iter()
+            .enumerate()
+            .map(|(symbol, frequency)| (*frequency, vec![symbol]))
+            .collect();
+        while nodes.len() > 1 {
+            nodes.sort_by_key(|(weight, symbols)| (std::cmp::Reverse(*weight), symbols[0]));
+            let (weight_a, symbols_a) = nodes.pop().unwrap();
+            let (weight_b, symbols_b) = nodes.pop().unwrap();
+            for symbol in symbols_a.iter().chain(&symbols_b) {
+                lengths[*symbol] += 1;
+            }
+            nodes.push((weight_a + weight_b, [symbols_a, symbols_b].concat()));
+        }
+        if lengths.iter().all(|len| *len <= MAX_CODE_LEN) {
+            return lengths;
+        }
+        for frequency in frequencies.iter_mut() {
+            *frequency = *frequency / 2 + 1;
+        }
+    }
+}
+
+/// Values as runs of 2^k equal values.
+fn tokenize(values: &[u16]) -> Vec<(u16, u8)> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < values.len() {
+        let run = values[i..].iter().take_while(|v| **v == values[i]).count();
+        let mut left = run;
+        while left > 0 {
+            let k = (left.ilog2() as u8).min(MAX_RUN_LOG2);
+            tokens.push((values[i], k));
+            left -= 1 << k;
+        }
+        i += run;
+    }
+    tokens
+}
+
+fn compress(flags: u8, values: &[u16]) -> Compressed {
+    if values.iter().all(|value| *value == values[0]) {
+        return Compressed {
+            sizes: vec![flags | SINGLE_VALUE, values[0] as u8],
+            sparse_index: Vec::new(),
+            block_lengths: Vec::new(),
+            blocks: Vec::new(),
+        };
+    }
+
+    let tokens = tokenize(values);
+    let mut frequencies: BTreeMap<(u16, u8), u64> = BTreeMap::new();
+    for token in &tokens {
+        *frequencies.entry(*token).or_default() += 1;
+    }
+    let coded: Vec<(u16, u8)> = frequencies.keys().copied().collect();
+    let lengths = code_lengths(&frequencies.values().copied().collect::<Vec<_>>());
+    let (min_len, max_len) = (
+        *lengths.iter().min().unwrap(),
+        *lengths.iter().max().unwrap(),
+    );
+
+    // Longer codes get lower symbols, runs only used inside others come last
+    let mut order: Vec<usize> = (0..coded.len()).collect();
+    order.sort_by_key(|i| (std::cmp::Reverse(lengths[*i]), coded[*i]));
+    let mut symbols: Vec<(u16, u8)> = order.iter().map(|i| coded[*i]).collect();
+    let mut code_len: BTreeMap<(u16, u8), u32> = BTreeMap::new();
+    for i in &order {
+        code_len.insert(coded[*i], lengths[*i]);
+    }
+    let mut next = 0;
+    while next < symbols.len() {
+        let (value, k) = symbols[next];
+        if k > 0 && !symbols.contains(&(value, k - 1)) {
+            symbols.push((value, k - 1));
+        }
+        next += 1;
+    }
+    let id: BTreeMap<(u16, u8), usize> = symbols.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+
+    // Canonical code, lengths from the shortest
+    let count = |len: u32| lengths.iter().filter(|l| **l == len).count() as u64;
+    let len_count = (max_len - min_len + 1) as usize;
+    let mut lowest = vec![0u64; len_count];
+    let mut base = vec![0u64; len_count];
+    for i in (0..len_count - 1).rev() {
+        let next_count = count(min_len + i as u32 + 1);
+        lowest[i] = lowest[i + 1] + next_count;
+        base[i] = (base[i + 1] + next_count) / 2;
+    }
+    let code = |symbol: (u16, u8)| {
+        let len = code_len[&symbol];
+        let i = (len - min_len) as usize;
+        (base[i] + id[&symbol] as u64 - lowest[i], len)
+    };
+
+    // Blocks hold whole symbols
+    let block_bits = 8usize << BLOCK_SIZE_LOG2;
+    let mut blocks: Vec<Vec<(u64, u32)>> = vec![Vec::new()];
+    let mut block_values = vec![0usize];
+    let mut used = 0;
+    for token in &tokens {
+        let (code, len) = code(*token);
+        let run = 1usize << token.1;
+        if used + len as usize > block_bits || block_values.last().unwrap() + run > MAX_BLOCK_VALUES
+        {
+            blocks.push(Vec::new());
+            block_values.push(0);
+            used = 0;
+        }
+        blocks.last_mut().unwrap().push((code, len));
+        *block_values.last_mut().unwrap() += run;
+        used += len as usize;
+    }
+
+    let mut data = Vec::new();
+    for block in &blocks {
+        let mut bytes = vec![0u8; 1 << BLOCK_SIZE_LOG2];
+        let mut bit = 0;
+        for (code, len) in block {
+            for b in (0..*len).rev() {
+                if (code >> b) & 1 == 1 {
+                    bytes[bit / 8] |= 0x80 >> (bit % 8);
+                }
+                bit += 1;
+            }
+        }
+        data.extend(bytes);
+    }
+
+    // One padding block so that spans past the end point somewhere
+    let mut block_lengths = Vec::new();
+    for values in block_values
+        .iter()
+        .map(|v| v - 1)
+        .chain([u16::MAX as usize])
+    {
+        block_lengths.extend((values as u16).to_le_bytes());
+    }
+    let span = 1usize << SPAN_LOG2;
+    let mut sparse_index = Vec::new();
+    for k in 0..values.len().div_ceil(span) {
+        let mut offset = k * span + span / 2;
+        let mut block = 0;
+        while block < blocks.len() && offset >= block_values[block] {
+            offset -= block_values[block];
+            block += 1;
+        }
+        sparse_index.extend((block as u32).to_le_bytes());
+        sparse_index.extend((offset as u16).to_le_bytes());
+    }
+
+    let mut sizes = vec![flags, BLOCK_SIZE_LOG2, SPAN_LOG2, 1];
+    sizes.extend((blocks.len() as u32).to_le_bytes());
+    sizes.extend([max_len as u8, min_len as u8]);
+    for lowest in &lowest {
+        sizes.extend((*lowest as u16).to_le_bytes());
+    }
+    sizes.extend((symbols.len() as u16).to_le_bytes());
+    for (value, k) in &symbols {
+        let (left, right) = if *k == 0 {
+            (*value as usize, 0xFFF)
+        } else {
+            let half = id[&(*value, k - 1)];
+            (half, half)
+        };
+        sizes.extend([
+            left as u8,
+            ((left >> 8) & 0xF) as u8 | ((right & 0xF) << 4) as u8,
+            (right >> 4) as u8,
+        ]);
+    }
+    if symbols.len() % 2 == 1 {
+        sizes.push(0);
+    }
+    Compressed {
+        sizes,
+        sparse_index,
+        block_lengths,
+        blocks: data,
+    }
+}
+
+/// Table `key` with white's pieces first, storing `value` for every position
+/// with white's `pieces` in table order against the black king. Pawns come
+/// first, then the two kings, so that pawnless tables lead with the kings.
+pub(super) fn write_table(
+    kind: Kind,
+    key: &str,
+    pieces: &[PieceType],
+    value: impl Fn(&State) -> Option<u16>,
+) -> Vec<u8> {
+    let has_pawns = pieces.contains(&PieceType::Pawn);
+    let mut codes = Vec::new();
+    for piece in pieces {
+        codes.push(*piece as u8 + 1);
+        if *piece == PieceType::King {
+            codes.push(PieceType::King as u8 + 9);
+        }
+    }
+    let mut header = match kind {
+        Kind::Wdl => WDL_MAGIC.to_vec(),
+        Kind::Dtz => DTZ_MAGIC.to_vec(),
+    };
+    header.push(SPLIT | if has_pawns { HAS_PAWNS } else { 0 });
+    for _ in 0..if has_pawns { 4 } else { 1 } {
+        // Groups are multiplied in piece order
+        header.push(0);
+        header.extend(codes.iter().map(|code| code | code << 4));
+    }
+    if header.len() % 2 == 1 {
+        header.push(0);
+    }
+
+    let mut table = Table::layout(kind, key, header.clone()).unwrap();
+    table.parse_pieces().unwrap();
+    let mut values: Vec<Vec<Vec<Option<u16>>>> = table
+        .subtables()
+        .iter()
+        .map(|files| {
+            files
+                .iter()
+                .map(|subtable| vec![None; subtable.size() as usize])
+                .collect()
+        })
+        .collect();
+    let white_pieces: Vec<PieceType> = pieces
+        .iter()
+        .filter(|piece| **piece != PieceType::King)
+        .chain(&[PieceType::King])
+        .copied()
+        .collect();
+    let stm: &[Color] = match kind {
+        Kind::Wdl => &[Color::White, Color::Black],
+        // Positions of the winning side
+        Kind::Dtz => &[Color::White],
+    };
+    for (_, state) in positions(&white_pieces, stm) {
+        let location = table.locate(&state, false).unwrap();
+        let stored = &mut values[location.side][location.file][location.index as usize];
+        let value = value(&state);
+        if stored.is_some() && value.is_some() {
+            assert_eq!(*stored, value, "{}", state.to_fen());
+        }
+        *stored = stored.or(value);
+    }
+
+    let flags = match kind {
+        Kind::Wdl => 0,
+        Kind::Dtz => WIN_PLIES | LOSS_PLIES,
+    };
+    let mut compressed = Vec::new();
+    for file in 0..values[0].len() {
+        for side in values.iter_mut() {
+            let values = &mut side[file];
+            let mut previous = values.iter().flatten().next().copied().unwrap_or(0);
+            let values: Vec<u16> = values
+                .iter()
+                .map(|value| {
+                    previous = value.unwrap_or(previous);
+                    previous
+                })
+                .collect();
+            compressed.push(compress(flags, &values));
+        }
+    }
+
+    let mut bytes = header;
+    for subtable in &compressed {
+        bytes.extend(&subtable.sizes);
+    }
+    for subtable in &compressed {
+        bytes.extend(&subtable.sparse_index);
+    }
+    for subtable in &compressed {
+        bytes.extend(&subtable.block_lengths);
+    }
+    for subtable in &compressed {
+        bytes.resize((bytes.len() + 0x3F) & !0x3F, 0);
+        bytes.extend(&subtable.blocks);
+    }
+    bytes
+}
+
+/// Write the WDL table and, unless `dtz` is `None`, the DTZ table of `key`.
+fn write_fixture(
+    directory: &Path,
+    key: &str,
+    pieces: &[PieceType],
+    tables: &EndgameTables,
+    dtz: Option<&[Option<i32>]>,
+) {
+    let wdl = write_table(Kind::Wdl, key, pieces, |state| {
+        Some((outcome(tables, state) as i32 + 2) as u16)
+    });
+    fs::write(directory.join(format!("{}.rtbw", key)), wdl).unwrap();
+    if let Some(dtz) = dtz {
+        let piece = *pieces
+            .iter()
+            .find(|piece| **piece != PieceType::King)
+            .unwrap();
+        let dtz = write_table(Kind::Dtz, key, pieces, |state| {
+            // Draws and zeroing wins aren't read
+            let mut position = Position::new(HashedState::new(state.clone(), NoopHasher {}));
+            let pawns = state.active_boards().pawn;
+            let zeroing_win = position.legal_moves().into_iter().any(|m| {
+                position.make(m);
+                let wins = outcome(tables, position.state.get()) == Wdl::Loss;
+                position.unmake(m);
+                (m.code().is_capture() || pawns.get(m.from())) && wins
+            });
+            let distance = dtz[raw_index(state, piece)].unwrap();
+            (distance != 0 && !zeroing_win).then(|| (distance.abs() - 1) as u16)
+        });
+        fs::write(directory.join(format!("{}.rtbz", key)), dtz).unwrap();
+    }
+}
+
+#[test]
+#[ignore = "regenerates the fixture tables"]
+fn generate_fixtures() {
+    let tables = EndgameTables::generate(&["KQK", "KRK", "KPK", "KNNK"]).unwrap();
+    let directory = Path::new(FIXTURES);
+    fs::create_dir_all(directory).unwrap();
+    for (key, piece) in [
+        ("KQvK", PieceType::Queen),
+        ("KRvK", PieceType::Rook),
+        ("KPvK", PieceType::Pawn),
+    ] {
+        let dtz = distances_to_zeroing(&tables, piece);
+        let pieces = if piece == PieceType::Pawn {
+            [piece, PieceType::King]
+        } else {
+            [PieceType::King, piece]
+        };
+        write_fixture(directory, key, &pieces, &tables, Some(&dtz));
+    }
+    write_fixture(
+        directory,
+        "KNNvK",
+        &[PieceType::King, PieceType::Knight, PieceType::Knight],
+        &tables,
+        None,
+    );
+}
@@ -1,16 +1,251 @@
 use chess_core::{
-    r#move::{MoveGenerator, MoveList},
-    state::{game_state::GameState, make_unmake::MakeUnmaker},
+    clock::move_budget,
+    color::Color,
+    eco::{self, classify},
+    hash::{HashedState, Hasher, zobrist::ZobristHasher},
+    r#move::{
+        Move, MoveList,
+        san::{from_san, to_san},
+    },
+    pgn::{PgnGame, parse_pgn},
+    position::Position,
+    square::{CastleSide, Square},
+    state::{State, bitboard::BitBoard, chess_board::PieceType},
 };
-use chess_engines::alpha_beta::search::SearchContext;
-use chrono::Duration;
+use chess_engines::alpha_beta::{
+    analysis::{analyse_game, annotate},
+    evaluation::SimpleEval,
+    search::SearchContext,
+    strength::{Strength, choose_move},
+};
+use chrono::{Duration, Local};
 
 use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::error::{ApiError, ErrorKind};
+
+// Types of the payloads crossing into JavaScript as plain objects, kept next
+// to the structs they describe.
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export type File = "a" | "b" | "c" | "d" | "e" | "f" | "g" | "h";
+export type Rank = "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8";
+export type Square = `${File}${Rank}`;
+export type PromotionPiece = "q" | "r" | "b" | "n";
+/** Piece letters as in SAN, P for pawns. */
+export type PieceLetter = "P" | "N" | "B" | "R" | "Q" | "K";
+
+export interface Move {
+    from: Square;
+    to: Square;
+    promotion?: PromotionPiece;
+}
+
+export interface FullGameState {
+    fen: string;
+    pgn: string;
+}
+
+export interface EvaluationResult {
+    /** Centipawns for the side to move. */
+    score: number;
+    best_move: Move;
+}
+
+export interface ClockTimes {
+    white_ms: number;
+    black_ms: number;
+    /** Added per move, to both sides. */
+    increment_ms?: number;
+    /** Moves before more time is added, if any. */
+    moves_to_go?: number;
+}
+
+export interface StrengthSettings {
+    /** From 0 to 20. */
+    skill_level?: number;
+    elo?: number;
+}
+
+export interface MoveDetails {
+    move: Move;
+    san: string;
+    piece: PieceLetter;
+    captured?: PieceLetter;
+    promotion?: PieceLetter;
+    castle?: "king" | "queen";
+    en_passant: boolean;
+    check: boolean;
+}
+
+export interface MoveReview {
+    move: Move;
+    san: string;
+    /** Evaluation after the move from white's side as in [%eval], like 0.35 or #-2. */
+    eval: string;
+    /** Winning chances of white after the move, for an evaluation graph. */
+    white_win_percent: number;
+    best_move: Move;
+    best_san: string;
+    /** Winning chances lost by the move, in percentage points. */
+    loss: number;
+    accuracy: number;
+    classification: "best" | "good" | "inaccuracy" | "mistake" | "blunder";
+}
+
+export interface Opening {
+    /** ECO code, like C60. */
+    eco: string;
+    name: string;
+    /** SAN moves of the named line from the starting position. */
+    moves: string[];
+}
+
+export interface GameReview {
+    moves: MoveReview[];
+    white_accuracy?: number;
+    black_accuracy?: number;
+    /** The game with [%eval] comments and glyphs. */
+    pgn: string;
+}
+"#;
+
+/// Search depth of the game review when none is given.
+const REVIEW_DEPTH: u8 = 4;
+
+/// A move as an object, e.g. `{ from: "e7", to: "e8", promotion: "q" }`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UciMove {
+    pub from: Square,
+    pub to: Square,
+    /// Lowercase piece letter.
+    pub promotion: Option<char>,
+}
+
+impl UciMove {
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!("{}{}", self.from, self.to);
+        uci.extend(self.promotion.map(|piece| piece.to_ascii_lowercase()));
+        uci
+    }
+}
+
+impl From<Move> for UciMove {
+    fn from(r#move: Move) -> Self {
+        UciMove {
+            from: r#move.from(),
+            to: r#move.to(),
+            promotion: r#move
+                .code()
+                .as_promotion()
+                .map(|piece| char::from(piece).to_ascii_lowercase()),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct EvaluationResult {
     pub score: i32,
-    pub best_move: String, // TODO: change to pv
+    pub best_move: UciMove, // TODO: change to pv
+}
+
+/// Playing strength of `respond_with_strength`. The Elo takes precedence over
+/// the skill level, and without either the engine plays at full strength.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct StrengthSettings {
+    /// From 0 to 20.
+    pub skill_level: Option<u8>,
+    pub elo: Option<u32>,
+}
+
+impl StrengthSettings {
+    pub(crate) fn strength(&self) -> Option<Strength> {
+        match (self.elo, self.skill_level) {
+            (Some(elo), _) => Some(Strength::from_elo(elo)),
+            (None, Some(level)) => Some(Strength::from_skill(level)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Time on the clocks, for `respond_with_clock` to share out.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ClockTimes {
+    pub white_ms: u32,
+    pub black_ms: u32,
+    pub increment_ms: Option<u32>,
+    pub moves_to_go: Option<u32>,
+}
+
+impl ClockTimes {
+    /// Time the side to move should think, as the UCI engine would.
+    pub(crate) fn budget(&self, side: Color) -> Duration {
+        let left = match side {
+            Color::White => self.white_ms,
+            Color::Black => self.black_ms,
+        };
+        let budget = move_budget(
+            std::time::Duration::from_millis(left as u64),
+            std::time::Duration::from_millis(self.increment_ms.unwrap_or(0) as u64),
+            self.moves_to_go,
+        );
+        Duration::from_std(budget).unwrap_or(Duration::zero())
+    }
+}
+
+/// What a legal move does, for a board widget to animate and announce it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MoveDetails {
+    pub r#move: UciMove,
+    pub san: String,
+    /// Piece letters as in SAN, P for pawns.
+    pub piece: PieceType,
+    pub captured: Option<PieceType>,
+    pub promotion: Option<PieceType>,
+    /// "king" or "queen" for the side castled to.
+    pub castle: Option<String>,
+    pub en_passant: bool,
+    pub check: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MoveReview {
+    pub r#move: UciMove,
+    pub san: String,
+    pub eval: String,
+    pub white_win_percent: f64,
+    pub best_move: UciMove,
+    pub best_san: String,
+    pub loss: f64,
+    pub accuracy: f64,
+    pub classification: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameReview {
+    pub moves: Vec<MoveReview>,
+    pub white_accuracy: Option<f64>,
+    pub black_accuracy: Option<f64>,
+    /// Annotated PGN.
+    pub pgn: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+    pub moves: Vec<String>,
+}
+
+impl From<&eco::Opening> for Opening {
+    fn from(opening: &eco::Opening) -> Self {
+        Opening {
+            eco: opening.eco.to_string(),
+            name: opening.name.to_string(),
+            moves: opening.moves.split_whitespace().map(String::from).collect(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -19,81 +254,262 @@ pub struct FullGameState {
     pub pgn: String,
 }
 
-pub fn evaluate(fgs: FullGameState) -> EvaluationResult {
-    let state = &mut GameState::from_fen(fgs.fen);
-    let search_ctx = &mut SearchContext::new(state, None);
-    let (score, pv) = search_ctx.iterative_deepen(Duration::new(1, 0).unwrap());
-
-    EvaluationResult {
-        score,
-        best_move: format!("{}", pv.last().unwrap()),
-    }
+fn position(fen: &str) -> Result<Position<ZobristHasher>, ApiError> {
+    let state = State::try_from_fen(fen).map_err(ApiError::invalid_fen)?;
+    Ok(Position::new(HashedState::new(state, ZobristHasher::new())))
 }
 
-/// Does not account for promotion
-pub fn is_move_legal(fen: String, r#move: String) -> bool {
-    let state = &mut GameState::from_fen(fen);
-    let move_generator = &MoveGenerator::new();
-    let make_unmaker = &mut MakeUnmaker::new(state);
+/// Find the pseudo legal move matching a perft string (e2e4) in the position.
+fn find_move(position: &Position<ZobristHasher>, r#move: &str) -> Option<Move> {
     let move_list = &mut MoveList::new();
     move_list.new_ply();
-    move_generator.get_pseudo_legal_moves(make_unmaker.state, move_list);
-    let pseudo_legal_move = move_list
+    position.pseudo_legal_moves(move_list);
+    move_list
         .current_ply()
-        .into_iter()
-        .find(|m| m.matches_perft_string(r#move.split_at(4).0));
-    if let Some(pseudo_legal_move) = pseudo_legal_move {
-        make_unmaker.make_move(*pseudo_legal_move);
-        move_generator.was_move_legal(state)
-    } else {
-        false
+        .iter()
+        .find(|m| m.matches_perft_string(r#move))
+        .copied()
+}
+
+pub(crate) fn square(name: &str) -> Result<Square, ApiError> {
+    Square::try_from(name)
+        .map_err(|_| ApiError::new(ErrorKind::InvalidArgument, format!("No square {}", name)))
+}
+
+/// Names of the squares on `board`, from a1 to h8.
+pub(crate) fn square_names(board: BitBoard) -> Vec<String> {
+    let mut board = board;
+    std::iter::from_fn(|| board.pop_first_square())
+        .map(|square| square.to_string())
+        .collect()
+}
+
+/// The move of `moves` written `move` in UCI notation. Without a promotion
+/// piece the queen is taken, as it comes first.
+pub(crate) fn match_uci(moves: Vec<Move>, r#move: &str) -> Option<Move> {
+    let r#move = r#move.to_lowercase();
+    moves.into_iter().find(|m| {
+        let uci = m.to_string().to_lowercase();
+        uci == r#move || (r#move.len() == 4 && uci.starts_with(&r#move))
+    })
+}
+
+/// The legal move given in UCI notation.
+pub(crate) fn find_legal_move<H: Hasher>(
+    position: &mut Position<H>,
+    r#move: &str,
+) -> Result<Move, ApiError> {
+    squares(r#move)?;
+    match_uci(position.legal_moves(), r#move).ok_or(ApiError::illegal_move(r#move))
+}
+
+pub(crate) fn details<H: Hasher>(position: &mut Position<H>, r#move: Move) -> MoveDetails {
+    let info = position.move_info(r#move);
+    MoveDetails {
+        r#move: UciMove::from(r#move),
+        san: to_san(position, r#move),
+        piece: info.piece,
+        captured: info.captured,
+        promotion: info.promotion,
+        castle: info.castle.map(|side| {
+            match side {
+                CastleSide::King => "king",
+                CastleSide::Queen => "queen",
+            }
+            .to_string()
+        }),
+        en_passant: info.en_passant,
+        check: info.check,
     }
 }
 
-pub fn needs_promotion(fen: String, r#move: String) -> bool {
-    let state = &mut GameState::from_fen(fen);
-    let move_generator = &MoveGenerator::new();
-    let make_unmaker = &mut MakeUnmaker::new(state);
-    let move_list = &mut MoveList::new();
-    move_list.new_ply();
-    move_generator.get_pseudo_legal_moves(make_unmaker.state, move_list);
-    move_list
-        .current_ply()
-        .into_iter()
-        .find(|m| m.matches_perft_string(r#move.split_at(4).0))
-        .unwrap()
-        .code()
-        .promotion()
-        .is_some()
-}
-
-pub fn make_move(fgs: FullGameState, r#move: String) -> FullGameState {
-    let state = &mut GameState::from_fen(fgs.fen);
-    let move_generator = &MoveGenerator::new();
-    let make_unmaker = &mut MakeUnmaker::new(state);
-    let mut move_list = Vec::new();
-    move_generator.get_pseudo_legal_moves(make_unmaker.state, &mut move_list);
-    let pseudo_legal_move = move_list
+/// The origin and destination squares of a move in UCI notation, e2 and e4 in e2e4.
+fn squares(r#move: &str) -> Result<&str, ApiError> {
+    match r#move.get(..4) {
+        Some(squares) if r#move.len() <= 5 => Ok(squares),
+        _ => Err(ApiError::new(
+            ErrorKind::InvalidMove,
+            format!("Move {} is not in UCI notation", r#move),
+        )),
+    }
+}
+
+pub fn evaluate(fgs: FullGameState) -> Result<EvaluationResult, ApiError> {
+    let position = position(&fgs.fen)?;
+    let search_ctx = &mut SearchContext::new(position, SimpleEval::default(), None);
+    let (score, pv) = search_ctx.iterative_deepen(Duration::new(1, 0).unwrap());
+
+    Ok(EvaluationResult {
+        score,
+        best_move: UciMove::from(*pv.last().ok_or_else(ApiError::game_over)?),
+    })
+}
+
+/// A promotion given without a piece is legal when promoting to any piece is.
+pub fn is_move_legal(fen: String, r#move: String) -> Result<bool, ApiError> {
+    let position = &mut position(&fen)?;
+    match find_legal_move(position, &r#move) {
+        Ok(_) => Ok(true),
+        Err(error) if error.kind == ErrorKind::IllegalMove => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn legal_destinations(fen: String, from: String) -> Result<Vec<String>, ApiError> {
+    let from = square(&from)?;
+    Ok(square_names(position(&fen)?.legal_destinations(from)))
+}
+
+pub fn movable_squares(fen: String) -> Result<Vec<String>, ApiError> {
+    Ok(square_names(position(&fen)?.movable_squares()))
+}
+
+/// Promotion pieces in UCI notation, q first, empty when the move is not a promotion.
+pub fn promotion_choices(fen: String, from: String, to: String) -> Result<Vec<String>, ApiError> {
+    let (from, to) = (square(&from)?, square(&to)?);
+    Ok(position(&fen)?
+        .promotion_choices(from, to)
         .into_iter()
-        .find(|m| m.matches_perft_string(r#move.as_str()))
-        .unwrap();
-    make_unmaker.make_move(pseudo_legal_move);
-    FullGameState {
-        fen: state.to_fen(),
+        .map(|piece| char::from(piece).to_ascii_lowercase().to_string())
+        .collect())
+}
+
+pub fn move_details(fen: String, r#move: String) -> Result<MoveDetails, ApiError> {
+    let position = &mut position(&fen)?;
+    let found = find_legal_move(position, &r#move)?;
+    Ok(details(position, found))
+}
+
+pub fn needs_promotion(fen: String, r#move: String) -> Result<bool, ApiError> {
+    let position = position(&fen)?;
+    let found = find_move(&position, squares(&r#move)?).ok_or(ApiError::illegal_move(&r#move))?;
+    Ok(found.code().as_promotion().is_some())
+}
+
+pub fn make_move(fgs: FullGameState, r#move: String) -> Result<FullGameState, ApiError> {
+    let position = &mut position(&fgs.fen)?;
+    squares(&r#move)?;
+    let pseudo_legal_move =
+        find_move(position, r#move.as_str()).ok_or(ApiError::illegal_move(&r#move))?;
+    position.make(pseudo_legal_move);
+    if !position.was_move_legal() {
+        return Err(ApiError::illegal_move(&r#move));
+    }
+    Ok(FullGameState {
+        fen: position.state.get().to_fen(),
         pgn: "".to_string(),
+    })
+}
+
+/// Review the `moves` of `pgn`, played from `start`.
+pub(crate) fn review(pgn: &PgnGame, start: State, moves: &[Move], depth: Option<u8>) -> GameReview {
+    let position = Position::new(HashedState::new(start, ZobristHasher::new()));
+    let context = &mut SearchContext::new(position, SimpleEval::default(), None);
+    let analysis = analyse_game(context, moves, depth.unwrap_or(REVIEW_DEPTH));
+    let moves = analysis
+        .moves
+        .iter()
+        .map(|m| MoveReview {
+            r#move: UciMove::from(m.r#move),
+            san: m.san.clone(),
+            eval: m.eval.to_string(),
+            white_win_percent: m.eval.win_percent(Color::White),
+            best_move: UciMove::from(m.best_move),
+            best_san: m.best_san.clone(),
+            loss: m.loss,
+            accuracy: m.accuracy,
+            classification: m.classification.to_string(),
+        })
+        .collect();
+    GameReview {
+        moves,
+        white_accuracy: analysis.accuracy(Color::White),
+        black_accuracy: analysis.accuracy(Color::Black),
+        pgn: annotate(pgn, &analysis).to_string(),
     }
 }
 
-pub fn respond(fgs: FullGameState) -> FullGameState {
-    let state = &mut GameState::from_fen(fgs.fen);
-    let search_ctx = &mut SearchContext::new(state, None);
-    let (_, m) = search_ctx.iterative_deepen(Duration::new(0, 300_000_000).unwrap());
-    let make_unmaker = &mut MakeUnmaker::new(state);
-    make_unmaker.make_move(*m.last().unwrap());
-    FullGameState {
-        fen: state.to_fen(),
-        pgn: "".to_string(),
+/// Review the first game of `pgn`, searching every position to `depth`.
+pub fn review_pgn(pgn: String, depth: Option<u8>) -> Result<GameReview, ApiError> {
+    let games = parse_pgn(&pgn).map_err(|e| ApiError::new(ErrorKind::InvalidPgn, e))?;
+    let game = games
+        .first()
+        .ok_or(ApiError::new(ErrorKind::InvalidPgn, "No game in the PGN"))?;
+    let start = game.starting_state().map_err(ApiError::invalid_fen)?;
+    let mut position = Position::new(HashedState::new(start.clone(), ZobristHasher::new()));
+    let mut moves = Vec::new();
+    for san in &game.moves {
+        let r#move = from_san(&mut position, san).map_err(ApiError::from_san)?;
+        position.make(r#move);
+        moves.push(r#move);
     }
+    Ok(review(game, start, &moves, depth))
+}
+
+/// The opening whose line reaches exactly this position.
+pub fn opening(fen: String) -> Result<Option<Opening>, ApiError> {
+    let state = State::try_from_fen(&fen).map_err(ApiError::invalid_fen)?;
+    Ok(classify(&state).map(Opening::from))
+}
+
+/// The deepest named opening the first game of `pgn` went through.
+pub fn pgn_opening(pgn: String) -> Result<Option<Opening>, ApiError> {
+    let games = parse_pgn(&pgn).map_err(|e| ApiError::new(ErrorKind::InvalidPgn, e))?;
+    let game = games
+        .first()
+        .ok_or(ApiError::new(ErrorKind::InvalidPgn, "No game in the PGN"))?;
+    let opening = game.opening().map_err(ApiError::invalid_fen)?;
+    Ok(opening.map(Opening::from))
+}
+
+pub fn respond(fgs: FullGameState) -> Result<FullGameState, ApiError> {
+    respond_in(fgs, Duration::new(0, 300_000_000).unwrap())
+}
+
+/// Respond in the time the clocks leave to the side to move.
+pub fn respond_with_clock(
+    fgs: FullGameState,
+    clock: ClockTimes,
+) -> Result<FullGameState, ApiError> {
+    let side = position(&fgs.fen)?.state.get().flags.active_color();
+    respond_in(fgs, clock.budget(side))
+}
+
+fn respond_in(fgs: FullGameState, time: Duration) -> Result<FullGameState, ApiError> {
+    let position = position(&fgs.fen)?;
+    let search_ctx = &mut SearchContext::new(position, SimpleEval::default(), None);
+    // The deadline stops a depth that would overrun the time
+    search_ctx.deadline = Some(Local::now() + time);
+    let (_, pv) = search_ctx.iterative_deepen(time);
+    // Not even the first depth completed in time
+    let first = search_ctx.position.legal_moves().first().copied();
+    let r#move = pv
+        .last()
+        .copied()
+        .or(first)
+        .ok_or_else(ApiError::game_over)?;
+    search_ctx.make(r#move);
+    Ok(FullGameState {
+        fen: search_ctx.position.state.get().to_fen(),
+        pgn: "".to_string(),
+    })
+}
+
+pub fn respond_with_strength(
+    fgs: FullGameState,
+    settings: StrengthSettings,
+) -> Result<FullGameState, ApiError> {
+    let Some(strength) = settings.strength() else {
+        return respond(fgs);
+    };
+    let position = position(&fgs.fen)?;
+    let search_ctx = &mut SearchContext::new(position, SimpleEval::default(), None);
+    let m = choose_move(search_ctx, &strength).ok_or_else(ApiError::game_over)?;
+    search_ctx.make(m);
+    Ok(FullGameState {
+        fen: search_ctx.position.state.get().to_fen(),
+        pgn: "".to_string(),
+    })
 }
 
 #[cfg(test)]
@@ -106,9 +522,11 @@ mod tests {
             fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
             pgn: String::new(),
         };
-        let res = evaluate(fgs);
-        println!("{}", res.best_move);
-        assert_eq!(res.score, 35);
+        let fen = fgs.fen.clone();
+        let res = evaluate(fgs).unwrap();
+        // The depth reached in the time budget depends on the machine
+        assert!((-100..=100).contains(&res.score), "{}", res.score);
+        assert!(is_move_legal(fen, res.best_move.to_uci()).unwrap());
     }
 
     #[test]
@@ -118,7 +536,222 @@ mod tests {
             fen: fen.to_string(),
             pgn: String::new(),
         };
-        let _res = evaluate(fgs);
+        let _res = evaluate(fgs).unwrap();
         dbg!(_res.best_move);
     }
+
+    #[test]
+    fn test_respond_with_strength() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        for settings in [
+            StrengthSettings {
+                skill_level: Some(0),
+                elo: None,
+            },
+            StrengthSettings {
+                skill_level: Some(20),
+                elo: Some(1000),
+            },
+        ] {
+            let fgs = FullGameState {
+                fen: fen.to_string(),
+                pgn: String::new(),
+            };
+            let res = respond_with_strength(fgs, settings).unwrap();
+            assert!(res.fen.contains(" b KQkq "));
+        }
+        assert_eq!(StrengthSettings::default().strength(), None);
+        let settings = StrengthSettings {
+            skill_level: Some(3),
+            elo: Some(2200),
+        };
+        assert_eq!(settings.strength(), Some(Strength::from_skill(20)));
+    }
+
+    #[test]
+    fn test_uci_move() {
+        let position = &mut position("8/P6k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let promotion = find_legal_move(position, "a7a8").unwrap();
+        let r#move = UciMove::from(promotion);
+        assert_eq!(r#move.promotion, Some('q'));
+        assert_eq!(r#move.to_uci(), "a7a8q");
+        let r#move = UciMove {
+            from: square("a1").unwrap(),
+            to: square("b1").unwrap(),
+            promotion: None,
+        };
+        assert_eq!(r#move.to_uci(), "a1b1");
+    }
+
+    #[test]
+    fn test_errors() {
+        let fgs = |fen: &str| FullGameState {
+            fen: fen.to_string(),
+            pgn: String::new(),
+        };
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let kind = |result: Result<FullGameState, ApiError>| result.err().map(|e| e.kind);
+
+        assert_eq!(
+            kind(make_move(fgs("8/8 w - - 0 1"), "e2e4".to_string())),
+            Some(ErrorKind::InvalidFen)
+        );
+        assert_eq!(
+            kind(make_move(fgs(start), "e2e5".to_string())),
+            Some(ErrorKind::IllegalMove)
+        );
+        assert_eq!(
+            kind(make_move(fgs(start), "e2".to_string())),
+            Some(ErrorKind::InvalidMove)
+        );
+        assert_eq!(kind(make_move(fgs(start), "e2e4".to_string())), None);
+        // Pinned pieces can't move
+        let pinned = "4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1";
+        assert_eq!(
+            kind(make_move(fgs(pinned), "e2d2".to_string())),
+            Some(ErrorKind::IllegalMove)
+        );
+
+        let mate = "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1";
+        assert_eq!(kind(respond(fgs(mate))), Some(ErrorKind::GameOver));
+        assert_eq!(
+            is_move_legal(start.to_string(), "e7e5".to_string()),
+            Ok(false)
+        );
+        assert_eq!(
+            needs_promotion(start.to_string(), "e2e5".to_string()).map_err(|e| e.kind),
+            Err(ErrorKind::IllegalMove)
+        );
+    }
+
+    #[test]
+    fn test_move_queries() {
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1".to_string();
+        assert_eq!(
+            legal_destinations(fen.clone(), "e5".to_string()),
+            Ok(vec!["d6".to_string(), "e6".to_string()])
+        );
+        assert_eq!(
+            movable_squares(fen.clone()).unwrap(),
+            ["a1", "e1", "h1", "e5", "b7"]
+        );
+        assert_eq!(
+            promotion_choices(fen.clone(), "b7".to_string(), "b8".to_string()).unwrap(),
+            ["q", "r", "b", "n"]
+        );
+        assert_eq!(
+            legal_destinations(fen.clone(), "z9".to_string()).map_err(|e| e.kind),
+            Err(ErrorKind::InvalidArgument)
+        );
+
+        // Promotions without a piece are to a queen
+        assert_eq!(is_move_legal(fen.clone(), "b7a8".to_string()), Ok(true));
+        assert_eq!(is_move_legal(fen.clone(), "b7a8n".to_string()), Ok(true));
+        assert_eq!(is_move_legal(fen.clone(), "e5e6q".to_string()), Ok(false));
+        assert_eq!(
+            move_details(fen.clone(), "b7a8".to_string()),
+            Ok(MoveDetails {
+                r#move: UciMove {
+                    from: square("b7").unwrap(),
+                    to: square("a8").unwrap(),
+                    promotion: Some('q'),
+                },
+                san: "bxa8=Q+".to_string(),
+                piece: PieceType::Pawn,
+                captured: Some(PieceType::Rook),
+                promotion: Some(PieceType::Queen),
+                castle: None,
+                en_passant: false,
+                check: true,
+            })
+        );
+        let details = move_details(fen.clone(), "e1c1".to_string()).unwrap();
+        assert_eq!(details.castle.as_deref(), Some("queen"));
+        assert_eq!(details.san, "O-O-O");
+        assert!(move_details(fen, "e5d6".to_string()).unwrap().en_passant);
+    }
+
+    #[test]
+    fn test_review_pgn() {
+        let pgn = "[Event \"Review\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n";
+        let review = review_pgn(pgn.to_string(), Some(2)).unwrap();
+        assert_eq!(review.moves.len(), 4);
+        assert_eq!(review.moves[2].san, "g4");
+        assert_eq!(review.moves[2].classification, "blunder");
+        assert_eq!(review.moves[2].eval, "#-1");
+        assert_eq!(review.moves[2].white_win_percent, 0.0);
+        assert!(review.white_accuracy < review.black_accuracy);
+        assert!(review.pgn.starts_with("[Event \"Review\"]"));
+        assert!(review.pgn.contains("2. g4 $4"));
+
+        let kind = |pgn: &str| {
+            review_pgn(pgn.to_string(), Some(1))
+                .map(|_| ())
+                .map_err(|e| e.kind)
+        };
+        assert_eq!(kind(""), Err(ErrorKind::InvalidPgn));
+        assert_eq!(kind("1. e4 {"), Err(ErrorKind::InvalidPgn));
+        assert_eq!(kind("1. e4 e5 2. Ke3 *"), Err(ErrorKind::IllegalMove));
+        assert_eq!(
+            kind("[FEN \"8/8 w\"]\n\n1. e4 *"),
+            Err(ErrorKind::InvalidFen)
+        );
+    }
+
+    #[test]
+    fn test_respond_with_clock() {
+        let clock = ClockTimes {
+            white_ms: 60_000,
+            black_ms: 3_000,
+            increment_ms: Some(100),
+            moves_to_go: None,
+        };
+        assert_eq!(clock.budget(Color::White), Duration::milliseconds(2050));
+        assert_eq!(clock.budget(Color::Black), Duration::milliseconds(150));
+
+        let fgs = FullGameState {
+            fen: "6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1".to_string(),
+            pgn: String::new(),
+        };
+        let result = respond_with_clock(fgs, clock).unwrap();
+        assert_ne!(result.fen, "6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1");
+
+        // A small budget is kept in a position with deep searches
+        let clock = ClockTimes {
+            white_ms: 3_000,
+            ..ClockTimes::default()
+        };
+        let fgs = FullGameState {
+            fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+            pgn: String::new(),
+        };
+        let start = std::time::Instant::now();
+        assert!(respond_with_clock(fgs, clock.clone()).is_ok());
+        let budget = clock.budget(Color::White).to_std().unwrap();
+        assert!(start.elapsed() < budget * 2, "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_opening() {
+        let fen = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2";
+        let sicilian = opening(fen.to_string()).unwrap().unwrap();
+        assert_eq!(sicilian.eco, "B20");
+        assert_eq!(sicilian.name, "Sicilian Defense");
+        assert_eq!(sicilian.moves, ["e4", "c5"]);
+        assert!(opening("8/8 w".to_string()).is_err());
+
+        let pgn = "1. d4 Nf6 2. c4 e6 3. Nc3 Bb4 4. a3 *";
+        let nimzo = pgn_opening(pgn.to_string()).unwrap().unwrap();
+        assert_eq!(nimzo.eco, "E20");
+        assert_eq!(pgn_opening("1. a3 *".to_string()), Ok(None));
+        let bad_fen = "[FEN \"8/8 w - - 0 1\"]\n\n1. e4 *";
+        assert_eq!(
+            pgn_opening(bad_fen.to_string()).map_err(|e| e.kind),
+            Err(ErrorKind::InvalidFen)
+        );
+        assert_eq!(
+            pgn_opening(String::new()).map_err(|e| e.kind),
+            Err(ErrorKind::InvalidPgn)
+        );
+    }
 }
@@ -0,0 +1,137 @@
+// Errors of the exports. They are thrown in JavaScript as an `Error` whose
+// `name` and `kind` give the kind of error, so the front end can tell a move
+// the user got wrong from a bug:
+//
+//     try {
+//         state = make_move(state, { from: "e2", to: "e5" });
+//     } catch (e) {
+//         if (e.kind === "IllegalMove") showMessage(e.message);
+//         else throw e;
+//     }
+
+use std::fmt::{self, Display};
+
+use chess_core::r#move::san::SanError;
+use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export type ErrorKind =
+    | "InvalidFen"
+    | "InvalidPgn"
+    | "InvalidMove"
+    | "IllegalMove"
+    | "AmbiguousMove"
+    | "GameOver"
+    | "InvalidArgument";
+
+/** Thrown by the exports, with the kind also as its name. */
+export interface ChessError extends Error {
+    kind: ErrorKind;
+}
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    InvalidFen,
+    InvalidPgn,
+    /// Not a move in UCI notation or in SAN.
+    InvalidMove,
+    IllegalMove,
+    /// A SAN move matching more than one legal move.
+    AmbiguousMove,
+    /// No legal move left to play or search.
+    GameOver,
+    /// An argument that doesn't have the expected shape.
+    InvalidArgument,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        ApiError {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_fen(message: &str) -> Self {
+        Self::new(ErrorKind::InvalidFen, message)
+    }
+
+    pub fn illegal_move(r#move: &str) -> Self {
+        Self::new(ErrorKind::IllegalMove, format!("Illegal move {}", r#move))
+    }
+
+    pub fn game_over() -> Self {
+        Self::new(ErrorKind::GameOver, "No legal move in the position")
+    }
+
+    pub fn from_san(error: SanError) -> Self {
+        let kind = match error {
+            SanError::Invalid(_) => ErrorKind::InvalidMove,
+            SanError::Illegal => ErrorKind::IllegalMove,
+            SanError::Ambiguous => ErrorKind::AmbiguousMove,
+        };
+        Self::new(kind, error.to_string())
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<serde_wasm_bindgen::Error> for ApiError {
+    fn from(error: serde_wasm_bindgen::Error) -> Self {
+        Self::new(ErrorKind::InvalidArgument, error.to_string())
+    }
+}
+
+impl From<ApiError> for JsValue {
+    fn from(error: ApiError) -> Self {
+        let js_error = js_sys::Error::new(&error.message);
+        let kind = JsValue::from_str(&error.kind.to_string());
+        js_error.set_name(&error.kind.to_string());
+        // Setting a property on a fresh object can't fail
+        js_sys::Reflect::set(&js_error, &JsValue::from_str("kind"), &kind).unwrap();
+        js_error.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_core::{hash::NoopHasher, r#move::san::from_san, position::Position};
+
+    use super::*;
+
+    #[test]
+    fn test_from_san() {
+        let fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        let mut position = Position::from_fen(fen, NoopHasher {});
+        let kind = |position: &mut Position<NoopHasher>, san| {
+            ApiError::from_san(from_san(position, san).unwrap_err()).kind
+        };
+        assert_eq!(kind(&mut position, "Nd2"), ErrorKind::AmbiguousMove);
+        assert_eq!(kind(&mut position, "Nd3"), ErrorKind::IllegalMove);
+        assert_eq!(kind(&mut position, "Z"), ErrorKind::InvalidMove);
+        assert_eq!(
+            ApiError::invalid_fen("FEN board needs 8 ranks").to_string(),
+            "InvalidFen: FEN board needs 8 ranks"
+        );
+    }
+}
@@ -0,0 +1,665 @@
+// A game session kept between calls from JavaScript. Unlike the stateless
+// functions working on FENs it knows the moves played, so it detects
+// repetitions, can take moves back and writes the PGN, and its search keeps
+// the transposition table from one move to the next.
+
+use chess_core::{
+    clock::{Clock, TimeControl},
+    color::Color,
+    eco::classify_game,
+    game::Game as GameHistory,
+    hash::{HashedState, zobrist::ZobristHasher},
+    r#move::{Move, san::from_san},
+    position::Position,
+    state::State,
+};
+use chess_engines::alpha_beta::{
+    evaluation::SimpleEval, search::SearchContext, strength::choose_move,
+};
+use chrono::{Duration, Local};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
+
+use crate::{
+    api::{self, GameReview, Opening, StrengthSettings, UciMove},
+    error::{ApiError, ErrorKind},
+    uci_move,
+    utils::{set_panic_hook, to_nullable},
+};
+
+/// Search time when the limits give neither a depth nor a time.
+const DEFAULT_TIME_MS: u32 = 300;
+
+/// Limits of `Game.search`. A depth takes precedence over a time, and a
+/// strength setting plays weaker moves. Without either the clock of the game
+/// tells the time, if it has one.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub time_ms: Option<u32>,
+    #[serde(flatten)]
+    pub strength: StrengthSettings,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SearchResult {
+    /// Score of the side to move in centipawns, not given at a limited strength.
+    pub score: Option<i32>,
+    /// `None` when the game is over.
+    pub best_move: Option<UciMove>,
+    /// Principal variation, best move first.
+    pub pv: Vec<UciMove>,
+}
+
+/// Times left on the clock of a game.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ClockState {
+    pub time_control: String,
+    pub white_ms: u64,
+    pub black_ms: u64,
+    /// "white" or "black" once its time ran out.
+    pub flagged: Option<String>,
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export interface SearchLimits extends StrengthSettings {
+    depth?: number;
+    time_ms?: number;
+}
+
+export interface ClockState {
+    /** As in the PGN tag, like 40/5400+30:1800+30. */
+    time_control: string;
+    white_ms: number;
+    black_ms: number;
+    /** Side whose time ran out. */
+    flagged?: "white" | "black";
+}
+
+export interface SearchResult {
+    /** Centipawns for the side to move, not given at a limited strength. */
+    score?: number;
+    /** Not given when the game is over. */
+    best_move?: Move;
+    /** Principal variation, best move first. */
+    pv: Move[];
+}
+"#;
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
+
+#[wasm_bindgen]
+pub struct Game {
+    history: GameHistory,
+    /// Moves taken back, the last one first to be replayed.
+    redo: Vec<Move>,
+    /// Follows the position of the game, so its tables survive between searches.
+    engine: SearchContext<SimpleEval>,
+    clock: Option<Clock>,
+    /// The clock before each move played with it and the number of moves
+    /// played before, to put it back when the move is taken back.
+    clocks: Vec<(usize, Clock)>,
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// A game from the starting position, or from `fen` if given.
+    #[wasm_bindgen(constructor)]
+    pub fn new(fen: Option<String>) -> Result<Game, ApiError> {
+        set_panic_hook();
+
+        let state = match fen {
+            Some(fen) => State::try_from_fen(&fen).map_err(ApiError::invalid_fen)?,
+            None => State::default(),
+        };
+        let position = Position::new(HashedState::new(state.clone(), ZobristHasher::new()));
+        Ok(Game {
+            history: GameHistory::new(state),
+            redo: Vec::new(),
+            engine: SearchContext::new(position, SimpleEval::default(), None),
+            clock: None,
+            clocks: Vec::new(),
+        })
+    }
+
+    /// Legal moves of the side to move.
+    #[wasm_bindgen(unchecked_return_type = "Move[]")]
+    pub fn legal_moves(&mut self) -> Result<JsValue, ApiError> {
+        let moves = self.history.legal_moves().into_iter().map(UciMove::from);
+        Ok(serde_wasm_bindgen::to_value(&moves.collect::<Vec<_>>())?)
+    }
+
+    /// Squares the piece on `from` can move to.
+    #[wasm_bindgen(unchecked_return_type = "Square[]")]
+    pub fn legal_destinations(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "Square")] from: &str,
+    ) -> Result<Vec<String>, ApiError> {
+        let from = api::square(from)?;
+        Ok(api::square_names(
+            self.engine.position.legal_destinations(from),
+        ))
+    }
+
+    /// Squares of the pieces that can move.
+    #[wasm_bindgen(unchecked_return_type = "Square[]")]
+    pub fn movable_squares(&mut self) -> Vec<String> {
+        api::square_names(self.engine.position.movable_squares())
+    }
+
+    /// Pieces a pawn moving from `from` to `to` can promote to, as "q", "r", "b" and "n".
+    #[wasm_bindgen(unchecked_return_type = "PromotionPiece[]")]
+    pub fn promotion_choices(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "Square")] from: &str,
+        #[wasm_bindgen(unchecked_param_type = "Square")] to: &str,
+    ) -> Result<Vec<String>, ApiError> {
+        let (from, to) = (api::square(from)?, api::square(to)?);
+        Ok(self
+            .engine
+            .position
+            .promotion_choices(from, to)
+            .into_iter()
+            .map(|piece| char::from(piece).to_ascii_lowercase().to_string())
+            .collect())
+    }
+
+    /// What a legal move does, see `move_details`.
+    #[wasm_bindgen(unchecked_return_type = "MoveDetails")]
+    pub fn move_details(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "Move")] r#move: JsValue,
+    ) -> Result<JsValue, ApiError> {
+        let found = api::find_legal_move(&mut self.engine.position, &uci_move(r#move)?)?;
+        let details = api::details(&mut self.engine.position, found);
+        Ok(serde_wasm_bindgen::to_value(&details)?)
+    }
+
+    /// Play a move given as an object, or as a string in UCI notation or in
+    /// SAN. Taken back moves can no longer be redone. With a clock, the time
+    /// thought on the move is taken from it, and a move played after the
+    /// time ran out loses the game instead.
+    pub fn make_move(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "Move | string")] r#move: JsValue,
+        elapsed_ms: Option<u32>,
+    ) -> Result<(), ApiError> {
+        let notation = match r#move.as_string() {
+            Some(notation) => notation,
+            None => uci_move(r#move)?,
+        };
+        let elapsed = elapsed_ms.map(|ms| std::time::Duration::from_millis(ms as u64));
+        self.play_timed_move(&notation, elapsed)
+    }
+
+    /// Give the game a clock with a time control as in the PGN tag, like
+    /// `300+3` or `40/5400+30:1800+30`, or remove it with `-`.
+    pub fn set_time_control(&mut self, time_control: String) -> Result<(), ApiError> {
+        let control = TimeControl::parse(&time_control)
+            .map_err(|e| ApiError::new(ErrorKind::InvalidArgument, e))?;
+        self.clock = match control.is_untimed() {
+            true => None,
+            false => Some(
+                Clock::new(control).map_err(|e| ApiError::new(ErrorKind::InvalidArgument, e))?,
+            ),
+        };
+        self.clocks.clear();
+        Ok(())
+    }
+
+    /// Times left on the clock, `null` without a clock.
+    #[wasm_bindgen(unchecked_return_type = "ClockState | null")]
+    pub fn clock(&self) -> Result<JsValue, ApiError> {
+        Ok(to_nullable(&self.clock_state())?)
+    }
+
+    /// Whether the time of the side to move ran out after thinking
+    /// `elapsed_ms` on its move, which then ends the game.
+    pub fn check_flag(&mut self, elapsed_ms: u32) -> bool {
+        self.check_time(std::time::Duration::from_millis(elapsed_ms as u64))
+    }
+
+    /// Take back the last move and return it.
+    #[wasm_bindgen(unchecked_return_type = "Move | undefined")]
+    pub fn undo(&mut self) -> Result<JsValue, ApiError> {
+        let r#move = self.take_back().map(UciMove::from);
+        Ok(serde_wasm_bindgen::to_value(&r#move)?)
+    }
+
+    /// Replay the last move taken back and return it.
+    #[wasm_bindgen(unchecked_return_type = "Move | undefined")]
+    pub fn redo(&mut self) -> Result<JsValue, ApiError> {
+        let r#move = self.replay().map(UciMove::from);
+        Ok(serde_wasm_bindgen::to_value(&r#move)?)
+    }
+
+    pub fn fen(&self) -> String {
+        self.history.state().to_fen()
+    }
+
+    pub fn pgn(&mut self) -> String {
+        let mut pgn = self.history.to_pgn();
+        if let Some(clock) = &self.clock {
+            pgn.tags
+                .push(("TimeControl".to_string(), clock.control().to_string()));
+        }
+        pgn.to_string()
+    }
+
+    /// Why the game ended, like "white mates" or "stalemate", `None` while it goes on.
+    pub fn outcome(&mut self) -> Option<String> {
+        self.history.outcome().map(|outcome| outcome.to_string())
+    }
+
+    /// Result as written in PGN: "1-0", "0-1", "1/2-1/2" or "*" while the game goes on.
+    pub fn result(&mut self) -> String {
+        self.history
+            .outcome()
+            .map_or("*", |outcome| outcome.result())
+            .to_string()
+    }
+
+    /// The deepest named opening the game went through, `null` if none.
+    #[wasm_bindgen(unchecked_return_type = "Opening | null")]
+    pub fn opening(&self) -> Result<JsValue, ApiError> {
+        Ok(to_nullable(&self.opening_line())?)
+    }
+
+    /// Review the moves played so far, see `review_game`.
+    #[wasm_bindgen(unchecked_return_type = "GameReview")]
+    pub fn review(&mut self, depth: Option<u8>) -> Result<JsValue, ApiError> {
+        Ok(serde_wasm_bindgen::to_value(&self.review_with(depth))?)
+    }
+
+    /// Search the current position with limits given as
+    /// `{ depth, time_ms, skill_level, elo }`, all optional.
+    #[wasm_bindgen(unchecked_return_type = "SearchResult")]
+    pub fn search(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "SearchLimits")] limits: JsValue,
+    ) -> Result<JsValue, ApiError> {
+        let limits: SearchLimits = serde_wasm_bindgen::from_value(limits)?;
+        Ok(serde_wasm_bindgen::to_value(&self.search_with(&limits))?)
+    }
+}
+
+impl Game {
+    fn play(&mut self, r#move: Move) {
+        self.history.make(r#move);
+        self.engine.make(r#move);
+    }
+
+    /// Play a move given in UCI notation or in SAN.
+    pub fn play_move(&mut self, r#move: &str) -> Result<(), ApiError> {
+        self.play_timed_move(r#move, None)
+    }
+
+    /// Play a move after thinking `elapsed` on it, taken from the clock if any.
+    pub fn play_timed_move(
+        &mut self,
+        r#move: &str,
+        elapsed: Option<std::time::Duration>,
+    ) -> Result<(), ApiError> {
+        let found = self.find_move(r#move)?;
+        let side = self.history.state().flags.active_color();
+        let before = self.clock.clone();
+        if let (Some(clock), Some(elapsed)) = (&mut self.clock, elapsed)
+            && let Some(color) = clock.press(side, elapsed)
+        {
+            self.history.flag(color);
+            let message = format!("The time of {} ran out", color_name(color));
+            return Err(ApiError::new(ErrorKind::GameOver, message));
+        }
+        self.save_clock(before);
+        self.play(found);
+        self.redo.clear();
+        Ok(())
+    }
+
+    pub fn check_time(&mut self, elapsed: std::time::Duration) -> bool {
+        let side = self.history.state().flags.active_color();
+        let Some(clock) = &mut self.clock else {
+            return false;
+        };
+        if self.history.outcome().is_some() {
+            return false;
+        }
+        match clock.check(side, elapsed) {
+            Some(color) => {
+                self.history.flag(color);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clock_state(&self) -> Option<ClockState> {
+        let clock = self.clock.as_ref()?;
+        Some(ClockState {
+            time_control: clock.control().to_string(),
+            white_ms: clock.remaining(Color::White).as_millis() as u64,
+            black_ms: clock.remaining(Color::Black).as_millis() as u64,
+            flagged: clock.flagged().map(|color| color_name(color).to_string()),
+        })
+    }
+
+    /// The legal move given in UCI notation or in SAN.
+    fn find_move(&mut self, r#move: &str) -> Result<Move, ApiError> {
+        if self.history.outcome().is_some() {
+            return Err(ApiError::new(ErrorKind::GameOver, "The game is over"));
+        }
+        match api::match_uci(self.history.legal_moves(), r#move) {
+            Some(m) => Ok(m),
+            None => from_san(&mut self.engine.position, r#move).map_err(ApiError::from_san),
+        }
+    }
+
+    fn save_clock(&mut self, clock: Option<Clock>) {
+        if let Some(clock) = clock {
+            self.clocks.push((self.history.moves().len(), clock));
+        }
+    }
+
+    /// Take back the last move, and the clock with it. A flag falls with it
+    /// too, the clock starts over if it has no state from before the move.
+    pub fn take_back(&mut self) -> Option<Move> {
+        let r#move = self.history.undo()?;
+        self.engine.unmake(r#move);
+        self.redo.push(r#move);
+        let plies = self.history.moves().len();
+        if self.clocks.last().is_some_and(|(ply, _)| *ply == plies) {
+            self.clock = self.clocks.pop().map(|(_, clock)| clock);
+        } else if let Some(clock) = &mut self.clock
+            && clock.flagged().is_some()
+            && let Ok(fresh) = Clock::new(clock.control().clone())
+        {
+            *clock = fresh;
+        }
+        Some(r#move)
+    }
+
+    pub fn replay(&mut self) -> Option<Move> {
+        let r#move = self.redo.pop()?;
+        self.save_clock(self.clock.clone());
+        self.play(r#move);
+        Some(r#move)
+    }
+
+    pub fn opening_line(&self) -> Option<Opening> {
+        classify_game(self.history.start(), self.history.moves()).map(Opening::from)
+    }
+
+    pub fn review_with(&mut self, depth: Option<u8>) -> GameReview {
+        let pgn = self.history.to_pgn();
+        let start = self.history.start().clone();
+        api::review(&pgn, start, self.history.moves(), depth)
+    }
+
+    pub fn search_with(&mut self, limits: &SearchLimits) -> SearchResult {
+        if self.history.outcome().is_some() {
+            return SearchResult {
+                score: None,
+                best_move: None,
+                pv: Vec::new(),
+            };
+        }
+        if let Some(strength) = limits.strength.strength() {
+            let best_move = choose_move(&mut self.engine, &strength).map(UciMove::from);
+            return SearchResult {
+                score: None,
+                pv: best_move.iter().cloned().collect(),
+                best_move,
+            };
+        }
+
+        let (score, pv) = match limits.depth {
+            Some(depth) => {
+                let prev_depth = self.engine.max_depth;
+                let mut result = (0, Vec::new());
+                for depth in 1..=depth.max(1) {
+                    self.engine.max_depth = depth;
+                    result = self.engine.search(result.1);
+                }
+                self.engine.max_depth = prev_depth;
+                result
+            }
+            None => {
+                let side = self.history.state().flags.active_color();
+                let clock_time = self
+                    .clock
+                    .as_ref()
+                    .and_then(|clock| Duration::from_std(clock.budget(side)).ok());
+                let time = match limits.time_ms {
+                    Some(time) => Duration::milliseconds(time as i64),
+                    None => clock_time.unwrap_or(Duration::milliseconds(DEFAULT_TIME_MS as i64)),
+                };
+                // The deadline stops a depth that would overrun the time
+                self.engine.deadline = Some(Local::now() + time);
+                let result = self.engine.iterative_deepen(time);
+                self.engine.deadline = None;
+                result
+            }
+        };
+        // Not even the first depth completed in time
+        let pv = match pv.is_empty() {
+            true => self.history.legal_moves().into_iter().take(1).collect(),
+            false => pv,
+        };
+        SearchResult {
+            score: Some(score),
+            best_move: pv.last().copied().map(UciMove::from),
+            pv: pv.into_iter().rev().map(UciMove::from).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Move in UCI notation, e.g. e2e4 or a7a8q.
+    fn uci(r#move: Move) -> String {
+        r#move.to_string().to_lowercase()
+    }
+
+    fn play(game: &mut Game, moves: &str) {
+        for m in moves.split_whitespace() {
+            game.play_move(m).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_make_move() {
+        let mut game = Game::new(None).unwrap();
+        assert_eq!(game.history.legal_moves().len(), 20);
+        play(&mut game, "e2e4 e5 Nf3 b8c6");
+        assert_eq!(
+            game.fen(),
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 1"
+        );
+        assert!(game.play_move("e4e5").is_err());
+        assert_eq!(
+            game.play_move("Qh5+").map_err(|e| e.kind),
+            Err(ErrorKind::IllegalMove)
+        );
+        assert!(Game::new(Some("8/8 w - - 0 1".to_string())).is_err());
+
+        let mut game = Game::new(Some("8/P6k/8/8/8/8/8/K7 w - - 0 1".to_string())).unwrap();
+        play(&mut game, "a7a8n");
+        assert_eq!(game.fen(), "N7/7k/8/8/8/8/8/K7 b - - 0 1");
+
+        // Without a promotion piece the pawn becomes a queen
+        let mut game = Game::new(Some("8/P6k/8/8/8/8/8/K7 w - - 0 1".to_string())).unwrap();
+        play(&mut game, "A7A8");
+        assert_eq!(game.fen(), "Q7/7k/8/8/8/8/8/K7 b - - 0 1");
+    }
+
+    #[test]
+    fn test_move_queries() {
+        let mut game = Game::new(None).unwrap();
+        assert_eq!(game.movable_squares().len(), 10);
+        assert_eq!(game.legal_destinations("g1").unwrap(), ["f3", "h3"]);
+        play(&mut game, "e4 d5 e5 f5");
+        assert_eq!(game.legal_destinations("e5").unwrap(), ["e6", "f6"]);
+        assert!(game.legal_destinations("e9").is_err());
+        assert!(game.promotion_choices("e5", "e6").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game = Game::new(None).unwrap();
+        assert_eq!(game.take_back(), None);
+        play(&mut game, "e2e4 e7e5 g1f3");
+        assert_eq!(game.take_back().map(uci).as_deref(), Some("g1f3"));
+        assert_eq!(game.take_back().map(uci).as_deref(), Some("e7e5"));
+        assert_eq!(game.replay().map(uci).as_deref(), Some("e7e5"));
+        assert_eq!(game.engine.position.state.get().to_fen(), game.fen());
+
+        // A new move drops the moves taken back
+        play(&mut game, "b1c3");
+        assert_eq!(game.replay(), None);
+        assert!(game.pgn().ends_with("\n1. e4 e5 2. Nc3 *\n"));
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mut game = Game::new(None).unwrap();
+        assert_eq!(game.outcome(), None);
+        assert_eq!(game.result(), "*");
+        play(&mut game, "f3 e5 g4 Qh4#");
+        assert_eq!(game.outcome(), Some("black mates".to_string()));
+        assert_eq!(game.result(), "0-1");
+        assert!(game.pgn().contains("[Result \"0-1\"]"));
+        assert_eq!(game.search_with(&SearchLimits::default()).best_move, None);
+        assert_eq!(
+            game.play_move("e2e4").map_err(|e| e.kind),
+            Err(ErrorKind::GameOver)
+        );
+
+        // Repetitions are found across moves
+        let mut game = Game::new(None).unwrap();
+        play(&mut game, "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8");
+        assert_eq!(game.outcome(), Some("threefold repetition".to_string()));
+    }
+
+    #[test]
+    fn test_review() {
+        let mut game = Game::new(None).unwrap();
+        play(&mut game, "e4 e5 Qh5 Nc6 Bc4 Nf6 Qxf7#");
+        let review = game.review_with(Some(2));
+        assert_eq!(review.moves.len(), 7);
+        assert_eq!(review.moves[5].classification, "blunder");
+        assert!(review.pgn.contains("3... Nf6 $4"));
+        assert_eq!(game.history.moves().len(), 7);
+    }
+
+    #[test]
+    fn test_opening() {
+        let mut game = Game::new(None).unwrap();
+        assert_eq!(game.opening_line(), None);
+        play(&mut game, "e4 e6 d4 d5 Nc3 Bb4 a3");
+        let opening = game.opening_line().unwrap();
+        assert_eq!(opening.eco, "C15");
+        assert!(
+            game.pgn()
+                .contains("[Opening \"French Defense: Winawer Variation\"]")
+        );
+    }
+
+    #[test]
+    fn test_clock() {
+        let secs = std::time::Duration::from_secs;
+        let mut game = Game::new(None).unwrap();
+        assert_eq!(game.clock_state(), None);
+        assert!(!game.check_time(secs(1000)));
+        game.set_time_control("10+1".to_string()).unwrap();
+        game.play_timed_move("e4", Some(secs(5))).unwrap();
+        // Moves without a time don't touch the clock
+        game.play_move("e5").unwrap();
+        let state = game.clock_state().unwrap();
+        assert_eq!((state.white_ms, state.black_ms), (6_000, 10_000));
+        assert!(game.pgn().contains("[TimeControl \"10+1\"]"));
+
+        // A search without limits takes its time from the clock
+        let result = game.search_with(&SearchLimits::default());
+        assert!(result.best_move.is_some());
+
+        // and keeps within it in a position with deep searches
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut deep = Game::new(Some(fen.to_string())).unwrap();
+        deep.set_time_control("3".to_string()).unwrap();
+        let budget = deep.clock.as_ref().unwrap().budget(Color::White);
+        let start = std::time::Instant::now();
+        assert!(
+            deep.search_with(&SearchLimits::default())
+                .best_move
+                .is_some()
+        );
+        assert!(start.elapsed() < budget * 2, "{:?}", start.elapsed());
+
+        assert!(!game.check_time(secs(6)));
+        assert!(game.check_time(secs(7)));
+        assert_eq!(game.outcome(), Some("black wins on time".to_string()));
+        assert_eq!(
+            game.clock_state().unwrap().flagged,
+            Some("white".to_string())
+        );
+        assert!(game.pgn().contains("[Termination \"time forfeit\"]"));
+
+        // Taking back a move takes back the flag and the clock
+        game.take_back();
+        assert_eq!(game.outcome(), None);
+        let state = game.clock_state().unwrap();
+        assert_eq!((state.white_ms, state.black_ms), (6_000, 10_000));
+        assert_eq!(state.flagged, None);
+        game.take_back();
+        assert_eq!(game.clock_state().unwrap().white_ms, 10_000);
+        game.replay();
+        assert_eq!(game.clock_state().unwrap().white_ms, 10_000);
+
+        let mut game = Game::new(None).unwrap();
+        game.set_time_control("10".to_string()).unwrap();
+        assert_eq!(
+            game.play_timed_move("e4", Some(secs(11)))
+                .map_err(|e| e.kind),
+            Err(ErrorKind::GameOver)
+        );
+        assert_eq!(game.result(), "0-1");
+        assert_eq!(game.history.moves().len(), 0);
+        assert!(game.set_time_control("5+".to_string()).is_err());
+        game.set_time_control("-".to_string()).unwrap();
+        assert_eq!(game.clock_state(), None);
+    }
+
+    #[test]
+    fn test_search() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        let mut game = Game::new(Some(fen.to_string())).unwrap();
+        let limits = SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        };
+        let result = game.search_with(&limits);
+        let best_move = result.best_move.unwrap();
+        assert_eq!(best_move.to_uci(), "a1a8");
+        assert_eq!(result.pv[0], best_move);
+        assert_eq!(game.fen(), fen);
+
+        let limits = SearchLimits {
+            strength: StrengthSettings {
+                skill_level: Some(0),
+                elo: None,
+            },
+            ..SearchLimits::default()
+        };
+        let result = game.search_with(&limits);
+        assert_eq!(result.score, None);
+        game.play_move(&result.best_move.unwrap().to_uci()).unwrap();
+    }
+}
@@ -1,50 +1,185 @@
-use api::FullGameState;
-use utils::set_panic_hook;
+use api::{ClockTimes, FullGameState, StrengthSettings, UciMove};
+use error::ApiError;
+use utils::{set_panic_hook, to_nullable};
 use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
 
 mod api;
+mod error;
+mod game;
+mod search;
 mod utils;
 
-#[wasm_bindgen]
-pub fn evaluate(fgs: JsValue) -> JsValue {
+pub use game::Game;
+pub use search::Search;
+
+/// A `Move` object in UCI notation.
+pub(crate) fn uci_move(r#move: JsValue) -> Result<String, ApiError> {
+    let r#move: UciMove = serde_wasm_bindgen::from_value(r#move)?;
+    Ok(r#move.to_uci())
+}
+
+#[wasm_bindgen(unchecked_return_type = "EvaluationResult")]
+pub fn evaluate(
+    #[wasm_bindgen(unchecked_param_type = "FullGameState")] fgs: JsValue,
+) -> Result<JsValue, ApiError> {
     set_panic_hook();
 
-    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs).unwrap();
-    let result = api::evaluate(fgs);
+    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs)?;
+    let result = api::evaluate(fgs)?;
 
-    serde_wasm_bindgen::to_value(&result).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&result)?)
 }
 
+/// A promotion given without a piece is legal when promoting to any piece is.
 #[wasm_bindgen]
-pub fn is_move_legal(fen: String, r#move: String) -> bool {
+pub fn is_move_legal(
+    fen: String,
+    #[wasm_bindgen(unchecked_param_type = "Move")] r#move: JsValue,
+) -> Result<bool, ApiError> {
     set_panic_hook();
 
-    api::is_move_legal(fen, r#move)
+    api::is_move_legal(fen, uci_move(r#move)?)
 }
 
 #[wasm_bindgen]
-pub fn needs_promotion(fen: String, r#move: String) -> bool {
+pub fn needs_promotion(
+    fen: String,
+    #[wasm_bindgen(unchecked_param_type = "Move")] r#move: JsValue,
+) -> Result<bool, ApiError> {
     set_panic_hook();
 
-    api::needs_promotion(fen, r#move)
+    api::needs_promotion(fen, uci_move(r#move)?)
 }
 
-#[wasm_bindgen]
-pub fn make_move(fgs: JsValue, r#move: String) -> JsValue {
+/// Squares the piece on `from` can move to, for highlighting drop targets.
+#[wasm_bindgen(unchecked_return_type = "Square[]")]
+pub fn legal_destinations(
+    fen: String,
+    #[wasm_bindgen(unchecked_param_type = "Square")] from: String,
+) -> Result<Vec<String>, ApiError> {
     set_panic_hook();
 
-    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs).unwrap();
-    let result = api::make_move(fgs, r#move);
+    api::legal_destinations(fen, from)
+}
+
+/// Squares of the pieces that can move.
+#[wasm_bindgen(unchecked_return_type = "Square[]")]
+pub fn movable_squares(fen: String) -> Result<Vec<String>, ApiError> {
+    set_panic_hook();
 
-    serde_wasm_bindgen::to_value(&result).unwrap()
+    api::movable_squares(fen)
 }
 
-#[wasm_bindgen]
-pub fn respond(fgs: JsValue) -> JsValue {
+/// Pieces to offer when a pawn moves from `from` to `to`, as "q", "r", "b" and "n".
+#[wasm_bindgen(unchecked_return_type = "PromotionPiece[]")]
+pub fn promotion_choices(
+    fen: String,
+    #[wasm_bindgen(unchecked_param_type = "Square")] from: String,
+    #[wasm_bindgen(unchecked_param_type = "Square")] to: String,
+) -> Result<Vec<String>, ApiError> {
+    set_panic_hook();
+
+    api::promotion_choices(fen, from, to)
+}
+
+/// What a legal move does: `{ move, san, piece, captured, promotion, castle, en_passant, check }`.
+#[wasm_bindgen(unchecked_return_type = "MoveDetails")]
+pub fn move_details(
+    fen: String,
+    #[wasm_bindgen(unchecked_param_type = "Move")] r#move: JsValue,
+) -> Result<JsValue, ApiError> {
+    set_panic_hook();
+
+    let result = api::move_details(fen, uci_move(r#move)?)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+#[wasm_bindgen(unchecked_return_type = "FullGameState")]
+pub fn make_move(
+    #[wasm_bindgen(unchecked_param_type = "FullGameState")] fgs: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "Move")] r#move: JsValue,
+) -> Result<JsValue, ApiError> {
+    set_panic_hook();
+
+    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs)?;
+    let result = api::make_move(fgs, uci_move(r#move)?)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+/// Review the first game of `pgn` for a post-game screen: the evaluation and
+/// classification of every move, the accuracy of each side and the annotated
+/// PGN. Every position is searched to `depth`, 4 by default.
+#[wasm_bindgen(unchecked_return_type = "GameReview")]
+pub fn review_game(pgn: String, depth: Option<u8>) -> Result<JsValue, ApiError> {
+    set_panic_hook();
+
+    let result = api::review_pgn(pgn, depth)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+/// ECO code and name of the opening reaching exactly this position, or `null`.
+#[wasm_bindgen(unchecked_return_type = "Opening | null")]
+pub fn opening(fen: String) -> Result<JsValue, ApiError> {
+    set_panic_hook();
+
+    let result = api::opening(fen)?;
+
+    Ok(to_nullable(&result)?)
+}
+
+/// ECO code and name of the deepest opening line the first game of `pgn`
+/// went through, or `null`.
+#[wasm_bindgen(unchecked_return_type = "Opening | null")]
+pub fn pgn_opening(pgn: String) -> Result<JsValue, ApiError> {
+    set_panic_hook();
+
+    let result = api::pgn_opening(pgn)?;
+
+    Ok(to_nullable(&result)?)
+}
+
+#[wasm_bindgen(unchecked_return_type = "FullGameState")]
+pub fn respond(
+    #[wasm_bindgen(unchecked_param_type = "FullGameState")] fgs: JsValue,
+) -> Result<JsValue, ApiError> {
+    set_panic_hook();
+
+    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs)?;
+    let result = api::respond(fgs)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+/// Respond in the time the clocks leave to the side to move, given as
+/// `{ white_ms, black_ms, increment_ms, moves_to_go }`, the last two optional.
+#[wasm_bindgen(unchecked_return_type = "FullGameState")]
+pub fn respond_with_clock(
+    #[wasm_bindgen(unchecked_param_type = "FullGameState")] fgs: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "ClockTimes")] clock: JsValue,
+) -> Result<JsValue, ApiError> {
+    set_panic_hook();
+
+    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs)?;
+    let clock: ClockTimes = serde_wasm_bindgen::from_value(clock)?;
+    let result = api::respond_with_clock(fgs, clock)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+/// Respond at a limited strength, given as `{ skill_level, elo }` with either field optional.
+#[wasm_bindgen(unchecked_return_type = "FullGameState")]
+pub fn respond_with_strength(
+    #[wasm_bindgen(unchecked_param_type = "FullGameState")] fgs: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "StrengthSettings")] settings: JsValue,
+) -> Result<JsValue, ApiError> {
     set_panic_hook();
 
-    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs).unwrap();
-    let result = api::respond(fgs);
+    let fgs: FullGameState = serde_wasm_bindgen::from_value(fgs)?;
+    let settings: StrengthSettings = serde_wasm_bindgen::from_value(settings)?;
+    let result = api::respond_with_strength(fgs, settings)?;
 
-    serde_wasm_bindgen::to_value(&result).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&result)?)
 }
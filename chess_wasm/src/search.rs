@@ -0,0 +1,366 @@
+// Search in steps, for a Web Worker. Every call to `step` searches a budget of
+// nodes and returns, so the worker can post the update and handle its
+// messages, a `stop` among them, before the next step:
+//
+//     const search = new Search(fen, { time_ms: 5000 });
+//     function loop() {
+//         const update = search.step(20000);
+//         if (update) postMessage(update);
+//         if (!search.is_done()) setTimeout(loop);
+//     }
+//
+// A step never searches more than its budget. A depth is searched one root
+// move at a time, so a step that runs out of nodes keeps the root moves it
+// finished and the next step goes on from the first unfinished one. A root
+// move that doesn't fit in a whole step never will, the search then ends with
+// the last completed depth.
+
+use chess_core::{
+    hash::{HashedState, NoopHasher, zobrist::ZobristHasher},
+    r#move::{Move, san::to_san},
+    position::Position,
+    state::State,
+};
+use chess_engines::alpha_beta::{
+    evaluation::SimpleEval,
+    search::{MATE_SCORE, SearchContext},
+};
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
+
+use crate::{api::UciMove, error::ApiError, utils::set_panic_hook};
+
+/// Deepest search when the limits give no depth.
+const MAX_DEPTH: u8 = 64;
+
+/// When a stepped search ends, without limits it runs until stopped.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct StepLimits {
+    pub depth: Option<u8>,
+    pub time_ms: Option<u32>,
+}
+
+/// Result of the deepest completed depth.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchUpdate {
+    pub depth: u8,
+    /// Score of the side to move in centipawns.
+    pub score: i32,
+    /// Nodes searched since the start.
+    pub nodes: u64,
+    /// `None` when there is no legal move.
+    pub best_move: Option<UciMove>,
+    /// Principal variation in SAN, best move first.
+    pub pv: Vec<String>,
+    /// Whether this is the last update.
+    pub done: bool,
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export interface StepLimits {
+    depth?: number;
+    time_ms?: number;
+}
+
+export interface SearchUpdate {
+    depth: number;
+    /** Centipawns for the side to move. */
+    score: number;
+    nodes: number;
+    /** Not given when there is no legal move. */
+    best_move?: Move;
+    /** Principal variation in SAN, best move first. */
+    pv: string[];
+    done: boolean;
+}
+"#;
+
+#[wasm_bindgen]
+pub struct Search {
+    context: SearchContext<SimpleEval>,
+    root: State,
+    limits: StepLimits,
+    start: DateTime<Local>,
+    /// Last completed depth, with its score and principal variation, best move last.
+    depth: u8,
+    score: i32,
+    pv: Vec<Move>,
+    /// Progress of the depth being searched.
+    iteration: Option<Iteration>,
+    done: bool,
+}
+
+/// A depth searched one root move at a time.
+struct Iteration {
+    /// Root moves, the best one of the previous depth first.
+    moves: Vec<Move>,
+    /// Root moves searched so far.
+    searched: usize,
+    /// Best score so far and its principal variation, best move last.
+    score: i32,
+    pv: Vec<Move>,
+}
+
+impl Iteration {
+    fn new(mut moves: Vec<Move>, prev_pv: &[Move]) -> Self {
+        if let Some(best) = prev_pv.last()
+            && let Some(index) = moves.iter().position(|m| m == best)
+        {
+            moves[..=index].rotate_right(1);
+        }
+        Iteration {
+            moves,
+            searched: 0,
+            score: i32::MIN + 1,
+            pv: Vec::new(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Search {
+    /// Start a search of `fen` with limits given as `{ depth, time_ms }`, both optional.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        fen: String,
+        #[wasm_bindgen(unchecked_param_type = "StepLimits | undefined")] limits: JsValue,
+    ) -> Result<Search, ApiError> {
+        set_panic_hook();
+
+        let limits = if limits.is_undefined() || limits.is_null() {
+            StepLimits::default()
+        } else {
+            serde_wasm_bindgen::from_value(limits)?
+        };
+        Self::with_limits(&fen, limits)
+    }
+
+    /// Search at most `node_budget` nodes, and return an update when a depth
+    /// was completed or the search ended.
+    #[wasm_bindgen(unchecked_return_type = "SearchUpdate | null")]
+    pub fn step(&mut self, node_budget: u32) -> Result<JsValue, ApiError> {
+        match self.step_nodes(node_budget as u64) {
+            Some(update) => Ok(serde_wasm_bindgen::to_value(&update)?),
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    /// End the search and return its final update.
+    #[wasm_bindgen(unchecked_return_type = "SearchUpdate")]
+    pub fn stop(&mut self) -> Result<JsValue, ApiError> {
+        Ok(serde_wasm_bindgen::to_value(&self.finish())?)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+impl Search {
+    pub fn with_limits(fen: &str, limits: StepLimits) -> Result<Search, ApiError> {
+        let root = State::try_from_fen(fen).map_err(ApiError::invalid_fen)?;
+        let position = Position::new(HashedState::new(root.clone(), ZobristHasher::new()));
+        Ok(Search {
+            context: SearchContext::new(position, SimpleEval::default(), None),
+            root,
+            limits,
+            start: Local::now(),
+            depth: 0,
+            score: 0,
+            pv: Vec::new(),
+            iteration: None,
+            done: false,
+        })
+    }
+
+    pub fn step_nodes(&mut self, node_budget: u64) -> Option<SearchUpdate> {
+        if self.done {
+            return None;
+        }
+        let moves = self.context.position.legal_moves();
+        if moves.is_empty() {
+            // Checkmated or stalemated
+            self.score = if self.context.position.state.get().is_check() {
+                -MATE_SCORE
+            } else {
+                0
+            };
+            return Some(self.finish());
+        }
+
+        let depth = self.depth + 1;
+        let prev_pv = &self.pv;
+        let iteration = self
+            .iteration
+            .get_or_insert_with(|| Iteration::new(moves, prev_pv));
+        let context = &mut self.context;
+        context.node_limit = Some(context.nodes + node_budget.max(1));
+        context.deadline = self
+            .limits
+            .time_ms
+            .map(|time| self.start + Duration::milliseconds(time as i64));
+        // The root move is the first ply of the depth
+        context.max_depth = depth - 1;
+        let first = iteration.searched;
+        while let Some(&r#move) = iteration.moves.get(iteration.searched) {
+            // The best line of the previous depth is tried first
+            let prev_pv = match iteration.searched {
+                0 => self.pv[..self.pv.len().saturating_sub(1)].to_vec(),
+                _ => Vec::new(),
+            };
+            context.make(r#move);
+            let (score, mut line) = context.search_window(i32::MIN + 1, -iteration.score, prev_pv);
+            context.unmake(r#move);
+            if context.aborted() {
+                break;
+            }
+            if -score > iteration.score {
+                line.push(r#move);
+                (iteration.score, iteration.pv) = (-score, line);
+            }
+            iteration.searched += 1;
+        }
+        let stuck = iteration.searched == first;
+        let completed = iteration.searched == iteration.moves.len();
+        if completed && let Some(iteration) = self.iteration.take() {
+            (self.depth, self.score, self.pv) = (depth, iteration.score, iteration.pv);
+        }
+
+        let out_of_time = self
+            .limits
+            .time_ms
+            .is_some_and(|time| Local::now() - self.start >= Duration::milliseconds(time as i64));
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+        if out_of_time || stuck || self.depth >= max_depth {
+            Some(self.finish())
+        } else if completed {
+            Some(self.update())
+        } else {
+            None
+        }
+    }
+
+    pub fn finish(&mut self) -> SearchUpdate {
+        self.done = true;
+        self.update()
+    }
+
+    fn update(&mut self) -> SearchUpdate {
+        // Without a completed depth any legal move will do
+        let best_move = self
+            .pv
+            .last()
+            .copied()
+            .or_else(|| self.context.position.legal_moves().first().copied());
+        let mut position = Position::new(HashedState::new(self.root.clone(), NoopHasher {}));
+        let pv = self
+            .pv
+            .iter()
+            .rev()
+            .map(|m| {
+                let san = to_san(&mut position, *m);
+                position.make(*m);
+                san
+            })
+            .collect();
+        SearchUpdate {
+            depth: self.depth,
+            score: self.score,
+            nodes: self.context.nodes,
+            best_move: best_move.map(UciMove::from),
+            pv,
+            done: self.done,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn test_steps() {
+        let limits = StepLimits {
+            depth: Some(3),
+            time_ms: None,
+        };
+        let mut search = Search::with_limits(START, limits).unwrap();
+        let mut updates = Vec::new();
+        let mut steps = 0;
+        while !search.is_done() {
+            steps += 1;
+            let nodes = search.context.nodes;
+            updates.extend(search.step_nodes(400));
+            assert!(search.context.nodes - nodes <= 400);
+        }
+        // Depths that didn't fit in a step went on in the next ones
+        assert!(steps > updates.len());
+        assert_eq!(
+            updates.iter().map(|u| u.depth).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        let last = updates.last().unwrap();
+        assert!(last.done && !updates[0].done);
+        assert_eq!(last.pv.len(), 3);
+        assert_eq!(search.step_nodes(200), None);
+
+        // Same result as searching in one go
+        let position = Position::from_fen(START, ZobristHasher::new());
+        let mut context = SearchContext::new(position, SimpleEval::default(), None);
+        let mut pv = Vec::new();
+        let mut score = 0;
+        for depth in 1..=3 {
+            context.max_depth = depth;
+            (score, pv) = context.search(pv);
+        }
+        let best = *pv.last().unwrap();
+        assert_eq!(last.score, score);
+        assert_eq!(last.best_move, Some(UciMove::from(best)));
+        assert_eq!(last.pv[0], to_san(&mut context.position, best));
+    }
+
+    #[test]
+    fn test_stop() {
+        let mut search = Search::with_limits(START, StepLimits::default()).unwrap();
+        // Too few nodes to complete the first depth
+        assert_eq!(search.step_nodes(5), None);
+        let update = search.finish();
+        assert!(update.done && search.is_done());
+        assert_eq!(update.depth, 0);
+        assert!(update.best_move.is_some());
+
+        let mut search =
+            Search::with_limits("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", StepLimits::default()).unwrap();
+        let update = search.step_nodes(100).unwrap();
+        assert!(update.done);
+        assert_eq!(update.best_move, None);
+        assert_eq!(update.score, 0);
+
+        let mut search =
+            Search::with_limits("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", StepLimits::default()).unwrap();
+        let update = search.step_nodes(100).unwrap();
+        assert_eq!((update.best_move, update.score), (None, -MATE_SCORE));
+
+        let limits = StepLimits {
+            depth: None,
+            time_ms: Some(0),
+        };
+        let mut search = Search::with_limits(START, limits).unwrap();
+        let update = search.step_nodes(1_000_000).unwrap();
+        // The deadline stops the step long before its budget
+        assert!(update.done && update.nodes < 10_000);
+        assert!(Search::with_limits("8/8 w", StepLimits::default()).is_err());
+
+        // A root move that doesn't fit in a whole step ends the search
+        let mut search = Search::with_limits(START, StepLimits::default()).unwrap();
+        let mut last = None;
+        while !search.is_done() {
+            last = search.step_nodes(3).or(last);
+        }
+        assert_eq!(last.map(|update| update.depth), Some(1));
+    }
+}
@@ -1,3 +1,6 @@
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
     // `set_panic_hook` function at least once during initialization, and then
@@ -8,3 +11,12 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/// `value` as a JavaScript value, `null` for `None` where serde_wasm_bindgen
+/// would give `undefined`.
+pub fn to_nullable<T: Serialize>(value: &Option<T>) -> Result<JsValue, serde_wasm_bindgen::Error> {
+    match value {
+        Some(value) => serde_wasm_bindgen::to_value(value),
+        None => Ok(JsValue::NULL),
+    }
+}